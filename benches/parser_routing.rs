@@ -0,0 +1,84 @@
+//! Compares routing transactions to their candidate parsers through the registry against the
+//! previous path, which created every parser for every transaction and asked it to `check_match`.
+//!
+//! Run with `cargo bench --bench parser_routing`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::hint::black_box;
+use ton::parser::RoutingBench;
+use ton::types::{Trace, TracesResponse, TracesResponseRest};
+
+/// How many times the fixture transactions are repeated in the synthetic trace.
+const COPIES: [usize; 2] = [10, 100];
+
+fn traces_from_file(file_path: &str) -> Vec<Trace> {
+    let body = std::fs::read(file_path).expect("Failed to read JSON test file");
+    // Toncenter returns control characters in some strings, the client drops them the same way
+    let clean_text: String = String::from_utf8_lossy(&body)
+        .chars()
+        .filter(|c| !c.is_control() || *c == '\n' || *c == '\t')
+        .collect();
+    let rest: TracesResponseRest =
+        serde_json::from_str(&clean_text).expect("Failed to deserialize test transaction data");
+
+    TracesResponse::from(rest).traces
+}
+
+/// One trace with the transactions of every fixture trace, `copies` times over.
+fn large_trace(traces: &[Trace], copies: usize) -> Trace {
+    let mut trace = traces.first().expect("No fixture traces").clone();
+    trace.transactions = traces
+        .iter()
+        .flat_map(|trace| trace.transactions.iter())
+        .cloned()
+        .collect::<Vec<_>>()
+        .repeat(copies);
+    trace
+}
+
+fn parser_routing(c: &mut Criterion) {
+    let mut traces = traces_from_file("tests/data/v3_traces.json");
+    let its_traces = traces_from_file("tests/data/v3_traces_its.json");
+
+    let execution = traces.get(9).expect("Missing execution trace");
+    let gateway = &execution
+        .transactions
+        .get(4)
+        .expect("Missing gateway")
+        .account;
+    let gas_service = &execution
+        .transactions
+        .get(1)
+        .expect("Missing gas service")
+        .account;
+    let its = &its_traces
+        .first()
+        .and_then(|trace| trace.transactions.get(5))
+        .expect("Missing ITS")
+        .account;
+    let bench = RoutingBench::new(gateway, gas_service, its, "ton2");
+    traces.extend(its_traces.iter().cloned());
+
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to start runtime");
+    let mut group = c.benchmark_group("parser_routing");
+    for copies in COPIES {
+        let trace = large_trace(&traces, copies);
+        // Both paths have to agree for the comparison to mean anything
+        assert_eq!(
+            runtime.block_on(bench.routed(&trace)),
+            runtime.block_on(bench.sequential(&trace))
+        );
+
+        let size = trace.transactions.len();
+        group.bench_with_input(BenchmarkId::new("registry", size), &trace, |b, trace| {
+            b.iter(|| runtime.block_on(bench.routed(black_box(trace))))
+        });
+        group.bench_with_input(BenchmarkId::new("sequential", size), &trace, |b, trace| {
+            b.iter(|| runtime.block_on(bench.sequential(black_box(trace))))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parser_routing);
+criterion_main!(benches);
@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS ton_broadcasts (
+    message_hash_norm TEXT PRIMARY KEY,
+    wallet_address TEXT NOT NULL,
+    actions JSONB NOT NULL,
+    -- Every task a broadcast carries, in the order of its actions, e.g. all tasks of a batch
+    messages JSONB NOT NULL DEFAULT '[]',
+    status TEXT NOT NULL DEFAULT 'pending',
+    exit_code INT,
+    trace_id TEXT,
+    resent_as TEXT,
+    attempts INT NOT NULL DEFAULT 1,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ,
+    -- Pending broadcasts are claimed by one confirmation tracker at a time until the claim runs out
+    claimed_until TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS ton_broadcasts_status_idx ON ton_broadcasts(status);
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS ton_quarantined_traces (
+    trace_id TEXT PRIMARY KEY,
+    payload TEXT NOT NULL,
+    stage TEXT NOT NULL,
+    parser TEXT,
+    error TEXT NOT NULL,
+    attempts INT NOT NULL DEFAULT 1,
+    status TEXT NOT NULL DEFAULT 'quarantined',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS ton_quarantined_traces_status_idx ON ton_quarantined_traces(status);
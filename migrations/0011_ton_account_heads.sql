@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS ton_account_heads (
+    account TEXT PRIMARY KEY,
+    tx_hash TEXT NOT NULL,
+    tx_lt BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
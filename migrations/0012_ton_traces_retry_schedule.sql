@@ -0,0 +1,12 @@
+ALTER TABLE ton_traces ADD COLUMN IF NOT EXISTS next_retry_at TIMESTAMPTZ;
+ALTER TABLE ton_traces ADD COLUMN IF NOT EXISTS abandoned_at TIMESTAMPTZ;
+-- Set once a complete version of the trace was handed on, so changes to it later on don't send it again
+ALTER TABLE ton_traces ADD COLUMN IF NOT EXISTS emitted_at TIMESTAMPTZ;
+
+-- Traces that ran out of retries before retries could be abandoned
+UPDATE ton_traces SET abandoned_at = NOW() WHERE is_incomplete = true AND retries <= 0;
+-- Complete traces so far were all handed on
+UPDATE ton_traces SET emitted_at = COALESCE(updated_at, created_at) WHERE is_incomplete = false;
+
+CREATE INDEX IF NOT EXISTS ton_traces_next_retry_at_idx ON ton_traces(next_retry_at)
+    WHERE is_incomplete = true AND abandoned_at IS NULL;
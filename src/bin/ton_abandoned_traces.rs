@@ -0,0 +1,74 @@
+//! Lists traces that the retry subscriber abandoned while they were still incomplete, the
+//! messages in them are stuck until someone looks.
+//!
+//! ```text
+//! ton_abandoned_traces [limit]
+//! ton_abandoned_traces ack <trace_id>...
+//! ```
+//!
+//! `ack` stops alerting on the given traces, they are still listed.
+
+use anyhow::bail;
+use dotenv::dotenv;
+use relayer_core::config::config_from_yaml;
+use relayer_core::logging::setup_logging;
+use relayer_core::redis::connection_manager;
+use sqlx::PgPool;
+use ton::config::TONConfig;
+use ton::retry_subscriber::RedisAbandonedTraceAlert;
+use ton::ton_trace::{PgTONTraceModel, Retriable};
+use tracing::info;
+
+const DEFAULT_LIMIT: u32 = 100;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv().ok();
+    let network = std::env::var("NETWORK").expect("NETWORK must be set");
+    let config: TONConfig = config_from_yaml(&format!("config.{network}.yaml"))?;
+
+    let (_sentry_guard, otel_guard) = setup_logging(&config.common_config);
+
+    let redis_client = redis::Client::open(config.common_config.redis_server.clone())?;
+    let redis_conn = connection_manager(redis_client, None, None, None).await?;
+    let alert = RedisAbandonedTraceAlert::new(redis_conn, &config.common_config.chain_name);
+
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("ack") => {
+            let trace_ids: Vec<String> = args.collect();
+            if trace_ids.is_empty() {
+                bail!("No trace ids given");
+            }
+            alert.acknowledge(&trace_ids).await?;
+            info!("Acknowledged {} traces", trace_ids.len());
+        }
+        limit => {
+            let limit = match limit {
+                Some(limit) => limit.parse()?,
+                None => DEFAULT_LIMIT,
+            };
+            let pg_pool = PgPool::connect(&config.common_config.postgres_url).await?;
+            let traces = PgTONTraceModel::new(pg_pool).fetch_abandoned(limit).await?;
+            let unacknowledged = alert.unacknowledged().await?;
+
+            for trace in &traces {
+                info!(
+                    "Trace {}: start_lt={}, end_lt={}, abandoned_at={:?}, acknowledged={}",
+                    trace.trace_id,
+                    trace.start_lt,
+                    trace.end_lt,
+                    trace.abandoned_at,
+                    !unacknowledged.contains_key(&trace.trace_id)
+                );
+            }
+            info!("{} abandoned traces", traces.len());
+        }
+    }
+
+    otel_guard
+        .force_flush()
+        .expect("Failed to flush OTEL messages");
+
+    Ok(())
+}
@@ -5,10 +5,12 @@ use relayer_core::logging::setup_logging;
 use relayer_core::redis::connection_manager;
 use relayer_core::utils::setup_heartbeat;
 use std::str::FromStr;
+use std::time::Duration;
 use tokio::signal::unix::{signal, SignalKind};
 use ton::check_accounts::check_accounts;
-use ton::client::TONRpcClient;
 use ton::config::TONConfig;
+use ton::failover_client::FailoverRestClient;
+use ton::rate_limiter::{spawn_stats_reporter, RequestPriority};
 use tonlib_core::TonAddress;
 
 const MIN_BALANCE: u64 = 10_000_000_000;
@@ -38,16 +40,18 @@ async fn main() -> anyhow::Result<()> {
     our_addresses.push(TonAddress::from_str(&config.ton_gas_service)?);
     our_addresses.push(TonAddress::from_str(&config.ton_its)?);
 
-    let client = TONRpcClient::new(config.ton_rpc.clone(), config.ton_api_key.clone(), 5, 5, 30)
+    let client = FailoverRestClient::from_config(&config, RequestPriority::AccountCheck)
         .await
         .map_err(|e| error_stack::report!(SubscriberError::GenericError(e.to_string())))
         .expect("Failed to create RPC client");
+    let stats_reporter = spawn_stats_reporter(Duration::from_secs(60));
 
     tokio::select! {
         _ = sigint.recv()  => {},
         _ = sigterm.recv() => {},
         _ = check_accounts(&client, our_addresses, MIN_BALANCE, true) => {}
     }
+    stats_reporter.abort();
 
     otel_guard
         .force_flush()
@@ -0,0 +1,181 @@
+//! Scans a logical time range of the watched accounts again, e.g. after an outage, and publishes
+//! the traces that weren't seen before to `events`, page by page as they are found. The
+//! subscriber cursors are left as they are.
+//!
+//! ```text
+//! ton_backfill <start_lt> <end_lt> [account...]
+//! ton_backfill tx <hash...>
+//! ```
+//!
+//! Without accounts, the gateway, gas service and ITS contracts are scanned. With `tx`, the traces
+//! containing the given transaction or message hashes are published again, seen before or not.
+
+use anyhow::{anyhow, bail};
+use dotenv::dotenv;
+use relayer_core::config::config_from_yaml;
+use relayer_core::database::PostgresDB;
+use relayer_core::error::SubscriberError;
+use relayer_core::logging::setup_logging;
+use relayer_core::queue::{Queue, QueueItem};
+use relayer_core::subscriber::TransactionPoller;
+use sqlx::PgPool;
+use ton::config::TONConfig;
+use ton::failover_client::FailoverRestClient;
+use ton::rate_limiter::RequestPriority;
+use ton::subscriber::TONSubscriber;
+use ton::ton_trace::PgTONTraceModel;
+use ton::types::Trace;
+use tonlib_core::TonAddress;
+use tracing::{info, warn};
+
+enum Backfill {
+    Range {
+        start_lt: i64,
+        end_lt: i64,
+        accounts: Vec<TonAddress>,
+    },
+    Hashes(Vec<String>),
+}
+
+fn parse_args(contracts: &[TonAddress]) -> anyhow::Result<Backfill> {
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("tx") {
+        let hashes: Vec<String> = args.skip(1).collect();
+        if hashes.is_empty() {
+            bail!("No transaction or message hashes given");
+        }
+        return Ok(Backfill::Hashes(hashes));
+    }
+
+    let start_lt: i64 = args
+        .next()
+        .ok_or_else(|| anyhow!("Usage: ton_backfill <start_lt> <end_lt> [account...]"))?
+        .parse()?;
+    let end_lt: i64 = args
+        .next()
+        .ok_or_else(|| anyhow!("Missing end_lt"))?
+        .parse()?;
+    if start_lt > end_lt {
+        bail!("start_lt {} is after end_lt {}", start_lt, end_lt);
+    }
+    let mut accounts = args
+        .map(|account| TonAddress::from_base64_url(&account))
+        .collect::<Result<Vec<_>, _>>()?;
+    if accounts.is_empty() {
+        accounts = contracts.to_vec();
+    }
+
+    Ok(Backfill::Range {
+        start_lt,
+        end_lt,
+        accounts,
+    })
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv().ok();
+    let network = std::env::var("NETWORK").expect("NETWORK must be set");
+    let config: TONConfig = config_from_yaml(&format!("config.{network}.yaml"))?;
+
+    let (_sentry_guard, otel_guard) = setup_logging(&config.common_config);
+
+    let contracts = vec![
+        TonAddress::from_base64_url(config.ton_gateway.as_str())?,
+        TonAddress::from_base64_url(config.ton_gas_service.as_str())?,
+        TonAddress::from_base64_url(config.ton_its.as_str())?,
+    ];
+    let backfill = parse_args(&contracts)?;
+
+    let events_queue = Queue::new(
+        &config.common_config.queue_address,
+        "events",
+        config.common_config.num_workers,
+    )
+    .await;
+    let postgres_db = PostgresDB::new(&config.common_config.postgres_url).await?;
+    let pg_pool = PgPool::connect(&config.common_config.postgres_url).await?;
+    let ton_traces = PgTONTraceModel::new(pg_pool);
+
+    let client = FailoverRestClient::from_config(&config, RequestPriority::Poll)
+        .await
+        .map_err(|e| error_stack::report!(SubscriberError::GenericError(e.to_string())))
+        .expect("Failed to create RPC client");
+
+    let subscriber = |context: String| {
+        let client = client.clone();
+        let postgres_db = postgres_db.clone();
+        let ton_traces = ton_traces.clone();
+        let config = &config;
+        let contracts = &contracts;
+        async move {
+            let mut ton_sub = TONSubscriber::new(
+                client,
+                postgres_db,
+                context,
+                config.common_config.chain_name.clone(),
+                ton_traces,
+            )
+            .await?;
+            if let Some(depth) = config.ton_finality_depth {
+                ton_sub = ton_sub.with_finality_depth(depth);
+            }
+            if config.ton_local_completeness {
+                ton_sub = ton_sub.with_relevant_accounts(contracts.clone());
+            }
+            Ok::<_, anyhow::Error>(ton_sub)
+        }
+    };
+
+    match backfill {
+        Backfill::Range {
+            start_lt,
+            end_lt,
+            accounts,
+        } => {
+            for account in accounts {
+                let ton_sub = subscriber(account.to_string()).await?;
+                let events_queue = &events_queue;
+                let published = ton_sub
+                    .backfill(account.clone(), start_lt, end_lt, |traces| async move {
+                        info!("Publishing {} unseen traces", traces.len());
+                        publish(events_queue, traces).await
+                    })
+                    .await?;
+                info!("Published {} unseen traces of {}", published, account);
+            }
+        }
+        Backfill::Hashes(hashes) => {
+            let mut ton_sub = subscriber(config.ton_gateway.clone()).await?;
+            let mut traces = vec![];
+            for hash in hashes {
+                match ton_sub.poll_tx(hash.clone()).await {
+                    Ok(trace) => {
+                        info!("Publishing trace {} of {}", trace.trace_id, hash);
+                        traces.push(trace);
+                    }
+                    Err(err) => warn!("Skipping {}: {:?}", hash, err),
+                }
+            }
+            publish(&events_queue, traces).await?;
+        }
+    }
+
+    events_queue.close().await;
+
+    otel_guard
+        .force_flush()
+        .expect("Failed to flush OTEL messages");
+
+    Ok(())
+}
+
+async fn publish(events_queue: &Queue, traces: Vec<Trace>) -> anyhow::Result<()> {
+    for trace in traces {
+        let item = serde_json::to_string(&trace)?;
+        events_queue
+            .publish(QueueItem::Transaction(Box::new(item)))
+            .await;
+    }
+    Ok(())
+}
@@ -6,11 +6,14 @@ use relayer_core::utils::setup_heartbeat;
 use relayer_core::{database::PostgresDB, gmp_api, payload_cache::PayloadCache, queue::Queue};
 use sqlx::PgPool;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::signal::unix::{signal, SignalKind};
 use tokio_util::sync::CancellationToken;
 use ton::config::TONConfig;
 use ton::high_load_query_id_db_wrapper::HighLoadQueryIdDbWrapper;
 use ton::includer::TONIncluder;
+use ton::rate_limiter::spawn_stats_reporter;
+use ton::ton_broadcast::PgTONBroadcastModel;
 use ton::ton_wallet_query_id::PgTONWalletQueryIdModel;
 use tracing::log::info;
 
@@ -44,14 +47,17 @@ async fn main() -> anyhow::Result<()> {
     let model = PgTONWalletQueryIdModel::new(pg_pool.clone());
     let gmp_api = gmp_api::construct_gmp_api(pg_pool.clone(), &config.common_config, true)?;
 
+    let stats_reporter = spawn_stats_reporter(Duration::from_secs(60));
     let high_load_query_id_wrapper = HighLoadQueryIdDbWrapper::new(model).await;
-    let ton_includer = TONIncluder::new(
+    let broadcast_log = Arc::new(PgTONBroadcastModel::new(pg_pool.clone()));
+    let (ton_includer, confirmation_tracker) = TONIncluder::new(
         config,
         gmp_api,
         redis_conn.clone(),
         payload_cache_for_includer,
         Arc::clone(&construct_proof_queue),
         Arc::new(high_load_query_id_wrapper),
+        broadcast_log,
     )
     .await
     .expect("Failed to construct TONIncluder");
@@ -74,6 +80,11 @@ async fn main() -> anyhow::Result<()> {
         async move { ton_includer.run(tasks, token_clone).await }
     });
 
+    let tracker_handle = tokio::spawn({
+        let token_clone = token.clone();
+        async move { confirmation_tracker.run(token_clone).await }
+    });
+
     tokio::pin!(handle);
 
     tokio::select! {
@@ -91,6 +102,8 @@ async fn main() -> anyhow::Result<()> {
     tasks_queue.close().await;
     construct_proof_queue.close().await;
     let _ = handle.await;
+    let _ = tracker_handle.await;
+    stats_reporter.abort();
 
     otel_guard
         .force_flush()
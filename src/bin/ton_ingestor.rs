@@ -14,6 +14,7 @@ use ton::config::TONConfig;
 use ton::gas_calculator::GasCalculator;
 use ton::ingestor::TONIngestor;
 use ton::parser::TraceParser;
+use ton::ton_quarantined_trace::PgTONQuarantinedTraceModel;
 use ton::ton_trace::PgTONTraceModel;
 use tonlib_core::TonAddress;
 
@@ -64,13 +65,18 @@ async fn main() -> anyhow::Result<()> {
         its,
         gas_calculator,
         config.common_config.chain_name,
-    );
+    )
+    .with_parsers_config(&config.parsers)
+    .with_finality_gated(config.ton_finality_depth.is_some());
 
     let redis_client = redis::Client::open(config.common_config.redis_server.clone())?;
     let redis_conn = connection_manager(redis_client, None, None, None).await?;
 
     let ton_trace_model = PgTONTraceModel::new(pg_pool.clone());
-    let ton_ingestor = TONIngestor::new(parser, ton_trace_model);
+    let quarantine = PgTONQuarantinedTraceModel::new(pg_pool.clone());
+    let ton_ingestor = TONIngestor::new(parser, ton_trace_model)
+        .with_quarantine(Arc::new(quarantine))
+        .with_partial_parsing(config.parsers.partial_parsing);
 
     let logging_ctx_cache = RedisLoggingCtxCache::new(redis_conn.clone());
 
@@ -0,0 +1,139 @@
+//! Reparses stored traces with the current parsers and reports how the events differ from the
+//! ones sent when the traces were ingested.
+//!
+//! ```text
+//! ton_reparse [--republish] ids <trace_id>...
+//! ton_reparse [--republish] lt <from_lt> <to_lt>
+//! ton_reparse [--republish] time <from_rfc3339> <to_rfc3339>
+//! ```
+//!
+//! With `--republish`, only the missing events are sent to the GMP API and recorded with the
+//! trace's events. Events that were sent already aren't sent again.
+
+use anyhow::{anyhow, bail};
+use dotenv::dotenv;
+use relayer_core::config::config_from_yaml;
+use relayer_core::database::PostgresDB;
+use relayer_core::gmp_api;
+use relayer_core::logging::setup_logging;
+use relayer_core::price_view::PriceView;
+use sqlx::PgPool;
+use std::str::FromStr;
+use ton::config::TONConfig;
+use ton::gas_calculator::GasCalculator;
+use ton::parser::TraceParser;
+use ton::reparse::{summary, Reparser};
+use ton::ton_trace::{PgTONTraceModel, TraceSelection};
+use tonlib_core::TonAddress;
+use tracing::info;
+
+fn parse_args(mut args: Vec<String>) -> anyhow::Result<(TraceSelection, bool)> {
+    let republish = match args.iter().position(|arg| arg == "--republish") {
+        Some(position) => {
+            args.remove(position);
+            true
+        }
+        None => false,
+    };
+
+    let mut args = args.into_iter();
+    let selection = match args.next().as_deref() {
+        Some("ids") => {
+            let ids: Vec<String> = args.collect();
+            if ids.is_empty() {
+                bail!("No trace ids given");
+            }
+            TraceSelection::Ids(ids)
+        }
+        Some("lt") => TraceSelection::LtRange {
+            from: args.next().ok_or_else(|| anyhow!("Missing from_lt"))?.parse()?,
+            to: args.next().ok_or_else(|| anyhow!("Missing to_lt"))?.parse()?,
+        },
+        Some("time") => TraceSelection::TimeRange {
+            from: args.next().ok_or_else(|| anyhow!("Missing from"))?.parse()?,
+            to: args.next().ok_or_else(|| anyhow!("Missing to"))?.parse()?,
+        },
+        _ => bail!("Usage: ton_reparse [--republish] ids <trace_id>... | lt <from> <to> | time <from> <to>"),
+    };
+
+    Ok((selection, republish))
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv().ok();
+    let network = std::env::var("NETWORK").expect("NETWORK must be set");
+    let config: TONConfig = config_from_yaml(&format!("config.{network}.yaml"))?;
+
+    let (_sentry_guard, otel_guard) = setup_logging(&config.common_config);
+
+    let (selection, republish) = parse_args(std::env::args().skip(1).collect())?;
+
+    let postgres_db = PostgresDB::new(&config.common_config.postgres_url).await?;
+    let pg_pool = PgPool::connect(&config.common_config.postgres_url).await?;
+    let price_view = PriceView::new(postgres_db.clone());
+
+    let mut our_addresses = vec![];
+    for wallet in config.wallets {
+        our_addresses.push(TonAddress::from_str(&wallet.address)?);
+    }
+    let gateway = TonAddress::from_str(&config.ton_gateway)?;
+    let gas_service = TonAddress::from_str(&config.ton_gas_service)?;
+    let its = TonAddress::from_str(&config.ton_its)?;
+
+    our_addresses.push(gateway.clone());
+    our_addresses.push(gas_service.clone());
+
+    let gas_calculator = GasCalculator::new(our_addresses);
+
+    let parser = TraceParser::new(
+        price_view,
+        gateway,
+        gas_service,
+        its,
+        gas_calculator,
+        config.common_config.chain_name,
+    )
+    .with_parsers_config(&config.parsers)
+    .with_finality_gated(config.ton_finality_depth.is_some());
+
+    let reparser = Reparser::new(parser, PgTONTraceModel::new(pg_pool.clone()));
+    let changed = reparser.reparse_selection(selection).await?;
+
+    for (_, diff) in &changed {
+        info!(
+            "Trace {}: {} unchanged, {} missing, {} stale, {} parser failures",
+            diff.trace_id,
+            diff.unchanged,
+            diff.missing.len(),
+            diff.stale.len(),
+            diff.failures.len()
+        );
+        for event in &diff.missing {
+            info!("  missing: {:?}", summary(event));
+        }
+        for event in &diff.stale {
+            info!("  stale: {:?}", event);
+        }
+        for failure in &diff.failures {
+            info!("  failure: {:?}", failure);
+        }
+    }
+
+    if republish {
+        let gmp_api = gmp_api::construct_gmp_api(pg_pool, &config.common_config, true)?;
+
+        let mut republished = 0;
+        for (trace, diff) in &changed {
+            reparser.republish(gmp_api.as_ref(), trace, diff).await?;
+            republished += diff.missing.len();
+        }
+        info!("Republished {} missing events", republished);
+    }
+
+    otel_guard
+        .force_flush()
+        .expect("Failed to flush OTEL messages");
+
+    Ok(())
+}
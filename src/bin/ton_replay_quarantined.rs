@@ -0,0 +1,72 @@
+//! Requeues quarantined traces into `events`, the queue the subscriber publishes traces to, e.g.
+//! once a parser fix has been deployed.
+//!
+//! `ton_replay_quarantined` requeues every quarantined trace, `ton_replay_quarantined <trace_id>...`
+//! only the given ones. Traces that fail again are quarantined again, with one more attempt.
+
+use dotenv::dotenv;
+use relayer_core::config::config_from_yaml;
+use relayer_core::logging::setup_logging;
+use relayer_core::queue::{Queue, QueueItem};
+use sqlx::PgPool;
+use ton::config::TONConfig;
+use ton::ton_quarantined_trace::{PgTONQuarantinedTraceModel, QuarantineStatus, TraceQuarantine};
+use tracing::{info, warn};
+
+const REPLAY_LIMIT: i64 = 1000;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv().ok();
+    let network = std::env::var("NETWORK").expect("NETWORK must be set");
+    let config: TONConfig = config_from_yaml(&format!("config.{network}.yaml"))?;
+
+    let (_sentry_guard, otel_guard) = setup_logging(&config.common_config);
+
+    let pg_pool = PgPool::connect(&config.common_config.postgres_url).await?;
+    let quarantine = PgTONQuarantinedTraceModel::new(pg_pool);
+
+    let trace_ids: Vec<String> = std::env::args().skip(1).collect();
+    let traces = if trace_ids.is_empty() {
+        quarantine.fetch_quarantined(REPLAY_LIMIT).await?
+    } else {
+        let mut traces = vec![];
+        for trace_id in trace_ids {
+            match quarantine.find(&trace_id).await? {
+                Some(trace) if trace.status == QuarantineStatus::Quarantined.as_str() => {
+                    traces.push(trace)
+                }
+                Some(trace) => warn!("Trace {} is {}, skipping", trace_id, trace.status),
+                None => warn!("Trace {} is not quarantined", trace_id),
+            }
+        }
+        traces
+    };
+
+    let tasks_queue = Queue::new(
+        &config.common_config.queue_address,
+        "events",
+        config.common_config.num_workers,
+    )
+    .await;
+
+    for trace in &traces {
+        info!(
+            "Requeueing trace {} (attempts={}, stage={}, parser={:?}): {}",
+            trace.trace_id, trace.attempts, trace.stage, trace.parser, trace.error
+        );
+        tasks_queue
+            .publish(QueueItem::Transaction(Box::new(trace.payload.clone())))
+            .await;
+        quarantine.mark_requeued(&trace.trace_id).await?;
+    }
+    info!("Requeued {} traces", traces.len());
+
+    tasks_queue.close().await;
+
+    otel_guard
+        .force_flush()
+        .expect("Failed to flush OTEL messages");
+
+    Ok(())
+}
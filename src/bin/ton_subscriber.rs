@@ -9,12 +9,17 @@ use relayer_core::subscriber::Subscriber;
 use relayer_core::utils::setup_heartbeat;
 use sqlx::PgPool;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::signal::unix::{signal, SignalKind};
 use tokio::task::JoinHandle;
-use ton::client::TONRpcClient;
+use ton::block_subscriber::MasterchainTONSubscriber;
 use ton::config::TONConfig;
-use ton::retry_subscriber::RetryTONSubscriber;
+use ton::failover_client::FailoverRestClient;
+use ton::rate_limiter::{spawn_stats_reporter, RequestPriority};
+use ton::retry_subscriber::{RedisAbandonedTraceAlert, RetryPolicy, RetryTONSubscriber};
+use ton::stream_client::{run_trace_stream, StreamClient, StreamFeed, TONStreamClient};
 use ton::subscriber::TONSubscriber;
+use ton::ton_account_head::PgTONAccountHeadModel;
 use ton::ton_trace::PgTONTraceModel;
 use tonlib_core::TonAddress;
 
@@ -37,6 +42,15 @@ async fn main() -> anyhow::Result<()> {
     let gateway_account = TonAddress::from_base64_url(config.ton_gateway.as_str())?;
     let gas_service_account = TonAddress::from_base64_url(config.ton_gas_service.as_str())?;
     let its_account = TonAddress::from_base64_url(config.ton_its.as_str())?;
+    let relevant_accounts = if config.ton_local_completeness {
+        vec![
+            gateway_account.clone(),
+            gas_service_account.clone(),
+            its_account.clone(),
+        ]
+    } else {
+        vec![]
+    };
 
     let mut sigint = signal(SignalKind::interrupt())?;
     let mut sigterm = signal(SignalKind::terminate())?;
@@ -44,38 +58,124 @@ async fn main() -> anyhow::Result<()> {
     let redis_client = redis::Client::open(config.common_config.redis_server.clone())?;
     let redis_conn = connection_manager(redis_client, None, None, None).await?;
 
-    setup_heartbeat("heartbeat:subscriber".to_owned(), redis_conn, None);
+    setup_heartbeat("heartbeat:subscriber".to_owned(), redis_conn.clone(), None);
 
     let pg_pool = PgPool::connect(&config.common_config.postgres_url).await?;
 
     let ton_traces = PgTONTraceModel::new(pg_pool.clone());
+    let account_heads = Arc::new(PgTONAccountHeadModel::new(pg_pool.clone()));
 
     let mut handles: Vec<JoinHandle<()>> = vec![];
 
-    let client = TONRpcClient::new(config.ton_rpc.clone(), config.ton_api_key.clone(), 5, 5, 30)
+    let client = FailoverRestClient::from_config(&config, RequestPriority::Poll)
         .await
         .map_err(|e| error_stack::report!(SubscriberError::GenericError(e.to_string())))
         .expect("Failed to create RPC client");
-
-    for acct in [gateway_account.clone(), gas_service_account, its_account] {
-        let ton_sub = TONSubscriber::new(
+    handles.push(spawn_stats_reporter(Duration::from_secs(60)));
+
+    let stream_client: Option<Arc<dyn StreamClient>> = match &config.ton_streaming {
+        Some(streaming) => Some(Arc::new(
+            TONStreamClient::new(
+                streaming
+                    .url
+                    .clone()
+                    .unwrap_or_else(|| config.ton_rpc.clone()),
+                config.ton_api_key.clone(),
+                5,
+            )
+            .map_err(|e| error_stack::report!(SubscriberError::GenericError(e.to_string())))
+            .expect("Failed to create streaming client"),
+        )),
+        None => None,
+    };
+
+    let contracts = vec![gateway_account.clone(), gas_service_account, its_account];
+    if config.ton_subscribe_by_block {
+        let mut ton_sub = MasterchainTONSubscriber::new(
             client.clone(),
             postgres_db.clone(),
-            acct.to_string(),
+            "masterchain".to_string(),
             config.common_config.chain_name.clone(),
             ton_traces.clone(),
+            contracts,
         )
-        .await?;
+        .await?
+        .with_relevant_accounts(relevant_accounts.clone());
+        if let Some(blocks) = config.ton_blocks_per_poll {
+            ton_sub = ton_sub.with_blocks_per_poll(blocks);
+        }
+        if let Some(depth) = config.ton_finality_depth {
+            ton_sub = ton_sub.with_finality_depth(depth);
+        }
+        if let Some(retry) = &config.trace_retry {
+            ton_sub = ton_sub.with_retry_budget(retry.budget);
+        }
 
         let mut sub = Subscriber::new(ton_sub);
         let queue_clone = Arc::clone(&events_queue);
+        let acct = gateway_account.clone();
         let handle = tokio::spawn(async move {
             sub.run(acct, queue_clone).await;
         });
         handles.push(handle);
+    } else {
+        for acct in contracts {
+            let mut ton_sub = TONSubscriber::new(
+                client.clone(),
+                postgres_db.clone(),
+                acct.to_string(),
+                config.common_config.chain_name.clone(),
+                ton_traces.clone(),
+            )
+            .await?
+            .with_account_heads(Arc::clone(&account_heads))
+            .with_relevant_accounts(relevant_accounts.clone());
+            if let Some(depth) = config.ton_finality_depth {
+                ton_sub = ton_sub.with_finality_depth(depth);
+            }
+            if let Some(retry) = &config.trace_retry {
+                ton_sub = ton_sub.with_retry_budget(retry.budget);
+            }
+            if let (Some(stream_client), Some(streaming)) = (&stream_client, &config.ton_streaming)
+            {
+                let (sender, receiver) = tokio::sync::mpsc::channel(1000);
+                handles.push(tokio::spawn(run_trace_stream(
+                    Arc::clone(stream_client),
+                    vec![acct.clone()],
+                    sender,
+                )));
+                ton_sub = ton_sub.with_stream(StreamFeed::new(
+                    receiver,
+                    Duration::from_secs(streaming.catch_up_interval_secs),
+                ));
+            }
+
+            let mut sub = Subscriber::new(ton_sub);
+            let queue_clone = Arc::clone(&events_queue);
+            let handle = tokio::spawn(async move {
+                sub.run(acct, queue_clone).await;
+            });
+            handles.push(handle);
+        }
     }
 
-    let retry_subscriber = RetryTONSubscriber::new(client.clone(), ton_traces.clone()).await?;
+    let mut retry_subscriber = RetryTONSubscriber::new(client.clone(), ton_traces.clone())
+        .await?
+        .with_relevant_accounts(relevant_accounts)
+        .with_alert(Arc::new(RedisAbandonedTraceAlert::new(
+            redis_conn,
+            &config.common_config.chain_name,
+        )));
+    if let Some(depth) = config.ton_finality_depth {
+        retry_subscriber = retry_subscriber.with_finality_depth(depth);
+    }
+    if let Some(retry) = &config.trace_retry {
+        retry_subscriber = retry_subscriber.with_retry_policy(RetryPolicy {
+            budget: retry.budget,
+            base_delay: Duration::from_secs(retry.base_delay_secs),
+            max_delay: Duration::from_secs(retry.max_delay_secs),
+        });
+    }
     let mut sub = Subscriber::new(retry_subscriber);
     let events_clone = Arc::clone(&events_queue);
     let handle = tokio::spawn(async move {
@@ -0,0 +1,464 @@
+/*!
+
+Walks masterchain blocks in order and adds the traces of the watched accounts to a queue.
+
+Unlike [`TONSubscriber`](crate::subscriber::TONSubscriber), which polls the traces of every account
+separately by logical time, the cursor here is the masterchain seqno. Every block is read in full,
+and the cursor only moves past it once the traces of all transactions of the watched accounts in it
+were fetched, so nothing is skipped because the indexer lags behind for one of the accounts.
+
+With a finality depth, only blocks that are that deep are walked. A trace that continues into later
+blocks is stored as incomplete, so the retry subscriber picks it up once it is final.
+
+*/
+
+use super::client::RestClient;
+use crate::completeness::settle;
+use crate::finality::is_final;
+use crate::subscriber::trace_by_hash;
+use crate::ton_trace::{AtomicUpsert, TONTrace, DEFAULT_TRACE_RETRIES};
+use crate::types::Trace;
+use relayer_core::database::Database;
+use relayer_core::error::SubscriberError;
+use relayer_core::subscriber::{ChainTransaction, TransactionPoller};
+use std::collections::HashMap;
+use tonlib_core::TonAddress;
+use tracing::{debug, info, warn};
+
+pub const DEFAULT_BLOCKS_PER_POLL: u32 = 20;
+const TRACE_IDS_PER_REQUEST: usize = 50;
+
+pub struct MasterchainTONSubscriber<DB: Database, TM: AtomicUpsert, CL: RestClient> {
+    client: CL,
+    latest_seqno: Option<u32>,
+    db: DB,
+    context: String,
+    chain_name: String,
+    ton_trace_model: TM,
+    accounts: Vec<TonAddress>,
+    blocks_per_poll: u32,
+    finality_depth: Option<u64>,
+    retry_budget: i32,
+    relevant_accounts: Vec<TonAddress>,
+}
+
+impl<DB: Database, TM: AtomicUpsert, CL: RestClient> MasterchainTONSubscriber<DB, TM, CL> {
+    pub async fn new(
+        client: CL,
+        db: DB,
+        context: String,
+        chain_name: String,
+        ton_trace_model: TM,
+        accounts: Vec<TonAddress>,
+    ) -> Result<Self, SubscriberError> {
+        let latest_seqno = db
+            .get_latest_height(&chain_name, &context)
+            .await
+            .map_err(|e| SubscriberError::GenericError(e.to_string()))?
+            .map(|seqno| seqno as u32);
+
+        if let Some(seqno) = latest_seqno {
+            info!(
+                "TON masterchain subscriber for {}: starting after block {}",
+                context, seqno
+            );
+        }
+        Ok(MasterchainTONSubscriber {
+            client,
+            latest_seqno,
+            db,
+            context,
+            chain_name,
+            ton_trace_model,
+            accounts,
+            blocks_per_poll: DEFAULT_BLOCKS_PER_POLL,
+            finality_depth: None,
+            retry_budget: DEFAULT_TRACE_RETRIES,
+            relevant_accounts: vec![],
+        })
+    }
+
+    /// Masterchain blocks walked per poll at most.
+    pub fn with_blocks_per_poll(mut self, blocks: u32) -> Self {
+        self.blocks_per_poll = blocks.max(1);
+        self
+    }
+
+    /// Only walks blocks that are `depth` masterchain blocks deep.
+    pub fn with_finality_depth(mut self, depth: u64) -> Self {
+        self.finality_depth = Some(depth);
+        self
+    }
+
+    /// Retries an incomplete trace gets before it is abandoned.
+    pub fn with_retry_budget(mut self, retries: i32) -> Self {
+        self.retry_budget = retries;
+        self
+    }
+
+    /// Treats an incomplete trace as complete once every message in it that involves one of
+    /// `accounts` has been delivered.
+    pub fn with_relevant_accounts(mut self, accounts: Vec<TonAddress>) -> Self {
+        self.relevant_accounts = accounts;
+        self
+    }
+
+    /// Traces of the watched accounts in `[start, end]`, each with the first block it was seen
+    /// in. A block that fails to load ends the walk early, unless it is the first one.
+    async fn walk_blocks(
+        &self,
+        start: u32,
+        end: u32,
+    ) -> Result<(Vec<(String, u32)>, u32), anyhow::Error> {
+        let mut trace_ids: Vec<(String, u32)> = vec![];
+        let mut walked = None;
+        for seqno in start..=end {
+            let transactions = match self
+                .client
+                .get_transactions_by_masterchain_block(seqno)
+                .await
+            {
+                Ok(transactions) => transactions,
+                Err(err) => match walked {
+                    Some(_) => {
+                        warn!("Failed to load block {}, stopping here: {:?}", seqno, err);
+                        break;
+                    }
+                    None => return Err(err.into()),
+                },
+            };
+            for tx in transactions {
+                if self.accounts.contains(&tx.account)
+                    && !trace_ids.iter().any(|(id, _)| *id == tx.trace_id)
+                {
+                    trace_ids.push((tx.trace_id, seqno));
+                }
+            }
+            walked = Some(seqno);
+        }
+
+        Ok((trace_ids, walked.unwrap_or(end)))
+    }
+
+    /// Stores the traces and keeps the complete ones that are new or changed.
+    async fn unseen_traces(&self, traces: Vec<Trace>) -> Result<Vec<Trace>, anyhow::Error> {
+        let mut unseen_traces: Vec<Trace> = Vec::new();
+
+        for trace in traces {
+            let trace = settle(trace, &self.relevant_accounts);
+            let trace_model = TONTrace::from(&trace).with_retries(self.retry_budget);
+            if self
+                .ton_trace_model
+                .upsert_and_return_if_changed(trace_model)
+                .await?
+                .is_some()
+                && !trace.is_incomplete
+            {
+                debug!("Trace {} added from masterchain block", trace.trace_id);
+                unseen_traces.push(trace);
+            } else if trace.is_incomplete {
+                info!("Trace {} is incomplete, skipping", trace.trace_id);
+            } else {
+                info!("Trace {} already seen, skipping", trace.trace_id);
+            }
+        }
+
+        Ok(unseen_traces)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn store_latest_seqno(&mut self, seqno: u32) -> Result<(), SubscriberError> {
+        self.latest_seqno = Some(seqno);
+        self.db
+            .store_latest_height(&self.chain_name, &self.context, seqno as i64)
+            .await
+            .map_err(|e| SubscriberError::GenericError(e.to_string()))
+    }
+}
+
+impl<DB: Database, TM: AtomicUpsert, CL: RestClient> TransactionPoller
+    for MasterchainTONSubscriber<DB, TM, CL>
+{
+    type Transaction = Trace;
+    type Account = TonAddress;
+
+    fn make_queue_item(
+        &mut self,
+        tx: Self::Transaction,
+    ) -> Result<ChainTransaction, anyhow::Error> {
+        Ok(serde_json::to_string(&tx)?)
+    }
+
+    fn transaction_id(&self, tx: &Self::Transaction) -> Option<String> {
+        Some(tx.trace_id.to_string())
+    }
+
+    fn account_id(&self, account: &Self::Account) -> Option<String> {
+        Some(account.to_hex())
+    }
+
+    /// Walks the next blocks for all watched accounts, `_account_id` is only used by `Subscriber`.
+    #[tracing::instrument(skip(self))]
+    async fn poll_account(
+        &mut self,
+        _account_id: TonAddress,
+    ) -> Result<Vec<Self::Transaction>, anyhow::Error> {
+        let latest_mc_seqno = self.client.get_masterchain_info().await?.last.seqno;
+        let depth = self.finality_depth.unwrap_or(0);
+        let target = (latest_mc_seqno as u64).saturating_sub(depth) as u32;
+
+        let start = match self.latest_seqno {
+            Some(seqno) => seqno.saturating_add(1),
+            None => {
+                info!("No masterchain cursor yet, starting at block {}", target);
+                target
+            }
+        };
+        if start > target {
+            debug!("No new masterchain blocks after {}", target);
+            return Ok(Vec::new());
+        }
+        let end = target.min(start.saturating_add(self.blocks_per_poll - 1));
+
+        let (trace_ids, mut walked) = self.walk_blocks(start, end).await?;
+        info!(
+            "Found {} traces of watched accounts in blocks {} to {}",
+            trace_ids.len(),
+            start,
+            walked
+        );
+
+        let mut traces: HashMap<String, Trace> = HashMap::new();
+        let ids: Vec<String> = trace_ids.iter().map(|(id, _)| id.clone()).collect();
+        for chunk in ids.chunks(TRACE_IDS_PER_REQUEST) {
+            let window = self
+                .client
+                .get_traces_for_account(None, Some(chunk.to_vec()), None)
+                .await?;
+            traces.extend(
+                window
+                    .traces
+                    .into_iter()
+                    .map(|trace| (trace.trace_id.clone(), trace)),
+            );
+        }
+
+        // A trace that isn't indexed yet is fetched again with its block next time
+        if let Some((id, seqno)) = trace_ids.iter().find(|(id, _)| !traces.contains_key(id)) {
+            warn!(
+                "Trace {} from block {} is not indexed yet, holding the cursor before it",
+                id, seqno
+            );
+            walked = walked.min(seqno.saturating_sub(1));
+        }
+
+        let mut found = vec![];
+        for (id, seqno) in trace_ids {
+            if seqno > walked {
+                break;
+            }
+            let Some(mut trace) = traces.remove(&id) else {
+                continue;
+            };
+            if self.finality_depth.is_some() && !is_final(&trace, latest_mc_seqno as u64, depth) {
+                info!(
+                    "Trace {} is not final yet, leaving it for retries",
+                    trace.trace_id
+                );
+                trace.is_incomplete = true;
+            }
+            found.push(trace);
+        }
+
+        let unseen_traces = self.unseen_traces(found).await?;
+
+        if walked >= start {
+            if let Err(err) = self.store_latest_seqno(walked).await {
+                warn!("{:?}", err);
+            }
+        }
+
+        Ok(unseen_traces)
+    }
+
+    /// Like `TONSubscriber::poll_tx`, without touching the masterchain cursor.
+    #[tracing::instrument(skip(self))]
+    async fn poll_tx(&mut self, tx_hash: String) -> Result<Self::Transaction, anyhow::Error> {
+        let trace = trace_by_hash(
+            &self.client,
+            &tx_hash,
+            &self.relevant_accounts,
+            self.finality_depth,
+        )
+        .await?;
+
+        let trace_model = TONTrace::from(&trace).with_retries(self.retry_budget);
+        if self
+            .ton_trace_model
+            .upsert_and_return_if_changed(trace_model)
+            .await?
+            .is_none()
+        {
+            info!(
+                "Trace {} was seen before, returning it again",
+                trace.trace_id
+            );
+        }
+
+        Ok(trace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::TONRpcClient;
+    use crate::test_utils::fixtures::fixture_traces;
+    use crate::ton_trace::MockAtomicUpsert;
+    use httpmock::Method::GET;
+    use httpmock::MockServer;
+    use mockall::predicate::eq;
+    use relayer_core::database::MockDatabase;
+    use serde_json::json;
+
+    // Traces as Toncenter returns them
+    fn traces_body(traces: &[Trace]) -> serde_json::Value {
+        let traces: Vec<_> = traces
+            .iter()
+            .map(|trace| {
+                json!({
+                    "is_incomplete": trace.is_incomplete,
+                    "start_lt": trace.start_lt.to_string(),
+                    "end_lt": trace.end_lt.to_string(),
+                    "trace_id": trace.trace_id,
+                    "transactions": trace
+                        .transactions
+                        .iter()
+                        .map(|tx| (tx.hash.clone(), json!(tx)))
+                        .collect::<serde_json::Map<_, _>>(),
+                    "transactions_order": trace
+                        .transactions
+                        .iter()
+                        .map(|tx| tx.hash.clone())
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        json!({ "traces": traces })
+    }
+
+    // Blocks 100 and 101 with a trace of the gateway each, and a transaction of another account
+    fn mock_chain(server: &MockServer, indexed: &[Trace]) {
+        let traces = fixture_traces();
+        server.mock(|when, then| {
+            when.method(GET).path("/api/v3/masterchainInfo");
+            then.status(200).json_body(json!({
+                "first": {"workchain": -1, "shard": "8000000000000000", "seqno": 1},
+                "last": {"workchain": -1, "shard": "8000000000000000", "seqno": 101},
+            }));
+        });
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/api/v3/transactionsByMasterchainBlock")
+                .query_param("seqno", "100");
+            then.status(200)
+                .json_body(json!({"transactions": traces[1].transactions}));
+        });
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/api/v3/transactionsByMasterchainBlock")
+                .query_param("seqno", "101");
+            then.status(200).json_body(json!({
+                "transactions": [traces[0].transactions[0], traces[3].transactions[1]]
+            }));
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/api/v3/traces");
+            then.status(200).json_body(traces_body(indexed));
+        });
+    }
+
+    async fn subscriber(
+        server: &MockServer,
+        mock_db: MockDatabase,
+    ) -> MasterchainTONSubscriber<MockDatabase, MockAtomicUpsert, TONRpcClient> {
+        let client = TONRpcClient::new(server.base_url(), "test".to_string(), 0, 5, 5)
+            .await
+            .unwrap();
+        let mut mock_upsert = MockAtomicUpsert::new();
+        mock_upsert
+            .expect_upsert_and_return_if_changed()
+            .returning(|trace| Box::pin(async move { Ok(Some(trace)) }));
+        let gateway = fixture_traces()[1].transactions[1].account.clone();
+
+        MasterchainTONSubscriber::new(
+            client,
+            mock_db,
+            "masterchain".to_string(),
+            "test-chain".to_string(),
+            mock_upsert,
+            vec![gateway],
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_poll_walks_blocks_after_cursor() {
+        let server = MockServer::start();
+        let traces = fixture_traces();
+        mock_chain(&server, &[traces[1].clone(), traces[3].clone()]);
+
+        let mut mock_db = MockDatabase::new();
+        mock_db
+            .expect_get_latest_height()
+            .returning(|_, _| Ok(Some(99)));
+        mock_db
+            .expect_store_latest_height()
+            .with(eq("test-chain"), eq("masterchain"), eq(101))
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        let mut subscriber = subscriber(&server, mock_db).await;
+        let account = traces[1].transactions[1].account.clone();
+
+        let result = subscriber.poll_account(account.clone()).await.unwrap();
+
+        let ids: Vec<_> = result.iter().map(|trace| trace.trace_id.clone()).collect();
+        assert_eq!(
+            ids,
+            vec![traces[1].trace_id.clone(), traces[3].trace_id.clone()]
+        );
+
+        // Caught up with the masterchain
+        assert!(subscriber.poll_account(account).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_poll_holds_cursor_before_unindexed_trace() {
+        let server = MockServer::start();
+        let traces = fixture_traces();
+        mock_chain(&server, &[traces[1].clone()]);
+
+        let mut mock_db = MockDatabase::new();
+        mock_db
+            .expect_get_latest_height()
+            .returning(|_, _| Ok(Some(99)));
+        mock_db
+            .expect_store_latest_height()
+            .with(eq("test-chain"), eq("masterchain"), eq(100))
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        let mut subscriber = subscriber(&server, mock_db).await;
+
+        let result = subscriber
+            .poll_account(traces[1].transactions[1].account.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].trace_id, traces[1].trace_id);
+        assert_eq!(subscriber.latest_seqno, Some(100));
+    }
+}
@@ -0,0 +1,124 @@
+/*!
+Parses the internal message asking the gateway to call a contract, e.g. the one ITS sends
+alongside its logs. The gateway emits a `CallContractMessage` log for it, with the same
+destination and the keccak256 hash of the payload.
+
+# Usage Example
+
+```rust,no_run
+use ton::boc::call_contract_request::CallContractRequest;
+
+let boc = "te6cck...";
+
+match CallContractRequest::from_boc_b64(boc) {
+    Ok(request) => {
+        let payload_hash = request.payload_hash();
+        // ...
+    },
+    Err(e) => println!("Failed to parse message: {:?}", e),
+}
+```
+
+# See also
+
+- `CallContractMessage`
+
+*/
+
+use crate::boc::cell_to::CellTo;
+use crate::boc::op_code::compare_op_code;
+use crate::error::BocError;
+use crate::error::BocError::{BocParsingError, InvalidOpCode};
+use crate::hashing::keccak256;
+use crate::ton_constants::OP_CALL_CONTRACT;
+use tonlib_core::cell::Cell;
+use tonlib_core::tlb_types::tlb::TLB;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallContractRequest {
+    pub destination_chain: String,
+    pub destination_address: String,
+    pub payload: Vec<u8>,
+}
+
+impl CallContractRequest {
+    pub fn from_boc_b64(boc: &str) -> Result<Self, BocError> {
+        let cell = Cell::from_boc_b64(boc).map_err(|err| BocParsingError(err.to_string()))?;
+        let mut parser = cell.parser();
+
+        let op_code = parser
+            .load_bits(32)
+            .map_err(|err| BocParsingError(err.to_string()))?;
+
+        if !compare_op_code(OP_CALL_CONTRACT, &op_code) {
+            return Err(InvalidOpCode(format!(
+                "Expected {:08X}, got {}",
+                OP_CALL_CONTRACT,
+                hex::encode(&op_code)
+            )));
+        }
+
+        let destination_chain = parser
+            .next_reference()
+            .map_err(|err| BocParsingError(err.to_string()))?
+            .cell_to_string()?;
+
+        let destination_address = parser
+            .next_reference()
+            .map_err(|err| BocParsingError(err.to_string()))?
+            .cell_to_string()?;
+
+        let payload = parser
+            .next_reference()
+            .map_err(|err| BocParsingError(err.to_string()))?
+            .cell_to_buffer()?;
+
+        Ok(Self {
+            destination_chain,
+            destination_address,
+            payload,
+        })
+    }
+
+    pub fn payload_hash(&self) -> [u8; 32] {
+        keccak256(&self.payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::prelude::BASE64_STANDARD;
+    use base64::Engine;
+
+    #[test]
+    fn test_from_boc_b64() {
+        let boc = "te6cckEBBQEA+QADCAAAAAkBAgMADGF4ZWxhcgCCYXhlbGFyMTU3aGw3Z3B1a25qbWh0YWMycW5waHVhenYyeWVyZmFndmE3bHN1OXZ1ajJwZ24zMnoyMnFhMjZkazQBwAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAGAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAGAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAACQQAgAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAgng1/Jzdm961/8+C457Yl7CZ+i4/y0JoQNR+2e+oojKF62TbT";
+        let request = CallContractRequest::from_boc_b64(boc).unwrap();
+
+        assert_eq!(request.destination_chain, "axelar");
+        assert_eq!(
+            request.destination_address,
+            "axelar157hl7gpuknjmhtac2qnphuazv2yerfagva7lsu9vuj2pgn32z22qa26dk4"
+        );
+        assert_eq!(
+            BASE64_STANDARD.encode(&request.payload),
+            "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAYAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAYAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAJAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAACCeDX8nN2b3rX/z4LjntiXsJn6Lj/LQmhA1H7Z76iiMoQ=="
+        );
+        // Same as the payload hash in the gateway's call contract log
+        assert_eq!(
+            BASE64_STANDARD.encode(request.payload_hash()),
+            "IQBup4rxdld80lWFcBVi97AwbcGzoxMLL3V3EIFAMLc="
+        );
+    }
+
+    #[test]
+    fn test_wrong_op_code() {
+        // Call contract log of the gateway, which carries no op code
+        let boc = "te6cckEBBAEA5QADg4AcPMZ9bgNiMWiFLuLZ3ODT3Qj2rbcRiS/f1NA9opZaWPXUykhs4AH2lBVEFjqex7VaPbPTvuLH5GEs5sIeXm+pcAECAwAcYXZhbGFuY2hlLWZ1amkAVDB4ZDcwNjdBZTNDMzU5ZTgzNzg5MGIyOEI3QkQwZDIwODRDZkRmNDliNQDAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAACAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAE0hlbGxvIGZyb20gUmVsYXllciEAAAAAAAAAAAAAAAAAne0F4Q==";
+        assert!(matches!(
+            CallContractRequest::from_boc_b64(boc),
+            Err(InvalidOpCode(_))
+        ));
+    }
+}
@@ -0,0 +1,148 @@
+/*!
+
+Log emitted by ITS when an interchain transfer from another chain is executed on TON.
+
+The layout mirrors the outgoing `LogITSInterchainTransferMessage`: token id and amount are
+stored inline, source chain, source address, recipient and data are references.
+
+# Usage Example
+```rust,no_run
+use ton::boc::its_interchain_transfer_received::LogITSInterchainTransferReceivedMessage;
+
+let boc_b64 = "b64 boc";
+
+match LogITSInterchainTransferReceivedMessage::from_boc_b64(boc_b64) {
+    Ok(log) => {
+        // Handle fields
+    }
+    Err(e) => println!("Failed to parse message: {:?}", e),
+}
+```
+
+# See also
+
+- `LogITSInterchainTransferMessage`
+
+*/
+
+use crate::boc::cell_to::CellTo;
+use crate::boc::op_code::compare_op_code;
+use crate::error::BocError;
+use crate::error::BocError::{BocParsingError, InvalidOpCode};
+use crate::ton_constants::{OP_INTERCHAIN_TRANSFER_RECEIVED_LOG, WORKCHAIN};
+use num_bigint::BigUint;
+use tonlib_core::cell::Cell;
+use tonlib_core::tlb_types::tlb::TLB;
+use tonlib_core::{TonAddress, TonHash};
+
+#[derive(Debug, Clone)]
+pub struct LogITSInterchainTransferReceivedMessage {
+    pub(crate) token_id: BigUint,
+    pub(crate) source_chain: String,
+    pub(crate) source_address: String,
+    pub(crate) recipient: TonAddress,
+    pub(crate) jetton_amount: BigUint,
+    pub(crate) data: Vec<u8>,
+}
+
+impl LogITSInterchainTransferReceivedMessage {
+    pub fn from_boc_b64(boc_b64: &str) -> Result<Self, BocError> {
+        let cell = Cell::from_boc_b64(boc_b64).map_err(|err| BocParsingError(err.to_string()))?;
+        let mut parser = cell.parser();
+
+        let op_code = parser
+            .load_bits(32)
+            .map_err(|err| BocParsingError(err.to_string()))?;
+
+        if !compare_op_code(OP_INTERCHAIN_TRANSFER_RECEIVED_LOG, &op_code) {
+            return Err(InvalidOpCode(format!(
+                "Expected {:08X}, got {}",
+                OP_INTERCHAIN_TRANSFER_RECEIVED_LOG,
+                hex::encode(&op_code)
+            )));
+        }
+
+        let token_id = parser
+            .load_uint(256)
+            .map_err(|err| BocParsingError(err.to_string()))?;
+
+        let source_chain = parser
+            .next_reference()
+            .map_err(|err| BocParsingError(err.to_string()))?
+            .cell_to_string()?;
+
+        let source_address = parser
+            .next_reference()
+            .map_err(|err| BocParsingError(err.to_string()))?
+            .cell_to_buffer()?;
+        let source_address = format!("0x{}", hex::encode(&source_address));
+
+        let recipient: TonHash = parser
+            .next_reference()
+            .map_err(|err| BocParsingError(err.to_string()))?
+            .parser()
+            .load_bits(256)
+            .map_err(|err| BocParsingError(err.to_string()))?
+            .try_into()
+            .map_err(|_| BocParsingError("Invalid recipient length".to_string()))?;
+        let recipient = TonAddress::new(WORKCHAIN, recipient);
+
+        let jetton_amount = parser
+            .load_uint(256)
+            .map_err(|err| BocParsingError(err.to_string()))?;
+
+        let data: Vec<u8> = parser
+            .next_reference()
+            .map_err(|err| BocParsingError(err.to_string()))?
+            .data()
+            .into();
+
+        Ok(Self {
+            token_id,
+            source_chain,
+            source_address,
+            recipient,
+            jetton_amount,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::boc::its_interchain_transfer_received::LogITSInterchainTransferReceivedMessage;
+    use std::str::FromStr;
+    use tonlib_core::TonAddress;
+
+    #[test]
+    fn test_from_boc_b64() {
+        let log = LogITSInterchainTransferReceivedMessage::from_boc_b64(
+            "te6ccgEBBQEAlAAEiAAAAQYGqiTxVdSBp9+zVVBJQeRlDVIDh8DkrUzNRniAwuoJUwAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD0JAAQIDBAAcYXZhbGFuY2hlLWZ1amkAKBI0VniQEjRWeJASNFZ4kBI0VniQAEAf4PoOeCiJKMoFkIxhf7kL3H7d5CnhRsoEi9zTHIkOdgAA",
+        )
+        .unwrap();
+        assert_eq!(
+            log.token_id.to_str_radix(16),
+            "6aa24f155d481a7dfb355504941e4650d520387c0e4ad4ccd467880c2ea0953"
+        );
+        assert_eq!(log.source_chain, "avalanche-fuji");
+        assert_eq!(
+            log.source_address,
+            "0x1234567890123456789012345678901234567890"
+        );
+        assert_eq!(
+            log.recipient,
+            TonAddress::from_str("EQAf4PoOeCiJKMoFkIxhf7kL3H7d5CnhRsoEi9zTHIkOdh-4").unwrap()
+        );
+        assert_eq!(log.jetton_amount.to_string(), "1000000");
+        assert!(log.data.is_empty());
+    }
+
+    #[test]
+    fn test_wrong_op_code() {
+        // Outgoing interchain transfer log
+        let log = LogITSInterchainTransferReceivedMessage::from_boc_b64(
+            "te6cckEBBgEAlwAEiAAAARAGqiTxVdSBp9+zVVBJQeRlDVIDh8DkrUzNRniAwuoJUwAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABAQIDBABAH+D6DngoiSjKBZCMYX+5C9x+3eQp4UbKBIvc0xyJDnYAHGF2YWxhbmNoZS1mdWppAQAFAAAAKBI0VniQEjRWeJASNFZ4kBI0VniQPA51Og==",
+        );
+        assert!(log.is_err());
+    }
+}
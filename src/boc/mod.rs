@@ -1,10 +1,12 @@
 pub mod approve_message;
 mod buffer_to_cell;
 pub mod call_contract;
+pub mod call_contract_request;
 pub mod cc_message;
 pub mod cell_to;
 pub mod its_interchain_token_deployment_started;
 pub mod its_interchain_transfer;
+pub mod its_interchain_transfer_received;
 pub mod its_link_token_started;
 pub mod its_token_metadata_registered;
 pub mod jetton_gas_added;
@@ -22,16 +22,17 @@ let ton_cell = message.to_cell().unwrap();
 */
 
 use crate::error::BocError;
-use crate::error::BocError::BocEncodingError;
+use crate::error::BocError::{BocEncodingError, BocParsingError, InvalidOpCode};
 use crate::ton_constants::OP_NATIVE_REFUND;
 use num_bigint::BigUint;
 use serde::{Deserialize, Serialize};
 use tonlib_core::cell::{Cell, CellBuilder};
+use tonlib_core::tlb_types::tlb::TLB;
 use tonlib_core::{TonAddress, TonHash};
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct NativeRefundMessage {
-    tx_hash: TonHash,
+    pub(crate) tx_hash: TonHash,
     address: TonAddress,
     amount: BigUint,
 }
@@ -61,6 +62,37 @@ impl NativeRefundMessage {
             .map_err(|e| BocEncodingError(e.to_string()))?;
         builder.build().map_err(|e| BocEncodingError(e.to_string()))
     }
+
+    /// Parses a refund message sent to the gas service, e.g. to find the one of a broadcast.
+    pub fn from_boc_b64(boc_b64: &str) -> Result<Self, BocError> {
+        let cell = Cell::from_boc_b64(boc_b64).map_err(|err| BocParsingError(err.to_string()))?;
+        let mut parser = cell.parser();
+        let op_code = parser
+            .load_bits(32)
+            .map_err(|err| BocParsingError(err.to_string()))?;
+        if hex::encode(&op_code) != format!("{OP_NATIVE_REFUND:08x}") {
+            return Err(InvalidOpCode(format!(
+                "Expected {:?}, got {:?}",
+                OP_NATIVE_REFUND,
+                hex::encode(op_code)
+            )));
+        }
+        let tx_hash = parser
+            .load_tonhash()
+            .map_err(|err| BocParsingError(err.to_string()))?;
+        let address = parser
+            .load_address()
+            .map_err(|err| BocParsingError(err.to_string()))?;
+        let amount = parser
+            .load_coins()
+            .map_err(|err| BocParsingError(err.to_string()))?;
+
+        Ok(Self {
+            tx_hash,
+            address,
+            amount,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -85,4 +117,24 @@ mod tests {
         let res = message.to_cell().unwrap();
         assert_eq!(res.to_boc_b64(true).unwrap(), "te6cckEBAQEATQAAlQAAAEYrVO3uCB/HzeOFWYjU0o2Ui5nhMrBi9qckgScT7FMyFYAI0NRYDNjwlSK+fAOQp6YyvaSpkpHENbdnyVNn6+eOmuoCSCAiAVsrXmA=");
     }
+
+    #[test]
+    fn test_from_boc_b64() {
+        let address: TonAddress = "EQBGhqLAZseEqRXz4ByFPTGV7SVMlI4hrbs-Sps_Xzx01x8G"
+            .parse()
+            .unwrap();
+        let tx_hash =
+            TonHash::from_hex("2b54edee081fc7cde3855988d4d28d948b99e132b062f6a724812713ec533215")
+                .unwrap();
+        let amount = BigUint::from_str("4900000000").unwrap();
+
+        let res = super::NativeRefundMessage::from_boc_b64("te6cckEBAQEATQAAlQAAAEYrVO3uCB/HzeOFWYjU0o2Ui5nhMrBi9qckgScT7FMyFYAI0NRYDNjwlSK+fAOQp6YyvaSpkpHENbdnyVNn6+eOmuoCSCAiAVsrXmA=").unwrap();
+        assert_eq!(res, super::NativeRefundMessage::new(tx_hash, address, amount));
+    }
+
+    #[test]
+    fn test_from_boc_b64_wrong_op() {
+        let res = super::NativeRefundMessage::from_boc_b64("te6cckEBAQEASAAAi+sGXZ2TA0nQZDuUbZYexgD4DV5fMKsB328TYkPuUDXCgBw8xn1uA2IxaIUu4tnc4NPdCPattxGJL9/U0D2illpY6B/RX5+w1u5M");
+        assert!(res.is_err());
+    }
 }
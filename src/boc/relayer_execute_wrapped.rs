@@ -1,5 +1,6 @@
 /*!
-Parses a wallet-wrapped RelayerExecuteMessage. We only care about the message id.
+Parses a RelayerExecuteMessage, either wallet-wrapped or as the gateway receives it. We only care
+about the message id.
 
 
 # Usage Example
@@ -21,7 +22,8 @@ match RelayerExecuteWrappedMessage::from_boc_b64(boc) {
 
 use crate::boc::cell_to::CellTo;
 use crate::error::BocError;
-use crate::error::BocError::BocParsingError;
+use crate::error::BocError::{BocParsingError, InvalidOpCode};
+use crate::ton_constants::OP_RELAYER_EXECUTE;
 use serde::{Deserialize, Serialize};
 use tonlib_core::cell::{Cell, CellParser};
 use tonlib_core::tlb_types::tlb::TLB;
@@ -51,7 +53,28 @@ impl RelayerExecuteWrappedMessage {
         let cell = parser
             .next_reference()
             .map_err(|err| BocParsingError(err.to_string()))?;
+
+        Self::from_message_cell(&cell)
+    }
+
+    /// Parses the relayer execute message itself, e.g. one of several a wallet message carries.
+    pub fn from_message_boc_b64(boc: &str) -> Result<RelayerExecuteWrappedMessage, BocError> {
+        let cell = Cell::from_boc_b64(boc).map_err(|err| BocParsingError(err.to_string()))?;
+        Self::from_message_cell(&cell)
+    }
+
+    fn from_message_cell(cell: &Cell) -> Result<RelayerExecuteWrappedMessage, BocError> {
         let mut parser: CellParser = cell.parser();
+        let op_code = parser
+            .load_bits(32)
+            .map_err(|err| BocParsingError(err.to_string()))?;
+        if hex::encode(&op_code) != format!("{OP_RELAYER_EXECUTE:08x}") {
+            return Err(InvalidOpCode(format!(
+                "Expected {:?}, got {:?}",
+                OP_RELAYER_EXECUTE,
+                hex::encode(op_code)
+            )));
+        }
         let cell = parser
             .next_reference()
             .map_err(|err| BocParsingError(err.to_string()))?;
@@ -90,4 +113,24 @@ mod tests {
         );
         assert_eq!(res.source_chain, "ton2");
     }
+
+    #[test]
+    fn test_from_message_boc_b64() {
+        let boc = "te6cckECCAEAAYYAAUsAAAAIgBIHqwhg5lg4ES2+GWhwn4EVgGvmj7MoTr6OJXwhB8BysAEEAAIHAwQAhDB4NTg0ZGVmY2RjOTBmMmRmNjU3YjNhY2ZlNDg0ZjhlOWExODc5NDVhY2JlYjQ5MjgzN2E0MjkxOGM2ZWEyZTAwOQCEMDplMWU2MzNlYjcwMWIxMThiNDQyOTc3MTZjZWU3MDY5ZWU4NDdiNTZkYjg4YzQ5N2VmZWE2ODFlZDE0YjJkMmM3A4CuplJDZwAPtKCqILHU9j2q0e2enfcWPyMJZzYQ8vN9SwAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAmJaABQYHAMAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAIAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAATSGVsbG8gZnJvbSBSZWxheWVyIQAAAAAAAAAAAAAAAAAAQO0i3zQhmuJgOf2XfY5BmuFNeLGS6dtdz6NZeJkJZHDRAAh0b24yIG6F4g==";
+        let res = RelayerExecuteWrappedMessage::from_message_boc_b64(boc).unwrap();
+        assert_eq!(
+            res.message_id,
+            "0x584defcdc90f2df657b3acfe484f8e9a187945acbeb492837a42918c6ea2e009"
+        );
+
+        // The wallet message wrapping it is not a relayer execute message
+        let wrapped = "te6cckECDAEAAdcAARiuQuWkAAAAAAAAAAEBAgoOw8htAgIDAAABaCIAAAylVschFfb6H/c6NrbJTptO3VkqNFNOqJuDdTS8Wx0gk9HMAAAAAAAAAAAAAAAAAAEEAUsAAAAIgBIHqwhg5lg4ES2+GWhwn4EVgGvmj7MoTr6OJXwhB8BysAUEAAYLBwgAhDB4NTg0ZGVmY2RjOTBmMmRmNjU3YjNhY2ZlNDg0ZjhlOWExODc5NDVhY2JlYjQ5MjgzN2E0MjkxOGM2ZWEyZTAwOQCEMDplMWU2MzNlYjcwMWIxMThiNDQyOTc3MTZjZWU3MDY5ZWU4NDdiNTZkYjg4YzQ5N2VmZWE2ODFlZDE0YjJkMmM3A4CuplJDZwAPtKCqILHU9j2q0e2enfcWPyMJZzYQ8vN9SwAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAmJaACQoLAMAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAIAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAATSGVsbG8gZnJvbSBSZWxheWVyIQAAAAAAAAAAAAAAAAAAQO0i3zQhmuJgOf2XfY5BmuFNeLGS6dtdz6NZeJkJZHDRAAh0b24yL0nVVw==";
+        assert!(RelayerExecuteWrappedMessage::from_message_boc_b64(wrapped).is_err());
+        assert_eq!(
+            RelayerExecuteWrappedMessage::from_boc_b64(wrapped)
+                .unwrap()
+                .message_id,
+            res.message_id
+        );
+    }
 }
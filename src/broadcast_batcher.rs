@@ -0,0 +1,192 @@
+/*!
+
+Collects actions from concurrent broadcasts so they can be sent as one highload wallet
+external message.
+
+The first broadcast to arrive at an empty batch becomes its leader: it waits for the batching
+window to pass (or for the batch to fill up), takes the batch and sends it. Every other broadcast
+only waits for its result. There is no background task, so a batcher does nothing unless
+broadcasts go through it.
+
+Actions are built only once the wallet for the batch is known, because e.g. execute messages
+carry the relayer (wallet) address.
+
+# Usage Example
+
+```rust,no_run
+use std::time::Duration;
+use ton::broadcast_batcher::BroadcastBatcher;
+
+let batcher = BroadcastBatcher::new(Duration::from_millis(200), 50);
+```
+
+# Note
+
+If the leader's future is dropped while waiting, the batch is left without a leader and its
+broadcasts never complete. The includer always drives broadcasts to completion, so this only
+matters on shutdown.
+
+# See also
+
+- `TONBroadcaster::with_batching`
+
+*/
+
+use crate::client::V3MessageResponse;
+use crate::ton_broadcast::BroadcastMessage;
+use crate::ton_wallet_high_load_v3::MAX_ACTIONS_PER_TRANSFER;
+use relayer_core::error::BroadcasterError;
+use std::time::Duration;
+use tokio::sync::{oneshot, Mutex, Notify};
+use tonlib_core::tlb_types::block::out_action::OutAction;
+use tonlib_core::TonAddress;
+
+/// Bigger batches would be chained over extra internal transfers to self, each a transaction of its own.
+pub const MAX_BATCH_ACTIONS: usize = MAX_ACTIONS_PER_TRANSFER;
+
+/// Builds the action of a single broadcast, given the address of the wallet sending it.
+pub(crate) type ActionBuilder =
+    Box<dyn FnOnce(&TonAddress) -> Result<OutAction, BroadcasterError> + Send>;
+
+/// The outer error means the action was never sent (e.g. no wallet was available), the inner one
+/// is the result of sending it.
+pub(crate) type SendOutcome = Result<Result<V3MessageResponse, BroadcasterError>, BroadcasterError>;
+
+pub(crate) struct PendingAction {
+    pub(crate) build: ActionBuilder,
+    pub(crate) message: BroadcastMessage,
+    pub(crate) result: oneshot::Sender<SendOutcome>,
+}
+
+#[derive(Default)]
+struct BatchState {
+    pending: Vec<PendingAction>,
+    has_leader: bool,
+}
+
+pub struct BroadcastBatcher {
+    state: Mutex<BatchState>,
+    full: Notify,
+    window: Duration,
+    max_actions: usize,
+}
+
+impl BroadcastBatcher {
+    pub fn new(window: Duration, max_actions: usize) -> Self {
+        BroadcastBatcher {
+            state: Mutex::new(BatchState::default()),
+            full: Notify::new(),
+            window,
+            max_actions: max_actions.clamp(1, MAX_BATCH_ACTIONS),
+        }
+    }
+
+    /// Adds an action to the current batch. Returns true if the caller became the leader of
+    /// the batch and is responsible for sending it.
+    pub(crate) async fn push(&self, action: PendingAction) -> bool {
+        let mut state = self.state.lock().await;
+        state.pending.push(action);
+        if state.pending.len() >= self.max_actions {
+            self.full.notify_one();
+        }
+        if state.has_leader {
+            false
+        } else {
+            state.has_leader = true;
+            true
+        }
+    }
+
+    /// Waits until the batching window has passed or the batch is full.
+    pub(crate) async fn wait(&self) {
+        tokio::select! {
+            _ = tokio::time::sleep(self.window) => {}
+            _ = self.full.notified() => {}
+        }
+    }
+
+    /// Takes up to `max_actions` actions. The second value is true if actions are left over,
+    /// in which case the leader stays in charge of sending them as well.
+    pub(crate) async fn take(&self) -> (Vec<PendingAction>, bool) {
+        let mut state = self.state.lock().await;
+        if state.pending.len() > self.max_actions {
+            let rest = state.pending.split_off(self.max_actions);
+            (std::mem::replace(&mut state.pending, rest), true)
+        } else {
+            state.has_leader = false;
+            (std::mem::take(&mut state.pending), false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ton_broadcast::BroadcastKind;
+
+    fn pending() -> (PendingAction, oneshot::Receiver<SendOutcome>) {
+        let (sender, receiver) = oneshot::channel();
+        let action = PendingAction {
+            build: Box::new(|_: &TonAddress| {
+                Err(BroadcasterError::GenericError("unused".to_string()))
+            }),
+            message: BroadcastMessage::new(
+                BroadcastKind::Execute,
+                "0xabc-1".to_string(),
+                "avalanche-fuji".to_string(),
+            ),
+            result: sender,
+        };
+        (action, receiver)
+    }
+
+    #[tokio::test]
+    async fn test_first_push_leads() {
+        let batcher = BroadcastBatcher::new(Duration::from_millis(10), 10);
+
+        assert!(batcher.push(pending().0).await);
+        assert!(!batcher.push(pending().0).await);
+        assert!(!batcher.push(pending().0).await);
+
+        let (batch, more) = batcher.take().await;
+        assert_eq!(batch.len(), 3);
+        assert!(!more);
+
+        // Next batch gets a new leader
+        assert!(batcher.push(pending().0).await);
+    }
+
+    #[tokio::test]
+    async fn test_take_respects_max_actions() {
+        let batcher = BroadcastBatcher::new(Duration::from_secs(60), 2);
+
+        for _ in 0..5 {
+            batcher.push(pending().0).await;
+        }
+
+        // Batch is full, so waiting does not take the whole window
+        tokio::time::timeout(Duration::from_secs(1), batcher.wait())
+            .await
+            .unwrap();
+
+        let (batch, more) = batcher.take().await;
+        assert_eq!(batch.len(), 2);
+        assert!(more);
+        let (batch, more) = batcher.take().await;
+        assert_eq!(batch.len(), 2);
+        assert!(more);
+        let (batch, more) = batcher.take().await;
+        assert_eq!(batch.len(), 1);
+        assert!(!more);
+
+        assert!(batcher.push(pending().0).await);
+    }
+
+    #[test]
+    fn test_max_actions_is_clamped() {
+        let batcher = BroadcastBatcher::new(Duration::from_millis(10), 1000);
+        assert_eq!(batcher.max_actions, MAX_BATCH_ACTIONS);
+        let batcher = BroadcastBatcher::new(Duration::from_millis(10), 0);
+        assert_eq!(batcher.max_actions, 1);
+    }
+}
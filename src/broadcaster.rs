@@ -7,17 +7,33 @@ Broadcaster implementation for TON. Listens to GATEWAY_TX (essentially APPROVE m
 Relayer code assumes there is one message per transaction. This might not be a safe assumption,
 and broadcaster should potentially be returning a vector of BroadcastResults.
 
+When a `BroadcastLog` is set, every message accepted by Toncenter is recorded there so the
+`ConfirmationTracker` can follow it up. The broadcaster also implements `Resender`, which the
+tracker uses to sign expired messages again.
+
+# Batching
+
+With `with_batching`, approve, execute and refund actions of concurrent broadcasts are collected
+by a `BroadcastBatcher` and sent through one wallet as a single external message, spending one
+query id. Every task still gets its own result, all sharing the hash of that message.
+
 */
 
 use super::client::{RestClient, V3MessageResponse};
 use crate::boc::approve_message::ApproveMessages;
 use crate::boc::native_refund::NativeRefundMessage;
+use crate::broadcast_batcher::{ActionBuilder, BroadcastBatcher, PendingAction, SendOutcome};
+use crate::confirmation_tracker::Resender;
 use crate::gas_estimator::GasEstimator;
 use crate::high_load_query_id_db_wrapper::HighLoadQueryIdWrapper;
 use crate::out_action::out_action;
 use crate::relayer_execute_message::RelayerExecuteMessage;
+use crate::ton_broadcast::{
+    BroadcastKind, BroadcastLog, BroadcastMessage, BroadcastStatus, TONBroadcast,
+};
 use crate::ton_constants::REFUND_DUST;
 use crate::ton_wallet_high_load_v3::TonWalletHighLoadV3;
+use crate::toncenter_error::{ToncenterError, ToncenterErrorKind};
 use crate::wallet_manager::WalletManager;
 use async_trait::async_trait;
 use base64::engine::general_purpose;
@@ -30,14 +46,24 @@ use relayer_core::{
     error::BroadcasterError,
     includer::{BroadcastResult, Broadcaster},
 };
+use sqlx::types::Json;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
 use tonlib_core::tlb_types::block::out_action::OutAction;
 use tonlib_core::tlb_types::tlb::TLB;
 use tonlib_core::{TonAddress, TonHash};
 use tracing::{debug, error, info, warn};
 
-const HIGHLOAD_WALLET_ALREADY_EXECUTED: &str = "THROWIF 36";
+/// A failed batch reports the same error to every task, and `BroadcasterError` is not `Clone`.
+fn copy_error(error: &BroadcasterError) -> BroadcasterError {
+    match error {
+        BroadcasterError::RPCCallFailed(e) => BroadcasterError::RPCCallFailed(e.clone()),
+        BroadcasterError::GenericError(e) => BroadcasterError::GenericError(e.clone()),
+        other => BroadcasterError::GenericError(other.to_string()),
+    }
+}
 
 #[derive(Clone)]
 pub struct TONBroadcaster<GE> {
@@ -48,6 +74,8 @@ pub struct TONBroadcaster<GE> {
     gas_service_address: TonAddress,
     chain_name: String,
     gas_estimator: GE,
+    broadcast_log: Option<Arc<dyn BroadcastLog>>,
+    batcher: Option<Arc<BroadcastBatcher>>,
 }
 
 impl<GE> TONBroadcaster<GE>
@@ -71,9 +99,191 @@ where
             gas_service_address,
             chain_name,
             gas_estimator,
+            broadcast_log: None,
+            batcher: None,
         })
     }
 
+    pub fn with_broadcast_log(mut self, broadcast_log: Arc<dyn BroadcastLog>) -> Self {
+        self.broadcast_log = Some(broadcast_log);
+        self
+    }
+
+    pub fn with_batching(mut self, window: Duration, max_actions: usize) -> Self {
+        self.batcher = Some(Arc::new(BroadcastBatcher::new(window, max_actions)));
+        self
+    }
+
+    /// Sends a single action, either on its own or as part of a batch.
+    async fn dispatch(&self, build: ActionBuilder, message: BroadcastMessage) -> SendOutcome {
+        if let Some(batcher) = &self.batcher {
+            return self.dispatch_batched(batcher, build, message).await;
+        }
+
+        let wallet = self.wallet_manager.acquire().await.map_err(|e| {
+            error!("Error acquiring wallet: {e:?}");
+            BroadcasterError::GenericError(format!("Wallet acquire failed: {e:?}"))
+        })?;
+
+        let result = async {
+            let actions = vec![build(&wallet.address)?];
+            let res = self.send_to_chain(wallet, actions.clone(), None).await;
+            if let Ok(response) = &res {
+                self.record_broadcast(wallet, &actions, response, vec![message], 1)
+                    .await;
+            }
+            Ok(res)
+        }
+        .await;
+
+        self.wallet_manager.release(wallet).await;
+
+        result
+    }
+
+    async fn dispatch_batched(
+        &self,
+        batcher: &BroadcastBatcher,
+        build: ActionBuilder,
+        message: BroadcastMessage,
+    ) -> SendOutcome {
+        let (sender, receiver) = oneshot::channel();
+        let is_leader = batcher
+            .push(PendingAction {
+                build,
+                message,
+                result: sender,
+            })
+            .await;
+
+        if is_leader {
+            loop {
+                batcher.wait().await;
+                let (batch, more) = batcher.take().await;
+                self.send_batch(batch).await;
+                if !more {
+                    break;
+                }
+            }
+        }
+
+        receiver.await.map_err(|_| {
+            BroadcasterError::GenericError("Batch was dropped before sending".to_string())
+        })?
+    }
+
+    async fn send_batch(&self, batch: Vec<PendingAction>) {
+        let wallet = match self.wallet_manager.acquire().await {
+            Ok(wallet) => wallet,
+            Err(e) => {
+                error!("Error acquiring wallet: {e:?}");
+                for pending in batch {
+                    let _ = pending
+                        .result
+                        .send(Err(BroadcasterError::GenericError(format!(
+                            "Wallet acquire failed: {e:?}"
+                        ))));
+                }
+                return;
+            }
+        };
+
+        let mut actions = Vec::with_capacity(batch.len());
+        let mut waiting = Vec::with_capacity(batch.len());
+        for pending in batch {
+            match (pending.build)(&wallet.address) {
+                Ok(action) => {
+                    actions.push(action);
+                    waiting.push((pending.message, pending.result));
+                }
+                Err(e) => {
+                    let _ = pending.result.send(Err(e));
+                }
+            }
+        }
+
+        if !actions.is_empty() {
+            let message_ids: Vec<&str> = waiting
+                .iter()
+                .map(|(message, _)| message.message_id.as_str())
+                .collect();
+            info!(
+                "Sending batch of {} actions: message_ids={:?}",
+                actions.len(),
+                message_ids
+            );
+
+            match self.send_to_chain(wallet, actions.clone(), None).await {
+                Ok(response) => {
+                    let messages = waiting.iter().map(|(message, _)| message.clone()).collect();
+                    self.record_broadcast(wallet, &actions, &response, messages, 1)
+                        .await;
+                    for (_, result) in waiting {
+                        let _ = result.send(Ok(Ok(response.clone())));
+                    }
+                }
+                Err(e) => {
+                    for (_, result) in waiting {
+                        let _ = result.send(Ok(Err(copy_error(&e))));
+                    }
+                }
+            }
+        }
+
+        self.wallet_manager.release(wallet).await;
+    }
+
+    async fn record_broadcast(
+        &self,
+        wallet: &TonWalletHighLoadV3,
+        actions: &[OutAction],
+        response: &V3MessageResponse,
+        messages: Vec<BroadcastMessage>,
+        attempts: i32,
+    ) {
+        let Some(broadcast_log) = &self.broadcast_log else {
+            return;
+        };
+
+        let actions = match actions
+            .iter()
+            .map(|action| action.to_boc_b64(true))
+            .collect::<Result<Vec<String>, _>>()
+        {
+            Ok(actions) => actions,
+            Err(e) => {
+                warn!("Failed to serialize actions for tracking: {:?}", e);
+                return;
+            }
+        };
+
+        let now = chrono::Utc::now();
+        let broadcast = TONBroadcast {
+            message_hash_norm: response.message_hash_norm.clone(),
+            wallet_address: wallet.address.to_string(),
+            actions: Json(actions),
+            messages: Json(messages),
+            status: BroadcastStatus::Pending.as_str().to_string(),
+            exit_code: None,
+            trace_id: None,
+            resent_as: None,
+            attempts,
+            // The wallet rejects the message once timeout has passed since its created_at,
+            // which is a bit in the past, so this is on the safe side
+            expires_at: now + chrono::Duration::seconds(wallet.timeout as i64),
+            created_at: now,
+            updated_at: None,
+            claimed_until: None,
+        };
+
+        if let Err(e) = broadcast_log.record(broadcast).await {
+            warn!(
+                "Failed to record broadcast {}: {:?}",
+                response.message_hash_norm, e
+            );
+        }
+    }
+
     #[tracing::instrument(skip(self))]
     async fn send_to_chain(
         &self,
@@ -120,9 +330,12 @@ where
         match result {
             Ok(response) => Ok(response),
             Err(e) => {
-                let error_str = e.to_string();
-                // High load wallet "already executed" error
-                if error_str.contains(HIGHLOAD_WALLET_ALREADY_EXECUTED) {
+                let error = ToncenterError::classify(&e);
+                // The message was not executed, so it can be sent again with another query id
+                if matches!(
+                    error.kind,
+                    ToncenterErrorKind::QueryIdAlreadyUsed | ToncenterErrorKind::MessageExpired
+                ) {
                     let retries = match retries_left {
                         Some(r) if r > 0 => Some(r - 1),
                         None => Some(10),
@@ -131,14 +344,25 @@ where
 
                     if retries.is_some() {
                         warn!(
-                            "Encountered {} error, retrying. Retries left: {:?}",
-                            HIGHLOAD_WALLET_ALREADY_EXECUTED, retries
+                            "Encountered {:?} error, retrying. Retries left: {:?}",
+                            error.kind, retries
                         );
                         // https://rust-lang.github.io/async-book/07_workarounds/04_recursion.html
                         return Box::pin(self.send_to_chain(wallet, actions, retries)).await;
                     }
                 }
-                Err(RPCCallFailed(error_str))
+                match error.kind {
+                    ToncenterErrorKind::InsufficientBalance => error!(
+                        "Wallet {} can't pay for the message: {}",
+                        wallet.address, error.message
+                    ),
+                    ToncenterErrorKind::InvalidSignature => error!(
+                        "Wallet {} rejected the signature of the message: {}",
+                        wallet.address, error.message
+                    ),
+                    _ => {}
+                }
+                Err(RPCCallFailed(e.to_string()))
             }
         }
     }
@@ -182,38 +406,35 @@ where
             message.message_id, message.source_chain
         );
 
-        let actions: Vec<OutAction> = vec![out_action(
+        let action = out_action(
             tx_blob.as_str(),
             approve_message_value,
             self.gateway_address.clone(),
         )
-        .map_err(|e| BroadcasterError::GenericError(e.to_string()))?];
-
-        let wallet =
-            self.wallet_manager.acquire().await.map_err(|e| {
-                BroadcasterError::GenericError(format!("Wallet acquire failed: {e:?}"))
-            })?;
-
-        let result = async {
-            let res = self.send_to_chain(wallet, actions.clone(), None).await;
-            let (tx_hash, status) = match res {
-                Ok(response) => (response.message_hash, Ok(())),
-                Err(err) => (String::new(), Err(err)),
-            };
-
-            Ok(BroadcastResult {
-                transaction: TONTransaction,
-                tx_hash,
-                message_id: Some(message.message_id.clone()),
-                source_chain: Some(message.source_chain.clone()),
-                status,
-            })
-        }
-        .await;
-
-        self.wallet_manager.release(wallet).await;
+        .map_err(|e| BroadcasterError::GenericError(e.to_string()))?;
+
+        let res = self
+            .dispatch(
+                Box::new(move |_: &TonAddress| Ok(action)),
+                BroadcastMessage::new(
+                    BroadcastKind::Approve,
+                    message.message_id.clone(),
+                    message.source_chain.clone(),
+                ),
+            )
+            .await?;
+        let (tx_hash, status) = match res {
+            Ok(response) => (response.message_hash, Ok(())),
+            Err(err) => (String::new(), Err(err)),
+        };
 
-        result
+        Ok(BroadcastResult {
+            transaction: TONTransaction,
+            tx_hash,
+            message_id: Some(message.message_id.clone()),
+            source_chain: Some(message.source_chain.clone()),
+            status,
+        })
     }
 
     #[tracing::instrument(skip(self))]
@@ -265,61 +486,62 @@ where
             });
         }
 
-        let wallet = self.wallet_manager.acquire().await.map_err(|e| {
-            error!("Error acquiring wallet: {:?}", e);
-            BroadcasterError::GenericError(format!("Wallet acquire failed: {e:?}"))
-        })?;
+        let execute_message_value: BigUint =
+            BigUint::from(self.gas_estimator.execute_send(payload_len).await);
+
+        let build = {
+            let message_id = message_id.clone();
+            let source_chain = source_chain.clone();
+            let chain_name = self.chain_name.clone();
+            let gateway_address = self.gateway_address.clone();
+            move |relayer_address: &TonAddress| {
+                let relayer_execute_msg = RelayerExecuteMessage::new(
+                    message_id,
+                    source_chain,
+                    message.message.source_address,
+                    chain_name,
+                    destination_address,
+                    hex_payload,
+                    relayer_address.clone(),
+                );
 
-        let result = async {
-            let relayer_execute_msg = RelayerExecuteMessage::new(
-                message_id.clone(),
-                source_chain.clone(),
-                message.message.source_address,
-                self.chain_name.clone(),
-                destination_address,
-                hex_payload,
-                wallet.address.clone(),
-            );
+                let boc = relayer_execute_msg
+                    .to_cell()
+                    .map_err(|e| BroadcasterError::GenericError(e.to_string()))?
+                    .to_boc_hex(true)
+                    .map_err(|e| {
+                        BroadcasterError::GenericError(format!(
+                            "Failed to serialize relayer execute message: {e:?}"
+                        ))
+                    })?;
+
+                out_action(&boc, execute_message_value, gateway_address)
+                    .map_err(|e| BroadcasterError::GenericError(e.to_string()))
+            }
+        };
 
-            let boc = relayer_execute_msg
-                .to_cell()
-                .map_err(|e| BroadcasterError::GenericError(e.to_string()))?
-                .to_boc_hex(true)
-                .map_err(|e| {
-                    BroadcasterError::GenericError(format!(
-                        "Failed to serialize relayer execute message: {e:?}"
-                    ))
-                })?;
-
-            let execute_message_value: BigUint =
-                BigUint::from(self.gas_estimator.execute_send(payload_len).await);
-
-            let actions: Vec<OutAction> = vec![out_action(
-                &boc,
-                execute_message_value.clone(),
-                self.gateway_address.clone(),
+        let res = self
+            .dispatch(
+                Box::new(build),
+                BroadcastMessage::new(
+                    BroadcastKind::Execute,
+                    message_id.clone(),
+                    source_chain.clone(),
+                ),
             )
-            .map_err(|e| BroadcasterError::GenericError(e.to_string()))?];
-
-            let res = self.send_to_chain(wallet, actions.clone(), None).await;
-            let (tx_hash, status) = match res {
-                Ok(response) => (response.message_hash, Ok(())),
-                Err(err) => (String::new(), Err(err)),
-            };
-
-            Ok(BroadcastResult {
-                transaction: TONTransaction,
-                tx_hash,
-                message_id: Some(message_id.clone()),
-                source_chain: Some(source_chain.clone()),
-                status,
-            })
-        }
-        .await;
-
-        self.wallet_manager.release(wallet).await;
+            .await?;
+        let (tx_hash, status) = match res {
+            Ok(response) => (response.message_hash, Ok(())),
+            Err(err) => (String::new(), Err(err)),
+        };
 
-        result
+        Ok(BroadcastResult {
+            transaction: TONTransaction,
+            tx_hash,
+            message_id: Some(message_id),
+            source_chain: Some(source_chain),
+            status,
+        })
     }
 
     #[tracing::instrument(skip(self), fields(message_id))]
@@ -380,27 +602,69 @@ where
                 ))
             })?;
 
-        let wallet = self.wallet_manager.acquire().await.map_err(|e| {
-            error!("Error acquiring wallet: {e:?}");
-            BroadcasterError::GenericError(format!("Wallet acquire failed: {e:?}"))
-        })?;
+        let msg_value: BigUint = BigUint::from(REFUND_DUST);
+        let gas_service_address = self.gas_service_address.clone();
+
+        let res = self
+            .dispatch(
+                Box::new(move |_: &TonAddress| {
+                    out_action(&boc, msg_value, gas_service_address)
+                        .map_err(|e| BroadcasterError::GenericError(e.to_string()))
+                }),
+                BroadcastMessage::new(
+                    BroadcastKind::Refund,
+                    refund_task.message.message_id.clone(),
+                    refund_task.message.source_chain.clone(),
+                ),
+            )
+            .await?;
+        let tx_hash = match res {
+            Ok(response) => response.message_hash,
+            Err(_) => String::new(),
+        };
 
-        let result = async {
-            let msg_value: BigUint = BigUint::from(REFUND_DUST);
+        Ok(tx_hash)
+    }
+}
 
-            let actions: Vec<OutAction> =
-                vec![
-                    out_action(&boc, msg_value.clone(), self.gas_service_address.clone())
-                        .map_err(|e| BroadcasterError::GenericError(e.to_string()))?,
-                ];
+#[async_trait]
+impl<GE> Resender for TONBroadcaster<GE>
+where
+    GE: GasEstimator + ThreadSafe,
+{
+    #[tracing::instrument(skip(self, broadcast), fields(message_ids = ?broadcast.message_ids()))]
+    async fn resend(
+        &self,
+        broadcast: &TONBroadcast,
+    ) -> Result<V3MessageResponse, BroadcasterError> {
+        let actions = broadcast
+            .actions
+            .iter()
+            .map(|action| OutAction::from_boc_b64(action))
+            .collect::<Result<Vec<OutAction>, _>>()
+            .map_err(|e| BroadcasterError::GenericError(e.to_string()))?;
 
-            let res = self.send_to_chain(wallet, actions.clone(), None).await;
-            let (tx_hash, _status) = match res {
-                Ok(response) => (response.message_hash, Ok(())),
-                Err(err) => (String::new(), Err(err)),
-            };
+        let address = TonAddress::from_str(&broadcast.wallet_address)
+            .map_err(|e| BroadcasterError::GenericError(e.to_string()))?;
 
-            Ok(tx_hash)
+        // Same wallet, since e.g. execute messages carry the relayer address
+        let wallet = self
+            .wallet_manager
+            .acquire_address(&address)
+            .await
+            .map_err(|e| BroadcasterError::GenericError(format!("Wallet acquire failed: {e:?}")))?;
+
+        let result = async {
+            let response = self.send_to_chain(wallet, actions.clone(), None).await?;
+            self.record_broadcast(
+                wallet,
+                &actions,
+                &response,
+                broadcast.messages.0.clone(),
+                broadcast.attempts + 1,
+            )
+            .await;
+            Ok(response)
         }
         .await;
 
@@ -412,6 +676,7 @@ where
 
 #[cfg(test)]
 mod tests {
+    use crate::broadcast_batcher::BroadcastBatcher;
     use crate::broadcaster::{TONBroadcaster, TONTransaction};
     use crate::client::{MockRestClient, V3MessageResponse};
     use crate::gas_estimator::MockGasEstimator;
@@ -429,6 +694,7 @@ mod tests {
     use relayer_core::includer::{BroadcastResult, Broadcaster};
     use std::str::FromStr;
     use std::sync::Arc;
+    use std::time::Duration;
     use tonlib_core::cell::Cell;
     use tonlib_core::tlb_types::tlb::TLB;
     use tonlib_core::TonAddress;
@@ -485,6 +751,8 @@ mod tests {
             gas_service_address,
             chain_name: "ton2".to_string(),
             gas_estimator,
+            broadcast_log: None,
+            batcher: None,
         };
         let approve_message = hex::encode(BASE64_STANDARD.decode("te6cckECDAEAAYsAAggAAAAoAQIBYYAAAAAAAAAAAAAAAAAAAACAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAADf5gkADAQHABADi0LAAUYmshNOh1nWEdwB3eJHd51H6EH1kg3v2M30y32eQAAAAAAAAAAAAAAAAAAAAAQ+j+g0KWjWTaPqB9qQHuWZQn7IPz7x3xzwbprT1a85sjh0UlPlFU84LDdRcD4GZ6n6GJlEKKTlRW5QtlzKGrAsBAtAFBECeAcQjykQMXsK+7MnQoVK1T8jnpBbJMbcInq8iFgWvFwYHCAkAiDB4MTdmZDdkYTNkODE5Y2ZiYzQ2ZmYyOGYzZDgwOTgwNzcwZWMxYjgwZmQ3ZDFiMjI5Y2VjMzI1MTkzOWI5YjIzZi0xABxhdmFsYW5jaGUtZnVqaQBUMHhkNzA2N0FlM0MzNTllODM3ODkwYjI4QjdCRDBkMjA4NENmRGY0OWI1AgAKCwBAuHpKD2RLehhu5xoUVGNPcMIqYqyhprpna1F1wh1/2TAACHRvbjJLddsV").unwrap());
 
@@ -540,6 +808,8 @@ mod tests {
             gas_service_address,
             chain_name: "ton2".to_string(),
             gas_estimator,
+            broadcast_log: None,
+            batcher: None,
         };
 
         // Invalid base64 string for BOC (non-decodable)
@@ -598,6 +868,8 @@ mod tests {
             gas_service_address,
             chain_name: "ton2".to_string(),
             gas_estimator,
+            broadcast_log: None,
+            batcher: None,
         };
 
         let execute_task = ExecuteTaskFields {
@@ -668,6 +940,8 @@ mod tests {
             gas_service_address,
             chain_name: "ton2".to_string(),
             gas_estimator,
+            broadcast_log: None,
+            batcher: None,
         };
 
         let execute_task = ExecuteTaskFields {
@@ -737,6 +1011,8 @@ mod tests {
             gas_service_address,
             chain_name: "ton2".to_string(),
             gas_estimator,
+            broadcast_log: None,
+            batcher: None,
         };
 
         let refund_task = refund_task();
@@ -780,6 +1056,8 @@ mod tests {
             gas_service_address,
             chain_name: "ton2".to_string(),
             gas_estimator,
+            broadcast_log: None,
+            batcher: None,
         };
 
         let refund_task = refund_task();
@@ -875,6 +1153,8 @@ mod tests {
             gas_service_address,
             chain_name: "ton2".to_string(),
             gas_estimator,
+            broadcast_log: None,
+            batcher: None,
         };
 
         let wallet = broadcaster.wallet_manager.acquire().await.unwrap();
@@ -892,6 +1172,270 @@ mod tests {
         assert_eq!(force_shift_increase_count.load(Ordering::SeqCst), 5);
     }
 
+    #[tokio::test]
+    async fn test_broadcast_prover_message_is_recorded() {
+        use crate::ton_broadcast::{BroadcastKind, MockBroadcastLog};
+
+        let client = mock_rest_client();
+        let wallet_manager = load_wallets().await;
+
+        let mut gas_estimator = MockGasEstimator::new();
+        gas_estimator.expect_approve_send().returning(|_| 42u64);
+        gas_estimator
+            .expect_highload_wallet_send()
+            .returning(|_| 1024u64);
+
+        let mut broadcast_log = MockBroadcastLog::new();
+        broadcast_log
+            .expect_record()
+            .withf(|broadcast| {
+                broadcast.message_hash_norm == "ABC"
+                    && broadcast.attempts == 1
+                    && broadcast.actions.len() == 1
+                    && broadcast.messages.len() == 1
+                    && broadcast.messages[0].source_chain == "avalanche-fuji"
+                    && broadcast.messages[0].kind == BroadcastKind::Approve
+            })
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let broadcaster = TONBroadcaster {
+            wallet_manager: Arc::new(wallet_manager),
+            query_id_wrapper: Arc::new(MockQueryIdWrapper),
+            client: Arc::new(client),
+            gateway_address: TonAddress::from_str(
+                "0:0000000000000000000000000000000000000000000000000000000000000000",
+            )
+            .unwrap(),
+            gas_service_address: TonAddress::from_str(
+                "0:0000000000000000000000000000000000000000000000000000000000000fff",
+            )
+            .unwrap(),
+            chain_name: "ton2".to_string(),
+            gas_estimator,
+            broadcast_log: Some(Arc::new(broadcast_log)),
+            batcher: None,
+        };
+        let approve_message = hex::encode(BASE64_STANDARD.decode("te6cckECDAEAAYsAAggAAAAoAQIBYYAAAAAAAAAAAAAAAAAAAACAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAADf5gkADAQHABADi0LAAUYmshNOh1nWEdwB3eJHd51H6EH1kg3v2M30y32eQAAAAAAAAAAAAAAAAAAAAAQ+j+g0KWjWTaPqB9qQHuWZQn7IPz7x3xzwbprT1a85sjh0UlPlFU84LDdRcD4GZ6n6GJlEKKTlRW5QtlzKGrAsBAtAFBECeAcQjykQMXsK+7MnQoVK1T8jnpBbJMbcInq8iFgWvFwYHCAkAiDB4MTdmZDdkYTNkODE5Y2ZiYzQ2ZmYyOGYzZDgwOTgwNzcwZWMxYjgwZmQ3ZDFiMjI5Y2VjMzI1MTkzOWI5YjIzZi0xABxhdmFsYW5jaGUtZnVqaQBUMHhkNzA2N0FlM0MzNTllODM3ODkwYjI4QjdCRDBkMjA4NENmRGY0OWI1AgAKCwBAuHpKD2RLehhu5xoUVGNPcMIqYqyhprpna1F1wh1/2TAACHRvbjJLddsV").unwrap());
+
+        let res = broadcaster
+            .broadcast_prover_message(approve_message)
+            .await
+            .unwrap();
+        assert!(res.status.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_resend() {
+        use crate::confirmation_tracker::Resender;
+        use crate::out_action::out_action;
+        use crate::ton_broadcast::{
+            BroadcastKind, BroadcastMessage, BroadcastStatus, MockBroadcastLog, TONBroadcast,
+        };
+        use num_bigint::BigUint;
+        use sqlx::types::Json;
+
+        let client = mock_rest_client();
+        let wallet_manager = load_wallets().await;
+
+        let mut gas_estimator = MockGasEstimator::new();
+        gas_estimator
+            .expect_highload_wallet_send()
+            .returning(|_| 1024u64);
+
+        let mut broadcast_log = MockBroadcastLog::new();
+        broadcast_log
+            .expect_record()
+            .withf(|broadcast| {
+                broadcast.message_hash_norm == "ABC"
+                    && broadcast.attempts == 3
+                    && broadcast.message_ids() == vec!["0xabc-1"]
+                    && broadcast.messages[0].kind == BroadcastKind::Approve
+            })
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let gateway_address = TonAddress::from_str(
+            "0:0000000000000000000000000000000000000000000000000000000000000000",
+        )
+        .unwrap();
+
+        let broadcaster = TONBroadcaster {
+            wallet_manager: Arc::new(wallet_manager),
+            query_id_wrapper: Arc::new(MockQueryIdWrapper),
+            client: Arc::new(client),
+            gateway_address: gateway_address.clone(),
+            gas_service_address: TonAddress::from_str(
+                "0:0000000000000000000000000000000000000000000000000000000000000fff",
+            )
+            .unwrap(),
+            chain_name: "ton2".to_string(),
+            gas_estimator,
+            broadcast_log: Some(Arc::new(broadcast_log)),
+            batcher: None,
+        };
+
+        let approve_message = hex::encode(BASE64_STANDARD.decode("te6cckECDAEAAYsAAggAAAAoAQIBYYAAAAAAAAAAAAAAAAAAAACAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAADf5gkADAQHABADi0LAAUYmshNOh1nWEdwB3eJHd51H6EH1kg3v2M30y32eQAAAAAAAAAAAAAAAAAAAAAQ+j+g0KWjWTaPqB9qQHuWZQn7IPz7x3xzwbprT1a85sjh0UlPlFU84LDdRcD4GZ6n6GJlEKKTlRW5QtlzKGrAsBAtAFBECeAcQjykQMXsK+7MnQoVK1T8jnpBbJMbcInq8iFgWvFwYHCAkAiDB4MTdmZDdkYTNkODE5Y2ZiYzQ2ZmYyOGYzZDgwOTgwNzcwZWMxYjgwZmQ3ZDFiMjI5Y2VjMzI1MTkzOWI5YjIzZi0xABxhdmFsYW5jaGUtZnVqaQBUMHhkNzA2N0FlM0MzNTllODM3ODkwYjI4QjdCRDBkMjA4NENmRGY0OWI1AgAKCwBAuHpKD2RLehhu5xoUVGNPcMIqYqyhprpna1F1wh1/2TAACHRvbjJLddsV").unwrap());
+        let action = out_action(&approve_message, BigUint::from(100u32), gateway_address).unwrap();
+
+        let broadcast = TONBroadcast {
+            message_hash_norm: "OLD".to_string(),
+            wallet_address: "EQAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAADz6z".to_string(),
+            actions: Json(vec![action.to_boc_b64(true).unwrap()]),
+            messages: Json(vec![BroadcastMessage::new(
+                BroadcastKind::Approve,
+                "0xabc-1".to_string(),
+                "avalanche-fuji".to_string(),
+            )]),
+            status: BroadcastStatus::Pending.as_str().to_string(),
+            exit_code: None,
+            trace_id: None,
+            resent_as: None,
+            attempts: 2,
+            expires_at: chrono::Utc::now(),
+            created_at: chrono::Utc::now(),
+            updated_at: None,
+            claimed_until: None,
+        };
+
+        let response = broadcaster.resend(&broadcast).await.unwrap();
+        assert_eq!(response.message_hash_norm, "ABC");
+
+        // The wallet is released afterwards
+        let address =
+            TonAddress::from_base64_url("EQAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAADz6z")
+                .unwrap();
+        assert!(broadcaster
+            .wallet_manager
+            .acquire_address(&address)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_batches_concurrent_tasks() {
+        use crate::ton_broadcast::MockBroadcastLog;
+
+        let mut client = MockRestClient::new();
+        client.expect_post_v3_message().times(1).returning(|_| {
+            Ok(V3MessageResponse {
+                message_hash: "abc".to_string(),
+                message_hash_norm: "ABC".to_string(),
+            })
+        });
+
+        let mut gas_estimator = MockGasEstimator::new();
+        gas_estimator.expect_approve_send().returning(|_| 42u64);
+        gas_estimator
+            .expect_highload_wallet_send()
+            .withf(|actions| *actions == 3)
+            .times(1)
+            .returning(|_| 1024u64);
+
+        // One broadcast, carrying the ids of every task in the batch
+        let mut broadcast_log = MockBroadcastLog::new();
+        broadcast_log
+            .expect_record()
+            .withf(|broadcast| {
+                broadcast.actions.len() == 3
+                    && broadcast.messages.len() == 3
+                    && broadcast
+                        .messages
+                        .iter()
+                        .all(|message| message.source_chain == "avalanche-fuji")
+            })
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let broadcaster = TONBroadcaster {
+            wallet_manager: Arc::new(load_wallets().await),
+            query_id_wrapper: Arc::new(MockQueryIdWrapper),
+            client: Arc::new(client),
+            gateway_address: TonAddress::from_str(
+                "0:0000000000000000000000000000000000000000000000000000000000000000",
+            )
+            .unwrap(),
+            gas_service_address: TonAddress::from_str(
+                "0:0000000000000000000000000000000000000000000000000000000000000fff",
+            )
+            .unwrap(),
+            chain_name: "ton2".to_string(),
+            gas_estimator,
+            broadcast_log: Some(Arc::new(broadcast_log)),
+            batcher: Some(Arc::new(BroadcastBatcher::new(
+                Duration::from_millis(100),
+                10,
+            ))),
+        };
+        let approve_message = hex::encode(BASE64_STANDARD.decode("te6cckECDAEAAYsAAggAAAAoAQIBYYAAAAAAAAAAAAAAAAAAAACAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAADf5gkADAQHABADi0LAAUYmshNOh1nWEdwB3eJHd51H6EH1kg3v2M30y32eQAAAAAAAAAAAAAAAAAAAAAQ+j+g0KWjWTaPqB9qQHuWZQn7IPz7x3xzwbprT1a85sjh0UlPlFU84LDdRcD4GZ6n6GJlEKKTlRW5QtlzKGrAsBAtAFBECeAcQjykQMXsK+7MnQoVK1T8jnpBbJMbcInq8iFgWvFwYHCAkAiDB4MTdmZDdkYTNkODE5Y2ZiYzQ2ZmYyOGYzZDgwOTgwNzcwZWMxYjgwZmQ3ZDFiMjI5Y2VjMzI1MTkzOWI5YjIzZi0xABxhdmFsYW5jaGUtZnVqaQBUMHhkNzA2N0FlM0MzNTllODM3ODkwYjI4QjdCRDBkMjA4NENmRGY0OWI1AgAKCwBAuHpKD2RLehhu5xoUVGNPcMIqYqyhprpna1F1wh1/2TAACHRvbjJLddsV").unwrap());
+
+        let (first, second, third) = tokio::join!(
+            broadcaster.broadcast_prover_message(approve_message.clone()),
+            broadcaster.broadcast_prover_message(approve_message.clone()),
+            broadcaster.broadcast_prover_message(approve_message),
+        );
+
+        for result in [first, second, third] {
+            let result = result.unwrap();
+            assert!(result.status.is_ok());
+            assert_eq!(result.tx_hash, "abc");
+            assert_eq!(result.source_chain, Some("avalanche-fuji".to_string()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_batch_failure_reaches_every_task() {
+        use relayer_core::error::ClientError;
+
+        let mut client = MockRestClient::new();
+        client
+            .expect_post_v3_message()
+            .times(1)
+            .returning(|_| Err(ClientError::BadResponse("boom".to_string())));
+
+        let mut gas_estimator = MockGasEstimator::new();
+        gas_estimator.expect_approve_send().returning(|_| 42u64);
+        gas_estimator
+            .expect_highload_wallet_send()
+            .returning(|_| 1024u64);
+
+        let broadcaster = TONBroadcaster {
+            wallet_manager: Arc::new(load_wallets().await),
+            query_id_wrapper: Arc::new(MockQueryIdWrapper),
+            client: Arc::new(client),
+            gateway_address: TonAddress::from_str(
+                "0:0000000000000000000000000000000000000000000000000000000000000000",
+            )
+            .unwrap(),
+            gas_service_address: TonAddress::from_str(
+                "0:0000000000000000000000000000000000000000000000000000000000000fff",
+            )
+            .unwrap(),
+            chain_name: "ton2".to_string(),
+            gas_estimator,
+            broadcast_log: None,
+            batcher: Some(Arc::new(BroadcastBatcher::new(
+                Duration::from_millis(100),
+                10,
+            ))),
+        };
+        let approve_message = hex::encode(BASE64_STANDARD.decode("te6cckECDAEAAYsAAggAAAAoAQIBYYAAAAAAAAAAAAAAAAAAAACAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAADf5gkADAQHABADi0LAAUYmshNOh1nWEdwB3eJHd51H6EH1kg3v2M30y32eQAAAAAAAAAAAAAAAAAAAAAQ+j+g0KWjWTaPqB9qQHuWZQn7IPz7x3xzwbprT1a85sjh0UlPlFU84LDdRcD4GZ6n6GJlEKKTlRW5QtlzKGrAsBAtAFBECeAcQjykQMXsK+7MnQoVK1T8jnpBbJMbcInq8iFgWvFwYHCAkAiDB4MTdmZDdkYTNkODE5Y2ZiYzQ2ZmYyOGYzZDgwOTgwNzcwZWMxYjgwZmQ3ZDFiMjI5Y2VjMzI1MTkzOWI5YjIzZi0xABxhdmFsYW5jaGUtZnVqaQBUMHhkNzA2N0FlM0MzNTllODM3ODkwYjI4QjdCRDBkMjA4NENmRGY0OWI1AgAKCwBAuHpKD2RLehhu5xoUVGNPcMIqYqyhprpna1F1wh1/2TAACHRvbjJLddsV").unwrap());
+
+        let (first, second) = tokio::join!(
+            broadcaster.broadcast_prover_message(approve_message.clone()),
+            broadcaster.broadcast_prover_message(approve_message),
+        );
+
+        for result in [first, second] {
+            let result = result.unwrap();
+            assert!(matches!(
+                result.status,
+                Err(BroadcasterError::RPCCallFailed(_))
+            ));
+            assert_eq!(result.tx_hash, "");
+        }
+    }
+
     fn mock_rest_client() -> MockRestClient {
         let mut client = MockRestClient::new();
         client
@@ -13,6 +13,39 @@ async fn main() {
 }
 ```
 
+# Pagination
+
+`get_traces_for_account` follows `offset` in ascending `lt` order until the window that starts at
+`start_lt` is exhausted, or until the page cap is reached. Page size and cap default to
+[`DEFAULT_TRACES_PAGE_SIZE`] and [`DEFAULT_TRACES_MAX_PAGES`] and can be changed with
+[`TONRpcClient::with_traces_pagination`]. When the cap is hit the result is marked as truncated, so
+the caller knows not to move its cursor past the traces it actually received.
+
+`get_traces_for_account_in_range` pages the same way over a closed `[start_lt, end_lt]` range,
+which is what backfilling uses.
+
+`get_transactions_by_masterchain_block` always follows `offset` until the block is exhausted, since
+the block subscriber moves its cursor past the whole block.
+
+# Errors
+
+Non-success responses are classified by [`ToncenterError`]: rejected messages and malformed
+requests become `ClientError::BadRequest`, everything else `ClientError::BadResponse`.
+
+# Get-methods
+
+`run_get_method` runs a get-method of a contract through `/api/v3/runGetMethod`. The stack is
+encoded and decoded as [`StackEntry`] values, see [`crate::tvm_stack`]. A non-zero exit code is
+returned as part of the result rather than as an error.
+
+# Rate limiting
+
+With [`TONRpcClient::with_rate_limiter`], every request first waits for a token of the
+[`RateLimiter`] of its API key. Broadcasts wait with [`RequestPriority::Broadcast`], other requests
+with the priority set by [`TONRpcClient::with_priority`]. Transient errors are retried with
+exponential backoff, every retry waiting for a token of its own, but `429 Too Many Requests` is
+retried after its `Retry-After` instead, which also pauses every other request on the same key.
+
 # Notes
 
 Tonlib offers a client. However, in practice, it does not reliably work, has no support for v3 API,
@@ -20,28 +53,49 @@ and it is less flexible and more time consuming to get it running than writing o
 
 */
 
+use crate::rate_limiter::{RateLimiter, RequestPriority};
+use crate::toncenter_error::ToncenterError;
+use crate::tvm_stack::{RawStackEntry, StackEntry};
 pub(crate) use crate::types::{
-    AccountState, AccountStatesResponse, Trace, TracesResponse, TracesResponseRest,
+    AccountState, AccountStatesResponse, MasterchainInfo, Trace, TracesResponse,
+    TracesResponseRest, Transaction, TransactionsResponse,
 };
 use async_trait::async_trait;
 use relayer_core::error::ClientError;
-use relayer_core::error::ClientError::{BadRequest, BadResponse, ConnectionFailed};
-use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
-use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+use relayer_core::error::ClientError::{BadResponse, ConnectionFailed};
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware, RequestBuilder};
+use reqwest_retry::{
+    default_on_request_failure, default_on_request_success, policies::ExponentialBackoff,
+    RetryDecision, RetryPolicy, Retryable,
+};
 use serde::Deserialize;
 use serde_json::json;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tonlib_core::TonAddress;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+pub const DEFAULT_TRACES_PAGE_SIZE: usize = 100;
+pub const DEFAULT_TRACES_MAX_PAGES: usize = 10;
+// Wait after a 429 without a usable Retry-After header
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
 
 #[derive(Clone, Debug)]
 pub struct TONRpcClient {
     url: String,
     client: ClientWithMiddleware,
     api_key: String,
+    traces_page_size: usize,
+    traces_max_pages: usize,
+    max_retries: u32,
+    retry_policy: ExponentialBackoff,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    priority: RequestPriority,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct V3MessageResponse {
     pub message_hash: String,
     pub message_hash_norm: String,
@@ -53,6 +107,27 @@ pub struct V3ErrorResponse {
     pub error: String,
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct TracesWindow {
+    pub traces: Vec<Trace>,
+    // Page cap was reached before the window was exhausted
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunGetMethodResult {
+    pub gas_used: i64,
+    pub exit_code: i32,
+    pub stack: Vec<StackEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RunGetMethodResponse {
+    gas_used: i64,
+    exit_code: i32,
+    stack: Vec<RawStackEntry>,
+}
+
 #[cfg_attr(any(test), mockall::automock)]
 #[async_trait]
 pub trait RestClient: Send + Sync {
@@ -62,11 +137,35 @@ pub trait RestClient: Send + Sync {
         account: Option<TonAddress>,
         trace_ids: Option<Vec<String>>,
         start_lt: Option<i64>,
-    ) -> Result<Vec<Trace>, ClientError>;
+    ) -> Result<TracesWindow, ClientError>;
+    async fn get_traces_for_account_in_range(
+        &self,
+        account: TonAddress,
+        start_lt: i64,
+        end_lt: i64,
+    ) -> Result<TracesWindow, ClientError>;
+    async fn get_trace_by_message_hash(
+        &self,
+        message_hash: String,
+    ) -> Result<Option<Trace>, ClientError>;
+    /// Trace containing the transaction, if Toncenter indexed it.
+    async fn get_trace_by_tx_hash(&self, tx_hash: String) -> Result<Option<Trace>, ClientError>;
     async fn get_account_states(
         &self,
         addresses: Vec<TonAddress>,
     ) -> Result<Vec<AccountState>, ClientError>;
+    async fn get_masterchain_info(&self) -> Result<MasterchainInfo, ClientError>;
+    /// Transactions of the shard blocks committed in the masterchain block, oldest first.
+    async fn get_transactions_by_masterchain_block(
+        &self,
+        seqno: u32,
+    ) -> Result<Vec<Transaction>, ClientError>;
+    async fn run_get_method(
+        &self,
+        address: TonAddress,
+        method: String,
+        stack: Vec<StackEntry>,
+    ) -> Result<RunGetMethodResult, ClientError>;
 }
 
 impl TONRpcClient {
@@ -84,18 +183,236 @@ impl TONRpcClient {
             .timeout(Duration::from_secs(timeout))
             .pool_idle_timeout(Some(Duration::from_secs(300)));
 
+        // Retried in `send`, so that every attempt goes through the rate limiter
         let client = ClientBuilder::new(
             client_builder
                 .build()
                 .map_err(|e| ConnectionFailed(e.to_string()))?,
         )
-        .with(RetryTransientMiddleware::new_with_policy(retry_policy))
         .build();
 
         Ok(Self {
             url,
             client,
             api_key,
+            traces_page_size: DEFAULT_TRACES_PAGE_SIZE,
+            traces_max_pages: DEFAULT_TRACES_MAX_PAGES,
+            max_retries,
+            retry_policy,
+            rate_limiter: None,
+            priority: RequestPriority::Poll,
+        })
+    }
+
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Priority of every request other than broadcasts.
+    pub fn with_priority(mut self, priority: RequestPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Sends the request once the rate limiter lets it through, again after a 429, and again
+    /// with backoff after a transient error.
+    async fn send(
+        &self,
+        priority: RequestPriority,
+        request: RequestBuilder,
+    ) -> Result<reqwest::Response, ClientError> {
+        let started_at = SystemTime::now();
+        let mut attempt = 0;
+        let mut transient_retries = 0;
+        loop {
+            let attempt_request = request
+                .try_clone()
+                .ok_or_else(|| ConnectionFailed("Request can't be cloned".to_string()))?;
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.acquire(priority).await;
+            }
+
+            let result = attempt_request.send().await;
+            let response = match result {
+                Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => response,
+                result => {
+                    let retryable = match &result {
+                        Ok(response) => default_on_request_success(response),
+                        Err(err) => default_on_request_failure(err),
+                    };
+                    if matches!(retryable, Some(Retryable::Transient)) {
+                        if let RetryDecision::Retry { execute_after } = self
+                            .retry_policy
+                            .should_retry(started_at, transient_retries)
+                        {
+                            let backoff = execute_after
+                                .duration_since(SystemTime::now())
+                                .unwrap_or_default();
+                            tokio::time::sleep(backoff).await;
+                            transient_retries += 1;
+                            continue;
+                        }
+                    }
+                    return result.map_err(|err| ConnectionFailed(err.to_string()));
+                }
+            };
+
+            let retry_after = retry_after(&response).unwrap_or(DEFAULT_RETRY_AFTER);
+            match &self.rate_limiter {
+                Some(rate_limiter) => rate_limiter.throttle(retry_after).await,
+                None => warn!(
+                    "Rate limited by Toncenter, retrying after {:?}",
+                    retry_after
+                ),
+            }
+            if attempt >= self.max_retries {
+                return Ok(response);
+            }
+            if self.rate_limiter.is_none() {
+                tokio::time::sleep(retry_after).await;
+            }
+            attempt += 1;
+        }
+    }
+
+    pub fn with_traces_pagination(mut self, page_size: usize, max_pages: usize) -> Self {
+        self.traces_page_size = page_size.max(1);
+        self.traces_max_pages = max_pages.max(1);
+        self
+    }
+
+    async fn fetch_traces_page(
+        &self,
+        query_params: &[(&str, String)],
+        offset: usize,
+    ) -> Result<Vec<Trace>, ClientError> {
+        let url = format!("{}/api/v3/traces", self.url.trim_end_matches('/'));
+
+        let mut query_params = query_params.to_vec();
+        query_params.push(("limit", self.traces_page_size.to_string()));
+        if offset > 0 {
+            query_params.push(("offset", offset.to_string()));
+        }
+
+        info!("Fetching TON traces from: {:?} {:?}", url, query_params);
+
+        let response = self
+            .send(
+                self.priority,
+                self.client
+                    .get(url)
+                    .header("X-API-Key", &self.api_key)
+                    .query(&query_params),
+            )
+            .await?;
+
+        let status = response.status();
+        let raw_bytes = response
+            .bytes()
+            .await
+            .map_err(|err| BadResponse(err.to_string()))?;
+
+        // We sometimes get bad UTF8 from the api, so let's make sure to clean it up
+        let clean_text = clean_json_string_full(&raw_bytes);
+
+        if status.is_success() {
+            serde_json::from_str::<TracesResponseRest>(&clean_text)
+                .map(TracesResponse::from)
+                .map(|res| res.traces)
+                .map_err(|err| BadResponse(format!("Failed to parse traces list: {err}")))
+        } else {
+            Err(self.handle_non_success_response(
+                status,
+                &clean_text,
+                format!("{query_params:?}").as_str(),
+            ))
+        }
+    }
+
+    async fn fetch_transactions_page(
+        &self,
+        query_params: &[(&str, String)],
+        offset: usize,
+    ) -> Result<Vec<Transaction>, ClientError> {
+        let url = format!(
+            "{}/api/v3/transactionsByMasterchainBlock",
+            self.url.trim_end_matches('/')
+        );
+
+        let mut query_params = query_params.to_vec();
+        query_params.push(("limit", self.traces_page_size.to_string()));
+        if offset > 0 {
+            query_params.push(("offset", offset.to_string()));
+        }
+
+        info!(
+            "Fetching TON transactions from: {:?} {:?}",
+            url, query_params
+        );
+
+        let response = self
+            .send(
+                self.priority,
+                self.client
+                    .get(url)
+                    .header("X-API-Key", &self.api_key)
+                    .query(&query_params),
+            )
+            .await?;
+
+        let status = response.status();
+        let raw_bytes = response
+            .bytes()
+            .await
+            .map_err(|err| BadResponse(err.to_string()))?;
+
+        let clean_text = clean_json_string_full(&raw_bytes);
+
+        if status.is_success() {
+            serde_json::from_str::<TransactionsResponse>(&clean_text)
+                .map(|res| res.transactions)
+                .map_err(|err| BadResponse(format!("Failed to parse transactions list: {err}")))
+        } else {
+            Err(self.handle_non_success_response(
+                status,
+                &clean_text,
+                format!("{query_params:?}").as_str(),
+            ))
+        }
+    }
+
+    /// Follows `offset` over the traces matching `query_params`, oldest first, up to the page cap.
+    async fn fetch_traces_window(
+        &self,
+        mut query_params: Vec<(&str, String)>,
+    ) -> Result<TracesWindow, ClientError> {
+        // Oldest first, so that a truncated window is always a prefix of the full one
+        query_params.push(("sort", "asc".to_string()));
+
+        let mut traces = vec![];
+        let mut offset = 0;
+        for _ in 0..self.traces_max_pages {
+            let page = self.fetch_traces_page(&query_params, offset).await?;
+            let page_len = page.len();
+            traces.extend(page);
+            if page_len < self.traces_page_size {
+                return Ok(TracesWindow {
+                    traces,
+                    truncated: false,
+                });
+            }
+            offset += page_len;
+        }
+
+        warn!(
+            "Reached page cap of {} while fetching traces {:?}, window is truncated",
+            self.traces_max_pages, query_params
+        );
+
+        Ok(TracesWindow {
+            traces,
+            truncated: true,
         })
     }
 
@@ -105,21 +422,27 @@ impl TONRpcClient {
         text: &str,
         context: &str,
     ) -> ClientError {
+        let error = ToncenterError::from_response(status, text);
         error!(
-            "TON RPC request failed: {}: {}, (sent {})",
-            status, text, context
+            "TON RPC request failed: {}: {:?}: {}, (sent {})",
+            status, error.kind, text, context
         );
-        if status.as_u16() == 400 {
-            match serde_json::from_str::<V3ErrorResponse>(text) {
-                Ok(err_body) => BadRequest(err_body.error),
-                Err(err) => BadResponse(format!("Invalid 400 body: {err}")),
-            }
-        } else {
-            BadResponse(format!("Unexpected status {status}: {text}"))
-        }
+        error.into()
     }
 }
 
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let seconds = response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<f64>()
+        .ok()?;
+    (seconds.is_finite() && seconds >= 0.0).then(|| Duration::from_secs_f64(seconds))
+}
+
 pub(crate) fn clean_json_string_full(input: &[u8]) -> String {
     let json_str = String::from_utf8_lossy(input);
     json_str
@@ -138,13 +461,14 @@ impl RestClient for TONRpcClient {
 
         let url = format!("{}/api/v3/message", self.url.trim_end_matches('/'));
         let response = self
-            .client
-            .post(url)
-            .header("X-API-Key", &self.api_key)
-            .json(&body)
-            .send()
-            .await
-            .map_err(|err| ConnectionFailed(err.to_string()))?;
+            .send(
+                RequestPriority::Broadcast,
+                self.client
+                    .post(url)
+                    .header("X-API-Key", &self.api_key)
+                    .json(&body),
+            )
+            .await?;
 
         let status = response.status();
         let text = response
@@ -165,10 +489,8 @@ impl RestClient for TONRpcClient {
         account: Option<TonAddress>,
         trace_ids: Option<Vec<String>>,
         start_lt: Option<i64>,
-    ) -> Result<Vec<Trace>, ClientError> {
-        let url = format!("{}/api/v3/traces", self.url.trim_end_matches('/'));
-
-        let mut query_params = vec![("limit", "100".to_string())];
+    ) -> Result<TracesWindow, ClientError> {
+        let mut query_params = vec![];
 
         if let Some(account) = account {
             query_params.push(("account", account.to_string()));
@@ -180,42 +502,48 @@ impl RestClient for TONRpcClient {
             }
         }
 
-        if let Some(lt_min_val) = start_lt {
-            query_params.push(("start_lt", (lt_min_val + 1).to_string()));
-        }
+        let Some(lt_min_val) = start_lt else {
+            // Without a lower bound there is no window to exhaust, just return the latest page
+            let traces = self.fetch_traces_page(&query_params, 0).await?;
+            return Ok(TracesWindow {
+                traces,
+                truncated: false,
+            });
+        };
 
-        info!("Fetching TON traces from: {:?} {:?}", url, query_params);
+        query_params.push(("start_lt", (lt_min_val + 1).to_string()));
 
-        let response = self
-            .client
-            .get(url)
-            .header("X-API-Key", &self.api_key)
-            .query(&query_params)
-            .send()
-            .await
-            .map_err(|err| ConnectionFailed(err.to_string()))?;
+        self.fetch_traces_window(query_params).await
+    }
 
-        let status = response.status();
-        let raw_bytes = response
-            .bytes()
-            .await
-            .map_err(|err| BadResponse(err.to_string()))?;
+    async fn get_traces_for_account_in_range(
+        &self,
+        account: TonAddress,
+        start_lt: i64,
+        end_lt: i64,
+    ) -> Result<TracesWindow, ClientError> {
+        let query_params = vec![
+            ("account", account.to_string()),
+            ("start_lt", start_lt.to_string()),
+            ("end_lt", end_lt.to_string()),
+        ];
+
+        self.fetch_traces_window(query_params).await
+    }
 
-        // We sometimes get bad UTF8 from the api, so let's make sure to clean it up
-        let clean_text = clean_json_string_full(&raw_bytes);
+    async fn get_trace_by_message_hash(
+        &self,
+        message_hash: String,
+    ) -> Result<Option<Trace>, ClientError> {
+        let query_params = vec![("msg_hash", message_hash)];
+        let traces = self.fetch_traces_page(&query_params, 0).await?;
+        Ok(traces.into_iter().next())
+    }
 
-        if status.is_success() {
-            serde_json::from_str::<TracesResponseRest>(&clean_text)
-                .map(TracesResponse::from)
-                .map(|res| res.traces)
-                .map_err(|err| BadResponse(format!("Failed to parse traces list: {err}")))
-        } else {
-            Err(self.handle_non_success_response(
-                status,
-                &clean_text,
-                format!("{query_params:?}").as_str(),
-            ))
-        }
+    async fn get_trace_by_tx_hash(&self, tx_hash: String) -> Result<Option<Trace>, ClientError> {
+        let query_params = vec![("tx_hash", tx_hash)];
+        let traces = self.fetch_traces_page(&query_params, 0).await?;
+        Ok(traces.into_iter().next())
     }
 
     async fn get_account_states(
@@ -233,13 +561,14 @@ impl RestClient for TONRpcClient {
         }
 
         let response = self
-            .client
-            .get(url)
-            .query(&query_params)
-            .header("X-API-Key", &self.api_key)
-            .send()
-            .await
-            .map_err(|err| ConnectionFailed(err.to_string()))?;
+            .send(
+                self.priority,
+                self.client
+                    .get(url)
+                    .query(&query_params)
+                    .header("X-API-Key", &self.api_key),
+            )
+            .await?;
 
         let status = response.status();
         let text = response
@@ -255,6 +584,103 @@ impl RestClient for TONRpcClient {
             Err(self.handle_non_success_response(status, &text, "get_account_states"))
         }
     }
+
+    async fn get_masterchain_info(&self) -> Result<MasterchainInfo, ClientError> {
+        let url = format!("{}/api/v3/masterchainInfo", self.url.trim_end_matches('/'));
+
+        let response = self
+            .send(
+                self.priority,
+                self.client.get(url).header("X-API-Key", &self.api_key),
+            )
+            .await?;
+
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|err| BadResponse(err.to_string()))?;
+
+        if status.is_success() {
+            serde_json::from_str::<MasterchainInfo>(&text)
+                .map_err(|err| BadResponse(format!("Failed to parse masterchain info: {err}")))
+        } else {
+            Err(self.handle_non_success_response(status, &text, "get_masterchain_info"))
+        }
+    }
+
+    async fn get_transactions_by_masterchain_block(
+        &self,
+        seqno: u32,
+    ) -> Result<Vec<Transaction>, ClientError> {
+        let query_params = vec![("seqno", seqno.to_string()), ("sort", "asc".to_string())];
+
+        let mut transactions = vec![];
+        loop {
+            let page = self
+                .fetch_transactions_page(&query_params, transactions.len())
+                .await?;
+            let page_len = page.len();
+            transactions.extend(page);
+            if page_len < self.traces_page_size {
+                return Ok(transactions);
+            }
+        }
+    }
+
+    async fn run_get_method(
+        &self,
+        address: TonAddress,
+        method: String,
+        stack: Vec<StackEntry>,
+    ) -> Result<RunGetMethodResult, ClientError> {
+        let stack = stack
+            .iter()
+            .map(StackEntry::to_raw)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| ClientError::BadRequest(err.to_string()))?;
+        let body = json!({
+            "address": address.to_string(),
+            "method": method,
+            "stack": stack,
+        });
+
+        let url = format!("{}/api/v3/runGetMethod", self.url.trim_end_matches('/'));
+        let response = self
+            .send(
+                self.priority,
+                self.client
+                    .post(url)
+                    .header("X-API-Key", &self.api_key)
+                    .json(&body),
+            )
+            .await?;
+
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|err| BadResponse(err.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_non_success_response(status, &text, method.as_str()));
+        }
+
+        let response = serde_json::from_str::<RunGetMethodResponse>(&text)
+            .map_err(|err| BadResponse(format!("Failed to parse get-method result: {err}")))?;
+        let stack = response
+            .stack
+            .into_iter()
+            .map(StackEntry::try_from)
+            .collect::<Result<_, _>>()
+            .map_err(|err| BadResponse(format!("Failed to decode get-method stack: {err}")))?;
+
+        Ok(RunGetMethodResult {
+            gas_used: response.gas_used,
+            exit_code: response.exit_code,
+            stack,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -263,6 +689,7 @@ mod tests {
     use httpmock::prelude::HttpMockRequest;
     use httpmock::Method::{GET, POST};
     use httpmock::MockServer;
+    use relayer_core::error::ClientError::BadRequest;
     use std::str::FromStr;
 
     #[tokio::test]
@@ -312,6 +739,70 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_post_v3_message_query_id_already_used() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST).path("/api/v3/message");
+            then.status(500).json_body(json!({
+                "code": 500,
+                "error": "LITE_SERVER_UNKNOWN: cannot apply external message to current state : External message was not accepted\nexitcode=36, steps=40, gas_used=0\nVM Log (truncated):\n...execute THROWIF 36"
+            }));
+        });
+
+        let client = TONRpcClient::new(server.base_url(), "test".to_string(), 0, 5, 5)
+            .await
+            .unwrap();
+
+        let err = client
+            .post_v3_message("test".to_string())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, BadRequest(_)));
+        assert_eq!(
+            ToncenterError::classify(&err).kind,
+            crate::toncenter_error::ToncenterErrorKind::QueryIdAlreadyUsed
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_v3_message_too_many_requests() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST).path("/api/v3/message");
+            then.status(429)
+                .header("Retry-After", "0")
+                .json_body(json!({"code": 429, "error": "Ratelimit exceed"}));
+        });
+
+        let rate_limiter = Arc::new(RateLimiter::new(100.0));
+        let client = TONRpcClient::new(server.base_url(), "test".to_string(), 1, 5, 5)
+            .await
+            .unwrap()
+            .with_rate_limiter(Arc::clone(&rate_limiter))
+            .with_priority(RequestPriority::AccountCheck);
+
+        let result = client.post_v3_message("test".to_string()).await;
+
+        assert!(matches!(result, Err(BadResponse(_))));
+        // Retried once after the Retry-After, not by the transient retry middleware
+        mock.assert_hits(2);
+        assert_eq!(rate_limiter.throttled(), 2);
+        assert_eq!(
+            rate_limiter
+                .queue_stats(RequestPriority::Broadcast)
+                .requests,
+            2
+        );
+        assert_eq!(
+            rate_limiter
+                .queue_stats(RequestPriority::AccountCheck)
+                .requests,
+            0
+        );
+    }
+
     #[tokio::test]
     async fn test_get_traces_with_start_lt() {
         let server = MockServer::start();
@@ -355,7 +846,9 @@ mod tests {
             "Expected successful result with start_lt, got: {result:?}"
         );
 
-        let traces = result.unwrap();
+        let window = result.unwrap();
+        assert!(!window.truncated);
+        let traces = window.traces;
         assert_eq!(traces.len(), 25);
 
         let txs = &traces[0].transactions;
@@ -436,6 +929,233 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_get_traces_follows_offset_until_exhausted() {
+        let server = MockServer::start();
+
+        let file_path = "tests/data/v3_traces.json";
+        let body = std::fs::read_to_string(file_path).expect("Failed to read JSON test file");
+
+        let first_page = server.mock(|when, then| {
+            when.method(GET)
+                .path("/api/v3/traces")
+                .query_param("start_lt", "2")
+                .query_param("sort", "asc")
+                .query_param("limit", "25")
+                .matches(|req: &HttpMockRequest| {
+                    req.query_params
+                        .as_ref()
+                        .is_some_and(|params| !params.iter().any(|(key, _)| key == "offset"))
+                });
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .body(body.clone());
+        });
+
+        let second_page = server.mock(|when, then| {
+            when.method(GET)
+                .path("/api/v3/traces")
+                .query_param("start_lt", "2")
+                .query_param("limit", "25")
+                .query_param("offset", "25");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .json_body(json!({"traces": []}));
+        });
+
+        let client = TONRpcClient::new(server.base_url(), "test".to_string(), 5, 5, 5)
+            .await
+            .unwrap()
+            .with_traces_pagination(25, 5);
+
+        let window = client
+            .get_traces_for_account(
+                Some(
+                    TonAddress::from_str(
+                        "0:AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+                    )
+                    .unwrap(),
+                ),
+                None,
+                Some(1),
+            )
+            .await
+            .unwrap();
+
+        first_page.assert_hits(1);
+        second_page.assert_hits(1);
+        assert_eq!(window.traces.len(), 25);
+        assert!(!window.truncated);
+    }
+
+    #[tokio::test]
+    async fn test_get_traces_stops_at_page_cap() {
+        let server = MockServer::start();
+
+        let file_path = "tests/data/v3_traces.json";
+        let body = std::fs::read_to_string(file_path).expect("Failed to read JSON test file");
+
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/api/v3/traces")
+                .query_param("start_lt", "2")
+                .query_param("limit", "25");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .body(body.clone());
+        });
+
+        let client = TONRpcClient::new(server.base_url(), "test".to_string(), 5, 5, 5)
+            .await
+            .unwrap()
+            .with_traces_pagination(25, 3);
+
+        let window = client
+            .get_traces_for_account(
+                Some(
+                    TonAddress::from_str(
+                        "0:AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+                    )
+                    .unwrap(),
+                ),
+                None,
+                Some(1),
+            )
+            .await
+            .unwrap();
+
+        mock.assert_hits(3);
+        assert_eq!(window.traces.len(), 75);
+        assert!(window.truncated);
+    }
+
+    #[tokio::test]
+    async fn test_get_traces_in_range() {
+        let server = MockServer::start();
+
+        let file_path = "tests/data/v3_traces.json";
+        let body = std::fs::read_to_string(file_path).expect("Failed to read JSON test file");
+
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/api/v3/traces")
+                .query_param(
+                    "account",
+                    "EQCqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqseb",
+                )
+                .query_param("start_lt", "100")
+                .query_param("end_lt", "200")
+                .query_param("sort", "asc")
+                .query_param("limit", "100");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .body(body.clone());
+        });
+
+        let client = TONRpcClient::new(server.base_url(), "test".to_string(), 5, 5, 5)
+            .await
+            .unwrap();
+
+        let window = client
+            .get_traces_for_account_in_range(
+                TonAddress::from_str(
+                    "0:AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+                )
+                .unwrap(),
+                100,
+                200,
+            )
+            .await
+            .unwrap();
+
+        mock.assert_hits(1);
+        assert_eq!(window.traces.len(), 25);
+        assert!(!window.truncated);
+    }
+
+    #[tokio::test]
+    async fn test_get_trace_by_message_hash() {
+        let server = MockServer::start();
+
+        let file_path = "tests/data/v3_traces.json";
+        let body = std::fs::read_to_string(file_path).expect("Failed to read JSON test file");
+
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/api/v3/traces")
+                .query_param("msg_hash", "ABC123");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .body(body.clone());
+        });
+
+        let client = TONRpcClient::new(server.base_url(), "test".to_string(), 5, 5, 5)
+            .await
+            .unwrap();
+
+        let trace = client
+            .get_trace_by_message_hash("ABC123".to_string())
+            .await
+            .unwrap();
+
+        mock.assert();
+        assert_eq!(trace.unwrap().trace_id, "abcd");
+    }
+
+    #[tokio::test]
+    async fn test_get_trace_by_message_hash_not_found() {
+        let server = MockServer::start();
+
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/api/v3/traces")
+                .query_param("msg_hash", "ABC123");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .json_body(json!({"traces": []}));
+        });
+
+        let client = TONRpcClient::new(server.base_url(), "test".to_string(), 5, 5, 5)
+            .await
+            .unwrap();
+
+        let trace = client
+            .get_trace_by_message_hash("ABC123".to_string())
+            .await
+            .unwrap();
+
+        assert!(trace.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_trace_by_tx_hash() {
+        let server = MockServer::start();
+
+        let file_path = "tests/data/v3_traces.json";
+        let body = std::fs::read_to_string(file_path).expect("Failed to read JSON test file");
+
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/api/v3/traces")
+                .query_param("tx_hash", "DEF456");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .body(body.clone());
+        });
+
+        let client = TONRpcClient::new(server.base_url(), "test".to_string(), 5, 5, 5)
+            .await
+            .unwrap();
+
+        let trace = client
+            .get_trace_by_tx_hash("DEF456".to_string())
+            .await
+            .unwrap();
+
+        mock.assert();
+        assert_eq!(trace.unwrap().trace_id, "abcd");
+    }
+
     #[tokio::test]
     async fn test_post_v3_message_retries_on_failure() {
         let server = MockServer::start();
@@ -458,6 +1178,55 @@ mod tests {
         mock.assert_hits_async(max_retries as usize + 1).await; // initial + retries
     }
 
+    #[tokio::test]
+    async fn test_retries_wait_for_rate_limiter() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST).path("/api/v3/message");
+            then.status(503).body("Service Temporarily Unavailable");
+        });
+
+        let rate_limiter = Arc::new(RateLimiter::new(100.0));
+        let client = TONRpcClient::new(server.base_url(), "test".to_string(), 1, 5, 5)
+            .await
+            .unwrap()
+            .with_rate_limiter(Arc::clone(&rate_limiter));
+
+        let result = client.post_v3_message("test".to_string()).await;
+
+        assert!(matches!(result, Err(BadResponse(_))));
+        mock.assert_hits(2);
+        // The retry took a token of its own
+        assert_eq!(
+            rate_limiter
+                .queue_stats(RequestPriority::Broadcast)
+                .requests,
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plain_text_server_error_is_transient() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/api/v3/masterchainInfo");
+            then.status(503).body("Service Temporarily Unavailable");
+        });
+
+        let client = TONRpcClient::new(server.base_url(), "test".to_string(), 0, 5, 30)
+            .await
+            .unwrap();
+
+        let err = client.get_masterchain_info().await.unwrap_err();
+        let error = ToncenterError::classify(&err);
+        assert_eq!(
+            error.kind,
+            crate::toncenter_error::ToncenterErrorKind::Unavailable
+        );
+        assert!(error.kind.is_transient());
+        assert!(error.message.contains("Service Temporarily Unavailable"));
+    }
+
     #[tokio::test]
     async fn test_post_v3_message_retries_on_timeout() {
         use std::time::Duration;
@@ -487,6 +1256,98 @@ mod tests {
         mock.assert_hits(max_retries as usize + 1);
     }
 
+    #[tokio::test]
+    async fn test_get_masterchain_info() {
+        let server = MockServer::start();
+
+        let block = |seqno: u32| {
+            json!({
+                "workchain": -1,
+                "shard": "8000000000000000",
+                "seqno": seqno,
+                "root_hash": "8sTjpEnSyB8m6gXrtbp6TQyoGsMLiTvM2Q8MiIjGYSU=",
+                "file_hash": "Y1J8iKu0rxDp2vDDkfiwqF0FOVfCIMYxe2eVOlz9tfM=",
+                "global_id": -3,
+                "version": 0,
+                "gen_utime": "1752338069"
+            })
+        };
+        let mock_response = json!({
+            "first": block(3),
+            "last": block(34567890),
+        });
+
+        server.mock(|when, then| {
+            when.method(GET).path("/api/v3/masterchainInfo");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .json_body(mock_response);
+        });
+
+        let client = TONRpcClient::new(server.base_url(), "test".to_string(), 3, 5, 10)
+            .await
+            .unwrap();
+
+        let info = client.get_masterchain_info().await.unwrap();
+        assert_eq!(info.first.seqno, 3);
+        assert_eq!(info.last.seqno, 34567890);
+        assert_eq!(info.last.workchain, -1);
+    }
+
+    #[tokio::test]
+    async fn test_get_transactions_by_masterchain_block() {
+        let server = MockServer::start();
+
+        let transactions = crate::test_utils::fixtures::fixture_traces()[1]
+            .transactions
+            .clone();
+
+        let first_page = server.mock(|when, then| {
+            when.method(GET)
+                .path("/api/v3/transactionsByMasterchainBlock")
+                .query_param("seqno", "34567890")
+                .query_param("sort", "asc")
+                .query_param("limit", "2")
+                .matches(|req: &HttpMockRequest| {
+                    req.query_params
+                        .as_ref()
+                        .is_some_and(|params| !params.iter().any(|(key, _)| key == "offset"))
+                });
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .json_body(json!({"transactions": transactions[..2]}));
+        });
+
+        let second_page = server.mock(|when, then| {
+            when.method(GET)
+                .path("/api/v3/transactionsByMasterchainBlock")
+                .query_param("seqno", "34567890")
+                .query_param("limit", "2")
+                .query_param("offset", "2");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .json_body(json!({"transactions": transactions[2..]}));
+        });
+
+        // The page cap doesn't apply, a block is always read to the end
+        let client = TONRpcClient::new(server.base_url(), "test".to_string(), 3, 5, 10)
+            .await
+            .unwrap()
+            .with_traces_pagination(2, 1);
+
+        let result = client
+            .get_transactions_by_masterchain_block(34567890)
+            .await
+            .unwrap();
+
+        first_page.assert_hits(1);
+        second_page.assert_hits(1);
+        let hashes: Vec<_> = result.iter().map(|tx| tx.hash.clone()).collect();
+        let expected: Vec<_> = transactions.iter().map(|tx| tx.hash.clone()).collect();
+        assert_eq!(hashes, expected);
+        assert_eq!(result[0].account, transactions[0].account);
+    }
+
     #[tokio::test]
     async fn test_get_account_states() {
         let server = MockServer::start();
@@ -535,4 +1396,58 @@ mod tests {
         assert_eq!(accounts[0].balance, "327063115");
         assert_eq!(accounts[0].status, "active");
     }
+
+    #[tokio::test]
+    async fn test_run_get_method() {
+        let server = MockServer::start();
+        let address = TonAddress::from_str(
+            "0:294D72EC421B930C60854F413478C162FDCEEC65746084EBACE25227182979A2",
+        )
+        .unwrap();
+
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/api/v3/runGetMethod")
+                .json_body(json!({
+                    "address": address.to_string(),
+                    "method": "processed?",
+                    "stack": [
+                        {"type": "num", "value": "0x2a"},
+                        {"type": "num", "value": "0x0"}
+                    ]
+                }));
+            then.status(200).json_body(json!({
+                "gas_used": 1234,
+                "exit_code": 0,
+                "stack": [
+                    {"type": "num", "value": "-0x1"},
+                    {"type": "tuple", "value": [{"type": "null"}]}
+                ]
+            }));
+        });
+
+        let client = TONRpcClient::new(server.base_url(), "test".to_string(), 3, 5, 10)
+            .await
+            .unwrap();
+
+        let result = client
+            .run_get_method(
+                address,
+                "processed?".to_string(),
+                vec![StackEntry::int(42), StackEntry::int(0)],
+            )
+            .await
+            .unwrap();
+
+        mock.assert();
+        assert_eq!(result.gas_used, 1234);
+        assert_eq!(result.exit_code, 0);
+        assert_eq!(
+            result.stack,
+            vec![
+                StackEntry::int(-1),
+                StackEntry::Tuple(vec![StackEntry::Null])
+            ]
+        );
+    }
 }
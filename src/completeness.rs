@@ -0,0 +1,164 @@
+/*!
+
+Decides locally whether the part of a trace that matters to us is complete.
+
+Toncenter only marks a trace complete once every message in it has been delivered, which can
+take long because of hops that have nothing to do with Axelar, e.g. jetton notifications sent to
+third parties after an ITS transfer. The part we care about is complete when every internal
+message sent by or to one of our contracts has reached its destination, and nothing still in
+flight could lead back to them, i.e. the only undelivered messages are downstream of our
+contracts.
+
+*/
+
+use crate::types::{Trace, Transaction, TransactionMessage};
+use std::collections::{HashMap, HashSet};
+use tonlib_core::TonAddress;
+use tracing::info;
+
+/// Internal messages of the trace that don't have a destination transaction (yet).
+pub fn undelivered_messages(trace: &Trace) -> Vec<(&Transaction, &TransactionMessage)> {
+    let delivered: HashSet<&str> = on_chain(trace)
+        .filter_map(|tx| tx.in_msg.as_ref())
+        .map(|msg| msg.hash.as_str())
+        .collect();
+
+    on_chain(trace)
+        .flat_map(|tx| tx.out_msgs.iter().map(move |msg| (tx, msg)))
+        .filter(|(_, msg)| msg.destination.is_some() && !delivered.contains(msg.hash.as_str()))
+        .collect()
+}
+
+/// Whether every message of the trace that involves `accounts`, or could still lead to them, has
+/// been delivered.
+pub fn is_relevant_part_complete(trace: &Trace, accounts: &[TonAddress]) -> bool {
+    let relevant = |account: &TonAddress| accounts.contains(account);
+
+    // Transaction that sent the message each transaction was triggered by
+    let senders: HashMap<&str, &Transaction> = on_chain(trace)
+        .flat_map(|tx| tx.out_msgs.iter().map(move |msg| (msg.hash.as_str(), tx)))
+        .collect();
+    let downstream_of_relevant = |tx: &Transaction| {
+        let mut current = Some(tx);
+        let mut visited = HashSet::new();
+        while let Some(tx) = current {
+            if relevant(&tx.account) {
+                return true;
+            }
+            if !visited.insert(tx.hash.as_str()) {
+                break;
+            }
+            current = tx
+                .in_msg
+                .as_ref()
+                .and_then(|msg| senders.get(msg.hash.as_str()).copied());
+        }
+        false
+    };
+
+    undelivered_messages(trace).into_iter().all(|(tx, msg)| {
+        let involves_relevant =
+            relevant(&tx.account) || msg.destination.as_ref().is_some_and(relevant);
+        !involves_relevant && downstream_of_relevant(tx)
+    })
+}
+
+/// Marks an incomplete trace complete if the part of it involving `accounts` is.
+pub fn settle(mut trace: Trace, accounts: &[TonAddress]) -> Trace {
+    if trace.is_incomplete && !accounts.is_empty() && is_relevant_part_complete(&trace, accounts) {
+        info!(
+            "Trace {} is incomplete, but its relevant part is complete",
+            trace.trace_id
+        );
+        trace.is_incomplete = false;
+    }
+    trace
+}
+
+// Emulated transactions haven't happened on chain
+fn on_chain(trace: &Trace) -> impl Iterator<Item = &Transaction> {
+    trace.transactions.iter().filter(|tx| !tx.emulated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fixtures::fixture_traces;
+
+    // Trace from an account through the gateway to other accounts and back
+    fn trace() -> (Trace, Vec<TonAddress>) {
+        let trace = fixture_traces()[2].clone();
+        let gateway = trace.transactions[2].account.clone();
+        (trace, vec![gateway])
+    }
+
+    fn remove(trace: &mut Trace, hash_prefix: &str) {
+        trace
+            .transactions
+            .retain(|tx| !tx.hash.starts_with(hash_prefix));
+    }
+
+    #[test]
+    fn test_complete_trace() {
+        let (trace, accounts) = trace();
+
+        assert!(undelivered_messages(&trace).is_empty());
+        assert!(is_relevant_part_complete(&trace, &accounts));
+    }
+
+    #[test]
+    fn test_undelivered_message_from_relevant_account() {
+        let (mut trace, accounts) = trace();
+        remove(&mut trace, "8eC9fP1T");
+
+        assert_eq!(undelivered_messages(&trace).len(), 1);
+        assert!(!is_relevant_part_complete(&trace, &accounts));
+    }
+
+    #[test]
+    fn test_emulated_transaction_is_not_delivered() {
+        let (mut trace, accounts) = trace();
+        for tx in trace.transactions.iter_mut() {
+            if tx.hash.starts_with("8eC9fP1T") {
+                tx.emulated = true;
+            }
+        }
+
+        assert!(!is_relevant_part_complete(&trace, &accounts));
+    }
+
+    #[test]
+    fn test_undelivered_message_upstream_of_relevant_account() {
+        let (mut trace, accounts) = trace();
+        // The message into the gateway is still delivered, but one next to it isn't
+        let third_party = trace.transactions[3].account.clone();
+        let mut msg = trace.transactions[0].out_msgs[0].clone();
+        msg.hash = "upstream".to_string();
+        msg.destination = Some(third_party);
+        trace.transactions[0].out_msgs.push(msg);
+
+        assert!(!is_relevant_part_complete(&trace, &accounts));
+    }
+
+    #[test]
+    fn test_undelivered_message_downstream_of_relevant_account() {
+        let (mut trace, accounts) = trace();
+        let sender = trace.transactions[0].account.clone();
+        // A notification from an account the gateway sent to, to one that isn't ours
+        let notifier = trace
+            .transactions
+            .iter_mut()
+            .find(|tx| tx.hash.starts_with("nSkwPWFT"))
+            .unwrap();
+        let mut msg = notifier.in_msg.clone().unwrap();
+        msg.hash = "notification".to_string();
+        msg.destination = Some(sender);
+        notifier.out_msgs.push(msg);
+        trace.is_incomplete = true;
+
+        assert_eq!(undelivered_messages(&trace).len(), 1);
+        assert!(is_relevant_part_complete(&trace, &accounts));
+        assert!(!settle(trace.clone(), &accounts).is_incomplete);
+        assert!(settle(trace, &[]).is_incomplete);
+    }
+}
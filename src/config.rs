@@ -26,6 +26,54 @@ pub struct GasEstimates {
     pub its_execute_minimum: u64,
 }
 
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct BroadcastBatchConfig {
+    // How long the first task of a batch waits for others to join it
+    pub window_ms: u64,
+    pub max_actions: usize,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ParsersConfig {
+    // Parser names (e.g. ParserNativeGasAdded) or categories (call_contract, gas_credit, its,
+    // standalone, execution_details, execution_failure) that are not run
+    #[serde(default)]
+    pub disabled: Vec<String>,
+    // Drop only the events a parser fails on and report the rest of the trace, instead of
+    // quarantining the whole trace
+    #[serde(default)]
+    pub partial_parsing: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct TraceRetryConfig {
+    // Retries of an incomplete trace before it is abandoned
+    pub budget: i32,
+    // Delay after the first retry, doubled after every further one up to max_delay_secs
+    pub base_delay_secs: u64,
+    pub max_delay_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct RpcEndpointConfig {
+    pub url: String,
+    // ton_api_key if unset
+    #[serde(default)]
+    pub api_key: Option<String>,
+    // Requests per second allowed for api_key, ton_rpc_rps if unset
+    #[serde(default)]
+    pub rps: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct StreamingConfig {
+    // Streaming API base url, ton_rpc if unset
+    #[serde(default)]
+    pub url: Option<String>,
+    // How often traces are still polled while the stream is up
+    pub catch_up_interval_secs: u64,
+}
+
 #[derive(Debug, Clone, Deserialize, Default)]
 pub struct TONConfig {
     #[serde(flatten)]
@@ -37,5 +85,45 @@ pub struct TONConfig {
     pub ton_its: String,
     pub ton_rpc: String,
     pub ton_api_key: String,
+    // Requests per second allowed for ton_api_key, shared through Redis by all clients of every
+    // process, unlimited if unset
+    #[serde(default)]
+    pub ton_rpc_rps: Option<f64>,
+    // Endpoints to fail over to when ton_rpc is down or lagging
+    #[serde(default)]
+    pub ton_rpc_fallbacks: Vec<RpcEndpointConfig>,
+    // Endpoints a message is sent to at once, 1 if unset
+    #[serde(default)]
+    pub ton_broadcast_fanout: Option<usize>,
+    // Traces per Toncenter request and max requests per poll, client defaults if unset
+    #[serde(default)]
+    pub ton_traces_page_size: Option<usize>,
+    #[serde(default)]
+    pub ton_traces_max_pages: Option<usize>,
+    // Walk masterchain blocks for all contracts at once instead of polling the traces of each
+    #[serde(default)]
+    pub ton_subscribe_by_block: bool,
+    // Masterchain blocks walked per poll, subscriber default if unset
+    #[serde(default)]
+    pub ton_blocks_per_poll: Option<u32>,
+    // Receive traces from the streaming API as they complete, polling only as a fallback
+    #[serde(default)]
+    pub ton_streaming: Option<StreamingConfig>,
+    // Masterchain blocks a trace has to be deep before it is queued, queued when complete if unset
+    #[serde(default)]
+    pub ton_finality_depth: Option<u64>,
+    // Treat traces as complete once the messages involving the gateway, gas service and ITS are
+    // delivered, instead of waiting for Toncenter to mark the whole trace complete
+    #[serde(default)]
+    pub ton_local_completeness: bool,
+    // Retry budget and backoff of incomplete traces, retry subscriber defaults if unset
+    #[serde(default)]
+    pub trace_retry: Option<TraceRetryConfig>,
+    // Batching of concurrent broadcasts into one external message, disabled if unset
+    #[serde(default)]
+    pub broadcast_batch: Option<BroadcastBatchConfig>,
+    // All parsers run if unset
+    #[serde(default)]
+    pub parsers: ParsersConfig,
     pub gas_estimates: GasEstimates,
 }
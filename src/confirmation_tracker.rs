@@ -0,0 +1,1057 @@
+/*!
+
+Confirmation tracker for messages sent by the broadcaster.
+
+`post_v3_message` only tells us that Toncenter accepted the external message. It does not tell us
+whether the highload wallet accepted it before its `timeout`, nor whether the approve, execute or
+refund it carried succeeded once it reached the gateway or the gas service.
+
+Every successful send is recorded in `ton_broadcasts` under its `message_hash_norm`. The tracker
+periodically claims a batch of pending messages, so that trackers of several includers don't check
+(and resend) the same one, looks up the trace of each and classifies it:
+
+- **success** – the trace is complete and none of the actions failed on its way from the wallet
+  through the gateway or the gas service,
+- **failed** – a transaction in the chain of one of the actions failed its compute or action phase,
+  or the wallet never sent the action (exit code is stored). Failures elsewhere in the trace, e.g.
+  a destination contract bouncing the excesses, don't count,
+- **expired** – no trace showed up until well after the wallet timeout, so the message can no
+  longer be accepted. The same actions are signed again with a fresh query id and the new message
+  is tracked in its place, up to `MAX_RESEND_ATTEMPTS` times. The broadcast is marked `resending`
+  before the new message goes out, so it can't be resent twice.
+
+A broadcast may carry several actions, so each of its messages is classified on its own and keeps
+its outcome in `messages`. The broadcast itself is failed if any of them failed.
+
+Messages that are still pending are released for the next round. A claim that is never released,
+e.g. because the includer died, runs out after `CLAIM_LEASE_SECS`.
+
+Every final outcome is handed to an `OutcomeReporter`. The relayer core includer has no hook for
+results that arrive after `BroadcastResult` was returned, so this is the place where the includer
+side reacts to them. `GmpApiOutcomeReporter` tells the GMP API about executions that expired for
+good, since no trace will ever report them. Executions that failed on chain are reported by the
+ingestor from their trace.
+
+# Usage Example
+
+```rust,no_run
+use std::sync::Arc;
+use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
+use ton::client::{RestClient, TONRpcClient};
+use ton::confirmation_tracker::{ConfirmationTracker, OutcomeReporter, Resender};
+use ton::ton_broadcast::PgTONBroadcastModel;
+
+async fn run(resender: Arc<dyn Resender>, reporter: Arc<dyn OutcomeReporter>) {
+    let client: Arc<dyn RestClient> = Arc::new(
+        TONRpcClient::new("https://testnet.toncenter.com".to_string(), "test".to_string(), 5, 5, 30)
+            .await
+            .unwrap(),
+    );
+    let pool = PgPool::connect("psql://foo?bar").await.unwrap();
+    let tracker = ConfirmationTracker::new(
+        client,
+        Arc::new(PgTONBroadcastModel::new(pool)),
+        resender,
+        reporter,
+    );
+    tracker.run(CancellationToken::new()).await;
+}
+```
+
+# See also
+
+- https://docs.ton.org/v3/guidelines/smart-contracts/howto/wallet#-high-load-wallet-v3
+
+*/
+
+use crate::boc::approve_message::ApproveMessages;
+use crate::boc::native_refund::NativeRefundMessage;
+use crate::boc::relayer_execute_wrapped::RelayerExecuteWrappedMessage;
+use crate::client::{RestClient, V3MessageResponse};
+use crate::ton_broadcast::{
+    BroadcastKind, BroadcastLog, BroadcastMessage, BroadcastStatus, MessageOutcome, TONBroadcast,
+};
+use crate::ton_constants::{OP_APPROVE_MESSAGES, OP_NATIVE_REFUND, OP_RELAYER_EXECUTE};
+use crate::transaction_parser::message_chain::{
+    chain_failure, Failure, APPROVAL_OPCODES, EXECUTION_OPCODES, REFUND_OPCODES,
+};
+use crate::types::{Trace, TransactionMessage};
+use async_trait::async_trait;
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use relayer_core::error::BroadcasterError;
+use relayer_core::gmp_api::gmp_types::{CannotExecuteMessageReason, CommonEventFields, Event};
+use relayer_core::gmp_api::GmpApiTrait;
+use relayer_core::utils::ThreadSafe;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tonlib_core::{TonAddress, TonHash};
+use tracing::{debug, error, info, warn};
+
+pub const MAX_RESEND_ATTEMPTS: i32 = 5;
+// Toncenter needs some time to index a trace, so we don't give up right at the wallet timeout
+const EXPIRY_GRACE_SECS: i64 = 60;
+const POLL_INTERVAL_SECS: u64 = 5;
+const PENDING_BATCH: i64 = 100;
+// Long enough to check a whole batch while requests wait for the rate limiter
+const CLAIM_LEASE_SECS: u64 = 300;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BroadcastOutcome {
+    Pending,
+    /// The trace is complete. `messages` are those of the broadcast, each with its own outcome.
+    Landed {
+        trace_id: String,
+        messages: Vec<BroadcastMessage>,
+    },
+    Expired,
+}
+
+impl BroadcastOutcome {
+    /// Exit code of the first message that failed, if any did.
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            BroadcastOutcome::Landed { messages, .. } => messages
+                .iter()
+                .find_map(|message| message.outcome.and_then(|outcome| outcome.exit_code())),
+            _ => None,
+        }
+    }
+}
+
+/// Signs the actions of an expired broadcast again and sends them with a new query id.
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait Resender: Send + Sync {
+    async fn resend(&self, broadcast: &TONBroadcast)
+        -> Result<V3MessageResponse, BroadcasterError>;
+}
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait OutcomeReporter: Send + Sync {
+    async fn report(&self, broadcast: &TONBroadcast, outcome: &BroadcastOutcome);
+}
+
+pub struct TracingOutcomeReporter;
+
+#[async_trait]
+impl OutcomeReporter for TracingOutcomeReporter {
+    async fn report(&self, broadcast: &TONBroadcast, outcome: &BroadcastOutcome) {
+        match outcome {
+            BroadcastOutcome::Landed { trace_id, messages } => {
+                for message in messages {
+                    match message.outcome {
+                        Some(MessageOutcome::Failed { exit_code }) => error!(
+                            "Broadcast {} failed on chain: message_id={}, kind={}, trace_id={}, exit_code={}",
+                            broadcast.message_hash_norm,
+                            message.message_id,
+                            message.kind.as_str(),
+                            trace_id,
+                            exit_code
+                        ),
+                        _ => info!(
+                            "Broadcast {} succeeded: message_id={}, kind={}, trace_id={}",
+                            broadcast.message_hash_norm,
+                            message.message_id,
+                            message.kind.as_str(),
+                            trace_id
+                        ),
+                    }
+                }
+            }
+            BroadcastOutcome::Expired => warn!(
+                "Broadcast {} expired: message_ids={:?}, attempts={}",
+                broadcast.message_hash_norm,
+                broadcast.message_ids(),
+                broadcast.attempts
+            ),
+            BroadcastOutcome::Pending => {}
+        }
+    }
+}
+
+/// Posts a `CannotExecuteMessageV2` event for every execution that expired for good, and logs
+/// every outcome.
+pub struct GmpApiOutcomeReporter<G> {
+    gmp_api: Arc<G>,
+}
+
+impl<G: GmpApiTrait + ThreadSafe> GmpApiOutcomeReporter<G> {
+    pub fn new(gmp_api: Arc<G>) -> Self {
+        Self { gmp_api }
+    }
+}
+
+#[async_trait]
+impl<G: GmpApiTrait + ThreadSafe> OutcomeReporter for GmpApiOutcomeReporter<G> {
+    async fn report(&self, broadcast: &TONBroadcast, outcome: &BroadcastOutcome) {
+        TracingOutcomeReporter.report(broadcast, outcome).await;
+
+        let events = expired_execution_events(broadcast, outcome);
+        if !events.is_empty() {
+            if let Err(e) = self.gmp_api.post_events(events).await {
+                error!(
+                    "Failed to report expired broadcast {}: {:?}",
+                    broadcast.message_hash_norm, e
+                );
+            }
+        }
+    }
+}
+
+/// Events telling that the executions carried by `broadcast` won't happen, one per message, if
+/// it ran out of resends.
+pub fn expired_execution_events(
+    broadcast: &TONBroadcast,
+    outcome: &BroadcastOutcome,
+) -> Vec<Event> {
+    // Resent broadcasts are tracked under their new hash
+    if *outcome != BroadcastOutcome::Expired || broadcast.attempts < MAX_RESEND_ATTEMPTS {
+        return vec![];
+    }
+
+    broadcast
+        .messages
+        .iter()
+        .filter(|message| message.kind == BroadcastKind::Execute)
+        .map(|message| Event::CannotExecuteMessageV2 {
+            common: CommonEventFields {
+                r#type: "CANNOT_EXECUTE_MESSAGE/V2".to_owned(),
+                event_id: format!(
+                    "cannot-execute-expired-{}-{}",
+                    broadcast.message_hash_norm, message.message_id
+                ),
+                meta: None,
+            },
+            message_id: message.message_id.clone(),
+            source_chain: message.source_chain.clone(),
+            reason: CannotExecuteMessageReason::Error,
+            details: format!(
+                "{}: expired after {} attempts",
+                broadcast.message_hash_norm, broadcast.attempts
+            ),
+        })
+        .collect()
+}
+
+/// Classifies the trace of `broadcast`, message by message. `expired` tells whether the wallet can still accept the
+/// external message, it is only consulted when there is no trace.
+///
+/// Only the transactions each message led to on its way through the gateway or the gas service
+/// count, a failure elsewhere in the trace (e.g. in the destination of an execution rejecting the
+/// excesses) doesn't make a message fail, nor does another message of the same broadcast.
+pub fn classify(
+    broadcast: &TONBroadcast,
+    trace: Option<&Trace>,
+    expired: bool,
+) -> BroadcastOutcome {
+    let trace = match trace {
+        Some(trace) => trace,
+        None if expired => return BroadcastOutcome::Expired,
+        None => return BroadcastOutcome::Pending,
+    };
+
+    if trace.is_incomplete {
+        return BroadcastOutcome::Pending;
+    }
+
+    let wallet = TonAddress::from_str(&broadcast.wallet_address).ok();
+    let messages = broadcast
+        .messages
+        .iter()
+        .map(|message| {
+            let outcome = match message_failure(trace, wallet.as_ref(), message) {
+                Some(exit_code) => MessageOutcome::Failed { exit_code },
+                None => MessageOutcome::Success,
+            };
+            message.clone().with_outcome(outcome)
+        })
+        .collect();
+
+    BroadcastOutcome::Landed {
+        trace_id: trace.trace_id.clone(),
+        messages,
+    }
+}
+
+/// Exit code of the first failure in the chain of transactions `message` led to, if any.
+fn message_failure(
+    trace: &Trace,
+    wallet: Option<&TonAddress>,
+    message: &BroadcastMessage,
+) -> Option<i32> {
+    let carrier = trace
+        .transactions
+        .iter()
+        .flat_map(|tx| tx.out_msgs.iter())
+        .find(|msg| carries(msg, message));
+
+    match carrier {
+        Some(msg) => chain_failure(trace, &msg.hash, chain_opcodes(&message.kind))
+            .map(|failure| failure.exit_code().unwrap_or(-1)),
+        // The wallet never sent the action, e.g. it couldn't pay for it
+        None => Some(
+            trace
+                .transactions
+                .iter()
+                .filter(|tx| Some(&tx.account) == wallet)
+                .find_map(Failure::of)
+                .and_then(|failure| failure.exit_code())
+                .unwrap_or(-1),
+        ),
+    }
+}
+
+fn chain_opcodes(kind: &BroadcastKind) -> &'static [u32] {
+    match kind {
+        BroadcastKind::Approve => &APPROVAL_OPCODES,
+        BroadcastKind::Execute => &EXECUTION_OPCODES,
+        BroadcastKind::Refund => &REFUND_OPCODES,
+    }
+}
+
+/// Whether `msg` is the action the broadcaster built for `message`.
+fn carries(msg: &TransactionMessage, message: &BroadcastMessage) -> bool {
+    let body = &msg.message_content.body;
+    match message.kind {
+        BroadcastKind::Approve => {
+            msg.opcode == Some(OP_APPROVE_MESSAGES)
+                && BASE64_STANDARD
+                    .decode(body)
+                    .ok()
+                    .and_then(|boc| ApproveMessages::from_boc_hex(&hex::encode(boc)).ok())
+                    .is_some_and(|approve| {
+                        approve.approve_messages.iter().any(|approved| {
+                            approved.message_id == message.message_id
+                                && approved.source_chain == message.source_chain
+                        })
+                    })
+        }
+        BroadcastKind::Execute => {
+            msg.opcode == Some(OP_RELAYER_EXECUTE)
+                && RelayerExecuteWrappedMessage::from_message_boc_b64(body).is_ok_and(|execute| {
+                    execute.message_id == message.message_id
+                        && execute.source_chain == message.source_chain
+                })
+        }
+        BroadcastKind::Refund => {
+            let tx_hash = message
+                .message_id
+                .strip_prefix("0x")
+                .unwrap_or(&message.message_id);
+            msg.opcode == Some(OP_NATIVE_REFUND)
+                && NativeRefundMessage::from_boc_b64(body).is_ok_and(|refund| {
+                    TonHash::from_hex(tx_hash).is_ok_and(|tx_hash| tx_hash == refund.tx_hash)
+                })
+        }
+    }
+}
+
+pub struct ConfirmationTracker {
+    client: Arc<dyn RestClient>,
+    broadcast_log: Arc<dyn BroadcastLog>,
+    resender: Arc<dyn Resender>,
+    reporter: Arc<dyn OutcomeReporter>,
+}
+
+impl ConfirmationTracker {
+    pub fn new(
+        client: Arc<dyn RestClient>,
+        broadcast_log: Arc<dyn BroadcastLog>,
+        resender: Arc<dyn Resender>,
+        reporter: Arc<dyn OutcomeReporter>,
+    ) -> Self {
+        Self {
+            client,
+            broadcast_log,
+            resender,
+            reporter,
+        }
+    }
+
+    pub async fn run(&self, token: CancellationToken) {
+        loop {
+            if let Err(e) = self.check_pending().await {
+                error!("Failed to check pending broadcasts: {:?}", e);
+            }
+
+            tokio::select! {
+                _ = token.cancelled() => {
+                    info!("Confirmation tracker stopped");
+                    return;
+                }
+                _ = tokio::time::sleep(Duration::from_secs(POLL_INTERVAL_SECS)) => {}
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn check_pending(&self) -> Result<(), BroadcasterError> {
+        let pending = self
+            .broadcast_log
+            .claim_pending(PENDING_BATCH, Duration::from_secs(CLAIM_LEASE_SECS))
+            .await
+            .map_err(|e| BroadcasterError::GenericError(e.to_string()))?;
+
+        debug!("Checking {} pending broadcasts", pending.len());
+
+        for broadcast in pending {
+            let resolved = match self.check(&broadcast).await {
+                Ok(outcome) => outcome != BroadcastOutcome::Pending,
+                Err(e) => {
+                    warn!(
+                        "Failed to check broadcast {}: {:?}",
+                        broadcast.message_hash_norm, e
+                    );
+                    false
+                }
+            };
+            if !resolved {
+                if let Err(e) = self
+                    .broadcast_log
+                    .release(&broadcast.message_hash_norm)
+                    .await
+                {
+                    warn!(
+                        "Failed to release broadcast {}: {:?}",
+                        broadcast.message_hash_norm, e
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn check(&self, broadcast: &TONBroadcast) -> Result<BroadcastOutcome, BroadcasterError> {
+        let trace = self
+            .client
+            .get_trace_by_message_hash(broadcast.message_hash_norm.clone())
+            .await
+            .map_err(|e| BroadcasterError::RPCCallFailed(e.to_string()))?;
+
+        let expired = chrono::Utc::now()
+            > broadcast.expires_at + chrono::Duration::seconds(EXPIRY_GRACE_SECS);
+
+        let outcome = classify(broadcast, trace.as_ref(), expired);
+
+        match &outcome {
+            BroadcastOutcome::Pending => return Ok(outcome),
+            BroadcastOutcome::Landed { trace_id, messages } => {
+                // The broadcast failed if any of its messages did, the messages tell which
+                let exit_code = outcome.exit_code();
+                let status = match exit_code {
+                    Some(_) => BroadcastStatus::Failed,
+                    None => BroadcastStatus::Success,
+                };
+                self.resolve(
+                    broadcast,
+                    status,
+                    exit_code,
+                    Some(trace_id),
+                    None,
+                    Some(messages.clone()),
+                )
+                .await?
+            }
+            BroadcastOutcome::Expired => {
+                let resent_as = if broadcast.attempts < MAX_RESEND_ATTEMPTS {
+                    Some(self.resend(broadcast).await?)
+                } else {
+                    None
+                };
+                self.resolve(
+                    broadcast,
+                    BroadcastStatus::Expired,
+                    None,
+                    None,
+                    resent_as,
+                    None,
+                )
+                .await?
+            }
+        }
+
+        self.reporter.report(broadcast, &outcome).await;
+
+        Ok(outcome)
+    }
+
+    /// Signs the actions of `broadcast` again and returns the hash of the new message.
+    ///
+    /// The broadcast is marked as resending before anything is sent, so if storing the new hash
+    /// fails it is left as it is instead of being claimed and resent once more. The resender has
+    /// already recorded the new message, which is tracked on its own.
+    async fn resend(&self, broadcast: &TONBroadcast) -> Result<String, BroadcasterError> {
+        let marked = self
+            .broadcast_log
+            .mark_resending(&broadcast.message_hash_norm)
+            .await
+            .map_err(|e| BroadcasterError::GenericError(e.to_string()))?;
+        if !marked {
+            return Err(BroadcasterError::GenericError(format!(
+                "Broadcast {} is no longer pending",
+                broadcast.message_hash_norm
+            )));
+        }
+
+        match self.resender.resend(broadcast).await {
+            Ok(response) => {
+                info!(
+                    "Broadcast {} expired, resent as {}",
+                    broadcast.message_hash_norm, response.message_hash_norm
+                );
+                Ok(response.message_hash_norm)
+            }
+            Err(e) => {
+                // Nothing was sent, so the broadcast is pending again for the next round
+                self.resolve(broadcast, BroadcastStatus::Pending, None, None, None, None)
+                    .await?;
+                Err(e)
+            }
+        }
+    }
+
+    async fn resolve(
+        &self,
+        broadcast: &TONBroadcast,
+        status: BroadcastStatus,
+        exit_code: Option<i32>,
+        trace_id: Option<&String>,
+        resent_as: Option<String>,
+        messages: Option<Vec<BroadcastMessage>>,
+    ) -> Result<(), BroadcasterError> {
+        self.broadcast_log
+            .resolve(
+                &broadcast.message_hash_norm,
+                status,
+                exit_code,
+                trace_id.cloned(),
+                resent_as,
+                messages,
+            )
+            .await
+            .map_err(|e| BroadcasterError::GenericError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::MockRestClient;
+    use crate::test_utils::fixtures::{fixture_reverted_traces, fixture_traces};
+    use crate::ton_broadcast::MockBroadcastLog;
+    use mockall::predicate::eq;
+    use mockall::Sequence;
+    use sqlx::types::Json;
+
+    fn broadcast(expires_in: i64, attempts: i32) -> TONBroadcast {
+        TONBroadcast {
+            message_hash_norm: "HASH".to_string(),
+            wallet_address: "EQAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAM9c".to_string(),
+            actions: Json(vec![]),
+            messages: Json(vec![BroadcastMessage::new(
+                BroadcastKind::Execute,
+                "0xabc-1".to_string(),
+                "avalanche-fuji".to_string(),
+            )]),
+            status: BroadcastStatus::Pending.as_str().to_string(),
+            exit_code: None,
+            trace_id: None,
+            resent_as: None,
+            attempts,
+            expires_at: chrono::Utc::now() + chrono::Duration::seconds(expires_in),
+            created_at: chrono::Utc::now(),
+            updated_at: None,
+            claimed_until: None,
+        }
+    }
+
+    /// Messages the broadcaster recorded for the actions with `opcode` the wallet sent in `trace`.
+    fn sent(trace: &Trace, opcode: u32) -> TONBroadcast {
+        let mut sent = broadcast(60, 1);
+        let mut messages = vec![];
+        for tx in &trace.transactions {
+            for msg in tx.out_msgs.iter().filter(|msg| msg.opcode == Some(opcode)) {
+                sent.wallet_address = tx.account.to_hex();
+                let body = &msg.message_content.body;
+                match opcode {
+                    OP_APPROVE_MESSAGES => {
+                        let boc = hex::encode(BASE64_STANDARD.decode(body).unwrap());
+                        let approve = ApproveMessages::from_boc_hex(&boc).unwrap();
+                        let approved = approve.approve_messages.first().unwrap();
+                        messages.push(BroadcastMessage::new(
+                            BroadcastKind::Approve,
+                            approved.message_id.clone(),
+                            approved.source_chain.clone(),
+                        ));
+                    }
+                    OP_RELAYER_EXECUTE => {
+                        let execute =
+                            RelayerExecuteWrappedMessage::from_message_boc_b64(body).unwrap();
+                        messages.push(BroadcastMessage::new(
+                            BroadcastKind::Execute,
+                            execute.message_id,
+                            execute.source_chain,
+                        ));
+                    }
+                    _ => {
+                        let refund = NativeRefundMessage::from_boc_b64(body).unwrap();
+                        messages.push(BroadcastMessage::new(
+                            BroadcastKind::Refund,
+                            format!("0x{}", refund.tx_hash),
+                            "ton2".to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+        sent.messages = Json(messages);
+        sent
+    }
+
+    fn landed(
+        trace: &Trace,
+        broadcast: &TONBroadcast,
+        outcomes: &[MessageOutcome],
+    ) -> BroadcastOutcome {
+        assert_eq!(broadcast.messages.len(), outcomes.len());
+        BroadcastOutcome::Landed {
+            trace_id: trace.trace_id.clone(),
+            messages: broadcast
+                .messages
+                .iter()
+                .zip(outcomes)
+                .map(|(message, outcome)| message.clone().with_outcome(*outcome))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        let traces = fixture_traces();
+
+        for (trace, opcode) in [
+            (&traces[2], OP_APPROVE_MESSAGES),
+            (&traces[7], OP_NATIVE_REFUND),
+            (&traces[11], OP_RELAYER_EXECUTE),
+        ] {
+            let broadcast = sent(trace, opcode);
+            assert_eq!(
+                classify(&broadcast, Some(trace), false),
+                landed(trace, &broadcast, &[MessageOutcome::Success])
+            );
+        }
+        let failed = sent(&traces[14], OP_RELAYER_EXECUTE);
+        let outcome = classify(&failed, Some(&traces[14]), false);
+        assert_eq!(
+            outcome,
+            landed(
+                &traces[14],
+                &failed,
+                &[MessageOutcome::Failed { exit_code: 106 }]
+            )
+        );
+        assert_eq!(outcome.exit_code(), Some(106));
+        // Compute phase succeeded, action phase did not
+        let failed = sent(&traces[15], OP_RELAYER_EXECUTE);
+        assert_eq!(
+            classify(&failed, Some(&traces[15]), false),
+            landed(
+                &traces[15],
+                &failed,
+                &[MessageOutcome::Failed { exit_code: 37 }]
+            )
+        );
+
+        let mut incomplete = traces[0].clone();
+        incomplete.is_incomplete = true;
+        assert_eq!(
+            classify(&broadcast(60, 1), Some(&incomplete), true),
+            BroadcastOutcome::Pending
+        );
+
+        assert_eq!(
+            classify(&broadcast(60, 1), None, false),
+            BroadcastOutcome::Pending
+        );
+        assert_eq!(
+            classify(&broadcast(60, 1), None, true),
+            BroadcastOutcome::Expired
+        );
+    }
+
+    #[test]
+    fn test_classify_ignores_failures_outside_the_chain() {
+        let traces = fixture_traces();
+
+        // The destination contracts of both executions failed on messages they sent themselves
+        for trace in [&traces[11], &traces[17]] {
+            let broadcast = sent(trace, OP_RELAYER_EXECUTE);
+            assert_eq!(
+                classify(&broadcast, Some(trace), false),
+                landed(trace, &broadcast, &[MessageOutcome::Success])
+            );
+        }
+
+        // A message the wallet never sent can't have succeeded
+        let not_sent = broadcast(60, 1);
+        assert_eq!(
+            classify(&not_sent, Some(&traces[11]), false),
+            landed(
+                &traces[11],
+                &not_sent,
+                &[MessageOutcome::Failed { exit_code: -1 }]
+            )
+        );
+    }
+
+    #[test]
+    fn test_classify_batch() {
+        let traces = fixture_reverted_traces();
+        let trace = &traces[2];
+        let batch = sent(trace, OP_RELAYER_EXECUTE);
+        assert_eq!(batch.messages.len(), 2);
+
+        // Only the second execution of the batch reverted
+        let outcome = classify(&batch, Some(trace), false);
+        assert_eq!(
+            outcome,
+            landed(
+                trace,
+                &batch,
+                &[
+                    MessageOutcome::Success,
+                    MessageOutcome::Failed { exit_code: 9 }
+                ]
+            )
+        );
+        assert_eq!(outcome.exit_code(), Some(9));
+    }
+
+    #[test]
+    fn test_expired_execution_events() {
+        let outcome = BroadcastOutcome::Expired;
+
+        // A batch of two executions and an approval
+        let mut batch = broadcast(-3600, MAX_RESEND_ATTEMPTS);
+        batch.messages.0.push(BroadcastMessage::new(
+            BroadcastKind::Execute,
+            "0xdef-2".to_string(),
+            "ethereum-sepolia".to_string(),
+        ));
+        batch.messages.0.push(BroadcastMessage::new(
+            BroadcastKind::Approve,
+            "0x123-3".to_string(),
+            "avalanche-fuji".to_string(),
+        ));
+
+        let events = expired_execution_events(&batch, &outcome);
+        assert_eq!(events.len(), 2);
+        match &events[1] {
+            Event::CannotExecuteMessageV2 {
+                common,
+                message_id,
+                source_chain,
+                ..
+            } => {
+                assert_eq!(common.event_id, "cannot-execute-expired-HASH-0xdef-2");
+                assert_eq!(message_id, "0xdef-2");
+                assert_eq!(source_chain, "ethereum-sepolia");
+            }
+            _ => panic!("Expected CannotExecuteMessageV2 event"),
+        }
+
+        // Resent, or not an execution
+        assert!(expired_execution_events(&broadcast(-3600, 1), &outcome).is_empty());
+        let mut approve = broadcast(-3600, MAX_RESEND_ATTEMPTS);
+        approve.messages.0[0].kind = BroadcastKind::Approve;
+        assert!(expired_execution_events(&approve, &outcome).is_empty());
+        assert!(expired_execution_events(
+            &broadcast(-3600, MAX_RESEND_ATTEMPTS),
+            &BroadcastOutcome::Landed {
+                trace_id: "trace".to_string(),
+                messages: vec![]
+            }
+        )
+        .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_check_pending_failed() {
+        let traces = fixture_traces();
+        let trace = traces[14].clone();
+        let trace_id = trace.trace_id.clone();
+        let failed = sent(&trace, OP_RELAYER_EXECUTE);
+
+        let mut client = MockRestClient::new();
+        client
+            .expect_get_trace_by_message_hash()
+            .with(eq("HASH".to_string()))
+            .returning(move |_| Ok(Some(trace.clone())));
+
+        let mut log = MockBroadcastLog::new();
+        log.expect_claim_pending()
+            .returning(move |_, _| Ok(vec![failed.clone()]));
+        log.expect_release().never();
+        log.expect_resolve()
+            .withf(move |hash, status, exit_code, trace, resent_as, messages| {
+                hash == "HASH"
+                    && *status == BroadcastStatus::Failed
+                    && *exit_code == Some(106)
+                    && trace.as_deref() == Some(trace_id.as_str())
+                    && resent_as.is_none()
+                    && messages.as_ref().is_some_and(|messages| {
+                        messages[0].outcome == Some(MessageOutcome::Failed { exit_code: 106 })
+                    })
+            })
+            .times(1)
+            .returning(|_, _, _, _, _, _| Ok(()));
+
+        let mut reporter = MockOutcomeReporter::new();
+        reporter
+            .expect_report()
+            .withf(|_, outcome| outcome.exit_code() == Some(106))
+            .times(1)
+            .returning(|_, _| ());
+
+        let tracker = ConfirmationTracker::new(
+            Arc::new(client),
+            Arc::new(log),
+            Arc::new(MockResender::new()),
+            Arc::new(reporter),
+        );
+
+        tracker.check_pending().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_check_pending_not_landed_yet() {
+        let mut client = MockRestClient::new();
+        client
+            .expect_get_trace_by_message_hash()
+            .returning(|_| Ok(None));
+
+        let mut log = MockBroadcastLog::new();
+        log.expect_claim_pending()
+            .returning(|_, _| Ok(vec![broadcast(60, 1)]));
+        log.expect_resolve().never();
+        // Left for the next round
+        log.expect_release()
+            .withf(|hash| hash == "HASH")
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let mut reporter = MockOutcomeReporter::new();
+        reporter.expect_report().never();
+
+        let tracker = ConfirmationTracker::new(
+            Arc::new(client),
+            Arc::new(log),
+            Arc::new(MockResender::new()),
+            Arc::new(reporter),
+        );
+
+        tracker.check_pending().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_check_pending_expired_is_resent() {
+        let mut client = MockRestClient::new();
+        client
+            .expect_get_trace_by_message_hash()
+            .returning(|_| Ok(None));
+
+        let mut seq = Sequence::new();
+        let mut log = MockBroadcastLog::new();
+        let mut resender = MockResender::new();
+        log.expect_claim_pending()
+            .returning(|_, _| Ok(vec![broadcast(-3600, 1)]));
+        // Marked before it is sent
+        log.expect_mark_resending()
+            .withf(|hash| hash == "HASH")
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_| Ok(true));
+        resender
+            .expect_resend()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_| {
+                Ok(V3MessageResponse {
+                    message_hash: "new_hash".to_string(),
+                    message_hash_norm: "NEW_HASH".to_string(),
+                })
+            });
+        log.expect_resolve()
+            .withf(|hash, status, _, _, resent_as, messages| {
+                hash == "HASH"
+                    && *status == BroadcastStatus::Expired
+                    && resent_as.as_deref() == Some("NEW_HASH")
+                    && messages.is_none()
+            })
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _, _, _, _, _| Ok(()));
+
+        let mut reporter = MockOutcomeReporter::new();
+        reporter
+            .expect_report()
+            .withf(|_, outcome| *outcome == BroadcastOutcome::Expired)
+            .times(1)
+            .returning(|_, _| ());
+
+        let tracker = ConfirmationTracker::new(
+            Arc::new(client),
+            Arc::new(log),
+            Arc::new(resender),
+            Arc::new(reporter),
+        );
+
+        tracker.check_pending().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_check_pending_resend_not_stored() {
+        let mut client = MockRestClient::new();
+        client
+            .expect_get_trace_by_message_hash()
+            .returning(|_| Ok(None));
+
+        let mut log = MockBroadcastLog::new();
+        log.expect_claim_pending()
+            .returning(|_, _| Ok(vec![broadcast(-3600, 1)]));
+        log.expect_mark_resending().times(1).returning(|_| Ok(true));
+        // The new hash is lost, but the broadcast stays marked as resending rather than pending
+        log.expect_resolve()
+            .withf(|_, status, _, _, _, _| *status == BroadcastStatus::Expired)
+            .times(1)
+            .returning(|_, _, _, _, _, _| Err(anyhow::anyhow!("connection reset")));
+        log.expect_release().times(1).returning(|_| Ok(()));
+
+        let mut resender = MockResender::new();
+        resender.expect_resend().times(1).returning(|_| {
+            Ok(V3MessageResponse {
+                message_hash: "new_hash".to_string(),
+                message_hash_norm: "NEW_HASH".to_string(),
+            })
+        });
+
+        let mut reporter = MockOutcomeReporter::new();
+        reporter.expect_report().never();
+
+        let tracker = ConfirmationTracker::new(
+            Arc::new(client),
+            Arc::new(log),
+            Arc::new(resender),
+            Arc::new(reporter),
+        );
+
+        tracker.check_pending().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_check_pending_resend_failed() {
+        let mut client = MockRestClient::new();
+        client
+            .expect_get_trace_by_message_hash()
+            .returning(|_| Ok(None));
+
+        let mut log = MockBroadcastLog::new();
+        log.expect_claim_pending()
+            .returning(|_, _| Ok(vec![broadcast(-3600, 1)]));
+        log.expect_mark_resending().times(1).returning(|_| Ok(true));
+        // Nothing went out, so it is pending again
+        log.expect_resolve()
+            .withf(|_, status, _, _, resent_as, _| {
+                *status == BroadcastStatus::Pending && resent_as.is_none()
+            })
+            .times(1)
+            .returning(|_, _, _, _, _, _| Ok(()));
+        log.expect_release().times(1).returning(|_| Ok(()));
+
+        let mut resender = MockResender::new();
+        resender
+            .expect_resend()
+            .times(1)
+            .returning(|_| Err(BroadcasterError::RPCCallFailed("timeout".to_string())));
+
+        let mut reporter = MockOutcomeReporter::new();
+        reporter.expect_report().never();
+
+        let tracker = ConfirmationTracker::new(
+            Arc::new(client),
+            Arc::new(log),
+            Arc::new(resender),
+            Arc::new(reporter),
+        );
+
+        tracker.check_pending().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_check_pending_already_resending() {
+        let mut client = MockRestClient::new();
+        client
+            .expect_get_trace_by_message_hash()
+            .returning(|_| Ok(None));
+
+        let mut log = MockBroadcastLog::new();
+        log.expect_claim_pending()
+            .returning(|_, _| Ok(vec![broadcast(-3600, 1)]));
+        log.expect_mark_resending()
+            .times(1)
+            .returning(|_| Ok(false));
+        log.expect_resolve().never();
+        log.expect_release().times(1).returning(|_| Ok(()));
+
+        let mut resender = MockResender::new();
+        resender.expect_resend().never();
+
+        let tracker = ConfirmationTracker::new(
+            Arc::new(client),
+            Arc::new(log),
+            Arc::new(resender),
+            Arc::new(MockOutcomeReporter::new()),
+        );
+
+        tracker.check_pending().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_check_pending_expired_gives_up() {
+        let mut client = MockRestClient::new();
+        client
+            .expect_get_trace_by_message_hash()
+            .returning(|_| Ok(None));
+
+        let mut log = MockBroadcastLog::new();
+        log.expect_claim_pending()
+            .returning(|_, _| Ok(vec![broadcast(-3600, MAX_RESEND_ATTEMPTS)]));
+        log.expect_resolve()
+            .withf(|_, status, _, _, resent_as, _| {
+                *status == BroadcastStatus::Expired && resent_as.is_none()
+            })
+            .times(1)
+            .returning(|_, _, _, _, _, _| Ok(()));
+
+        let mut resender = MockResender::new();
+        resender.expect_resend().never();
+
+        let mut reporter = MockOutcomeReporter::new();
+        reporter.expect_report().times(1).returning(|_, _| ());
+
+        let tracker = ConfirmationTracker::new(
+            Arc::new(client),
+            Arc::new(log),
+            Arc::new(resender),
+            Arc::new(reporter),
+        );
+
+        tracker.check_pending().await.unwrap();
+    }
+}
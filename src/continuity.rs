@@ -0,0 +1,161 @@
+/*!
+
+Checks that the transactions of a watched account form an unbroken chain.
+
+Every transaction points back to the previous transaction of its account (`prev_trans_hash`,
+`prev_trans_lt`). If the transaction a new one points to is not the last one we've seen, the
+transactions in between were missed, e.g. because a poll was truncated or the indexer was
+lagging, and their traces need to be fetched.
+
+*/
+
+use crate::types::{Trace, Transaction};
+use std::collections::HashSet;
+use tonlib_core::TonAddress;
+
+/// Transaction a chain of an account is known up to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainLink {
+    pub hash: String,
+    pub lt: i64,
+}
+
+/// Missing transactions, within `[from_lt, to_lt]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gap {
+    pub from_lt: i64,
+    pub to_lt: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Continuity {
+    pub gaps: Vec<Gap>,
+    /// Newest transaction of the account, if the traces had any newer than the previous head
+    pub head: Option<ChainLink>,
+}
+
+/// Follows the transactions of `account` in the traces, starting from `head`, the last
+/// transaction seen on it before.
+pub fn check_continuity(
+    account: &TonAddress,
+    head: Option<ChainLink>,
+    traces: &[Trace],
+) -> Continuity {
+    let mut seen = HashSet::new();
+    let mut transactions: Vec<&Transaction> = traces
+        .iter()
+        .flat_map(|trace| trace.transactions.iter())
+        .filter(|tx| tx.account == *account && !tx.emulated)
+        .filter(|tx| seen.insert(tx.hash.as_str()))
+        .collect();
+    transactions.sort_by_key(|tx| tx.lt);
+
+    let mut continuity = Continuity::default();
+    let mut last = head;
+    for tx in transactions {
+        if let Some(last) = &last {
+            if tx.lt <= last.lt {
+                continue;
+            }
+            if tx.prev_trans_hash != last.hash {
+                let to_lt = tx.prev_trans_lt.parse().unwrap_or(tx.lt - 1);
+                continuity.gaps.push(Gap {
+                    from_lt: last.lt + 1,
+                    to_lt,
+                });
+            }
+        }
+        let link = ChainLink {
+            hash: tx.hash.clone(),
+            lt: tx.lt,
+        };
+        last = Some(link.clone());
+        continuity.head = Some(link);
+    }
+
+    continuity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fixtures::fixture_traces;
+
+    fn account() -> TonAddress {
+        fixture_traces()[1].transactions[0].account.clone()
+    }
+
+    // Trace with one transaction on the account, pointing back to prev
+    fn trace(hash: &str, lt: i64, prev_hash: &str, prev_lt: i64) -> Trace {
+        let mut trace = fixture_traces()[1].clone();
+        let mut tx = trace.transactions[0].clone();
+        tx.hash = hash.to_string();
+        tx.lt = lt;
+        tx.prev_trans_hash = prev_hash.to_string();
+        tx.prev_trans_lt = prev_lt.to_string();
+        trace.trace_id = format!("trace-{hash}");
+        trace.transactions = vec![tx];
+        trace
+    }
+
+    #[test]
+    fn test_unbroken_chain() {
+        let head = ChainLink {
+            hash: "a".to_string(),
+            lt: 10,
+        };
+        let traces = vec![trace("c", 30, "b", 20), trace("b", 20, "a", 10)];
+
+        let continuity = check_continuity(&account(), Some(head), &traces);
+
+        assert!(continuity.gaps.is_empty());
+        assert_eq!(
+            continuity.head,
+            Some(ChainLink {
+                hash: "c".to_string(),
+                lt: 30
+            })
+        );
+    }
+
+    #[test]
+    fn test_gap_after_head_and_within_traces() {
+        let head = ChainLink {
+            hash: "a".to_string(),
+            lt: 10,
+        };
+        // b (lt 20) and d (lt 40) are missing
+        let traces = vec![trace("c", 30, "b", 20), trace("e", 50, "d", 40)];
+
+        let continuity = check_continuity(&account(), Some(head), &traces);
+
+        assert_eq!(
+            continuity.gaps,
+            vec![
+                Gap {
+                    from_lt: 11,
+                    to_lt: 20
+                },
+                Gap {
+                    from_lt: 31,
+                    to_lt: 40
+                }
+            ]
+        );
+        assert_eq!(continuity.head.unwrap().hash, "e");
+    }
+
+    #[test]
+    fn test_no_head_and_old_transactions() {
+        let traces = vec![trace("b", 20, "a", 10)];
+
+        // Nothing to compare the first transaction with
+        let continuity = check_continuity(&account(), None, &traces);
+        assert!(continuity.gaps.is_empty());
+        assert_eq!(continuity.head.clone().unwrap().hash, "b");
+
+        // Transactions up to the head were checked before
+        let continuity = check_continuity(&account(), continuity.head, &traces);
+        assert_eq!(continuity, Continuity::default());
+    }
+}
@@ -1,3 +1,4 @@
+use relayer_core::error::ClientError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -20,6 +21,8 @@ pub enum TONRpcError {
 pub enum GasError {
     #[error("ConversionError: {0}")]
     ConversionError(String),
+    #[error("PriceUnavailable: {0}")]
+    PriceUnavailable(String),
     #[error("GasCalculationError: {0}")]
     GasCalculationError(String),
 }
@@ -34,6 +37,67 @@ pub enum TransactionParsingError {
     Gas(String),
     #[error("ITSWithoutPair: {0}")]
     ITSWithoutPair(String),
+    #[error("PriceUnavailable: {0}")]
+    PriceUnavailable(String),
     #[error("GeneralError: {0}")]
     Generic(String),
+    #[error("{parser}: {source}")]
+    Parser {
+        parser: String,
+        source: Box<TransactionParsingError>,
+    },
+}
+
+impl TransactionParsingError {
+    /// Attributes the error to the named parser, unless it already is.
+    pub fn in_parser(self, parser: &str) -> Self {
+        match self {
+            TransactionParsingError::Parser { .. } => self,
+            _ => TransactionParsingError::Parser {
+                parser: parser.to_string(),
+                source: Box::new(self),
+            },
+        }
+    }
+
+    /// Name of the parser that failed, if known.
+    pub fn parser(&self) -> Option<&str> {
+        match self {
+            TransactionParsingError::Parser { parser, .. } => Some(parser),
+            _ => None,
+        }
+    }
+
+    /// Whether parsing the same trace again may succeed, e.g. once the price view answers.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            TransactionParsingError::PriceUnavailable(_) => true,
+            TransactionParsingError::Parser { source, .. } => source.is_transient(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum TvmStackError {
+    #[error("EncodingError: {0}")]
+    Encoding(String),
+    #[error("DecodingError: {0}")]
+    Decoding(String),
+    #[error("UnexpectedEntry: expected {expected}, got {got}")]
+    UnexpectedEntry { expected: String, got: String },
+    #[error("MissingEntry")]
+    MissingEntry,
+    #[error("OutOfRange: {0}")]
+    OutOfRange(String),
+}
+
+#[derive(Error, Debug)]
+pub enum GetMethodError {
+    #[error("ClientError: {0}")]
+    Client(#[from] ClientError),
+    #[error("{method} exited with code {exit_code}")]
+    ExitCode { method: String, exit_code: i32 },
+    #[error("StackError: {0}")]
+    Stack(#[from] TvmStackError),
 }
@@ -0,0 +1,609 @@
+/*!
+
+RestClient over several Toncenter endpoints.
+
+Every endpoint keeps track of its health: a moving average of its latency, the errors it returned
+in a row, and how far its indexer is behind the freshest endpoint's masterchain block. Reads go to
+the healthiest endpoint first and fail over to the next one on connection errors and bad responses.
+A bad request is the caller's fault, so it is returned right away.
+
+`post_v3_message` is sent to the `broadcast_fanout` healthiest endpoints at once, and succeeds if
+any of them accepted the message. If all of them fail, the remaining endpoints are tried one by one.
+
+*/
+
+use crate::client::{
+    AccountState, MasterchainInfo, RestClient, RunGetMethodResult, TONRpcClient, Trace,
+    TracesWindow, Transaction, V3MessageResponse, DEFAULT_TRACES_MAX_PAGES,
+    DEFAULT_TRACES_PAGE_SIZE,
+};
+use crate::config::TONConfig;
+use crate::rate_limiter::{RateLimiter, RequestPriority};
+use crate::tvm_stack::StackEntry;
+use async_trait::async_trait;
+use relayer_core::error::ClientError;
+use relayer_core::error::ClientError::BadRequest;
+use relayer_core::redis::connection_manager;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{info, warn};
+
+// Weight of the latest request in the latency average
+const LATENCY_SMOOTHING: f64 = 0.2;
+// Assumed latency of an endpoint that wasn't used yet, so the primary is preferred until it slows down
+const DEFAULT_LATENCY_MS: f64 = 500.0;
+const ERROR_PENALTY_MS: f64 = 1_000.0;
+const LAG_PENALTY_MS_PER_BLOCK: f64 = 500.0;
+// Endpoints with this many errors in a row are only tried last, until the cooldown is over
+const COOLDOWN_AFTER_ERRORS: u32 = 3;
+const COOLDOWN: Duration = Duration::from_secs(30);
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Default)]
+pub struct EndpointHealth {
+    pub latency_ms: Option<f64>,
+    pub consecutive_errors: u32,
+    pub last_error_at: Option<Instant>,
+    // Latest masterchain block the endpoint's indexer knows about
+    pub mc_seqno: Option<u32>,
+}
+
+impl EndpointHealth {
+    fn record_success(&mut self, latency: Duration) {
+        let latency_ms = latency.as_secs_f64() * 1_000.0;
+        self.latency_ms = Some(match self.latency_ms {
+            Some(average) => average + LATENCY_SMOOTHING * (latency_ms - average),
+            None => latency_ms,
+        });
+        self.consecutive_errors = 0;
+    }
+
+    fn record_error(&mut self) {
+        self.consecutive_errors += 1;
+        self.last_error_at = Some(Instant::now());
+    }
+
+    /// Lower is healthier.
+    fn score(&self, best_mc_seqno: Option<u32>) -> f64 {
+        let cooling_down = self.consecutive_errors >= COOLDOWN_AFTER_ERRORS
+            && self.last_error_at.is_some_and(|at| at.elapsed() < COOLDOWN);
+        if cooling_down {
+            return f64::MAX;
+        }
+
+        let lag = match (best_mc_seqno, self.mc_seqno) {
+            (Some(best), Some(seqno)) => best.saturating_sub(seqno),
+            _ => 0,
+        };
+
+        self.latency_ms.unwrap_or(DEFAULT_LATENCY_MS)
+            + self.consecutive_errors as f64 * ERROR_PENALTY_MS
+            + lag as f64 * LAG_PENALTY_MS_PER_BLOCK
+    }
+}
+
+struct Endpoint<CL> {
+    name: String,
+    client: CL,
+    health: Mutex<EndpointHealth>,
+}
+
+impl<CL> Endpoint<CL> {
+    fn health(&self) -> std::sync::MutexGuard<'_, EndpointHealth> {
+        // Health is only ever replaced field by field, a poisoned lock still holds usable data
+        self.health
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Runs the request and records how it went.
+    async fn call<T, Fut>(&self, request: Fut) -> Result<T, ClientError>
+    where
+        Fut: Future<Output = Result<T, ClientError>>,
+    {
+        let started = Instant::now();
+        let result = request.await;
+        match &result {
+            Ok(_) | Err(BadRequest(_)) => self.health().record_success(started.elapsed()),
+            Err(err) => {
+                warn!("Endpoint {} failed: {:?}", self.name, err);
+                self.health().record_error();
+            }
+        }
+        result
+    }
+}
+
+#[derive(Clone)]
+pub struct FailoverRestClient<CL: RestClient + Clone + 'static> {
+    endpoints: Vec<Arc<Endpoint<CL>>>,
+    broadcast_fanout: usize,
+}
+
+impl<CL: RestClient + Clone + 'static> FailoverRestClient<CL> {
+    /// Named endpoints, in order of preference while they are equally healthy.
+    pub fn new(endpoints: Vec<(String, CL)>) -> Result<Self, ClientError> {
+        if endpoints.is_empty() {
+            return Err(ClientError::ConnectionFailed(
+                "No endpoints configured".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            endpoints: endpoints
+                .into_iter()
+                .map(|(name, client)| {
+                    Arc::new(Endpoint {
+                        name,
+                        client,
+                        health: Mutex::new(EndpointHealth::default()),
+                    })
+                })
+                .collect(),
+            broadcast_fanout: 1,
+        })
+    }
+
+    /// Number of endpoints a message is sent to at once.
+    pub fn with_broadcast_fanout(mut self, fanout: usize) -> Self {
+        self.broadcast_fanout = fanout.max(1);
+        self
+    }
+
+    pub fn health(&self) -> Vec<(String, EndpointHealth)> {
+        self.endpoints
+            .iter()
+            .map(|endpoint| (endpoint.name.clone(), endpoint.health().clone()))
+            .collect()
+    }
+
+    /// Asks every endpoint for its latest masterchain block, to see how far behind each is.
+    pub async fn refresh(&self) {
+        for endpoint in &self.endpoints {
+            if let Ok(info) = endpoint.call(endpoint.client.get_masterchain_info()).await {
+                endpoint.health().mc_seqno = Some(info.last.seqno);
+            }
+        }
+    }
+
+    /// Refreshes the health of the endpoints every `interval`.
+    pub fn spawn_health_checks(&self, interval: Duration) -> JoinHandle<()> {
+        let client = self.clone();
+        tokio::spawn(async move {
+            loop {
+                client.refresh().await;
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+
+    /// Endpoints from healthiest to least healthy.
+    fn ranked(&self) -> Vec<Arc<Endpoint<CL>>> {
+        let healths: Vec<EndpointHealth> = self
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.health().clone())
+            .collect();
+        let best_mc_seqno = healths.iter().filter_map(|health| health.mc_seqno).max();
+
+        let mut ranked: Vec<(f64, Arc<Endpoint<CL>>)> = healths
+            .iter()
+            .map(|health| health.score(best_mc_seqno))
+            .zip(self.endpoints.iter().cloned())
+            .collect();
+        // Stable, so equally healthy endpoints keep the configured order
+        ranked.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        ranked.into_iter().map(|(_, endpoint)| endpoint).collect()
+    }
+
+    /// Sends the request to the healthiest endpoint, failing over to the next ones.
+    async fn read<T, F, Fut>(&self, request: F) -> Result<T, ClientError>
+    where
+        F: Fn(CL) -> Fut,
+        Fut: Future<Output = Result<T, ClientError>>,
+    {
+        let mut last_err = None;
+        for endpoint in self.ranked() {
+            match endpoint.call(request(endpoint.client.clone())).await {
+                Err(err @ BadRequest(_)) => return Err(err),
+                Err(err) => last_err = Some(err),
+                ok => return ok,
+            }
+        }
+        Err(last_err
+            .unwrap_or_else(|| ClientError::ConnectionFailed("No endpoints configured".into())))
+    }
+}
+
+impl FailoverRestClient<TONRpcClient> {
+    /// Client over `ton_rpc` followed by `ton_rpc_fallbacks`, reading with `priority`. With
+    /// fallbacks, the health of the endpoints is refreshed in the background for as long as the
+    /// process runs, so that an endpoint falling behind is noticed by every user of the client.
+    pub async fn from_config(
+        config: &TONConfig,
+        priority: RequestPriority,
+    ) -> Result<Self, ClientError> {
+        let mut endpoints = vec![(
+            config.ton_rpc.clone(),
+            config.ton_api_key.clone(),
+            config.ton_rpc_rps,
+        )];
+        for fallback in &config.ton_rpc_fallbacks {
+            let api_key = fallback
+                .api_key
+                .clone()
+                .unwrap_or_else(|| config.ton_api_key.clone());
+            endpoints.push((
+                fallback.url.clone(),
+                api_key,
+                fallback.rps.or(config.ton_rpc_rps),
+            ));
+        }
+
+        // The processes using a key share its quota through Redis
+        let redis_conn = if endpoints.iter().any(|(_, _, rps)| rps.is_some()) {
+            let redis_client = redis::Client::open(config.common_config.redis_server.clone())
+                .map_err(|e| ClientError::ConnectionFailed(e.to_string()))?;
+            Some(
+                connection_manager(redis_client, None, None, None)
+                    .await
+                    .map_err(|e| ClientError::ConnectionFailed(e.to_string()))?,
+            )
+        } else {
+            None
+        };
+
+        let mut clients = vec![];
+        for (url, api_key, rps) in endpoints {
+            let rate_limiter = match (rps, &redis_conn) {
+                (Some(rps), Some(conn)) => {
+                    Some(RateLimiter::shared_in_redis(&api_key, rps, conn.clone()))
+                }
+                _ => None,
+            };
+            let mut client = TONRpcClient::new(url.clone(), api_key, 5, 5, 30)
+                .await?
+                .with_traces_pagination(
+                    config
+                        .ton_traces_page_size
+                        .unwrap_or(DEFAULT_TRACES_PAGE_SIZE),
+                    config
+                        .ton_traces_max_pages
+                        .unwrap_or(DEFAULT_TRACES_MAX_PAGES),
+                )
+                .with_priority(priority);
+            if let Some(rate_limiter) = rate_limiter {
+                client = client.with_rate_limiter(rate_limiter);
+            }
+            clients.push((url, client));
+        }
+
+        let client =
+            Self::new(clients)?.with_broadcast_fanout(config.ton_broadcast_fanout.unwrap_or(1));
+        if client.endpoints.len() > 1 {
+            client.spawn_health_checks(HEALTH_CHECK_INTERVAL);
+        }
+        Ok(client)
+    }
+}
+
+#[async_trait]
+impl<CL: RestClient + Clone + 'static> RestClient for FailoverRestClient<CL> {
+    async fn post_v3_message(&self, boc: String) -> Result<V3MessageResponse, ClientError> {
+        let ranked = self.ranked();
+        let (fanout, rest) = ranked.split_at(self.broadcast_fanout.min(ranked.len()));
+
+        let mut sends = JoinSet::new();
+        for endpoint in fanout {
+            let endpoint = Arc::clone(endpoint);
+            let boc = boc.clone();
+            sends.spawn(async move {
+                let result = endpoint.call(endpoint.client.post_v3_message(boc)).await;
+                (endpoint.name.clone(), result)
+            });
+        }
+
+        let mut accepted = None;
+        let mut errors = vec![];
+        while let Some(joined) = sends.join_next().await {
+            match joined {
+                Ok((name, Ok(response))) => {
+                    info!("Message {} accepted by {}", response.message_hash, name);
+                    accepted.get_or_insert(response);
+                }
+                Ok((_, Err(err))) => errors.push(err),
+                Err(err) => errors.push(ClientError::ConnectionFailed(err.to_string())),
+            }
+        }
+        if let Some(response) = accepted {
+            return Ok(response);
+        }
+
+        if !errors.iter().any(|err| matches!(err, BadRequest(_))) {
+            for endpoint in rest {
+                match endpoint
+                    .call(endpoint.client.post_v3_message(boc.clone()))
+                    .await
+                {
+                    Ok(response) => return Ok(response),
+                    Err(err) => errors.push(err),
+                }
+            }
+        }
+
+        // A rejected message says more than an endpoint that couldn't be reached
+        let position = errors
+            .iter()
+            .position(|err| matches!(err, BadRequest(_)))
+            .unwrap_or(errors.len().saturating_sub(1));
+        Err(errors.into_iter().nth(position).unwrap_or_else(|| {
+            ClientError::ConnectionFailed("No endpoints configured".to_string())
+        }))
+    }
+
+    async fn get_traces_for_account(
+        &self,
+        account: Option<tonlib_core::TonAddress>,
+        trace_ids: Option<Vec<String>>,
+        start_lt: Option<i64>,
+    ) -> Result<TracesWindow, ClientError> {
+        self.read(|client| {
+            let account = account.clone();
+            let trace_ids = trace_ids.clone();
+            async move {
+                client
+                    .get_traces_for_account(account, trace_ids, start_lt)
+                    .await
+            }
+        })
+        .await
+    }
+
+    async fn get_traces_for_account_in_range(
+        &self,
+        account: tonlib_core::TonAddress,
+        start_lt: i64,
+        end_lt: i64,
+    ) -> Result<TracesWindow, ClientError> {
+        self.read(|client| {
+            let account = account.clone();
+            async move {
+                client
+                    .get_traces_for_account_in_range(account, start_lt, end_lt)
+                    .await
+            }
+        })
+        .await
+    }
+
+    async fn get_trace_by_message_hash(
+        &self,
+        message_hash: String,
+    ) -> Result<Option<Trace>, ClientError> {
+        self.read(|client| {
+            let message_hash = message_hash.clone();
+            async move { client.get_trace_by_message_hash(message_hash).await }
+        })
+        .await
+    }
+
+    async fn get_trace_by_tx_hash(&self, tx_hash: String) -> Result<Option<Trace>, ClientError> {
+        self.read(|client| {
+            let tx_hash = tx_hash.clone();
+            async move { client.get_trace_by_tx_hash(tx_hash).await }
+        })
+        .await
+    }
+
+    async fn get_account_states(
+        &self,
+        addresses: Vec<tonlib_core::TonAddress>,
+    ) -> Result<Vec<AccountState>, ClientError> {
+        self.read(|client| {
+            let addresses = addresses.clone();
+            async move { client.get_account_states(addresses).await }
+        })
+        .await
+    }
+
+    async fn get_masterchain_info(&self) -> Result<MasterchainInfo, ClientError> {
+        let mut last_err = None;
+        for endpoint in self.ranked() {
+            match endpoint.call(endpoint.client.get_masterchain_info()).await {
+                Ok(info) => {
+                    endpoint.health().mc_seqno = Some(info.last.seqno);
+                    return Ok(info);
+                }
+                Err(err @ BadRequest(_)) => return Err(err),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err
+            .unwrap_or_else(|| ClientError::ConnectionFailed("No endpoints configured".into())))
+    }
+
+    async fn get_transactions_by_masterchain_block(
+        &self,
+        seqno: u32,
+    ) -> Result<Vec<Transaction>, ClientError> {
+        self.read(|client| async move { client.get_transactions_by_masterchain_block(seqno).await })
+            .await
+    }
+
+    async fn run_get_method(
+        &self,
+        address: tonlib_core::TonAddress,
+        method: String,
+        stack: Vec<StackEntry>,
+    ) -> Result<RunGetMethodResult, ClientError> {
+        self.read(|client| {
+            let address = address.clone();
+            let method = method.clone();
+            let stack = stack.clone();
+            async move { client.run_get_method(address, method, stack).await }
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::Method::{GET, POST};
+    use httpmock::{Mock, MockServer};
+    use serde_json::json;
+
+    async fn failover(servers: &[&MockServer]) -> FailoverRestClient<TONRpcClient> {
+        let mut clients = vec![];
+        for server in servers {
+            let client = TONRpcClient::new(server.base_url(), "test".to_string(), 0, 5, 5)
+                .await
+                .unwrap();
+            clients.push((server.base_url(), client));
+        }
+        FailoverRestClient::new(clients).unwrap()
+    }
+
+    fn mock_masterchain_info(server: &MockServer, seqno: u32) -> Mock<'_> {
+        server.mock(|when, then| {
+            when.method(GET).path("/api/v3/masterchainInfo");
+            then.status(200).json_body(json!({
+                "first": {"workchain": -1, "shard": "8000000000000000", "seqno": 1},
+                "last": {"workchain": -1, "shard": "8000000000000000", "seqno": seqno},
+            }));
+        })
+    }
+
+    fn mock_traces(server: &MockServer, status: u16) -> Mock<'_> {
+        server.mock(|when, then| {
+            when.method(GET).path("/api/v3/traces");
+            match status {
+                200 => then.status(200).json_body(json!({"traces": []})),
+                400 => then
+                    .status(400)
+                    .json_body(json!({"code": 400, "error": "invalid trace_id"})),
+                _ => then.status(status).body("unavailable"),
+            };
+        })
+    }
+
+    #[tokio::test]
+    async fn test_read_fails_over_to_next_endpoint() {
+        let primary = MockServer::start();
+        let secondary = MockServer::start();
+        let primary_traces = mock_traces(&primary, 503);
+        let secondary_traces = mock_traces(&secondary, 200);
+        let client = failover(&[&primary, &secondary]).await;
+
+        for _ in 0..2 {
+            let window = client
+                .get_traces_for_account(None, Some(vec!["abcd".to_string()]), None)
+                .await
+                .unwrap();
+            assert!(window.traces.is_empty());
+        }
+
+        // The primary is ranked below the secondary after failing once
+        primary_traces.assert_hits(1);
+        secondary_traces.assert_hits(2);
+        let health = client.health();
+        assert_eq!(health[0].1.consecutive_errors, 1);
+        assert_eq!(health[1].1.consecutive_errors, 0);
+        assert!(health[1].1.latency_ms.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_bad_request_does_not_fail_over() {
+        let primary = MockServer::start();
+        let secondary = MockServer::start();
+        mock_traces(&primary, 400);
+        let secondary_traces = mock_traces(&secondary, 200);
+        let client = failover(&[&primary, &secondary]).await;
+
+        let result = client
+            .get_traces_for_account(None, Some(vec!["abcd".to_string()]), None)
+            .await;
+
+        assert!(matches!(result, Err(BadRequest(msg)) if msg == "invalid trace_id"));
+        secondary_traces.assert_hits(0);
+        assert_eq!(client.health()[0].1.consecutive_errors, 0);
+    }
+
+    #[tokio::test]
+    async fn test_lagging_endpoint_is_ranked_last() {
+        let primary = MockServer::start();
+        let secondary = MockServer::start();
+        mock_masterchain_info(&primary, 100);
+        mock_masterchain_info(&secondary, 110);
+        let primary_traces = mock_traces(&primary, 200);
+        let secondary_traces = mock_traces(&secondary, 200);
+        let client = failover(&[&primary, &secondary]).await;
+
+        client.refresh().await;
+        client
+            .get_traces_for_account(None, Some(vec!["abcd".to_string()]), None)
+            .await
+            .unwrap();
+
+        primary_traces.assert_hits(0);
+        secondary_traces.assert_hits(1);
+        assert_eq!(client.health()[0].1.mc_seqno, Some(100));
+    }
+
+    #[tokio::test]
+    async fn test_post_message_fans_out() {
+        let primary = MockServer::start();
+        let secondary = MockServer::start();
+        let third = MockServer::start();
+        let primary_post = primary.mock(|when, then| {
+            when.method(POST).path("/api/v3/message");
+            then.status(502).body("bad gateway");
+        });
+        let secondary_post = secondary.mock(|when, then| {
+            when.method(POST)
+                .path("/api/v3/message")
+                .body(r#"{"boc":"test"}"#);
+            then.status(200)
+                .json_body(json!({"message_hash": "abc123", "message_hash_norm": "ABC123"}));
+        });
+        let third_post = third.mock(|when, then| {
+            when.method(POST).path("/api/v3/message");
+            then.status(200)
+                .json_body(json!({"message_hash": "abc123", "message_hash_norm": "ABC123"}));
+        });
+        let client = failover(&[&primary, &secondary, &third])
+            .await
+            .with_broadcast_fanout(2);
+
+        let response = client.post_v3_message("test".to_string()).await.unwrap();
+
+        assert_eq!(response.message_hash, "abc123");
+        primary_post.assert_hits(1);
+        secondary_post.assert_hits(1);
+        // Not needed, one of the fanned out endpoints accepted the message
+        third_post.assert_hits(0);
+    }
+
+    #[tokio::test]
+    async fn test_post_message_returns_rejection() {
+        let primary = MockServer::start();
+        let secondary = MockServer::start();
+        primary.mock(|when, then| {
+            when.method(POST).path("/api/v3/message");
+            then.status(503).body("unavailable");
+        });
+        let secondary_post = secondary.mock(|when, then| {
+            when.method(POST).path("/api/v3/message");
+            then.status(400)
+                .json_body(json!({"code": 400, "error": "Invalid BOC format"}));
+        });
+        let client = failover(&[&primary, &secondary]).await;
+
+        let result = client.post_v3_message("bad".to_string()).await;
+
+        secondary_post.assert_hits(1);
+        assert!(matches!(result, Err(BadRequest(msg)) if msg == "Invalid BOC format"));
+    }
+}
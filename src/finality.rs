@@ -0,0 +1,60 @@
+/*!
+
+Masterchain finality of traces.
+
+A trace is only handed on once every one of its transactions is a number of masterchain blocks
+deep, so that events are only sent for transactions that can't be rolled back.
+
+*/
+
+use crate::types::Trace;
+
+/// Highest masterchain block a transaction of the trace is referenced in.
+pub fn trace_mc_seqno(trace: &Trace) -> u64 {
+    trace
+        .transactions
+        .iter()
+        .map(|tx| tx.mc_block_seqno)
+        .max()
+        .unwrap_or(0)
+}
+
+pub fn is_final(trace: &Trace, latest_mc_seqno: u64, depth: u64) -> bool {
+    trace_mc_seqno(trace).saturating_add(depth) <= latest_mc_seqno
+}
+
+/// Splits traces into the final ones and the ones to hold until they are.
+pub fn split_final(
+    traces: Vec<Trace>,
+    latest_mc_seqno: u64,
+    depth: u64,
+) -> (Vec<Trace>, Vec<Trace>) {
+    traces
+        .into_iter()
+        .partition(|trace| is_final(trace, latest_mc_seqno, depth))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fixtures::fixture_traces;
+
+    #[test]
+    fn test_is_final() {
+        let trace = fixture_traces()[1].clone();
+        let seqno = trace_mc_seqno(&trace);
+        assert!(trace
+            .transactions
+            .iter()
+            .all(|tx| tx.mc_block_seqno <= seqno));
+
+        assert!(is_final(&trace, seqno, 0));
+        assert!(!is_final(&trace, seqno, 1));
+        assert!(is_final(&trace, seqno + 5, 5));
+        assert!(!is_final(&trace, seqno + 4, 5));
+
+        let (final_traces, pending) = split_final(vec![trace], seqno + 1, 2);
+        assert!(final_traces.is_empty());
+        assert_eq!(pending.len(), 1);
+    }
+}
@@ -5,6 +5,7 @@ This might be overly simplistic right now, we will test more to find a better wa
 */
 
 use crate::config::GasEstimates;
+use crate::ton_wallet_high_load_v3::internal_transfer_hops;
 use async_trait::async_trait;
 
 #[derive(Clone)]
@@ -51,7 +52,9 @@ impl GasEstimator for TONGasEstimator {
     }
 
     async fn highload_wallet_send(&self, num_actions: usize) -> u64 {
-        self.config.highload_wallet_send * num_actions as u64
+        // Every internal transfer to self needed to pack the actions costs about as much as an action
+        let hops = internal_transfer_hops(num_actions);
+        self.config.highload_wallet_send * (num_actions + hops) as u64
     }
 
     async fn approve_send(&self, _num_messages: usize) -> u64 {
@@ -142,6 +145,9 @@ mod tests {
         let estimator = TONGasEstimator::new(config);
         let approve = estimator.highload_wallet_send(3usize).await;
         assert_eq!(approve, 126);
+
+        let packed = estimator.highload_wallet_send(300usize).await;
+        assert_eq!(packed, 42 * 301);
     }
 
     #[tokio::test]
@@ -0,0 +1,306 @@
+/*!
+
+Typed wrappers around the get-methods of the contracts the relayer talks to.
+
+[`ContractReader`] runs a get-method of one contract through [`RestClient::run_get_method`] and
+fails with [`GetMethodError::ExitCode`] unless the method exited successfully (exit code 0 or 1).
+The wrappers for the gateway, gas service, ITS, jettons and the highload wallet decode the
+returned stack into plain values.
+
+# Example Usage
+
+```rust,no_run
+use std::sync::Arc;
+use ton::client::TONRpcClient;
+use ton::get_methods::HighloadWalletGetMethods;
+use tonlib_core::TonAddress;
+
+#[tokio::main]
+async fn main() {
+    let client = TONRpcClient::new("https://testnet.toncenter.com".to_string(), "test".to_string(), 5, 5, 30).await.unwrap();
+    let wallet = TonAddress::from_base64_url("EQAFmAW7-1jWhcB5YeO5dE8U4ZxBe9iyt4JNnMtP-S8_kP6J").unwrap();
+    let wallet = HighloadWalletGetMethods::new(Arc::new(client), wallet);
+    let processed = wallet.is_processed(42, false).await.unwrap();
+}
+```
+
+*/
+
+use crate::client::RestClient;
+use crate::error::GetMethodError;
+use crate::ton_constants::{
+    GET_METHOD_GAS_SERVICE_GAS_COLLECTOR, GET_METHOD_GATEWAY_EPOCH, GET_METHOD_GATEWAY_OPERATOR,
+    GET_METHOD_HIGHLOAD_LAST_CLEAN_TIME, GET_METHOD_HIGHLOAD_PROCESSED,
+    GET_METHOD_HIGHLOAD_PUBLIC_KEY, GET_METHOD_HIGHLOAD_SUBWALLET_ID, GET_METHOD_HIGHLOAD_TIMEOUT,
+    GET_METHOD_ITS_OPERATOR, GET_METHOD_JETTON_DATA, GET_METHOD_JETTON_WALLET_ADDRESS,
+};
+use crate::tvm_stack::{StackEntry, StackReader};
+use num_bigint::BigUint;
+use std::sync::Arc;
+use tonlib_core::cell::Cell;
+use tonlib_core::TonAddress;
+
+#[derive(Clone)]
+pub struct ContractReader {
+    client: Arc<dyn RestClient>,
+    address: TonAddress,
+}
+
+impl ContractReader {
+    pub fn new(client: Arc<dyn RestClient>, address: TonAddress) -> Self {
+        Self { client, address }
+    }
+
+    pub fn address(&self) -> &TonAddress {
+        &self.address
+    }
+
+    pub async fn call(
+        &self,
+        method: &str,
+        stack: Vec<StackEntry>,
+    ) -> Result<StackReader, GetMethodError> {
+        let result = self
+            .client
+            .run_get_method(self.address.clone(), method.to_string(), stack)
+            .await?;
+
+        // TVM exits with 1 when the method ended with an implicit RET
+        if !matches!(result.exit_code, 0 | 1) {
+            return Err(GetMethodError::ExitCode {
+                method: method.to_string(),
+                exit_code: result.exit_code,
+            });
+        }
+
+        Ok(StackReader::new(result.stack))
+    }
+}
+
+#[derive(Clone)]
+pub struct GatewayGetMethods(ContractReader);
+
+impl GatewayGetMethods {
+    pub fn new(client: Arc<dyn RestClient>, address: TonAddress) -> Self {
+        Self(ContractReader::new(client, address))
+    }
+
+    pub async fn operator(&self) -> Result<TonAddress, GetMethodError> {
+        let mut stack = self.0.call(GET_METHOD_GATEWAY_OPERATOR, vec![]).await?;
+        Ok(stack.next_address()?)
+    }
+
+    /// Epoch of the current signer set.
+    pub async fn epoch(&self) -> Result<u64, GetMethodError> {
+        let mut stack = self.0.call(GET_METHOD_GATEWAY_EPOCH, vec![]).await?;
+        Ok(stack.next_u64()?)
+    }
+}
+
+#[derive(Clone)]
+pub struct GasServiceGetMethods(ContractReader);
+
+impl GasServiceGetMethods {
+    pub fn new(client: Arc<dyn RestClient>, address: TonAddress) -> Self {
+        Self(ContractReader::new(client, address))
+    }
+
+    pub async fn gas_collector(&self) -> Result<TonAddress, GetMethodError> {
+        let mut stack = self
+            .0
+            .call(GET_METHOD_GAS_SERVICE_GAS_COLLECTOR, vec![])
+            .await?;
+        Ok(stack.next_address()?)
+    }
+}
+
+#[derive(Clone)]
+pub struct ITSGetMethods(ContractReader);
+
+impl ITSGetMethods {
+    pub fn new(client: Arc<dyn RestClient>, address: TonAddress) -> Self {
+        Self(ContractReader::new(client, address))
+    }
+
+    pub async fn operator(&self) -> Result<TonAddress, GetMethodError> {
+        let mut stack = self.0.call(GET_METHOD_ITS_OPERATOR, vec![]).await?;
+        Ok(stack.next_address()?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JettonData {
+    pub total_supply: BigUint,
+    pub mintable: bool,
+    pub admin: TonAddress,
+    pub content: Cell,
+    pub wallet_code: Cell,
+}
+
+/// TEP-74 jetton minter, e.g. of a token registered with ITS.
+#[derive(Clone)]
+pub struct JettonMinterGetMethods(ContractReader);
+
+impl JettonMinterGetMethods {
+    pub fn new(client: Arc<dyn RestClient>, address: TonAddress) -> Self {
+        Self(ContractReader::new(client, address))
+    }
+
+    pub async fn jetton_data(&self) -> Result<JettonData, GetMethodError> {
+        let mut stack = self.0.call(GET_METHOD_JETTON_DATA, vec![]).await?;
+        Ok(JettonData {
+            total_supply: stack.next_biguint()?,
+            mintable: stack.next_bool()?,
+            admin: stack.next_address()?,
+            content: stack.next_cell()?,
+            wallet_code: stack.next_cell()?,
+        })
+    }
+
+    /// Jetton wallet of `owner`.
+    pub async fn wallet_address(&self, owner: &TonAddress) -> Result<TonAddress, GetMethodError> {
+        let mut stack = self
+            .0
+            .call(
+                GET_METHOD_JETTON_WALLET_ADDRESS,
+                vec![StackEntry::address(owner)?],
+            )
+            .await?;
+        Ok(stack.next_address()?)
+    }
+}
+
+#[derive(Clone)]
+pub struct HighloadWalletGetMethods(ContractReader);
+
+impl HighloadWalletGetMethods {
+    pub fn new(client: Arc<dyn RestClient>, address: TonAddress) -> Self {
+        Self(ContractReader::new(client, address))
+    }
+
+    pub async fn public_key(&self) -> Result<BigUint, GetMethodError> {
+        let mut stack = self.0.call(GET_METHOD_HIGHLOAD_PUBLIC_KEY, vec![]).await?;
+        Ok(stack.next_biguint()?)
+    }
+
+    pub async fn subwallet_id(&self) -> Result<u64, GetMethodError> {
+        let mut stack = self
+            .0
+            .call(GET_METHOD_HIGHLOAD_SUBWALLET_ID, vec![])
+            .await?;
+        Ok(stack.next_u64()?)
+    }
+
+    /// Seconds a message stays valid after its `created_at`.
+    pub async fn timeout(&self) -> Result<u64, GetMethodError> {
+        let mut stack = self.0.call(GET_METHOD_HIGHLOAD_TIMEOUT, vec![]).await?;
+        Ok(stack.next_u64()?)
+    }
+
+    pub async fn last_clean_time(&self) -> Result<u64, GetMethodError> {
+        let mut stack = self
+            .0
+            .call(GET_METHOD_HIGHLOAD_LAST_CLEAN_TIME, vec![])
+            .await?;
+        Ok(stack.next_u64()?)
+    }
+
+    /// Whether a message with `query_id` was already executed. With `need_clean`, query ids that
+    /// the next message would clean up count as not processed.
+    pub async fn is_processed(
+        &self,
+        query_id: u64,
+        need_clean: bool,
+    ) -> Result<bool, GetMethodError> {
+        let need_clean = if need_clean { -1 } else { 0 };
+        let mut stack = self
+            .0
+            .call(
+                GET_METHOD_HIGHLOAD_PROCESSED,
+                vec![StackEntry::int(query_id), StackEntry::int(need_clean)],
+            )
+            .await?;
+        Ok(stack.next_bool()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{MockRestClient, RunGetMethodResult};
+    use crate::error::TvmStackError;
+    use std::str::FromStr;
+
+    fn address() -> TonAddress {
+        TonAddress::from_str("0:294D72EC421B930C60854F413478C162FDCEEC65746084EBACE25227182979A2")
+            .unwrap()
+    }
+
+    fn returning(exit_code: i32, stack: Vec<StackEntry>) -> RunGetMethodResult {
+        RunGetMethodResult {
+            gas_used: 100,
+            exit_code,
+            stack,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_highload_is_processed() {
+        let mut client = MockRestClient::new();
+        client
+            .expect_run_get_method()
+            .withf(|contract, method, stack| {
+                *contract == address()
+                    && method == "processed?"
+                    && *stack == vec![StackEntry::int(42u64), StackEntry::int(-1)]
+            })
+            .times(1)
+            .returning(|_, _, _| Ok(returning(0, vec![StackEntry::int(-1)])));
+
+        let wallet = HighloadWalletGetMethods::new(Arc::new(client), address());
+        assert!(wallet.is_processed(42, true).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_address_result() {
+        let operator = address();
+        let slice = StackEntry::address(&operator).unwrap();
+
+        let mut client = MockRestClient::new();
+        client
+            .expect_run_get_method()
+            .withf(|_, method, stack| method == "get_gas_collector" && stack.is_empty())
+            .returning(move |_, _, _| Ok(returning(0, vec![slice.clone()])));
+
+        let gas_service = GasServiceGetMethods::new(Arc::new(client), address());
+        assert_eq!(gas_service.gas_collector().await.unwrap(), operator);
+    }
+
+    #[tokio::test]
+    async fn test_failed_get_method() {
+        let mut client = MockRestClient::new();
+        client
+            .expect_run_get_method()
+            .returning(|_, _, _| Ok(returning(11, vec![])));
+
+        let wallet = HighloadWalletGetMethods::new(Arc::new(client), address());
+        assert!(matches!(
+            wallet.timeout().await,
+            Err(GetMethodError::ExitCode { exit_code: 11, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unexpected_stack() {
+        let mut client = MockRestClient::new();
+        client
+            .expect_run_get_method()
+            .returning(|_, _, _| Ok(returning(0, vec![StackEntry::Null])));
+
+        let gateway = GatewayGetMethods::new(Arc::new(client), address());
+        assert!(matches!(
+            gateway.epoch().await,
+            Err(GetMethodError::Stack(TvmStackError::UnexpectedEntry { .. }))
+        ));
+    }
+}
@@ -1,12 +1,16 @@
 use num_bigint::BigUint;
 use tiny_keccak::{Hasher, Keccak};
 
-pub fn payload_hash(payload: &[u8]) -> BigUint {
+pub fn keccak256(payload: &[u8]) -> [u8; 32] {
     let mut output = [0u8; 32];
     let mut hasher = Keccak::v256();
     hasher.update(payload);
     hasher.finalize(&mut output);
-    BigUint::from_bytes_be(&output)
+    output
+}
+
+pub fn payload_hash(payload: &[u8]) -> BigUint {
+    BigUint::from_bytes_be(&keccak256(payload))
 }
 
 #[cfg(test)]
@@ -74,6 +74,12 @@ async fn main() {
 }
 ```
 
+# Concurrency
+
+Allocation happens in a single database transaction that holds a row lock on the wallet's entry
+in `ton_wallet_query_id` while the next query id is computed and stored. Concurrent callers, in
+the same process or in different ones, are serialized on that lock and always get distinct ids.
+
 # Force shift increase
 
 Sometimes, we can run into trying to reuse the same queryid through no logic fault, e.g. accidentally
@@ -128,9 +134,15 @@ impl HighLoadQueryIdWrapper for HighLoadQueryIdDbWrapper {
         timeout: u64,
         force_shift_increase: bool,
     ) -> Result<HighLoadQueryId, HighLoadQueryIdWrapperError> {
+        let mut db_tx = self
+            .model
+            .begin()
+            .await
+            .map_err(|_| HighLoadQueryIdWrapperError::DatabaseError)?;
+
         let (mut shift, bitnumber) = self
             .model
-            .get_query_id(address)
+            .lock_query_id(&mut db_tx, address)
             .await
             .map_err(|_| HighLoadQueryIdWrapperError::DatabaseError)?;
 
@@ -163,22 +175,28 @@ impl HighLoadQueryIdWrapper for HighLoadQueryIdDbWrapper {
             }
         };
 
-        if query_id.query_id().await == 0 {
-            self.model
-                .upsert_query_id(
-                    address,
-                    query_id.shift as i32,
-                    query_id.bitnumber as i32,
-                    timeout as i32 * TIMEOUT_BUFFER_MULTIPLIER,
-                )
-                .await
-                .map_err(|_e| HighLoadQueryIdWrapperError::DatabaseError)?;
+        // Starting over from 0 also starts a new expiry window
+        let timeout = if query_id.query_id().await == 0 {
+            Some(timeout as i32 * TIMEOUT_BUFFER_MULTIPLIER)
         } else {
-            self.model
-                .update_query_id(address, query_id.shift as i32, query_id.bitnumber as i32)
-                .await
-                .map_err(|_e| HighLoadQueryIdWrapperError::DatabaseError)?;
-        }
+            None
+        };
+
+        self.model
+            .store_query_id(
+                &mut db_tx,
+                address,
+                query_id.shift as i32,
+                query_id.bitnumber as i32,
+                timeout,
+            )
+            .await
+            .map_err(|_e| HighLoadQueryIdWrapperError::DatabaseError)?;
+
+        db_tx
+            .commit()
+            .await
+            .map_err(|_e| HighLoadQueryIdWrapperError::DatabaseError)?;
 
         Ok(query_id)
     }
@@ -189,6 +207,8 @@ mod tests {
     use crate::high_load_query_id_db_wrapper::{HighLoadQueryIdDbWrapper, HighLoadQueryIdWrapper};
     use crate::ton_wallet_query_id::PgTONWalletQueryIdModel;
     use sqlx::PgPool;
+    use std::collections::HashSet;
+    use std::sync::Arc;
     use testcontainers::runners::AsyncRunner;
     use testcontainers_modules::postgres;
 
@@ -231,4 +251,67 @@ mod tests {
         let query_id_e = wrapper.next("wallet3", 60, false).await.unwrap();
         assert_eq!(query_id_e.query_id().await, 1027);
     }
+
+    #[tokio::test]
+    async fn test_next_concurrent() {
+        let container = postgres::Postgres::default()
+            .with_init_sql(
+                include_str!("../migrations/0005_ton_wallet_query_id.sql")
+                    .to_string()
+                    .into_bytes(),
+            )
+            .start()
+            .await
+            .unwrap();
+        let connection_string = format!(
+            "postgres://postgres:postgres@{}:{}/postgres",
+            container.get_host().await.unwrap(),
+            container.get_host_port_ipv4(5432).await.unwrap()
+        );
+
+        // Two pools stand in for two includer processes sharing the same wallet
+        let wrappers = [
+            Arc::new(
+                HighLoadQueryIdDbWrapper::new(PgTONWalletQueryIdModel::new(
+                    PgPool::connect(&connection_string).await.unwrap(),
+                ))
+                .await,
+            ),
+            Arc::new(
+                HighLoadQueryIdDbWrapper::new(PgTONWalletQueryIdModel::new(
+                    PgPool::connect(&connection_string).await.unwrap(),
+                ))
+                .await,
+            ),
+        ];
+
+        let tasks = 32;
+        let per_task = 20;
+
+        let mut handles = vec![];
+        for i in 0..tasks {
+            let wrapper = Arc::clone(&wrappers[i % wrappers.len()]);
+            handles.push(tokio::spawn(async move {
+                let mut ids = vec![];
+                for _ in 0..per_task {
+                    let query_id = wrapper.next("wallet1", 60, false).await.unwrap();
+                    ids.push(query_id.query_id().await);
+                }
+                ids
+            }));
+        }
+
+        let mut all_ids = vec![];
+        for handle in handles {
+            all_ids.extend(handle.await.unwrap());
+        }
+
+        let unique: HashSet<u64> = all_ids.iter().copied().collect();
+        assert_eq!(all_ids.len(), tasks * per_task);
+        assert_eq!(unique.len(), all_ids.len());
+        assert_eq!(
+            unique.iter().copied().max(),
+            Some((tasks * per_task - 1) as u64)
+        );
+    }
 }
@@ -1,9 +1,13 @@
-use super::{broadcaster::TONBroadcaster, client::TONRpcClient, refund_manager::TONRefundManager};
+use super::{broadcaster::TONBroadcaster, refund_manager::TONRefundManager};
 use crate::client::RestClient;
 use crate::config::TONConfig;
+use crate::confirmation_tracker::{ConfirmationTracker, GmpApiOutcomeReporter};
+use crate::failover_client::FailoverRestClient;
 use crate::gas_estimator::TONGasEstimator;
 use crate::high_load_query_id_db_wrapper::HighLoadQueryIdDbWrapper;
 use crate::lock_manager::RedisLockManager;
+use crate::rate_limiter::RequestPriority;
+use crate::ton_broadcast::BroadcastLog;
 use crate::wallet_manager::WalletManager;
 use redis::aio::ConnectionManager;
 use relayer_core::utils::ThreadSafe;
@@ -12,6 +16,7 @@ use relayer_core::{
     includer_worker::IncluderWorker, payload_cache::PayloadCache, queue::Queue,
 };
 use std::sync::Arc;
+use std::time::Duration;
 use tonlib_core::TonAddress;
 
 pub struct TONIncluder {}
@@ -25,12 +30,20 @@ impl TONIncluder {
         payload_cache_for_includer: PayloadCache<DB>,
         construct_proof_queue: Arc<Queue>,
         high_load_query_id_db_wrapper: Arc<HighLoadQueryIdDbWrapper>,
+        broadcast_log: Arc<dyn BroadcastLog>,
     ) -> error_stack::Result<
-        Includer<TONBroadcaster<TONGasEstimator>, Arc<dyn RestClient>, TONRefundManager, DB, G>,
+        (
+            Includer<TONBroadcaster<TONGasEstimator>, Arc<dyn RestClient>, TONRefundManager, DB, G>,
+            ConfirmationTracker,
+        ),
         BroadcasterError,
     > {
-        let ton_rpc = config.ton_rpc;
-        let ton_api_key = config.ton_api_key;
+        let client: Arc<dyn RestClient> = Arc::new(
+            FailoverRestClient::from_config(&config, RequestPriority::Poll)
+                .await
+                .map_err(|e| error_stack::report!(BroadcasterError::GenericError(e.to_string())))?,
+        );
+
         let wallets = config.wallets;
         let ton_gateway = config.ton_gateway;
         let ton_gas_service = config.ton_gas_service;
@@ -38,18 +51,12 @@ impl TONIncluder {
         let lock_manager = Arc::new(RedisLockManager::new(redis_conn.clone()));
         let wallet_manager = Arc::new(WalletManager::new(wallets, lock_manager).await);
 
-        let client: Arc<dyn RestClient> = Arc::new(
-            TONRpcClient::new(ton_rpc, ton_api_key, 5, 5, 30)
-                .await
-                .map_err(|e| error_stack::report!(BroadcasterError::GenericError(e.to_string())))?,
-        );
-
         let gateway_address = TonAddress::from_base64_url(ton_gateway.as_str())
             .map_err(|e| BroadcasterError::GenericError(e.to_string()))?;
         let gas_service_address = TonAddress::from_base64_url(ton_gas_service.as_str())
             .map_err(|e| BroadcasterError::GenericError(e.to_string()))?;
 
-        let broadcaster = TONBroadcaster::new(
+        let mut broadcaster = TONBroadcaster::new(
             Arc::clone(&wallet_manager),
             Arc::clone(&client),
             high_load_query_id_db_wrapper,
@@ -58,7 +65,20 @@ impl TONIncluder {
             config.common_config.chain_name,
             TONGasEstimator::new(config.gas_estimates.clone()),
         )
-        .map_err(|e| e.attach_printable("Failed to create TONBroadcaster"))?;
+        .map_err(|e| e.attach_printable("Failed to create TONBroadcaster"))?
+        .with_broadcast_log(Arc::clone(&broadcast_log));
+
+        if let Some(batch) = &config.broadcast_batch {
+            broadcaster = broadcaster
+                .with_batching(Duration::from_millis(batch.window_ms), batch.max_actions);
+        }
+
+        let confirmation_tracker = ConfirmationTracker::new(
+            Arc::clone(&client),
+            broadcast_log,
+            Arc::new(broadcaster.clone()),
+            Arc::new(GmpApiOutcomeReporter::new(Arc::clone(&gmp_api))),
+        );
 
         let refund_manager = TONRefundManager::new()
             .map_err(|e| error_stack::report!(BroadcasterError::GenericError(e.to_string())))?;
@@ -75,6 +95,6 @@ impl TONIncluder {
 
         let includer = Includer::new(worker);
 
-        Ok(includer)
+        Ok((includer, confirmation_tracker))
     }
 }
@@ -1,5 +1,7 @@
+use crate::hashing::keccak256;
+use crate::models::ton_quarantined_trace::{QuarantineStage, TraceQuarantine};
 use crate::models::ton_trace::{EventSummary, UpdateEvents};
-use crate::parser::TraceParserTrait;
+use crate::parser::{ParsedTrace, TraceParserTrait};
 use crate::types::Trace;
 use async_trait::async_trait;
 use opentelemetry::global::ObjectSafeSpan;
@@ -13,12 +15,15 @@ use relayer_core::ingestor::IngestorTrait;
 use relayer_core::models::gmp_events::EventModel;
 use relayer_core::subscriber::ChainTransaction;
 use relayer_core::utils::ThreadSafe;
+use std::sync::Arc;
 use tracing::{info, warn};
 
 #[derive(Clone)]
 pub struct TONIngestor<TP: TraceParserTrait + Sync, TM: UpdateEvents + ThreadSafe> {
     trace_parser: TP,
     ton_trace_model: TM,
+    quarantine: Option<Arc<dyn TraceQuarantine>>,
+    partial_parsing: bool,
 }
 
 impl<TP: TraceParserTrait + Sync, TM: UpdateEvents + ThreadSafe> TONIngestor<TP, TM> {
@@ -26,8 +31,60 @@ impl<TP: TraceParserTrait + Sync, TM: UpdateEvents + ThreadSafe> TONIngestor<TP,
         Self {
             trace_parser,
             ton_trace_model,
+            quarantine: None,
+            partial_parsing: false,
         }
     }
+
+    /// A failing parser only loses its own event, the failure is stored with the trace's
+    /// events. Otherwise it fails the whole trace.
+    pub fn with_partial_parsing(mut self, partial_parsing: bool) -> Self {
+        self.partial_parsing = partial_parsing;
+        self
+    }
+
+    /// Traces that can't be deserialized or parsed are stored here instead of failing the task.
+    /// Transient parse errors, e.g. a missing price, still fail the task so that it is retried.
+    pub fn with_quarantine(mut self, quarantine: Arc<dyn TraceQuarantine>) -> Self {
+        self.quarantine = Some(quarantine);
+        self
+    }
+
+    async fn quarantine(
+        &self,
+        trace_id: &str,
+        payload: &str,
+        stage: QuarantineStage,
+        parser: Option<String>,
+        error: String,
+    ) -> Result<Vec<Event>, IngestorError> {
+        let quarantine = match &self.quarantine {
+            Some(quarantine) => quarantine,
+            None => return Err(IngestorError::GenericError(error)),
+        };
+
+        warn!(
+            "Quarantining trace: trace_id={} stage={} parser={:?} error={}",
+            trace_id,
+            stage.as_str(),
+            parser,
+            error
+        );
+        quarantine
+            .quarantine(trace_id, payload, stage, parser, &error)
+            .await
+            .map_err(|e| IngestorError::GenericError(e.to_string()))?;
+
+        Ok(vec![])
+    }
+}
+
+/// Trace id of a queue item that isn't a valid trace, falling back to a hash of the item.
+fn payload_trace_id(payload: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(payload)
+        .ok()
+        .and_then(|value| value.get("trace_id")?.as_str().map(str::to_owned))
+        .unwrap_or_else(|| format!("unparsable-{}", hex::encode(keccak256(payload.as_bytes()))))
 }
 
 #[async_trait]
@@ -48,22 +105,60 @@ where
     #[tracing::instrument(skip(self))]
     async fn handle_transaction(
         &self,
-        trace: ChainTransaction,
+        payload: ChainTransaction,
     ) -> Result<Vec<Event>, IngestorError> {
         let tracer = global::tracer("ton_ingestor");
         let mut span =
             tracer.start_with_context("ton_ingestor.consume_transaction", &Context::current());
 
-        let trace: Trace = serde_json::from_str(&trace).unwrap();
+        let trace: Trace = match serde_json::from_str(&payload) {
+            Ok(trace) => trace,
+            Err(e) => {
+                span.set_attribute(KeyValue::new("quarantined", true));
+                let trace_id = payload_trace_id(&payload);
+                return self
+                    .quarantine(
+                        &trace_id,
+                        &payload,
+                        QuarantineStage::Deserialize,
+                        None,
+                        e.to_string(),
+                    )
+                    .await;
+            }
+        };
 
         let trace_id = trace.trace_id.clone();
         span.set_attribute(KeyValue::new("chain_trace_id", trace_id.clone()));
 
-        let events = self
-            .trace_parser
-            .parse_trace(trace)
-            .await
-            .map_err(|e| IngestorError::GenericError(e.to_string()))?;
+        let parsed = if self.partial_parsing {
+            self.trace_parser.parse_trace_partial(trace).await
+        } else {
+            self.trace_parser.parse_trace(trace).await
+        };
+        let ParsedTrace { events, failures } = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) if e.is_transient() => {
+                // Fail the task so the trace is retried, it isn't broken
+                warn!(
+                    "Transient error parsing trace: trace_id={} error={}",
+                    trace_id, e
+                );
+                return Err(IngestorError::GenericError(e.to_string()));
+            }
+            Err(e) => {
+                span.set_attribute(KeyValue::new("quarantined", true));
+                return self
+                    .quarantine(
+                        &trace_id,
+                        &payload,
+                        QuarantineStage::Parse,
+                        e.parser().map(str::to_owned),
+                        e.to_string(),
+                    )
+                    .await;
+            }
+        };
 
         // Map events to EventModels
         let event_models: Vec<EventModel> = events
@@ -81,12 +176,17 @@ where
             })
             .collect();
 
-        info!("Created {} event summaries", event_summaries.len());
+        info!(
+            "Created {} event summaries, {} parser failures",
+            event_summaries.len(),
+            failures.len()
+        );
+        span.set_attribute(KeyValue::new("parser_failures", failures.len() as i64));
 
         // Update the trace with the event summaries
-        if !event_summaries.is_empty() {
+        if !event_summaries.is_empty() || !failures.is_empty() {
             self.ton_trace_model
-                .update_events(trace_id, event_summaries)
+                .update_events(trace_id, event_summaries, failures)
                 .await
                 .map_err(|e| IngestorError::GenericError(e.to_string()))?;
 
@@ -127,8 +227,11 @@ where
 #[cfg(test)]
 mod tests {
     use crate::ingestor::TONIngestor;
+    use crate::models::ton_quarantined_trace::{MockTraceQuarantine, QuarantineStage};
     use crate::models::ton_trace::MockUpdateEvents;
-    use crate::parser::MockTraceParserTrait;
+    use crate::parser::{
+        MockTraceParserTrait, ParsedTrace, ParserFailure, TransactionParsingError,
+    };
     use crate::types::Trace;
     use relayer_core::error::IngestorError;
     use relayer_core::gmp_api::gmp_types::{
@@ -139,6 +242,7 @@ mod tests {
         WasmEvent,
     };
     use relayer_core::ingestor::IngestorTrait;
+    use std::sync::Arc;
 
     #[tokio::test]
     async fn test_handle_retriable_task_unimplemented() {
@@ -310,8 +414,10 @@ mod tests {
 
         // Setup mock parser to return our test events
         mock_parser.expect_parse_trace().returning(move |_| {
-            let events = events.clone();
-            Ok(events)
+            Ok(ParsedTrace {
+                events: events.clone(),
+                failures: vec![],
+            })
         });
 
         let mut mock_ton_trace_model = MockUpdateEvents::new();
@@ -319,8 +425,9 @@ mod tests {
         // Expect update_events to be called with the correct parameters
         mock_ton_trace_model
             .expect_update_events()
-            .withf(|trace_id, event_summaries| {
+            .withf(|trace_id, event_summaries, failures| {
                 trace_id == "trace1"
+                    && failures.is_empty()
                     && event_summaries.len() == 2
                     && event_summaries[0].event_id == "event1"
                     && event_summaries[0].message_id == Some("message1".to_string())
@@ -329,7 +436,7 @@ mod tests {
                     && event_summaries[1].message_id == Some("message2".to_string())
                     && event_summaries[1].event_type == "CANNOT_EXECUTE_MESSAGE_V2"
             })
-            .returning(|_, _| Box::pin(async { Ok(()) }));
+            .returning(|_, _, _| Box::pin(async { Ok(()) }));
 
         let ingestor = TONIngestor::new(mock_parser, mock_ton_trace_model);
 
@@ -376,4 +483,148 @@ mod tests {
             _ => panic!("Expected CannotExecuteMessageV2 event"),
         }
     }
+
+    fn test_trace() -> Trace {
+        Trace {
+            trace_id: "trace1".to_string(),
+            is_incomplete: false,
+            start_lt: 100,
+            end_lt: 200,
+            transactions: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_transaction_parse_error_quarantined() {
+        let mut mock_parser = MockTraceParserTrait::new();
+        mock_parser.expect_parse_trace().returning(|_| {
+            Err(TransactionParsingError::BocParsing("bad boc".to_string())
+                .in_parser("ParserCallContract"))
+        });
+
+        let mut quarantine = MockTraceQuarantine::new();
+        quarantine
+            .expect_quarantine()
+            .withf(|trace_id, payload, stage, parser, error| {
+                trace_id == "trace1"
+                    && payload.contains("trace1")
+                    && *stage == QuarantineStage::Parse
+                    && parser.as_deref() == Some("ParserCallContract")
+                    && error.contains("bad boc")
+            })
+            .times(1)
+            .returning(|_, _, _, _, _| Ok(()));
+
+        // No events, nothing to update
+        let ingestor = TONIngestor::new(mock_parser, MockUpdateEvents::new())
+            .with_quarantine(Arc::new(quarantine));
+
+        let result = ingestor
+            .handle_transaction(serde_json::to_string(&test_trace()).unwrap())
+            .await;
+        assert!(result.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_handle_transaction_transient_parse_error_not_quarantined() {
+        let mut mock_parser = MockTraceParserTrait::new();
+        mock_parser.expect_parse_trace().returning(|_| {
+            Err(
+                TransactionParsingError::PriceUnavailable("No price".to_string())
+                    .in_parser("ParserJettonGasPaid"),
+            )
+        });
+
+        let mut quarantine = MockTraceQuarantine::new();
+        quarantine.expect_quarantine().never();
+
+        let ingestor = TONIngestor::new(mock_parser, MockUpdateEvents::new())
+            .with_quarantine(Arc::new(quarantine));
+
+        let result = ingestor
+            .handle_transaction(serde_json::to_string(&test_trace()).unwrap())
+            .await;
+        assert!(
+            matches!(result, Err(IngestorError::GenericError(msg)) if msg.contains("No price"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_transaction_invalid_trace_quarantined() {
+        let mut quarantine = MockTraceQuarantine::new();
+        quarantine
+            .expect_quarantine()
+            .withf(|trace_id, payload, stage, parser, _| {
+                trace_id.starts_with("unparsable-")
+                    && payload == "not a trace"
+                    && *stage == QuarantineStage::Deserialize
+                    && parser.is_none()
+            })
+            .times(1)
+            .returning(|_, _, _, _, _| Ok(()));
+
+        let ingestor = TONIngestor::new(MockTraceParserTrait::new(), MockUpdateEvents::new())
+            .with_quarantine(Arc::new(quarantine));
+
+        let result = ingestor.handle_transaction("not a trace".to_string()).await;
+        assert!(result.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_handle_transaction_invalid_trace_without_quarantine() {
+        let ingestor = TONIngestor::new(MockTraceParserTrait::new(), MockUpdateEvents::new());
+
+        let result = ingestor
+            .handle_transaction(r#"{"trace_id": "trace1"}"#.to_string())
+            .await;
+        assert!(matches!(result, Err(IngestorError::GenericError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_handle_transaction_partial_parsing() {
+        let mut mock_parser = MockTraceParserTrait::new();
+        mock_parser.expect_parse_trace_partial().returning(|_| {
+            Ok(ParsedTrace {
+                events: vec![Event::CannotExecuteMessageV2 {
+                    common: CommonEventFields {
+                        r#type: "CANNOT_EXECUTE_MESSAGE/V2".to_string(),
+                        event_id: "event1".to_string(),
+                        meta: None,
+                    },
+                    message_id: "message1".to_string(),
+                    source_chain: "source1".to_string(),
+                    reason: CannotExecuteMessageReason::InsufficientGas,
+                    details: "details1".to_string(),
+                }],
+                failures: vec![ParserFailure {
+                    parser: Some("ParserJettonGasPaid".to_string()),
+                    message_id: Some("message2".to_string()),
+                    message_key: None,
+                    error: "GasError: no price".to_string(),
+                }],
+            })
+        });
+
+        let mut mock_ton_trace_model = MockUpdateEvents::new();
+        mock_ton_trace_model
+            .expect_update_events()
+            .withf(|trace_id, event_summaries, failures| {
+                trace_id == "trace1"
+                    && event_summaries.len() == 1
+                    && event_summaries[0].event_id == "event1"
+                    && failures.len() == 1
+                    && failures[0].parser == Some("ParserJettonGasPaid".to_string())
+            })
+            .times(1)
+            .returning(|_, _, _| Box::pin(async { Ok(()) }));
+
+        let ingestor =
+            TONIngestor::new(mock_parser, mock_ton_trace_model).with_partial_parsing(true);
+
+        let events = ingestor
+            .handle_transaction(serde_json::to_string(&test_trace()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+    }
 }
@@ -1,7 +1,15 @@
 #![warn(clippy::unwrap_used)]
+pub mod block_subscriber;
+pub mod broadcast_batcher;
 pub mod broadcaster;
 pub mod client;
+pub mod completeness;
 pub mod config;
+pub mod confirmation_tracker;
+pub mod continuity;
+pub mod failover_client;
+pub mod finality;
+pub mod get_methods;
 mod error;
 pub mod high_load_query_id;
 pub mod high_load_query_id_db_wrapper;
@@ -10,11 +18,19 @@ pub mod ingestor;
 pub mod lock_manager;
 mod models;
 pub mod out_action;
+pub mod rate_limiter;
 pub mod refund_manager;
+pub mod reparse;
 pub mod subscriber;
+pub mod stream_client;
 pub mod ton_constants;
+pub mod toncenter_error;
 pub mod ton_wallet_high_load_v3;
+pub mod tvm_stack;
 pub mod wallet_manager;
+pub use models::ton_account_head;
+pub use models::ton_broadcast;
+pub use models::ton_quarantined_trace;
 pub use models::ton_trace;
 pub use models::ton_wallet_query_id;
 pub mod boc;
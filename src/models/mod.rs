@@ -1,2 +1,5 @@
+pub mod ton_account_head;
+pub mod ton_broadcast;
+pub mod ton_quarantined_trace;
 pub mod ton_trace;
 pub mod ton_wallet_query_id;
@@ -0,0 +1,111 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+/// Last transaction seen on a watched account, the next one should point back to it.
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct TONAccountHead {
+    pub account: String,
+    pub tx_hash: String,
+    pub tx_lt: i64,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+const PG_TABLE_NAME: &str = "ton_account_heads";
+
+#[derive(Debug, Clone)]
+pub struct PgTONAccountHeadModel {
+    pool: PgPool,
+}
+
+impl PgTONAccountHeadModel {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait AccountHeads: Send + Sync {
+    async fn get_head(&self, account: &str) -> anyhow::Result<Option<TONAccountHead>>;
+    /// Moves the head forward, a transaction older than the current head is ignored.
+    async fn set_head(&self, account: &str, tx_hash: &str, tx_lt: i64) -> anyhow::Result<()>;
+}
+
+#[async_trait]
+impl AccountHeads for PgTONAccountHeadModel {
+    async fn get_head(&self, account: &str) -> anyhow::Result<Option<TONAccountHead>> {
+        let query = format!("SELECT * FROM {PG_TABLE_NAME} WHERE account = $1");
+
+        let row = sqlx::query_as::<_, TONAccountHead>(&query)
+            .bind(account)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row)
+    }
+
+    async fn set_head(&self, account: &str, tx_hash: &str, tx_lt: i64) -> anyhow::Result<()> {
+        let query = format!(
+            "INSERT INTO {PG_TABLE_NAME} (account, tx_hash, tx_lt)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (account) DO UPDATE
+                SET
+                    tx_hash = EXCLUDED.tx_hash,
+                    tx_lt = EXCLUDED.tx_lt,
+                    updated_at = NOW()
+                WHERE {PG_TABLE_NAME}.tx_lt < EXCLUDED.tx_lt"
+        );
+
+        sqlx::query(&query)
+            .bind(account)
+            .bind(tx_hash)
+            .bind(tx_lt)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::ton_account_head::{AccountHeads, PgTONAccountHeadModel};
+    use testcontainers::runners::AsyncRunner;
+    use testcontainers_modules::postgres;
+
+    #[tokio::test]
+    async fn test_set_head_only_moves_forward() {
+        let container = postgres::Postgres::default()
+            .with_init_sql(
+                include_str!("../../migrations/0011_ton_account_heads.sql")
+                    .to_string()
+                    .into_bytes(),
+            )
+            .start()
+            .await
+            .unwrap();
+        let connection_string = format!(
+            "postgres://postgres:postgres@{}:{}/postgres",
+            container.get_host().await.unwrap(),
+            container.get_host_port_ipv4(5432).await.unwrap()
+        );
+        let pool = sqlx::PgPool::connect(&connection_string).await.unwrap();
+        let model = PgTONAccountHeadModel::new(pool);
+
+        assert!(model.get_head("0:aa").await.unwrap().is_none());
+
+        model.set_head("0:aa", "hash2", 20).await.unwrap();
+        model.set_head("0:aa", "hash1", 10).await.unwrap();
+        let head = model.get_head("0:aa").await.unwrap().unwrap();
+        assert_eq!(head.tx_hash, "hash2");
+        assert_eq!(head.tx_lt, 20);
+
+        model.set_head("0:aa", "hash3", 30).await.unwrap();
+        let head = model.get_head("0:aa").await.unwrap().unwrap();
+        assert_eq!(head.tx_hash, "hash3");
+        assert_eq!(head.tx_lt, 30);
+
+        assert!(model.get_head("0:bb").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,391 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::PgPool;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BroadcastStatus {
+    Pending,
+    // Expired and being signed again, never handed out by `claim_pending`
+    Resending,
+    Success,
+    Failed,
+    Expired,
+}
+
+impl BroadcastStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BroadcastStatus::Pending => "pending",
+            BroadcastStatus::Resending => "resending",
+            BroadcastStatus::Success => "success",
+            BroadcastStatus::Failed => "failed",
+            BroadcastStatus::Expired => "expired",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BroadcastKind {
+    Approve,
+    Execute,
+    Refund,
+}
+
+impl BroadcastKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BroadcastKind::Approve => "approve",
+            BroadcastKind::Execute => "execute",
+            BroadcastKind::Refund => "refund",
+        }
+    }
+}
+
+/// How the action carrying a task ended once its trace was complete.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum MessageOutcome {
+    Success,
+    Failed { exit_code: i32 },
+}
+
+impl MessageOutcome {
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            MessageOutcome::Success => None,
+            MessageOutcome::Failed { exit_code } => Some(*exit_code),
+        }
+    }
+}
+
+/// Task carried by one of the actions of a broadcast.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct BroadcastMessage {
+    pub kind: BroadcastKind,
+    pub message_id: String,
+    pub source_chain: String,
+    // Set once the broadcast landed, messages of the same broadcast may end differently
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<MessageOutcome>,
+}
+
+impl BroadcastMessage {
+    pub fn new(kind: BroadcastKind, message_id: String, source_chain: String) -> Self {
+        Self {
+            kind,
+            message_id,
+            source_chain,
+            outcome: None,
+        }
+    }
+
+    pub fn with_outcome(mut self, outcome: MessageOutcome) -> Self {
+        self.outcome = Some(outcome);
+        self
+    }
+}
+
+/// External message sent by one of our highload wallets, kept until we know how it ended.
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct TONBroadcast {
+    pub message_hash_norm: String,
+    pub wallet_address: String,
+    // Base64 BOCs of the out actions, so the message can be signed again
+    pub actions: Json<Vec<String>>,
+    // Tasks of the actions, in the same order
+    pub messages: Json<Vec<BroadcastMessage>>,
+    pub status: String,
+    pub exit_code: Option<i32>,
+    pub trace_id: Option<String>,
+    pub resent_as: Option<String>,
+    pub attempts: i32,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    // Until when a confirmation tracker is checking the broadcast
+    #[sqlx(default)]
+    pub claimed_until: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl TONBroadcast {
+    pub fn message_ids(&self) -> Vec<&str> {
+        self.messages
+            .iter()
+            .map(|message| message.message_id.as_str())
+            .collect()
+    }
+}
+
+const PG_TABLE_NAME: &str = "ton_broadcasts";
+
+#[derive(Debug, Clone)]
+pub struct PgTONBroadcastModel {
+    pool: PgPool,
+}
+
+impl PgTONBroadcastModel {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait BroadcastLog: Send + Sync {
+    async fn record(&self, broadcast: TONBroadcast) -> anyhow::Result<()>;
+    /// Pending broadcasts no one else has claimed, claimed for `lease`.
+    async fn claim_pending(&self, limit: i64, lease: Duration)
+        -> anyhow::Result<Vec<TONBroadcast>>;
+    /// Gives up the claim of a broadcast that is still pending.
+    async fn release(&self, message_hash_norm: &str) -> anyhow::Result<()>;
+    /// Marks a pending broadcast as being resent, before it is. Returns false if it wasn't
+    /// pending anymore, e.g. because another tracker got to it first.
+    async fn mark_resending(&self, message_hash_norm: &str) -> anyhow::Result<bool>;
+    /// Stores the final status of a broadcast. `messages`, if given, replaces the recorded
+    /// messages, e.g. with the outcome of each.
+    async fn resolve(
+        &self,
+        message_hash_norm: &str,
+        status: BroadcastStatus,
+        exit_code: Option<i32>,
+        trace_id: Option<String>,
+        resent_as: Option<String>,
+        messages: Option<Vec<BroadcastMessage>>,
+    ) -> anyhow::Result<()>;
+}
+
+#[async_trait]
+impl BroadcastLog for PgTONBroadcastModel {
+    async fn record(&self, broadcast: TONBroadcast) -> anyhow::Result<()> {
+        let query = format!(
+            "INSERT INTO {PG_TABLE_NAME} (message_hash_norm, wallet_address, actions, messages, attempts, expires_at)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (message_hash_norm) DO NOTHING"
+        );
+
+        sqlx::query(&query)
+            .bind(broadcast.message_hash_norm)
+            .bind(broadcast.wallet_address)
+            .bind(broadcast.actions)
+            .bind(broadcast.messages)
+            .bind(broadcast.attempts)
+            .bind(broadcast.expires_at)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn claim_pending(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> anyhow::Result<Vec<TONBroadcast>> {
+        let query = format!(
+            "UPDATE {PG_TABLE_NAME}
+                SET claimed_until = NOW() + make_interval(secs => $3)
+                WHERE message_hash_norm IN (
+                    SELECT message_hash_norm FROM {PG_TABLE_NAME}
+                    WHERE status = $1 AND (claimed_until IS NULL OR claimed_until < NOW())
+                    ORDER BY created_at
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING *"
+        );
+
+        let mut rows = sqlx::query_as::<_, TONBroadcast>(&query)
+            .bind(BroadcastStatus::Pending.as_str())
+            .bind(limit)
+            .bind(lease.as_secs_f64())
+            .fetch_all(&self.pool)
+            .await?;
+        // RETURNING doesn't keep the order of the subquery
+        rows.sort_by_key(|row| row.created_at);
+
+        Ok(rows)
+    }
+
+    async fn release(&self, message_hash_norm: &str) -> anyhow::Result<()> {
+        let query =
+            format!("UPDATE {PG_TABLE_NAME} SET claimed_until = NULL WHERE message_hash_norm = $1");
+
+        sqlx::query(&query)
+            .bind(message_hash_norm)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn mark_resending(&self, message_hash_norm: &str) -> anyhow::Result<bool> {
+        let query = format!(
+            "UPDATE {PG_TABLE_NAME} SET status = $1, updated_at = NOW()
+                WHERE message_hash_norm = $2 AND status = $3"
+        );
+
+        let result = sqlx::query(&query)
+            .bind(BroadcastStatus::Resending.as_str())
+            .bind(message_hash_norm)
+            .bind(BroadcastStatus::Pending.as_str())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn resolve(
+        &self,
+        message_hash_norm: &str,
+        status: BroadcastStatus,
+        exit_code: Option<i32>,
+        trace_id: Option<String>,
+        resent_as: Option<String>,
+        messages: Option<Vec<BroadcastMessage>>,
+    ) -> anyhow::Result<()> {
+        let query = format!(
+            "UPDATE {PG_TABLE_NAME}
+                SET status = $1, exit_code = $2, trace_id = $3, resent_as = $4,
+                    messages = COALESCE($5, messages), updated_at = NOW(), claimed_until = NULL
+                WHERE message_hash_norm = $6"
+        );
+
+        sqlx::query(&query)
+            .bind(status.as_str())
+            .bind(exit_code)
+            .bind(trace_id)
+            .bind(resent_as)
+            .bind(messages.map(Json))
+            .bind(message_hash_norm)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::ton_broadcast::{
+        BroadcastKind, BroadcastLog, BroadcastMessage, BroadcastStatus, MessageOutcome,
+        PgTONBroadcastModel, TONBroadcast,
+    };
+    use sqlx::types::Json;
+    use std::time::Duration;
+    use testcontainers::runners::AsyncRunner;
+    use testcontainers_modules::postgres;
+
+    fn broadcast(hash: &str) -> TONBroadcast {
+        TONBroadcast {
+            message_hash_norm: hash.to_string(),
+            wallet_address: "EQAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAM9c".to_string(),
+            actions: Json(vec!["te6cckEBAQEAAgAAAEysuc0=".to_string()]),
+            messages: Json(vec![BroadcastMessage::new(
+                BroadcastKind::Execute,
+                "0xabc-1".to_string(),
+                "avalanche-fuji".to_string(),
+            )]),
+            status: BroadcastStatus::Pending.as_str().to_string(),
+            exit_code: None,
+            trace_id: None,
+            resent_as: None,
+            attempts: 1,
+            expires_at: chrono::Utc::now(),
+            created_at: chrono::Utc::now(),
+            updated_at: None,
+            claimed_until: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_and_resolve() {
+        let container = postgres::Postgres::default()
+            .with_init_sql(
+                include_str!("../../migrations/0009_ton_broadcasts.sql")
+                    .to_string()
+                    .into_bytes(),
+            )
+            .start()
+            .await
+            .unwrap();
+        let connection_string = format!(
+            "postgres://postgres:postgres@{}:{}/postgres",
+            container.get_host().await.unwrap(),
+            container.get_host_port_ipv4(5432).await.unwrap()
+        );
+        let pool = sqlx::PgPool::connect(&connection_string).await.unwrap();
+        let model = PgTONBroadcastModel::new(pool);
+
+        model.record(broadcast("hash1")).await.unwrap();
+        model.record(broadcast("hash2")).await.unwrap();
+        // Recording the same message twice is a no-op
+        model.record(broadcast("hash1")).await.unwrap();
+
+        let lease = Duration::from_secs(60);
+        let pending = model.claim_pending(10, lease).await.unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].actions.len(), 1);
+        assert_eq!(pending[0].message_ids(), vec!["0xabc-1"]);
+        assert_eq!(pending[0].messages[0].kind, BroadcastKind::Execute);
+
+        // Claimed broadcasts aren't handed out again until released
+        assert!(model.claim_pending(10, lease).await.unwrap().is_empty());
+        model.release("hash1").await.unwrap();
+        let pending = model.claim_pending(10, lease).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].message_hash_norm, "hash1");
+
+        let failed = pending[0]
+            .messages
+            .iter()
+            .cloned()
+            .map(|message| message.with_outcome(MessageOutcome::Failed { exit_code: 106 }))
+            .collect::<Vec<_>>();
+        model
+            .resolve(
+                "hash1",
+                BroadcastStatus::Failed,
+                Some(106),
+                Some("trace1".to_string()),
+                None,
+                Some(failed.clone()),
+            )
+            .await
+            .unwrap();
+        model
+            .resolve(
+                "hash2",
+                BroadcastStatus::Expired,
+                None,
+                None,
+                Some("hash3".to_string()),
+                None,
+            )
+            .await
+            .unwrap();
+
+        model.release("hash1").await.unwrap();
+        model.release("hash2").await.unwrap();
+        let pending = model.claim_pending(10, lease).await.unwrap();
+        assert!(pending.is_empty());
+
+        // A broadcast being resent is marked once and never handed out again
+        model.record(broadcast("hash4")).await.unwrap();
+        assert!(model.mark_resending("hash4").await.unwrap());
+        assert!(!model.mark_resending("hash4").await.unwrap());
+        assert!(!model.mark_resending("hash1").await.unwrap());
+        assert!(model.claim_pending(10, lease).await.unwrap().is_empty());
+
+        let query = "SELECT * FROM ton_broadcasts ORDER BY message_hash_norm";
+        let rows = sqlx::query_as::<_, TONBroadcast>(query)
+            .fetch_all(&model.pool)
+            .await
+            .unwrap();
+        assert_eq!(rows[0].messages.0, failed);
+        // Messages are kept as recorded when none are given
+        assert_eq!(rows[1].messages[0].outcome, None);
+    }
+}
@@ -0,0 +1,239 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+/// Where handling the trace failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuarantineStage {
+    Deserialize,
+    Parse,
+}
+
+impl QuarantineStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuarantineStage::Deserialize => "deserialize",
+            QuarantineStage::Parse => "parse",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuarantineStatus {
+    Quarantined,
+    Requeued,
+}
+
+impl QuarantineStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuarantineStatus::Quarantined => "quarantined",
+            QuarantineStatus::Requeued => "requeued",
+        }
+    }
+}
+
+/// Trace the ingestor could not handle, kept with the queue item it came in so it can be
+/// requeued once fixed.
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct TONQuarantinedTrace {
+    pub trace_id: String,
+    pub payload: String,
+    pub stage: String,
+    pub parser: Option<String>,
+    pub error: String,
+    pub attempts: i32,
+    pub status: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+const PG_TABLE_NAME: &str = "ton_quarantined_traces";
+
+#[derive(Debug, Clone)]
+pub struct PgTONQuarantinedTraceModel {
+    pool: PgPool,
+}
+
+impl PgTONQuarantinedTraceModel {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait TraceQuarantine: Send + Sync {
+    /// Stores the failed trace. A trace quarantined before is quarantined again with the new
+    /// error and one more attempt.
+    async fn quarantine(
+        &self,
+        trace_id: &str,
+        payload: &str,
+        stage: QuarantineStage,
+        parser: Option<String>,
+        error: &str,
+    ) -> anyhow::Result<()>;
+    async fn fetch_quarantined(&self, limit: i64) -> anyhow::Result<Vec<TONQuarantinedTrace>>;
+    async fn find(&self, trace_id: &str) -> anyhow::Result<Option<TONQuarantinedTrace>>;
+    async fn mark_requeued(&self, trace_id: &str) -> anyhow::Result<()>;
+}
+
+#[async_trait]
+impl TraceQuarantine for PgTONQuarantinedTraceModel {
+    async fn quarantine(
+        &self,
+        trace_id: &str,
+        payload: &str,
+        stage: QuarantineStage,
+        parser: Option<String>,
+        error: &str,
+    ) -> anyhow::Result<()> {
+        let query = format!(
+            "INSERT INTO {PG_TABLE_NAME} (trace_id, payload, stage, parser, error)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (trace_id) DO UPDATE
+                SET
+                    payload = EXCLUDED.payload,
+                    stage = EXCLUDED.stage,
+                    parser = EXCLUDED.parser,
+                    error = EXCLUDED.error,
+                    attempts = {PG_TABLE_NAME}.attempts + 1,
+                    status = $6,
+                    updated_at = NOW()"
+        );
+
+        sqlx::query(&query)
+            .bind(trace_id)
+            .bind(payload)
+            .bind(stage.as_str())
+            .bind(parser)
+            .bind(error)
+            .bind(QuarantineStatus::Quarantined.as_str())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn fetch_quarantined(&self, limit: i64) -> anyhow::Result<Vec<TONQuarantinedTrace>> {
+        let query =
+            format!("SELECT * FROM {PG_TABLE_NAME} WHERE status = $1 ORDER BY created_at LIMIT $2");
+
+        let rows = sqlx::query_as::<_, TONQuarantinedTrace>(&query)
+            .bind(QuarantineStatus::Quarantined.as_str())
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows)
+    }
+
+    async fn find(&self, trace_id: &str) -> anyhow::Result<Option<TONQuarantinedTrace>> {
+        let query = format!("SELECT * FROM {PG_TABLE_NAME} WHERE trace_id = $1");
+
+        let row = sqlx::query_as::<_, TONQuarantinedTrace>(&query)
+            .bind(trace_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row)
+    }
+
+    async fn mark_requeued(&self, trace_id: &str) -> anyhow::Result<()> {
+        let query = format!(
+            "UPDATE {PG_TABLE_NAME} SET status = $1, updated_at = NOW() WHERE trace_id = $2"
+        );
+
+        sqlx::query(&query)
+            .bind(QuarantineStatus::Requeued.as_str())
+            .bind(trace_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::ton_quarantined_trace::{
+        PgTONQuarantinedTraceModel, QuarantineStage, QuarantineStatus, TraceQuarantine,
+    };
+    use testcontainers::runners::AsyncRunner;
+    use testcontainers_modules::postgres;
+
+    #[tokio::test]
+    async fn test_quarantine_and_requeue() {
+        let container = postgres::Postgres::default()
+            .with_init_sql(
+                include_str!("../../migrations/0010_ton_quarantined_traces.sql")
+                    .to_string()
+                    .into_bytes(),
+            )
+            .start()
+            .await
+            .unwrap();
+        let connection_string = format!(
+            "postgres://postgres:postgres@{}:{}/postgres",
+            container.get_host().await.unwrap(),
+            container.get_host_port_ipv4(5432).await.unwrap()
+        );
+        let pool = sqlx::PgPool::connect(&connection_string).await.unwrap();
+        let model = PgTONQuarantinedTraceModel::new(pool);
+
+        model
+            .quarantine(
+                "trace1",
+                "{}",
+                QuarantineStage::Parse,
+                Some("ParserCallContract".to_string()),
+                "BocParsingError: bad boc",
+            )
+            .await
+            .unwrap();
+        model
+            .quarantine(
+                "trace2",
+                "not json",
+                QuarantineStage::Deserialize,
+                None,
+                "EOF",
+            )
+            .await
+            .unwrap();
+
+        let quarantined = model.fetch_quarantined(10).await.unwrap();
+        assert_eq!(quarantined.len(), 2);
+        assert_eq!(quarantined[0].trace_id, "trace1");
+        assert_eq!(quarantined[0].stage, "parse");
+        assert_eq!(
+            quarantined[0].parser,
+            Some("ParserCallContract".to_string())
+        );
+        assert_eq!(quarantined[0].attempts, 1);
+
+        model.mark_requeued("trace1").await.unwrap();
+        let quarantined = model.fetch_quarantined(10).await.unwrap();
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].trace_id, "trace2");
+
+        // Failing again after the replay puts it back
+        model
+            .quarantine(
+                "trace1",
+                "{}",
+                QuarantineStage::Parse,
+                Some("ParserCallContract".to_string()),
+                "BocParsingError: still bad",
+            )
+            .await
+            .unwrap();
+        let trace = model.find("trace1").await.unwrap().unwrap();
+        assert_eq!(trace.attempts, 2);
+        assert_eq!(trace.error, "BocParsingError: still bad");
+        assert_eq!(trace.status, QuarantineStatus::Quarantined.as_str());
+
+        assert!(model.find("trace3").await.unwrap().is_none());
+    }
+}
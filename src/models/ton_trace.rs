@@ -1,17 +1,28 @@
+use crate::parser::ParserFailure;
 use crate::types::{Trace, Transaction};
 use relayer_core::models::Model;
 use serde::{Deserialize, Serialize};
 use sqlx::types::Json;
 use sqlx::PgPool;
 use std::future::Future;
+use std::time::Duration;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct EventSummary {
     pub event_id: String,
     pub message_id: Option<String>,
     pub event_type: String,
 }
 
+/// Entry of `ton_traces.events`: an event sent for the trace, or a parser that failed to build
+/// one.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum TraceEventRecord {
+    Event(EventSummary),
+    Failure(ParserFailure),
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
 pub struct TONTrace {
     pub trace_id: String,
@@ -20,11 +31,19 @@ pub struct TONTrace {
     pub end_lt: i64,
     pub retries: i32,
     pub transactions: Json<Vec<Transaction>>,
-    pub events: Option<Json<Vec<EventSummary>>>,
+    pub events: Option<Json<Vec<TraceEventRecord>>>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub next_retry_at: Option<chrono::DateTime<chrono::Utc>>,
+    // Set once an incomplete trace ran out of retries
+    pub abandoned_at: Option<chrono::DateTime<chrono::Utc>>,
+    // Set once a complete version of the trace was returned as changed
+    pub emitted_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
+/// Times an incomplete trace is fetched again before it is abandoned, unless configured.
+pub const DEFAULT_TRACE_RETRIES: i32 = 10;
+
 impl TONTrace {
     pub fn from(trace: &Trace) -> Self {
         Self {
@@ -36,9 +55,65 @@ impl TONTrace {
             events: None,
             created_at: chrono::Utc::now(),
             updated_at: None,
-            retries: 5,
+            retries: DEFAULT_TRACE_RETRIES,
+            next_retry_at: None,
+            abandoned_at: None,
+            emitted_at: None,
         }
     }
+
+    pub fn with_retries(mut self, retries: i32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn to_trace(&self) -> Trace {
+        Trace {
+            is_incomplete: self.is_incomplete,
+            start_lt: self.start_lt,
+            end_lt: self.end_lt,
+            trace_id: self.trace_id.clone(),
+            transactions: self.transactions.0.clone(),
+        }
+    }
+
+    pub fn event_summaries(&self) -> Vec<&EventSummary> {
+        self.records()
+            .filter_map(|record| match record {
+                TraceEventRecord::Event(summary) => Some(summary),
+                TraceEventRecord::Failure(_) => None,
+            })
+            .collect()
+    }
+
+    pub fn parser_failures(&self) -> Vec<&ParserFailure> {
+        self.records()
+            .filter_map(|record| match record {
+                TraceEventRecord::Failure(failure) => Some(failure),
+                TraceEventRecord::Event(_) => None,
+            })
+            .collect()
+    }
+
+    fn records(&self) -> impl Iterator<Item = &TraceEventRecord> {
+        self.events.iter().flat_map(|events| events.iter())
+    }
+}
+
+/// Stored traces to go through again, e.g. to reparse them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceSelection {
+    Ids(Vec<String>),
+    /// Traces starting within the logical time range, inclusive
+    LtRange {
+        from: i64,
+        to: i64,
+    },
+    /// Traces stored within the time range, inclusive
+    TimeRange {
+        from: chrono::DateTime<chrono::Utc>,
+        to: chrono::DateTime<chrono::Utc>,
+    },
 }
 
 const PG_TABLE_NAME: &str = "ton_traces";
@@ -55,6 +130,9 @@ impl PgTONTraceModel {
 
 #[cfg_attr(test, mockall::automock)]
 pub trait AtomicUpsert {
+    /// Stores the trace and returns it if it changed. A complete trace is only returned the first
+    /// time, e.g. a trace settled before Toncenter marked it complete isn't returned again once
+    /// its remaining transactions arrive.
     fn upsert_and_return_if_changed(
         &self,
         tx: TONTrace,
@@ -63,10 +141,25 @@ pub trait AtomicUpsert {
 
 #[cfg_attr(test, mockall::automock)]
 pub trait Retriable {
+    /// Incomplete traces whose next retry is due.
     fn fetch_retry(&self, limit: u32)
         -> impl Future<Output = anyhow::Result<Vec<TONTrace>>> + Send;
 
-    fn decrease_retry(&self, tx: TONTrace) -> impl Future<Output = anyhow::Result<()>> + Send;
+    /// Uses up a retry, the next one is due after `delay`.
+    fn schedule_retry(
+        &self,
+        tx: TONTrace,
+        delay: Duration,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send;
+
+    /// Stops retrying the trace.
+    fn abandon(&self, tx: TONTrace) -> impl Future<Output = anyhow::Result<()>> + Send;
+
+    /// Abandoned traces that are still incomplete, most recently abandoned first.
+    fn fetch_abandoned(
+        &self,
+        limit: u32,
+    ) -> impl Future<Output = anyhow::Result<Vec<TONTrace>>> + Send;
 }
 
 #[cfg_attr(test, mockall::automock)]
@@ -75,14 +168,26 @@ pub trait UpdateEvents {
         &self,
         trace_id: String,
         event: Vec<EventSummary>,
+        failures: Vec<ParserFailure>,
     ) -> impl Future<Output = anyhow::Result<()>> + Send;
 }
 
+#[cfg_attr(test, mockall::automock)]
+pub trait StoredTraces {
+    /// Selected traces, oldest first.
+    fn fetch_stored(
+        &self,
+        selection: TraceSelection,
+        offset: i64,
+        limit: i64,
+    ) -> impl Future<Output = anyhow::Result<Vec<TONTrace>>> + Send;
+}
+
 impl AtomicUpsert for PgTONTraceModel {
     async fn upsert_and_return_if_changed(&self, tx: TONTrace) -> anyhow::Result<Option<TONTrace>> {
         let query = format!(
-            "INSERT INTO {PG_TABLE_NAME} (trace_id, is_incomplete, start_lt, end_lt, transactions)
-                VALUES ($1, $2, $3, $4, $5)
+            "INSERT INTO {PG_TABLE_NAME} (trace_id, is_incomplete, start_lt, end_lt, transactions, retries)
+                VALUES ($1, $2, $3, $4, $5, $6)
                 ON CONFLICT (trace_id) DO UPDATE
                 SET
                     is_incomplete = EXCLUDED.is_incomplete,
@@ -104,6 +209,28 @@ impl AtomicUpsert for PgTONTraceModel {
             .bind(tx.start_lt)
             .bind(tx.end_lt)
             .bind(tx.transactions)
+            .bind(tx.retries)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        match result {
+            Some(trace) if !trace.is_incomplete => self.mark_emitted(&trace.trace_id).await,
+            result => Ok(result),
+        }
+    }
+}
+
+impl PgTONTraceModel {
+    /// The trace, unless it was emitted before.
+    async fn mark_emitted(&self, trace_id: &str) -> anyhow::Result<Option<TONTrace>> {
+        let query = format!(
+            "UPDATE {PG_TABLE_NAME} SET emitted_at = NOW()
+                WHERE trace_id = $1 AND emitted_at IS NULL
+                RETURNING *"
+        );
+
+        let result = sqlx::query_as::<_, TONTrace>(&query)
+            .bind(trace_id)
             .fetch_optional(&self.pool)
             .await?;
 
@@ -114,7 +241,10 @@ impl AtomicUpsert for PgTONTraceModel {
 impl Retriable for PgTONTraceModel {
     async fn fetch_retry(&self, limit: u32) -> anyhow::Result<Vec<TONTrace>> {
         let query = format!(
-            "SELECT * FROM {PG_TABLE_NAME} WHERE retries > 0 AND is_incomplete = true ORDER BY updated_at NULLS FIRST LIMIT $1"
+            "SELECT * FROM {PG_TABLE_NAME}
+                WHERE is_incomplete = true AND retries > 0 AND abandoned_at IS NULL
+                    AND (next_retry_at IS NULL OR next_retry_at <= NOW())
+                ORDER BY next_retry_at NULLS FIRST, updated_at NULLS FIRST LIMIT $1"
         );
 
         let rows = sqlx::query_as::<_, TONTrace>(&query)
@@ -125,14 +255,47 @@ impl Retriable for PgTONTraceModel {
         Ok(rows)
     }
 
-    async fn decrease_retry(&self, tx: TONTrace) -> anyhow::Result<()> {
-        let query = format!("UPDATE {PG_TABLE_NAME} SET retries = retries - 1 WHERE trace_id = $1");
+    async fn schedule_retry(&self, tx: TONTrace, delay: Duration) -> anyhow::Result<()> {
+        let query = format!(
+            "UPDATE {PG_TABLE_NAME}
+                SET retries = retries - 1, next_retry_at = NOW() + make_interval(secs => $1)
+                WHERE trace_id = $2"
+        );
         sqlx::query(&query)
+            .bind(delay.as_secs_f64())
             .bind(tx.trace_id)
             .execute(&self.pool)
             .await?;
         Ok(())
     }
+
+    async fn abandon(&self, tx: TONTrace) -> anyhow::Result<()> {
+        let query = format!(
+            "UPDATE {PG_TABLE_NAME}
+                SET retries = 0, next_retry_at = NULL, abandoned_at = NOW()
+                WHERE trace_id = $1"
+        );
+        sqlx::query(&query)
+            .bind(tx.trace_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn fetch_abandoned(&self, limit: u32) -> anyhow::Result<Vec<TONTrace>> {
+        let query = format!(
+            "SELECT * FROM {PG_TABLE_NAME}
+                WHERE is_incomplete = true AND abandoned_at IS NOT NULL
+                ORDER BY abandoned_at DESC LIMIT $1"
+        );
+
+        let rows = sqlx::query_as::<_, TONTrace>(&query)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows)
+    }
 }
 
 impl UpdateEvents for PgTONTraceModel {
@@ -140,14 +303,21 @@ impl UpdateEvents for PgTONTraceModel {
         &self,
         trace_id: String,
         events: Vec<EventSummary>,
+        failures: Vec<ParserFailure>,
     ) -> anyhow::Result<()> {
+        let records: Vec<TraceEventRecord> = events
+            .into_iter()
+            .map(TraceEventRecord::Event)
+            .chain(failures.into_iter().map(TraceEventRecord::Failure))
+            .collect();
+
         let query = format!(
             "UPDATE {} SET events = $1, updated_at = NOW() WHERE trace_id = $2",
             PG_TABLE_NAME
         );
 
         sqlx::query(&query)
-            .bind(Json(events))
+            .bind(Json(records))
             .bind(trace_id)
             .execute(&self.pool)
             .await?;
@@ -156,6 +326,36 @@ impl UpdateEvents for PgTONTraceModel {
     }
 }
 
+impl StoredTraces for PgTONTraceModel {
+    async fn fetch_stored(
+        &self,
+        selection: TraceSelection,
+        offset: i64,
+        limit: i64,
+    ) -> anyhow::Result<Vec<TONTrace>> {
+        let filter = match selection {
+            TraceSelection::Ids(_) => "trace_id = ANY($3)",
+            TraceSelection::LtRange { .. } => "start_lt BETWEEN $3 AND $4",
+            TraceSelection::TimeRange { .. } => "created_at BETWEEN $3 AND $4",
+        };
+        let query = format!(
+            "SELECT * FROM {PG_TABLE_NAME} WHERE {filter} ORDER BY start_lt, trace_id OFFSET $1 LIMIT $2"
+        );
+
+        let query = sqlx::query_as::<_, TONTrace>(&query)
+            .bind(offset)
+            .bind(limit);
+        let query = match selection {
+            TraceSelection::Ids(ids) => query.bind(ids),
+            TraceSelection::LtRange { from, to } => query.bind(from).bind(to),
+            TraceSelection::TimeRange { from, to } => query.bind(from).bind(to),
+        };
+        let rows = query.fetch_all(&self.pool).await?;
+
+        Ok(rows)
+    }
+}
+
 impl Model<TONTrace, String> for PgTONTraceModel {
     async fn upsert(&self, tx: TONTrace) -> anyhow::Result<()> {
         self.upsert_and_return_if_changed(tx).await?;
@@ -185,21 +385,28 @@ impl Model<TONTrace, String> for PgTONTraceModel {
 #[cfg(test)]
 mod tests {
     use crate::models::ton_trace::{
-        AtomicUpsert, EventSummary, PgTONTraceModel, TONTrace, UpdateEvents,
+        AtomicUpsert, EventSummary, PgTONTraceModel, Retriable, StoredTraces, TONTrace,
+        TraceSelection, UpdateEvents, DEFAULT_TRACE_RETRIES,
     };
+    use crate::parser::ParserFailure;
     use crate::test_utils::fixtures::fixture_traces;
     use relayer_core::models::Model;
     use sqlx::types::Json;
     use testcontainers::runners::AsyncRunner;
     use testcontainers_modules::postgres;
 
+    fn ton_traces_init_sql() -> String {
+        format!(
+            "{}\n{}\n{}",
+            include_str!("../../migrations/0006_ton_traces.sql"),
+            include_str!("../../migrations/0008_ton_traces_events.sql"),
+            include_str!("../../migrations/0012_ton_traces_retry_schedule.sql")
+        )
+    }
+
     #[tokio::test]
     async fn test_crud() {
-        let init_sql = format!(
-            "{}\n{}",
-            include_str!("../../migrations/0006_ton_traces.sql"),
-            include_str!("../../migrations/0008_ton_traces_events.sql")
-        );
+        let init_sql = ton_traces_init_sql();
         let container = postgres::Postgres::default()
             .with_init_sql(init_sql.into_bytes())
             .start()
@@ -224,6 +431,9 @@ mod tests {
             created_at: chrono::Utc::now(),
             updated_at: Some(chrono::Utc::now()),
             retries: 5,
+            next_retry_at: None,
+            abandoned_at: None,
+            emitted_at: None,
         };
 
         let ret = model
@@ -262,6 +472,9 @@ mod tests {
             created_at: chrono::Utc::now(),
             updated_at: Some(chrono::Utc::now()),
             retries: 5,
+            next_retry_at: None,
+            abandoned_at: None,
+            emitted_at: None,
         };
 
         let ret = model
@@ -271,6 +484,21 @@ mod tests {
         assert!(ret.is_some());
         assert!(ret.unwrap().is_incomplete);
 
+        // Stored, but not returned again once it was complete before
+        let trace = TONTrace {
+            is_incomplete: false,
+            end_lt: 400,
+            ..trace
+        };
+        let ret = model
+            .upsert_and_return_if_changed(trace.clone())
+            .await
+            .unwrap();
+        assert!(ret.is_none());
+        let saved = model.find("123".to_string()).await.unwrap().unwrap();
+        assert_eq!(saved.end_lt, 400);
+        assert!(saved.emitted_at.is_some());
+
         model.delete(trace).await.unwrap();
         let saved = model.find("123".to_string()).await.unwrap();
         assert!(saved.is_none());
@@ -278,11 +506,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_update_events() {
-        let init_sql = format!(
-            "{}\n{}",
-            include_str!("../../migrations/0006_ton_traces.sql"),
-            include_str!("../../migrations/0008_ton_traces_events.sql")
-        );
+        let init_sql = ton_traces_init_sql();
         let container = postgres::Postgres::default()
             .with_init_sql(init_sql.into_bytes())
             .start()
@@ -307,6 +531,9 @@ mod tests {
             created_at: chrono::Utc::now(),
             updated_at: None,
             retries: 5,
+            next_retry_at: None,
+            abandoned_at: None,
+            emitted_at: None,
         };
 
         model.upsert(trace).await.unwrap();
@@ -324,15 +551,22 @@ mod tests {
             },
         ];
 
+        let failures = vec![ParserFailure {
+            parser: Some("ParserJettonGasPaid".to_string()),
+            message_id: Some("message3".to_string()),
+            message_key: None,
+            error: "GasError: no price".to_string(),
+        }];
+
         model
-            .update_events("123".to_string(), events.clone())
+            .update_events("123".to_string(), events.clone(), failures)
             .await
             .unwrap();
 
         let updated_trace = model.find("123".to_string()).await.unwrap().unwrap();
         assert!(updated_trace.events.is_some());
 
-        let updated_events = updated_trace.events.unwrap();
+        let updated_events = updated_trace.event_summaries();
         assert_eq!(updated_events.len(), 2);
         assert_eq!(updated_events[0].event_id, "event1");
         assert_eq!(updated_events[0].message_id, Some("message1".to_string()));
@@ -341,6 +575,136 @@ mod tests {
         assert_eq!(updated_events[1].message_id, Some("message2".to_string()));
         assert_eq!(updated_events[1].event_type, "CANNOT_EXECUTE_MESSAGE_V2");
 
+        let failures = updated_trace.parser_failures();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].parser, Some("ParserJettonGasPaid".to_string()));
+        assert_eq!(failures[0].message_id, Some("message3".to_string()));
+
         assert!(updated_trace.updated_at.is_some());
     }
+
+    #[tokio::test]
+    async fn test_fetch_stored() {
+        let container = postgres::Postgres::default()
+            .with_init_sql(ton_traces_init_sql().into_bytes())
+            .start()
+            .await
+            .unwrap();
+        let connection_string = format!(
+            "postgres://postgres:postgres@{}:{}/postgres",
+            container.get_host().await.unwrap(),
+            container.get_host_port_ipv4(5432).await.unwrap()
+        );
+        let pool = sqlx::PgPool::connect(&connection_string).await.unwrap();
+        let model = PgTONTraceModel::new(pool);
+
+        for trace in fixture_traces().iter().take(3) {
+            model.upsert(TONTrace::from(trace)).await.unwrap();
+        }
+        let traces = fixture_traces();
+
+        let selected = model
+            .fetch_stored(
+                TraceSelection::Ids(vec![
+                    traces[2].trace_id.clone(),
+                    traces[0].trace_id.clone(),
+                    "unknown".to_string(),
+                ]),
+                0,
+                10,
+            )
+            .await
+            .unwrap();
+        assert_eq!(selected.len(), 2);
+        assert!(selected[0].start_lt <= selected[1].start_lt);
+
+        let selected = model
+            .fetch_stored(
+                TraceSelection::LtRange {
+                    from: traces[1].start_lt,
+                    to: traces[1].start_lt,
+                },
+                0,
+                10,
+            )
+            .await
+            .unwrap();
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].trace_id, traces[1].trace_id);
+        assert_eq!(
+            selected[0].to_trace().transactions.len(),
+            traces[1].transactions.len()
+        );
+
+        let now = chrono::Utc::now();
+        let selection = TraceSelection::TimeRange {
+            from: now - chrono::Duration::hours(1),
+            to: now + chrono::Duration::hours(1),
+        };
+        let first_page = model.fetch_stored(selection.clone(), 0, 2).await.unwrap();
+        let second_page = model.fetch_stored(selection, 2, 2).await.unwrap();
+        assert_eq!(first_page.len(), 2);
+        assert_eq!(second_page.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry_schedule_and_abandon() {
+        let container = postgres::Postgres::default()
+            .with_init_sql(ton_traces_init_sql().into_bytes())
+            .start()
+            .await
+            .unwrap();
+        let connection_string = format!(
+            "postgres://postgres:postgres@{}:{}/postgres",
+            container.get_host().await.unwrap(),
+            container.get_host_port_ipv4(5432).await.unwrap()
+        );
+        let pool = sqlx::PgPool::connect(&connection_string).await.unwrap();
+        let model = PgTONTraceModel::new(pool);
+
+        let mut traces = fixture_traces();
+        traces[0].is_incomplete = true;
+        traces[1].is_incomplete = true;
+        model.upsert(TONTrace::from(&traces[0])).await.unwrap();
+        model
+            .upsert(TONTrace::from(&traces[1]).with_retries(3))
+            .await
+            .unwrap();
+        model.upsert(TONTrace::from(&traces[2])).await.unwrap();
+
+        let due = model.fetch_retry(10).await.unwrap();
+        assert_eq!(due.len(), 2);
+        let first = due
+            .iter()
+            .find(|trace| trace.trace_id == traces[0].trace_id)
+            .unwrap()
+            .clone();
+        assert_eq!(first.retries, DEFAULT_TRACE_RETRIES);
+
+        model
+            .schedule_retry(first, std::time::Duration::from_secs(3600))
+            .await
+            .unwrap();
+        let due = model.fetch_retry(10).await.unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].trace_id, traces[1].trace_id);
+        assert_eq!(due[0].retries, 3);
+
+        let scheduled = model
+            .find(traces[0].trace_id.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(scheduled.retries, DEFAULT_TRACE_RETRIES - 1);
+        assert!(scheduled.next_retry_at.unwrap() > chrono::Utc::now());
+
+        model.abandon(due[0].clone()).await.unwrap();
+        assert!(model.fetch_retry(10).await.unwrap().is_empty());
+
+        let abandoned = model.fetch_abandoned(10).await.unwrap();
+        assert_eq!(abandoned.len(), 1);
+        assert_eq!(abandoned[0].trace_id, traces[1].trace_id);
+        assert_eq!(abandoned[0].retries, 0);
+        assert!(abandoned[0].abandoned_at.is_some());
+    }
 }
@@ -1,5 +1,5 @@
 use async_trait::async_trait;
-use sqlx::{PgPool, Row};
+use sqlx::{PgConnection, PgPool, Postgres, Row, Transaction};
 
 const PG_TABLE_NAME: &str = "ton_wallet_query_id";
 
@@ -12,6 +12,10 @@ impl PgTONWalletQueryIdModel {
     pub fn new(pool: PgPool) -> Self {
         Self { pool }
     }
+
+    pub async fn begin(&self) -> anyhow::Result<Transaction<'static, Postgres>> {
+        Ok(self.pool.begin().await?)
+    }
 }
 
 #[async_trait]
@@ -30,6 +34,23 @@ pub trait TONWalletQueryId {
         bitnumber: i32,
         timeout: i32,
     ) -> anyhow::Result<()>;
+    /// Locks the row for `address` until the surrounding transaction ends, creating it if
+    /// needed. Returns (-1, -1) when there is no live query id.
+    async fn lock_query_id(
+        &self,
+        conn: &mut PgConnection,
+        address: &str,
+    ) -> anyhow::Result<(i32, i32)>;
+    /// Stores the query id on a row locked by `lock_query_id`. Expiry is only moved when
+    /// `timeout` is set.
+    async fn store_query_id(
+        &self,
+        conn: &mut PgConnection,
+        address: &str,
+        shift: i32,
+        bitnumber: i32,
+        timeout: Option<i32>,
+    ) -> anyhow::Result<()>;
 }
 
 #[async_trait]
@@ -94,4 +115,65 @@ impl TONWalletQueryId for PgTONWalletQueryIdModel {
 
         Ok(())
     }
+
+    async fn lock_query_id(
+        &self,
+        conn: &mut PgConnection,
+        address: &str,
+    ) -> anyhow::Result<(i32, i32)> {
+        // Make sure there is a row to lock. It is created already expired, so the caller
+        // starts from 0, 0 and sets the real expiry.
+        let insert = format!(
+            "INSERT INTO {PG_TABLE_NAME} (address, shift, bitnumber, expires_at)
+            VALUES ($1, 0, 0, to_timestamp(0))
+            ON CONFLICT (address) DO NOTHING"
+        );
+        sqlx::query(&insert)
+            .bind(address)
+            .execute(&mut *conn)
+            .await?;
+
+        let query = format!(
+            "SELECT shift, bitnumber, expires_at >= CURRENT_TIMESTAMP AS live
+            FROM {PG_TABLE_NAME} WHERE address = $1 FOR UPDATE"
+        );
+        let row = sqlx::query(&query)
+            .bind(address)
+            .fetch_one(&mut *conn)
+            .await?;
+
+        let live: bool = row.get("live");
+        if live {
+            Ok((row.get("shift"), row.get("bitnumber")))
+        } else {
+            Ok((-1, -1))
+        }
+    }
+
+    async fn store_query_id(
+        &self,
+        conn: &mut PgConnection,
+        address: &str,
+        shift: i32,
+        bitnumber: i32,
+        timeout: Option<i32>,
+    ) -> anyhow::Result<()> {
+        let query = format!(
+            "UPDATE {PG_TABLE_NAME}
+            SET shift = $1,
+                bitnumber = $2,
+                expires_at = COALESCE(CURRENT_TIMESTAMP + ($3 * INTERVAL '1 second'), expires_at),
+                updated_at = CURRENT_TIMESTAMP
+            WHERE address = $4"
+        );
+        sqlx::query(&query)
+            .bind(shift)
+            .bind(bitnumber)
+            .bind(timeout)
+            .bind(address)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,627 @@
+/*!
+
+Token bucket shared by every client using the same Toncenter API key.
+
+Toncenter enforces a requests-per-second quota per API key, no matter how many subscribers,
+includer workers and account checkers use it. Every request takes a token from the bucket of its
+key, which refills at `rps` tokens per second and holds at most one second worth of them.
+
+Requests wait in priority classes: while a broadcast is waiting, polls don't take tokens, and while
+either is waiting, account checks don't. When Toncenter answers `429 Too Many Requests`, the
+bucket is emptied and no tokens are handed out until its `Retry-After` has passed.
+
+The subscriber, includer and account checker run as separate processes. With
+[`RateLimiter::with_redis`] the bucket and the waiting requests are kept in Redis, so the processes
+share the quota and wait behind each other's higher priorities. If Redis can't be reached, the
+limiter falls back to the bucket of the process.
+
+The time spent waiting for a token is recorded per priority in the `toncenter.rate_limit.wait`
+histogram and 429s in the `toncenter.rate_limit.throttled` counter. [`spawn_stats_reporter`] also
+logs them.
+
+*/
+
+use crate::hashing::keccak256;
+use opentelemetry::metrics::{Counter, Histogram};
+use opentelemetry::{global, KeyValue};
+use redis::aio::ConnectionManager;
+use redis::Script;
+use std::collections::HashMap;
+use std::ops::{Index, IndexMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+// Waits longer than this are logged as they happen
+const SLOW_ACQUIRE: Duration = Duration::from_secs(5);
+// A waiting request is forgotten by the other processes unless it checks the bucket again within
+// this time, e.g. because its process died
+const WAITER_TTL: Duration = Duration::from_secs(5);
+const BUCKET_TTL: Duration = Duration::from_secs(60);
+
+static SHARED: LazyLock<Mutex<HashMap<String, Arc<RateLimiter>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(|| {
+    let meter = global::meter("ton_rate_limiter");
+    Metrics {
+        wait: meter
+            .f64_histogram("toncenter.rate_limit.wait")
+            .with_unit("s")
+            .with_description("Time a Toncenter request waited for a rate limit token")
+            .build(),
+        throttled: meter
+            .u64_counter("toncenter.rate_limit.throttled")
+            .with_description("Toncenter requests rejected with 429 Too Many Requests")
+            .build(),
+    }
+});
+
+// KEYS: the bucket, then the waiting requests of every priority from the highest one.
+// ARGV: rps, capacity, priority (1 is the highest), waiter, waiter ttl and bucket ttl in ms.
+// Returns 0 if a token was taken, otherwise the ms to wait before trying again.
+const ACQUIRE_SCRIPT: &str = r#"
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local rps = tonumber(ARGV[1])
+local capacity = tonumber(ARGV[2])
+local priority = tonumber(ARGV[3])
+
+for i = 2, #KEYS do
+  redis.call('ZREMRANGEBYSCORE', KEYS[i], '-inf', now)
+end
+local waiting = KEYS[priority + 1]
+redis.call('ZADD', waiting, now + tonumber(ARGV[5]), ARGV[4])
+redis.call('PEXPIRE', waiting, tonumber(ARGV[5]))
+
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'refilled_at', 'blocked_until')
+local tokens = tonumber(bucket[1]) or capacity
+local refilled_at = tonumber(bucket[2]) or now
+local blocked_until = tonumber(bucket[3]) or 0
+
+if blocked_until > now then
+  return blocked_until - now
+end
+for i = 2, priority do
+  if redis.call('ZCARD', KEYS[i]) > 0 then
+    return math.ceil(1000 / rps)
+  end
+end
+
+if now > refilled_at then
+  tokens = math.min(tokens + (now - refilled_at) * rps / 1000, capacity)
+  refilled_at = now
+end
+local wait = 0
+if tokens >= 1 then
+  tokens = tokens - 1
+  redis.call('ZREM', waiting, ARGV[4])
+else
+  wait = math.max(math.ceil((1 - tokens) * 1000 / rps), 1)
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'refilled_at', refilled_at)
+redis.call('PEXPIRE', KEYS[1], tonumber(ARGV[6]))
+return wait
+"#;
+
+// KEYS: the bucket. ARGV: retry after in ms, bucket ttl in ms.
+const THROTTLE_SCRIPT: &str = r#"
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local blocked_until = now + tonumber(ARGV[1])
+blocked_until = math.max(blocked_until, tonumber(redis.call('HGET', KEYS[1], 'blocked_until')) or 0)
+redis.call('HSET', KEYS[1], 'tokens', '0', 'refilled_at', blocked_until, 'blocked_until', blocked_until)
+redis.call('PEXPIRE', KEYS[1], blocked_until - now + tonumber(ARGV[2]))
+return 0
+"#;
+
+static ACQUIRE: LazyLock<Script> = LazyLock::new(|| Script::new(ACQUIRE_SCRIPT));
+static THROTTLE: LazyLock<Script> = LazyLock::new(|| Script::new(THROTTLE_SCRIPT));
+static NEXT_WAITER: AtomicU64 = AtomicU64::new(0);
+
+struct Metrics {
+    wait: Histogram<f64>,
+    throttled: Counter<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RequestPriority {
+    Broadcast,
+    Poll,
+    AccountCheck,
+}
+
+impl RequestPriority {
+    const ALL: [RequestPriority; 3] = [
+        RequestPriority::Broadcast,
+        RequestPriority::Poll,
+        RequestPriority::AccountCheck,
+    ];
+
+    // 1 for the highest priority
+    fn rank(&self) -> u8 {
+        match self {
+            RequestPriority::Broadcast => 1,
+            RequestPriority::Poll => 2,
+            RequestPriority::AccountCheck => 3,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ByPriority<T>([T; 3]);
+
+impl<T> Index<RequestPriority> for ByPriority<T> {
+    type Output = T;
+
+    fn index(&self, priority: RequestPriority) -> &T {
+        match priority {
+            RequestPriority::Broadcast => &self.0[0],
+            RequestPriority::Poll => &self.0[1],
+            RequestPriority::AccountCheck => &self.0[2],
+        }
+    }
+}
+
+impl<T> IndexMut<RequestPriority> for ByPriority<T> {
+    fn index_mut(&mut self, priority: RequestPriority) -> &mut T {
+        match priority {
+            RequestPriority::Broadcast => &mut self.0[0],
+            RequestPriority::Poll => &mut self.0[1],
+            RequestPriority::AccountCheck => &mut self.0[2],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct QueueStats {
+    pub requests: u64,
+    pub total_wait: Duration,
+    pub max_wait: Duration,
+}
+
+impl QueueStats {
+    pub fn avg_wait(&self) -> Duration {
+        if self.requests == 0 {
+            return Duration::ZERO;
+        }
+        self.total_wait / self.requests as u32
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+    blocked_until: Option<Instant>,
+    waiting: ByPriority<usize>,
+    stats: ByPriority<QueueStats>,
+    throttled: u64,
+}
+
+struct RedisBucket {
+    conn: ConnectionManager,
+    key: String,
+    // Tells the waiting requests of this process apart from the ones of others
+    process: String,
+}
+
+impl std::fmt::Debug for RedisBucket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisBucket")
+            .field("key", &self.key)
+            .field("process", &self.process)
+            .finish()
+    }
+}
+
+impl RedisBucket {
+    /// Takes a token, returning zero, or the time to wait before trying again.
+    async fn take(
+        &self,
+        priority: RequestPriority,
+        rps: f64,
+        waiter: &str,
+    ) -> Result<Duration, redis::RedisError> {
+        let mut invocation = ACQUIRE.key(&self.key);
+        for other in RequestPriority::ALL {
+            invocation.key(format!("{}_waiting_{:?}", self.key, other));
+        }
+        let wait: u64 = invocation
+            .arg(rps)
+            .arg(RateLimiter::capacity(rps))
+            .arg(priority.rank())
+            .arg(waiter)
+            .arg(WAITER_TTL.as_millis() as u64)
+            .arg(BUCKET_TTL.as_millis() as u64)
+            .invoke_async(&mut self.conn.clone())
+            .await?;
+        Ok(Duration::from_millis(wait))
+    }
+
+    async fn throttle(&self, retry_after: Duration) -> Result<(), redis::RedisError> {
+        THROTTLE
+            .key(&self.key)
+            .arg(retry_after.as_millis() as u64)
+            .arg(BUCKET_TTL.as_millis() as u64)
+            .invoke_async(&mut self.conn.clone())
+            .await
+    }
+}
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    rps: f64,
+    // Enough of the API key to tell the limiters apart without logging it
+    label: String,
+    bucket: Mutex<Bucket>,
+    redis: Option<RedisBucket>,
+}
+
+impl RateLimiter {
+    pub fn new(rps: f64) -> Self {
+        let rps = rps.max(0.1);
+        Self {
+            rps,
+            label: String::new(),
+            bucket: Mutex::new(Bucket {
+                tokens: Self::capacity(rps),
+                refilled_at: Instant::now(),
+                blocked_until: None,
+                waiting: ByPriority::default(),
+                stats: ByPriority::default(),
+                throttled: 0,
+            }),
+            redis: None,
+        }
+    }
+
+    /// Keeps the bucket of `api_key` in Redis, shared with every process using the key.
+    pub fn with_redis(mut self, conn: ConnectionManager, api_key: &str) -> Self {
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        self.redis = Some(RedisBucket {
+            conn,
+            key: format!(
+                "toncenter_rate_limit_{}",
+                hex::encode(keccak256(api_key.as_bytes()).get(..8).unwrap_or_default())
+            ),
+            process: format!("{}_{}", std::process::id(), started_at),
+        });
+        self
+    }
+
+    /// Limiter of the API key, created with `rps` by its first user.
+    pub fn shared(api_key: &str, rps: f64) -> Arc<Self> {
+        Self::shared_with(api_key, || Self::new(rps))
+    }
+
+    /// Limiter of the API key, created with `rps` by its first user, with the bucket in Redis.
+    pub fn shared_in_redis(api_key: &str, rps: f64, conn: ConnectionManager) -> Arc<Self> {
+        Self::shared_with(api_key, || Self::new(rps).with_redis(conn, api_key))
+    }
+
+    fn shared_with(api_key: &str, create: impl FnOnce() -> Self) -> Arc<Self> {
+        let mut shared = SHARED.lock().unwrap_or_else(|e| e.into_inner());
+        Arc::clone(shared.entry(api_key.to_string()).or_insert_with(|| {
+            let mut limiter = create();
+            limiter.label = format!(
+                "...{}",
+                api_key
+                    .get(api_key.len().saturating_sub(4)..)
+                    .unwrap_or_default()
+            );
+            Arc::new(limiter)
+        }))
+    }
+
+    /// Waits for a token, letting higher priorities go first. Returns the time waited.
+    pub async fn acquire(&self, priority: RequestPriority) -> Duration {
+        let started_at = Instant::now();
+        let _waiting = WaitingGuard::new(self, priority);
+        let waiter = self.redis.as_ref().map(|redis| {
+            format!(
+                "{}_{}",
+                redis.process,
+                NEXT_WAITER.fetch_add(1, Ordering::Relaxed)
+            )
+        });
+
+        loop {
+            let wait = match (&self.redis, &waiter) {
+                (Some(redis), Some(waiter)) => match redis.take(priority, self.rps, waiter).await {
+                    Ok(wait) => wait,
+                    Err(err) => {
+                        warn!("Rate limiter can't reach Redis, using the local bucket: {err}");
+                        self.take_local(priority)
+                    }
+                },
+                _ => self.take_local(priority),
+            };
+            if wait.is_zero() {
+                return self.record(priority, started_at.elapsed());
+            }
+            // Checked again before the waiter expires, so other processes keep seeing it
+            tokio::time::sleep(wait.min(WAITER_TTL / 2)).await;
+        }
+    }
+
+    /// Stops handing out tokens for `retry_after`, after Toncenter rejected a request for it.
+    pub async fn throttle(&self, retry_after: Duration) {
+        {
+            let mut bucket = self.lock();
+            let until = Instant::now() + retry_after;
+            let until = bucket
+                .blocked_until
+                .map_or(until, |blocked| blocked.max(until));
+            // Tokens only start refilling once the pause is over
+            bucket.tokens = 0.0;
+            bucket.refilled_at = until;
+            bucket.blocked_until = Some(until);
+            bucket.throttled += 1;
+        }
+        if let Some(redis) = &self.redis {
+            if let Err(err) = redis.throttle(retry_after).await {
+                warn!("Rate limiter can't pause the bucket in Redis: {err}");
+            }
+        }
+        METRICS
+            .throttled
+            .add(1, &[KeyValue::new("api_key", self.label.clone())]);
+        warn!(
+            "Rate limited by Toncenter, pausing requests for {:?}",
+            retry_after
+        );
+    }
+
+    pub fn queue_stats(&self, priority: RequestPriority) -> QueueStats {
+        self.lock().stats[priority]
+    }
+
+    /// Requests rejected with `429 Too Many Requests`.
+    pub fn throttled(&self) -> u64 {
+        self.lock().throttled
+    }
+
+    /// Takes a token of the process' bucket, returning zero, or the time to wait before trying
+    /// again.
+    fn take_local(&self, priority: RequestPriority) -> Duration {
+        let mut bucket = self.lock();
+        let now = Instant::now();
+        self.refill(&mut bucket, now);
+
+        let preceded = RequestPriority::ALL
+            .into_iter()
+            .take_while(|other| *other < priority)
+            .any(|other| bucket.waiting[other] > 0);
+
+        match bucket.blocked_until {
+            Some(until) if until > now => until - now,
+            _ if preceded => self.interval(),
+            _ if bucket.tokens >= 1.0 => {
+                bucket.tokens -= 1.0;
+                Duration::ZERO
+            }
+            _ => self.interval().mul_f64(1.0 - bucket.tokens),
+        }
+    }
+
+    fn record(&self, priority: RequestPriority, waited: Duration) -> Duration {
+        {
+            let mut bucket = self.lock();
+            let stats = &mut bucket.stats[priority];
+            stats.requests += 1;
+            stats.total_wait += waited;
+            stats.max_wait = stats.max_wait.max(waited);
+        }
+        METRICS.wait.record(
+            waited.as_secs_f64(),
+            &[
+                KeyValue::new("priority", format!("{:?}", priority)),
+                KeyValue::new("api_key", self.label.clone()),
+            ],
+        );
+        if waited > SLOW_ACQUIRE {
+            warn!(
+                "{:?} request waited {:?} for a rate limit token",
+                priority, waited
+            );
+        }
+        waited
+    }
+
+    fn capacity(rps: f64) -> f64 {
+        rps.max(1.0)
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.rps)
+    }
+
+    fn refill(&self, bucket: &mut Bucket, now: Instant) {
+        if now <= bucket.refilled_at {
+            return;
+        }
+        let elapsed = (now - bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rps).min(Self::capacity(self.rps));
+        bucket.refilled_at = now;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Bucket> {
+        self.bucket.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// Counts a request as waiting until it got its token or was cancelled
+struct WaitingGuard<'a> {
+    limiter: &'a RateLimiter,
+    priority: RequestPriority,
+}
+
+impl<'a> WaitingGuard<'a> {
+    fn new(limiter: &'a RateLimiter, priority: RequestPriority) -> Self {
+        limiter.lock().waiting[priority] += 1;
+        Self { limiter, priority }
+    }
+}
+
+impl Drop for WaitingGuard<'_> {
+    fn drop(&mut self) {
+        self.limiter.lock().waiting[self.priority] -= 1;
+    }
+}
+
+/// Logs the queueing delays of every shared limiter each `interval`.
+pub fn spawn_stats_reporter(interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let limiters: Vec<Arc<RateLimiter>> = SHARED
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .values()
+                .map(Arc::clone)
+                .collect();
+            for limiter in limiters {
+                for priority in RequestPriority::ALL {
+                    let stats = limiter.queue_stats(priority);
+                    info!(
+                        "Rate limiter {}: {:?} requests: {}, avg wait: {:?}, max wait: {:?}",
+                        limiter.label,
+                        priority,
+                        stats.requests,
+                        stats.avg_wait(),
+                        stats.max_wait
+                    );
+                }
+                info!(
+                    "Rate limiter {}: throttled {} times",
+                    limiter.label,
+                    limiter.throttled()
+                );
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_acquire_refills_at_rps() {
+        let limiter = RateLimiter::new(2.0);
+
+        // A full bucket is one second worth of tokens
+        assert_eq!(limiter.acquire(RequestPriority::Poll).await, Duration::ZERO);
+        assert_eq!(limiter.acquire(RequestPriority::Poll).await, Duration::ZERO);
+
+        let waited = limiter.acquire(RequestPriority::Poll).await;
+        assert_eq!(waited, Duration::from_millis(500));
+
+        let stats = limiter.queue_stats(RequestPriority::Poll);
+        assert_eq!(stats.requests, 3);
+        assert_eq!(stats.max_wait, Duration::from_millis(500));
+        assert_eq!(stats.total_wait, Duration::from_millis(500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_higher_priority_goes_first() {
+        let limiter = Arc::new(RateLimiter::new(1.0));
+        limiter.acquire(RequestPriority::Poll).await;
+
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        for priority in [
+            RequestPriority::AccountCheck,
+            RequestPriority::Poll,
+            RequestPriority::Broadcast,
+        ] {
+            let limiter = Arc::clone(&limiter);
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                limiter.acquire(priority).await;
+                sender.send(priority).unwrap();
+            });
+            tokio::task::yield_now().await;
+        }
+        drop(sender);
+
+        let mut order = vec![];
+        while let Some(priority) = receiver.recv().await {
+            order.push(priority);
+        }
+        assert_eq!(
+            order,
+            vec![
+                RequestPriority::Broadcast,
+                RequestPriority::Poll,
+                RequestPriority::AccountCheck
+            ]
+        );
+        assert_eq!(
+            limiter.queue_stats(RequestPriority::AccountCheck).max_wait,
+            Duration::from_secs(3)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttle_pauses_tokens() {
+        let limiter = RateLimiter::new(10.0);
+        limiter.throttle(Duration::from_secs(2)).await;
+
+        let waited = limiter.acquire(RequestPriority::Broadcast).await;
+        assert!(waited >= Duration::from_secs(2));
+        assert_eq!(limiter.throttled(), 1);
+    }
+
+    /// Two limiters on the same key stand for two processes, crammed in one test to save time on
+    /// container creation
+    #[tokio::test]
+    async fn test_bucket_shared_through_redis() {
+        use relayer_core::redis::connection_manager;
+        use testcontainers::core::{IntoContainerPort, WaitFor};
+        use testcontainers::runners::AsyncRunner;
+        use testcontainers::GenericImage;
+
+        let container = GenericImage::new("redis", "7.2.4")
+            .with_exposed_port(6379.tcp())
+            .with_wait_for(WaitFor::message_on_stdout("Ready to accept connections"))
+            .start()
+            .await
+            .unwrap();
+        let host = container.get_host().await.unwrap();
+        let host_port = container.get_host_port_ipv4(6379).await.unwrap();
+        let client = redis::Client::open(format!("redis://{host}:{host_port}")).unwrap();
+        let conn = connection_manager(client, None, None, None).await.unwrap();
+
+        let first = RateLimiter::new(2.0).with_redis(conn.clone(), "test_key");
+        let second = RateLimiter::new(2.0).with_redis(conn, "test_key");
+
+        // The first process takes the whole bucket, the second one waits for a refill
+        assert!(first.acquire(RequestPriority::Poll).await < Duration::from_millis(100));
+        assert!(first.acquire(RequestPriority::Poll).await < Duration::from_millis(100));
+        assert!(second.acquire(RequestPriority::Poll).await >= Duration::from_millis(400));
+
+        // A 429 seen by one process pauses the other
+        first.throttle(Duration::from_secs(1)).await;
+        assert!(second.acquire(RequestPriority::Broadcast).await >= Duration::from_millis(900));
+        assert_eq!(second.throttled(), 0);
+    }
+
+    #[test]
+    fn test_shared_per_api_key() {
+        let first = RateLimiter::shared("test_shared_per_api_key", 5.0);
+        let second = RateLimiter::shared("test_shared_per_api_key", 50.0);
+        let other = RateLimiter::shared("test_shared_per_api_key_other", 5.0);
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(&first, &other));
+    }
+}
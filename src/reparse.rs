@@ -0,0 +1,305 @@
+/*!
+
+Runs stored traces through the current parser again and compares the events with the ones
+recorded when the trace was ingested, e.g. after a parser fix.
+
+*/
+
+use crate::error::TransactionParsingError;
+use crate::models::ton_trace::{
+    EventSummary, StoredTraces, TONTrace, TraceSelection, UpdateEvents,
+};
+use crate::parser::{ParserFailure, TraceParserTrait};
+use relayer_core::gmp_api::gmp_types::Event;
+use relayer_core::gmp_api::GmpApiTrait;
+use relayer_core::models::gmp_events::EventModel;
+use relayer_core::utils::ThreadSafe;
+use tracing::{info, warn};
+
+const PAGE_SIZE: i64 = 100;
+
+/// Difference between the events recorded for a trace and the ones the parser emits now.
+#[derive(Debug, Clone, Default)]
+pub struct TraceDiff {
+    pub trace_id: String,
+    /// Emitted now, but not sent when the trace was ingested
+    pub missing: Vec<Event>,
+    /// Sent when the trace was ingested, but not emitted anymore
+    pub stale: Vec<EventSummary>,
+    pub unchanged: usize,
+    pub failures: Vec<ParserFailure>,
+}
+
+impl TraceDiff {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.stale.is_empty() && self.failures.is_empty()
+    }
+}
+
+pub fn summary(event: &Event) -> EventSummary {
+    let model = EventModel::from_event(event.clone());
+    EventSummary {
+        event_id: model.event_id,
+        message_id: model.message_id,
+        event_type: model.event_type,
+    }
+}
+
+pub fn diff_events(
+    trace_id: &str,
+    recorded: &[&EventSummary],
+    events: Vec<Event>,
+    failures: Vec<ParserFailure>,
+) -> TraceDiff {
+    let summaries: Vec<EventSummary> = events.iter().map(summary).collect();
+
+    let mut diff = TraceDiff {
+        trace_id: trace_id.to_string(),
+        failures,
+        ..Default::default()
+    };
+    for (event, summary) in events.into_iter().zip(summaries.iter()) {
+        if recorded.contains(&summary) {
+            diff.unchanged += 1;
+        } else {
+            diff.missing.push(event);
+        }
+    }
+    diff.stale = recorded
+        .iter()
+        .filter(|recorded| !summaries.contains(recorded))
+        .map(|recorded| (*recorded).clone())
+        .collect();
+
+    diff
+}
+
+/// Events recorded for a trace once its missing events are sent. Stale events were sent
+/// already, so they stay recorded.
+fn republished_summaries(trace: &TONTrace, diff: &TraceDiff) -> Vec<EventSummary> {
+    trace
+        .event_summaries()
+        .into_iter()
+        .cloned()
+        .chain(diff.missing.iter().map(summary))
+        .collect()
+}
+
+pub struct Reparser<TP: TraceParserTrait, SM: StoredTraces + ThreadSafe> {
+    trace_parser: TP,
+    ton_trace_model: SM,
+}
+
+impl<TP: TraceParserTrait, SM: StoredTraces + ThreadSafe> Reparser<TP, SM> {
+    pub fn new(trace_parser: TP, ton_trace_model: SM) -> Self {
+        Self {
+            trace_parser,
+            ton_trace_model,
+        }
+    }
+
+    /// Reparses a stored trace. A failing parser only loses its own event, the failure is part
+    /// of the diff.
+    pub async fn reparse(&self, trace: &TONTrace) -> Result<TraceDiff, TransactionParsingError> {
+        let parsed = self
+            .trace_parser
+            .parse_trace_partial(trace.to_trace())
+            .await?;
+
+        Ok(diff_events(
+            &trace.trace_id,
+            &trace.event_summaries(),
+            parsed.events,
+            parsed.failures,
+        ))
+    }
+
+    /// Reparses the selected traces, returning the traces whose events changed. Incomplete
+    /// traces were never parsed and are skipped.
+    pub async fn reparse_selection(
+        &self,
+        selection: TraceSelection,
+    ) -> anyhow::Result<Vec<(TONTrace, TraceDiff)>> {
+        let mut changed = vec![];
+        let mut offset = 0;
+        loop {
+            let traces = self
+                .ton_trace_model
+                .fetch_stored(selection.clone(), offset, PAGE_SIZE)
+                .await?;
+            let fetched = traces.len() as i64;
+
+            for trace in traces {
+                if trace.is_incomplete {
+                    continue;
+                }
+                match self.reparse(&trace).await {
+                    Ok(diff) if diff.is_empty() => {}
+                    Ok(diff) => changed.push((trace, diff)),
+                    Err(e) => warn!("Failed to reparse trace {}: {}", trace.trace_id, e),
+                }
+            }
+
+            if fetched < PAGE_SIZE {
+                break;
+            }
+            offset += fetched;
+        }
+        info!("{} traces changed", changed.len());
+
+        Ok(changed)
+    }
+}
+
+impl<TP: TraceParserTrait, SM: StoredTraces + UpdateEvents + ThreadSafe> Reparser<TP, SM> {
+    /// Sends only the missing events of a reparsed trace to the GMP API and records them with
+    /// the trace's events, as the ingestor does for the events of a new trace.
+    pub async fn republish<G: GmpApiTrait>(
+        &self,
+        gmp_api: &G,
+        trace: &TONTrace,
+        diff: &TraceDiff,
+    ) -> anyhow::Result<()> {
+        if diff.missing.is_empty() {
+            return Ok(());
+        }
+
+        gmp_api
+            .post_events(diff.missing.clone())
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to post events: {:?}", e))?;
+
+        self.ton_trace_model
+            .update_events(
+                trace.trace_id.clone(),
+                republished_summaries(trace, diff),
+                diff.failures.clone(),
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ton_trace::{MockStoredTraces, TraceEventRecord};
+    use crate::parser::{MockTraceParserTrait, ParsedTrace};
+    use crate::test_utils::fixtures::fixture_traces;
+    use relayer_core::gmp_api::gmp_types::{CannotExecuteMessageReason, CommonEventFields};
+    use sqlx::types::Json;
+
+    fn cannot_execute(event_id: &str) -> Event {
+        Event::CannotExecuteMessageV2 {
+            common: CommonEventFields {
+                r#type: "CANNOT_EXECUTE_MESSAGE/V2".to_string(),
+                event_id: event_id.to_string(),
+                meta: None,
+            },
+            message_id: "message1".to_string(),
+            source_chain: "source1".to_string(),
+            reason: CannotExecuteMessageReason::InsufficientGas,
+            details: "details1".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_diff_events() {
+        let unchanged = summary(&cannot_execute("event1"));
+        let stale = EventSummary {
+            event_id: "event3".to_string(),
+            message_id: Some("message1".to_string()),
+            event_type: "CANNOT_EXECUTE_MESSAGE/V2".to_string(),
+        };
+
+        let diff = diff_events(
+            "trace1",
+            &[&unchanged, &stale],
+            vec![cannot_execute("event1"), cannot_execute("event2")],
+            vec![],
+        );
+
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.missing.len(), 1);
+        assert_eq!(summary(&diff.missing[0]).event_id, "event2");
+        assert_eq!(diff.stale, vec![stale]);
+        assert!(!diff.is_empty());
+
+        let diff = diff_events(
+            "trace1",
+            &[&unchanged],
+            vec![cannot_execute("event1")],
+            vec![],
+        );
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn test_republished_summaries_keep_recorded_events() {
+        let mut trace = TONTrace::from(&fixture_traces()[0]);
+        let stale = summary(&cannot_execute("event1"));
+        trace.events = Some(Json(vec![TraceEventRecord::Event(stale.clone())]));
+
+        let diff = diff_events(
+            &trace.trace_id,
+            &trace.event_summaries(),
+            vec![cannot_execute("event2")],
+            vec![],
+        );
+
+        assert_eq!(
+            republished_summaries(&trace, &diff),
+            vec![stale, summary(&cannot_execute("event2"))]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reparse_selection_pages_and_skips_unchanged() {
+        let traces: Vec<TONTrace> = fixture_traces()
+            .iter()
+            .cycle()
+            .take(PAGE_SIZE as usize + 1)
+            .map(TONTrace::from)
+            .collect();
+
+        let mut mock_model = MockStoredTraces::new();
+        mock_model
+            .expect_fetch_stored()
+            .times(2)
+            .returning(move |_, offset, limit| {
+                let page = traces
+                    .iter()
+                    .skip(offset as usize)
+                    .take(limit as usize)
+                    .cloned()
+                    .collect();
+                Box::pin(async move { Ok(page) })
+            });
+
+        let mut mock_parser = MockTraceParserTrait::new();
+        mock_parser.expect_parse_trace_partial().returning(|trace| {
+            // Only the first fixture trace emits an event, which was never recorded
+            let events = if trace.trace_id == "abcd" {
+                vec![cannot_execute("event1")]
+            } else {
+                vec![]
+            };
+            Ok(ParsedTrace {
+                events,
+                failures: vec![],
+            })
+        });
+
+        let reparser = Reparser::new(mock_parser, mock_model);
+        let changed = reparser
+            .reparse_selection(TraceSelection::LtRange { from: 0, to: 1 })
+            .await
+            .unwrap();
+
+        let fixture_count = fixture_traces().len();
+        let abcd_count = (PAGE_SIZE as usize + 1).div_ceil(fixture_count);
+        assert_eq!(changed.len(), abcd_count);
+        assert!(changed
+            .iter()
+            .all(|(trace, diff)| trace.trace_id == "abcd" && diff.missing.len() == 1));
+    }
+}
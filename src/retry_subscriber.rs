@@ -2,22 +2,121 @@
 
 Retries any incomplete traces
 
+Every retry of a trace that is still incomplete uses up one of its retries and pushes the next
+one back exponentially. Once a trace runs out of retries it is abandoned: it isn't retried
+anymore, shows up in `fetch_abandoned` and is reported to the alert hook, if any.
+[`RedisAbandonedTraceAlert`] keeps abandoned traces in a Redis hash for monitoring to alert on,
+until they are acknowledged with `ton_abandoned_traces ack`.
+
 */
 
 use super::client::RestClient;
-use crate::ton_trace::{AtomicUpsert, Retriable, TONTrace};
+use crate::completeness::settle;
+use crate::finality::is_final;
+use crate::subscriber::trace_by_hash;
+use crate::ton_trace::{AtomicUpsert, Retriable, TONTrace, DEFAULT_TRACE_RETRIES};
 use crate::types::Trace;
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
 use relayer_core::error::SubscriberError;
 use relayer_core::subscriber::{ChainTransaction, TransactionPoller};
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::sleep;
 use tonlib_core::TonAddress;
-use tracing::{debug, info, warn};
+use tracing::{debug, error, info, warn};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const RETRY_BATCH_SIZE: u32 = 100;
+const ABANDONED_TRACES_KEY: &str = "abandoned_traces";
+
+/// Retries given to a new incomplete trace and the backoff between them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub budget: i32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            budget: DEFAULT_TRACE_RETRIES,
+            base_delay: Duration::from_secs(5),
+            max_delay: Duration::from_secs(600),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay after the `attempt`th retry (starting at 1), doubling up to `max_delay`.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.base_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+}
+
+/// Told about traces that were given up on, the messages in them are stuck until someone looks.
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait AbandonedTraceAlert: Send + Sync {
+    async fn trace_abandoned(&self, trace: &TONTrace);
+}
+
+/// Abandoned traces of a chain in a Redis hash, from trace id to when the trace was abandoned.
+pub struct RedisAbandonedTraceAlert {
+    conn: ConnectionManager,
+    key: String,
+}
+
+impl RedisAbandonedTraceAlert {
+    pub fn new(conn: ConnectionManager, chain_name: &str) -> Self {
+        Self {
+            conn,
+            key: format!("{ABANDONED_TRACES_KEY}:{chain_name}"),
+        }
+    }
+
+    /// Abandoned traces that weren't acknowledged yet, with when they were abandoned.
+    pub async fn unacknowledged(&self) -> Result<HashMap<String, String>, redis::RedisError> {
+        self.conn.clone().hgetall(&self.key).await
+    }
+
+    /// Stops alerting on the traces, e.g. once someone has looked at them.
+    pub async fn acknowledge(&self, trace_ids: &[String]) -> Result<(), redis::RedisError> {
+        self.conn.clone().hdel(&self.key, trace_ids).await
+    }
+}
+
+#[async_trait]
+impl AbandonedTraceAlert for RedisAbandonedTraceAlert {
+    async fn trace_abandoned(&self, trace: &TONTrace) {
+        let abandoned_at = chrono::Utc::now().to_rfc3339();
+        if let Err(e) = self
+            .conn
+            .clone()
+            .hset::<_, _, _, ()>(&self.key, &trace.trace_id, abandoned_at)
+            .await
+        {
+            error!(
+                "Failed to record abandoned trace {} in Redis: {}",
+                trace.trace_id, e
+            );
+        }
+    }
+}
 
 pub struct RetryTONSubscriber<TM: Retriable + AtomicUpsert, CL: RestClient> {
     client: CL,
     ton_trace_model: TM,
+    finality_depth: Option<u64>,
+    retry_policy: RetryPolicy,
+    alert: Option<Arc<dyn AbandonedTraceAlert>>,
+    relevant_accounts: Vec<TonAddress>,
 }
 
 impl<TM: Retriable + AtomicUpsert, CL: RestClient> RetryTONSubscriber<TM, CL> {
@@ -25,8 +124,60 @@ impl<TM: Retriable + AtomicUpsert, CL: RestClient> RetryTONSubscriber<TM, CL> {
         Ok(RetryTONSubscriber {
             client,
             ton_trace_model,
+            finality_depth: None,
+            retry_policy: RetryPolicy::default(),
+            alert: None,
+            relevant_accounts: vec![],
         })
     }
+
+    /// Holds completed traces until all their transactions are `depth` masterchain blocks deep.
+    pub fn with_finality_depth(mut self, depth: u64) -> Self {
+        self.finality_depth = Some(depth);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Treats an incomplete trace as complete once every message in it that involves one of
+    /// `accounts` has been delivered.
+    pub fn with_relevant_accounts(mut self, accounts: Vec<TonAddress>) -> Self {
+        self.relevant_accounts = accounts;
+        self
+    }
+
+    pub fn with_alert(mut self, alert: Arc<dyn AbandonedTraceAlert>) -> Self {
+        self.alert = Some(alert);
+        self
+    }
+
+    /// Uses up a retry of a trace that is still incomplete, or abandons it on its last one.
+    async fn retry_later(&self, trace: TONTrace) -> Result<(), anyhow::Error> {
+        if trace.retries <= 1 {
+            error!(
+                "Trace {} is still incomplete and out of retries, abandoning it",
+                trace.trace_id
+            );
+            self.ton_trace_model.abandon(trace.clone()).await?;
+            if let Some(alert) = &self.alert {
+                alert.trace_abandoned(&trace).await;
+            }
+            return Ok(());
+        }
+
+        let attempt = (self.retry_policy.budget - trace.retries + 1).max(1) as u32;
+        let delay = self.retry_policy.backoff(attempt);
+        info!(
+            "Trace {} still incomplete, {} retries left, retrying in {:?}",
+            trace.trace_id,
+            trace.retries - 1,
+            delay
+        );
+        self.ton_trace_model.schedule_retry(trace, delay).await
+    }
 }
 
 impl<TM: Retriable + AtomicUpsert, CL: RestClient> TransactionPoller
@@ -55,45 +206,52 @@ impl<TM: Retriable + AtomicUpsert, CL: RestClient> TransactionPoller
         &mut self,
         account_id: TonAddress,
     ) -> Result<Vec<Self::Transaction>, anyhow::Error> {
-        sleep(Duration::from_secs(5)).await;
+        sleep(POLL_INTERVAL).await;
 
-        let traces = self.ton_trace_model.fetch_retry(100).await?;
+        let traces = self.ton_trace_model.fetch_retry(RETRY_BATCH_SIZE).await?;
 
         if traces.is_empty() {
             debug!("No retry traces to process");
             return Ok(Vec::new());
         }
 
-        let mut retry_map: HashMap<String, i32> = HashMap::new();
-        let mut trace_ids: Vec<String> = Vec::with_capacity(traces.len());
-
+        let trace_ids: Vec<String> = traces.iter().map(|trace| trace.trace_id.clone()).collect();
         info!("Processing {} retry traces: {:?}", traces.len(), trace_ids);
 
-        for trace in &traces {
-            trace_ids.push(trace.trace_id.clone());
-            retry_map.insert(trace.trace_id.clone(), trace.retries);
-        }
+        let mut pending: HashMap<String, TONTrace> = traces
+            .into_iter()
+            .map(|trace| (trace.trace_id.clone(), trace))
+            .collect();
 
         let traces = self
             .client
             .get_traces_for_account(None, Some(trace_ids), None)
-            .await?;
+            .await?
+            .traces;
+
+        let latest_mc_seqno = match self.finality_depth {
+            Some(_) => self.client.get_masterchain_info().await?.last.seqno as u64,
+            None => 0,
+        };
 
         let mut unseen_traces: Vec<Trace> = Vec::new();
 
         for trace in traces {
+            let trace = settle(trace, &self.relevant_accounts);
+            let Some(stored) = pending.remove(&trace.trace_id) else {
+                warn!("Trace {} was not requested, skipping", trace.trace_id);
+                continue;
+            };
             let trace_model = TONTrace::from(&trace);
-            if trace_model.is_incomplete {
-                self.ton_trace_model.decrease_retry(trace_model).await?;
-                let retry = retry_map.get(&trace.trace_id).copied().unwrap_or(0);
-                if retry <= 1 {
-                    warn!(
-                        "Trace {} is still incomplete and retry count is {}, skipping",
-                        trace.trace_id, retry
-                    );
-                } else {
-                    info!("Trace {} still incomplete, skipping", trace.trace_id);
+            if let Some(depth) = self.finality_depth {
+                // Stays incomplete in the database, so it is retried without using up a retry
+                if !trace.is_incomplete && !is_final(&trace, latest_mc_seqno, depth) {
+                    info!("Trace {} is not final yet, skipping", trace.trace_id);
+                    continue;
                 }
+            }
+            if trace_model.is_incomplete {
+                self.retry_later(stored).await?;
             } else if self
                 .ton_trace_model
                 .upsert_and_return_if_changed(trace_model)
@@ -111,18 +269,47 @@ impl<TM: Retriable + AtomicUpsert, CL: RestClient> TransactionPoller
             }
         }
 
+        // Not returned by Toncenter at all, treated like a trace that is still incomplete
+        for stored in pending.into_values() {
+            warn!("Trace {} not found, retrying later", stored.trace_id);
+            self.retry_later(stored).await?;
+        }
+
         Ok(unseen_traces)
     }
 
-    async fn poll_tx(&mut self, _tx_hash: String) -> Result<Self::Transaction, anyhow::Error> {
-        unimplemented!();
+    /// Like `TONSubscriber::poll_tx`: a trace that is still incomplete is an error, not a retry.
+    #[tracing::instrument(skip(self))]
+    async fn poll_tx(&mut self, tx_hash: String) -> Result<Self::Transaction, anyhow::Error> {
+        let trace = trace_by_hash(
+            &self.client,
+            &tx_hash,
+            &self.relevant_accounts,
+            self.finality_depth,
+        )
+        .await?;
+
+        let trace_model = TONTrace::from(&trace);
+        if self
+            .ton_trace_model
+            .upsert_and_return_if_changed(trace_model)
+            .await?
+            .is_none()
+        {
+            info!(
+                "Trace {} was seen before, returning it again",
+                trace.trace_id
+            );
+        }
+
+        Ok(trace)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::client::MockRestClient;
+    use crate::client::{MockRestClient, TracesWindow};
     use crate::ton_trace::{MockAtomicUpsert, MockRetriable, TONTrace};
     use mockall::predicate::*;
 
@@ -146,8 +333,16 @@ mod tests {
             self.retriable.fetch_retry(limit).await
         }
 
-        async fn decrease_retry(&self, tx: TONTrace) -> anyhow::Result<()> {
-            self.retriable.decrease_retry(tx).await
+        async fn schedule_retry(&self, tx: TONTrace, delay: Duration) -> anyhow::Result<()> {
+            self.retriable.schedule_retry(tx, delay).await
+        }
+
+        async fn abandon(&self, tx: TONTrace) -> anyhow::Result<()> {
+            self.retriable.abandon(tx).await
+        }
+
+        async fn fetch_abandoned(&self, limit: u32) -> anyhow::Result<Vec<TONTrace>> {
+            self.retriable.fetch_abandoned(limit).await
         }
     }
 
@@ -170,6 +365,167 @@ mod tests {
         }
     }
 
+    fn account() -> TonAddress {
+        TonAddress::from_base64_url("EQAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAM9c").unwrap()
+    }
+
+    fn stored_trace(id: &str, retries: i32) -> TONTrace {
+        let mut trace = sample_trace(id, 1, 2);
+        trace.is_incomplete = true;
+        TONTrace::from(&trace).with_retries(retries)
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            budget: 10,
+            base_delay: Duration::from_secs(5),
+            max_delay: Duration::from_secs(60),
+        };
+
+        assert_eq!(policy.backoff(1), Duration::from_secs(5));
+        assert_eq!(policy.backoff(2), Duration::from_secs(10));
+        assert_eq!(policy.backoff(4), Duration::from_secs(40));
+        assert_eq!(policy.backoff(5), Duration::from_secs(60));
+        assert_eq!(policy.backoff(100), Duration::from_secs(60));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_poll_account_schedules_incomplete_traces() {
+        let mut retriable = MockRetriable::new();
+        retriable.expect_fetch_retry().returning(|_| {
+            Box::pin(async {
+                Ok(vec![
+                    stored_trace("incomplete", 8),
+                    stored_trace("missing", 10),
+                ])
+            })
+        });
+        // Third attempt of a budget of 10
+        retriable
+            .expect_schedule_retry()
+            .withf(|trace, delay| {
+                trace.trace_id == "incomplete" && *delay == Duration::from_secs(20)
+            })
+            .times(1)
+            .returning(|_, _| Box::pin(async { Ok(()) }));
+        retriable
+            .expect_schedule_retry()
+            .withf(|trace, delay| trace.trace_id == "missing" && *delay == Duration::from_secs(5))
+            .times(1)
+            .returning(|_, _| Box::pin(async { Ok(()) }));
+        retriable.expect_abandon().never();
+
+        let mut atomic_upsert = MockAtomicUpsert::new();
+        atomic_upsert
+            .expect_upsert_and_return_if_changed()
+            .withf(|trace| trace.trace_id == "complete")
+            .times(1)
+            .returning(|trace| Box::pin(async move { Ok(Some(trace)) }));
+
+        let mut client = MockRestClient::new();
+        client.expect_get_traces_for_account().returning(|_, _, _| {
+            let mut incomplete = sample_trace("incomplete", 1, 2);
+            incomplete.is_incomplete = true;
+            Ok(TracesWindow {
+                traces: vec![incomplete, sample_trace("complete", 3, 4)],
+                truncated: false,
+            })
+        });
+
+        let mut subscriber = RetryTONSubscriber::new(
+            client,
+            MockTraceModel {
+                retriable,
+                atomic_upsert,
+            },
+        )
+        .await
+        .unwrap();
+
+        let traces = subscriber.poll_account(account()).await.unwrap();
+
+        assert_eq!(traces.len(), 1);
+        assert_eq!(traces[0].trace_id, "complete");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_poll_account_abandons_exhausted_traces() {
+        let mut retriable = MockRetriable::new();
+        retriable
+            .expect_fetch_retry()
+            .returning(|_| Box::pin(async { Ok(vec![stored_trace("stuck", 1)]) }));
+        retriable.expect_schedule_retry().never();
+        retriable
+            .expect_abandon()
+            .withf(|trace| trace.trace_id == "stuck")
+            .times(1)
+            .returning(|_| Box::pin(async { Ok(()) }));
+
+        let mut client = MockRestClient::new();
+        client.expect_get_traces_for_account().returning(|_, _, _| {
+            let mut stuck = sample_trace("stuck", 1, 2);
+            stuck.is_incomplete = true;
+            Ok(TracesWindow {
+                traces: vec![stuck],
+                truncated: false,
+            })
+        });
+
+        let mut alert = MockAbandonedTraceAlert::new();
+        alert
+            .expect_trace_abandoned()
+            .withf(|trace| trace.trace_id == "stuck")
+            .times(1)
+            .return_const(());
+
+        let mut subscriber = RetryTONSubscriber::new(
+            client,
+            MockTraceModel {
+                retriable,
+                atomic_upsert: MockAtomicUpsert::new(),
+            },
+        )
+        .await
+        .unwrap()
+        .with_alert(Arc::new(alert));
+
+        let traces = subscriber.poll_account(account()).await.unwrap();
+
+        assert!(traces.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_redis_alert_until_acknowledged() {
+        use relayer_core::redis::connection_manager;
+        use testcontainers::core::{IntoContainerPort, WaitFor};
+        use testcontainers::runners::AsyncRunner;
+        use testcontainers::GenericImage;
+
+        let container = GenericImage::new("redis", "7.2.4")
+            .with_exposed_port(6379.tcp())
+            .with_wait_for(WaitFor::message_on_stdout("Ready to accept connections"))
+            .start()
+            .await
+            .unwrap();
+        let host = container.get_host().await.unwrap();
+        let host_port = container.get_host_port_ipv4(6379).await.unwrap();
+        let client = redis::Client::open(format!("redis://{host}:{host_port}")).unwrap();
+        let conn = connection_manager(client, None, None, None).await.unwrap();
+
+        let alert = RedisAbandonedTraceAlert::new(conn, "ton");
+        alert.trace_abandoned(&stored_trace("stuck1", 0)).await;
+        alert.trace_abandoned(&stored_trace("stuck2", 0)).await;
+
+        let abandoned = alert.unacknowledged().await.unwrap();
+        assert_eq!(abandoned.len(), 2);
+        assert!(abandoned.contains_key("stuck1"));
+
+        alert.acknowledge(&["stuck1".to_string()]).await.unwrap();
+        let abandoned = alert.unacknowledged().await.unwrap();
+        assert_eq!(abandoned.keys().collect::<Vec<_>>(), vec!["stuck2"]);
+    }
+
     #[test]
     fn test_transaction_id() {
         let mock_client = MockRestClient::new();
@@ -178,6 +534,10 @@ mod tests {
         let subscriber = RetryTONSubscriber {
             client: mock_client,
             ton_trace_model: mock_trace_model,
+            finality_depth: None,
+            retry_policy: RetryPolicy::default(),
+            alert: None,
+            relevant_accounts: vec![],
         };
 
         let trace = sample_trace("test-trace-id", 1, 2);
@@ -194,6 +554,10 @@ mod tests {
         let subscriber = RetryTONSubscriber {
             client: mock_client,
             ton_trace_model: mock_trace_model,
+            finality_depth: None,
+            retry_policy: RetryPolicy::default(),
+            alert: None,
+            relevant_accounts: vec![],
         };
 
         let address =
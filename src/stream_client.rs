@@ -0,0 +1,411 @@
+/*!
+
+Toncenter streaming client.
+
+Polling adds up to a whole poll interval of latency to every trace. The streaming API pushes a
+notification for every trace of the subscribed accounts instead, as server-sent events. Completed
+traces are handed to the subscriber through a channel, see [`StreamFeed`].
+
+The stream is best effort: it can drop, and nothing is replayed when it reconnects. It never moves
+the subscriber's cursor. Polling keeps running at a longer interval while the stream is up, and
+right away after the stream (re)connects or drops, so anything the stream missed is picked up from
+the cursor. Traces delivered by both go through the same `ton_traces` check, so they are only
+published once.
+
+# Example Usage
+
+```rust,no_run
+#[tokio::main]
+async fn main() {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use ton::stream_client::{run_trace_stream, StreamFeed, TONStreamClient};
+    let client = TONStreamClient::new("https://testnet.toncenter.com".to_string(), "test".to_string(), 5).unwrap();
+    let (sender, receiver) = tokio::sync::mpsc::channel(100);
+    tokio::spawn(run_trace_stream(Arc::new(client), vec![], sender));
+    let feed = StreamFeed::new(receiver, Duration::from_secs(60));
+}
+```
+
+*/
+
+use crate::client::clean_json_string_full;
+use crate::types::{Trace, TraceRest};
+use async_trait::async_trait;
+use relayer_core::error::ClientError;
+use relayer_core::error::ClientError::{BadResponse, ConnectionFailed};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout, Instant};
+use tonlib_core::TonAddress;
+use tracing::{debug, info, warn};
+
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+// How long a poll waits for the stream before it returns
+const STREAM_WAIT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub enum StreamUpdate {
+    Connected,
+    Disconnected,
+    Trace(Box<Trace>),
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamNotification {
+    #[serde(rename = "type")]
+    kind: String,
+    trace: Option<TraceRest>,
+}
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait StreamClient: Send + Sync {
+    /// Subscribes to the traces of `accounts` and sends updates until the stream ends.
+    async fn subscribe(
+        &self,
+        accounts: Vec<TonAddress>,
+        updates: mpsc::Sender<StreamUpdate>,
+    ) -> Result<(), ClientError>;
+}
+
+#[derive(Clone, Debug)]
+pub struct TONStreamClient {
+    url: String,
+    client: reqwest::Client,
+    api_key: String,
+}
+
+impl TONStreamClient {
+    pub fn new(url: String, api_key: String, connect_timeout: u64) -> Result<Self, ClientError> {
+        // No overall timeout, the response is read for as long as the stream is up
+        let client = reqwest::ClientBuilder::new()
+            .connect_timeout(Duration::from_secs(connect_timeout))
+            .build()
+            .map_err(|e| ConnectionFailed(e.to_string()))?;
+
+        Ok(Self {
+            url,
+            client,
+            api_key,
+        })
+    }
+}
+
+/// Data of the events that are complete in `buffer`, the incomplete rest stays in it.
+fn drain_events(buffer: &mut Vec<u8>) -> Vec<String> {
+    let mut events = vec![];
+    while let Some(end) = buffer.windows(2).position(|window| window == b"\n\n") {
+        let event: Vec<u8> = buffer.drain(..end + 2).collect();
+        let event = clean_json_string_full(&event);
+        let data: Vec<&str> = event
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(|data| data.strip_prefix(' ').unwrap_or(data))
+            .collect();
+        if !data.is_empty() {
+            events.push(data.join("\n"));
+        }
+    }
+    events
+}
+
+/// Completed trace of a notification, other notifications are ignored.
+fn parse_notification(data: &str) -> Result<Option<Trace>, ClientError> {
+    let notification = serde_json::from_str::<StreamNotification>(data)
+        .map_err(|err| BadResponse(format!("Failed to parse stream notification: {err}")))?;
+
+    match notification.trace {
+        Some(trace) if notification.kind == "trace" && !trace.is_incomplete => {
+            Ok(Some(Trace::from(trace)))
+        }
+        _ => Ok(None),
+    }
+}
+
+#[async_trait]
+impl StreamClient for TONStreamClient {
+    async fn subscribe(
+        &self,
+        accounts: Vec<TonAddress>,
+        updates: mpsc::Sender<StreamUpdate>,
+    ) -> Result<(), ClientError> {
+        let url = format!("{}/api/streaming/v1/sse", self.url.trim_end_matches('/'));
+        let body = json!({
+            "addresses": accounts.iter().map(|account| account.to_string()).collect::<Vec<_>>(),
+            "types": ["trace"],
+        });
+
+        let mut response = self
+            .client
+            .post(url)
+            .header("X-API-Key", &self.api_key)
+            .header("Accept", "text/event-stream")
+            .json(&body)
+            .send()
+            .await
+            .map_err(|err| ConnectionFailed(err.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(BadResponse(format!("Unexpected status {status}: {text}")));
+        }
+
+        info!("Trace stream connected for {:?}", accounts);
+        if updates.send(StreamUpdate::Connected).await.is_err() {
+            return Ok(());
+        }
+
+        // Events are only decoded once complete, a chunk can end in the middle of a character
+        let mut buffer: Vec<u8> = vec![];
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|err| ConnectionFailed(err.to_string()))?
+        {
+            buffer.extend(chunk.iter().filter(|byte| **byte != b'\r'));
+            for data in drain_events(&mut buffer) {
+                let trace = match parse_notification(&data) {
+                    Ok(Some(trace)) => trace,
+                    Ok(None) => continue,
+                    Err(err) => {
+                        warn!("{:?}", err);
+                        continue;
+                    }
+                };
+                debug!("Trace {} received from stream", trace.trace_id);
+                if updates
+                    .send(StreamUpdate::Trace(Box::new(trace)))
+                    .await
+                    .is_err()
+                {
+                    return Ok(());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Keeps the stream of `accounts` up, reconnecting with backoff, until `updates` is closed.
+pub async fn run_trace_stream(
+    client: Arc<dyn StreamClient>,
+    accounts: Vec<TonAddress>,
+    updates: mpsc::Sender<StreamUpdate>,
+) {
+    let mut delay = RECONNECT_BASE_DELAY;
+    loop {
+        let connected_at = Instant::now();
+        match client.subscribe(accounts.clone(), updates.clone()).await {
+            Ok(()) => warn!("Trace stream for {:?} ended", accounts),
+            Err(err) => warn!("Trace stream for {:?} failed: {:?}", accounts, err),
+        }
+        if updates.send(StreamUpdate::Disconnected).await.is_err() {
+            return;
+        }
+
+        // A stream that stayed up for a while starts over with a short delay
+        if connected_at.elapsed() > RECONNECT_MAX_DELAY {
+            delay = RECONNECT_BASE_DELAY;
+        }
+        sleep(delay).await;
+        delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+    }
+}
+
+/// Traces from the stream, and when the subscriber has to poll as well.
+pub struct StreamFeed {
+    updates: mpsc::Receiver<StreamUpdate>,
+    connected: bool,
+    catch_up: bool,
+    catch_up_interval: Duration,
+    last_poll: Option<Instant>,
+}
+
+impl StreamFeed {
+    pub fn new(updates: mpsc::Receiver<StreamUpdate>, catch_up_interval: Duration) -> Self {
+        Self {
+            updates,
+            connected: false,
+            catch_up: true,
+            catch_up_interval,
+            last_poll: None,
+        }
+    }
+
+    /// Traces received since the last call, waiting a moment for the first one.
+    pub async fn next_traces(&mut self) -> Vec<Trace> {
+        let mut updates = vec![];
+        if let Ok(Some(update)) = timeout(STREAM_WAIT, self.updates.recv()).await {
+            updates.push(update);
+            while let Ok(update) = self.updates.try_recv() {
+                updates.push(update);
+            }
+        }
+
+        let mut traces = vec![];
+        for update in updates {
+            match update {
+                StreamUpdate::Trace(trace) => traces.push(*trace),
+                // Whatever happened while the stream was down has to be polled
+                StreamUpdate::Connected => {
+                    self.connected = true;
+                    self.catch_up = true;
+                }
+                StreamUpdate::Disconnected => {
+                    self.connected = false;
+                    self.catch_up = true;
+                }
+            }
+        }
+        traces
+    }
+
+    /// Polling is the fallback while the stream is down, and catches up after it (re)connects
+    /// and every `catch_up_interval` while it is up.
+    pub fn should_poll(&self) -> bool {
+        !self.connected
+            || self.catch_up
+            || self
+                .last_poll
+                .is_none_or(|last_poll| last_poll.elapsed() >= self.catch_up_interval)
+    }
+
+    pub fn polled(&mut self) {
+        self.catch_up = false;
+        self.last_poll = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fixtures::fixture_traces;
+    use httpmock::Method::POST;
+    use httpmock::MockServer;
+
+    // Notification the way the stream sends it for the trace
+    fn notification(trace: &Trace) -> String {
+        let data = json!({
+            "type": "trace",
+            "trace": {
+                "is_incomplete": trace.is_incomplete,
+                "start_lt": trace.start_lt.to_string(),
+                "end_lt": trace.end_lt.to_string(),
+                "trace_id": trace.trace_id,
+                "transactions": trace
+                    .transactions
+                    .iter()
+                    .map(|tx| (tx.hash.clone(), json!(tx)))
+                    .collect::<serde_json::Map<_, _>>(),
+                "transactions_order": trace
+                    .transactions
+                    .iter()
+                    .map(|tx| tx.hash.clone())
+                    .collect::<Vec<_>>(),
+            }
+        });
+        format!("data: {data}\n\n")
+    }
+
+    #[test]
+    fn test_drain_events() {
+        let mut buffer = b": keepalive\n\ndata: {\"a\":\ndata: 1}\n\ndata: {\"b\"".to_vec();
+
+        let events = drain_events(&mut buffer);
+
+        assert_eq!(events, vec!["{\"a\":\n1}".to_string()]);
+        assert_eq!(buffer, b"data: {\"b\"");
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_sends_completed_traces() {
+        let server = MockServer::start();
+        let traces = fixture_traces();
+        let mut incomplete = traces[2].clone();
+        incomplete.is_incomplete = true;
+
+        let body = format!(
+            ": keepalive\n\n{}{}{}data: {{\"type\": \"account_state\"}}\n\n",
+            notification(&traces[1]),
+            notification(&incomplete),
+            notification(&traces[3])
+        );
+        let account = traces[1].transactions[1].account.clone();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/api/streaming/v1/sse")
+                .header("X-API-Key", "test")
+                .json_body(json!({
+                    "addresses": [account.to_string()],
+                    "types": ["trace"],
+                }));
+            then.status(200)
+                .header("Content-Type", "text/event-stream")
+                .body(body);
+        });
+
+        let client = TONStreamClient::new(server.base_url(), "test".to_string(), 5).unwrap();
+        let (sender, mut receiver) = mpsc::channel(10);
+
+        client.subscribe(vec![account], sender).await.unwrap();
+
+        mock.assert();
+        assert!(matches!(
+            receiver.recv().await,
+            Some(StreamUpdate::Connected)
+        ));
+        let mut ids = vec![];
+        while let Some(update) = receiver.recv().await {
+            match update {
+                StreamUpdate::Trace(trace) => ids.push(trace.trace_id),
+                other => panic!("Unexpected update {other:?}"),
+            }
+        }
+        assert_eq!(
+            ids,
+            vec![traces[1].trace_id.clone(), traces[3].trace_id.clone()]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_feed_polls_while_down_and_after_reconnect() {
+        let (sender, receiver) = mpsc::channel(10);
+        let mut feed = StreamFeed::new(receiver, Duration::from_secs(60));
+        assert!(feed.should_poll());
+
+        sender.send(StreamUpdate::Connected).await.unwrap();
+        assert!(feed.next_traces().await.is_empty());
+        // Catches up with what was missed before the stream connected
+        assert!(feed.should_poll());
+        feed.polled();
+        assert!(!feed.should_poll());
+
+        let trace = fixture_traces()[1].clone();
+        sender
+            .send(StreamUpdate::Trace(Box::new(trace.clone())))
+            .await
+            .unwrap();
+        let traces = feed.next_traces().await;
+        assert_eq!(traces.len(), 1);
+        assert_eq!(traces[0].trace_id, trace.trace_id);
+        assert!(!feed.should_poll());
+
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert!(feed.should_poll());
+        feed.polled();
+
+        sender.send(StreamUpdate::Disconnected).await.unwrap();
+        feed.next_traces().await;
+        assert!(feed.should_poll());
+        feed.polled();
+        // Polls on every call until the stream is back
+        assert!(feed.should_poll());
+    }
+}
@@ -5,11 +5,20 @@ Reads from the TON blockchain and adds transactions to a queue.
 */
 
 use super::client::RestClient;
-use crate::ton_trace::{AtomicUpsert, TONTrace};
+use crate::completeness::settle;
+use crate::continuity::{check_continuity, ChainLink};
+use crate::finality::{is_final, split_final};
+use crate::models::ton_account_head::AccountHeads;
+use crate::stream_client::StreamFeed;
+use crate::ton_trace::{AtomicUpsert, TONTrace, DEFAULT_TRACE_RETRIES};
+use crate::toncenter_error::ToncenterError;
 use crate::types::Trace;
+use anyhow::{anyhow, bail};
 use relayer_core::database::Database;
 use relayer_core::error::SubscriberError;
 use relayer_core::subscriber::{ChainTransaction, TransactionPoller};
+use std::future::Future;
+use std::sync::Arc;
 use tonlib_core::TonAddress;
 use tracing::{debug, info, warn};
 
@@ -20,6 +29,11 @@ pub struct TONSubscriber<DB: Database, TM: AtomicUpsert, CL: RestClient> {
     context: String,
     chain_name: String,
     ton_trace_model: TM,
+    account_heads: Option<Arc<dyn AccountHeads>>,
+    finality_depth: Option<u64>,
+    retry_budget: i32,
+    relevant_accounts: Vec<TonAddress>,
+    stream: Option<StreamFeed>,
 }
 
 impl<DB: Database, TM: AtomicUpsert, CL: RestClient> TONSubscriber<DB, TM, CL> {
@@ -49,9 +63,268 @@ impl<DB: Database, TM: AtomicUpsert, CL: RestClient> TONSubscriber<DB, TM, CL> {
             context,
             chain_name,
             ton_trace_model,
+            account_heads: None,
+            finality_depth: None,
+            retry_budget: DEFAULT_TRACE_RETRIES,
+            relevant_accounts: vec![],
+            stream: None,
         })
     }
 
+    /// Holds traces until all their transactions are `depth` masterchain blocks deep.
+    pub fn with_finality_depth(mut self, depth: u64) -> Self {
+        self.finality_depth = Some(depth);
+        self
+    }
+
+    /// Retries an incomplete trace gets before it is abandoned.
+    pub fn with_retry_budget(mut self, retries: i32) -> Self {
+        self.retry_budget = retries;
+        self
+    }
+
+    /// Treats an incomplete trace as complete once every message in it that involves one of
+    /// `accounts` has been delivered, instead of waiting for Toncenter to mark it complete.
+    pub fn with_relevant_accounts(mut self, accounts: Vec<TonAddress>) -> Self {
+        self.relevant_accounts = accounts;
+        self
+    }
+
+    /// Takes traces from the stream as they complete, and only polls as a fallback and to catch
+    /// up, see [`StreamFeed`].
+    pub fn with_stream(mut self, stream: StreamFeed) -> Self {
+        self.stream = Some(stream);
+        self
+    }
+
+    /// Checks that polled transactions of the account follow the last one seen, and fetches
+    /// the traces of the ones that were missed.
+    pub fn with_account_heads(mut self, account_heads: Arc<dyn AccountHeads>) -> Self {
+        self.account_heads = Some(account_heads);
+        self
+    }
+
+    /// Scans `[start_lt, end_lt]` for the account like polling does, but without reading or
+    /// moving the cursor. The traces not seen before are handed to `publish` page by page, as
+    /// soon as they are marked seen. Returns how many traces were published.
+    #[tracing::instrument(skip(self, publish))]
+    pub async fn backfill<F, Fut>(
+        &self,
+        account_id: TonAddress,
+        start_lt: i64,
+        end_lt: i64,
+        mut publish: F,
+    ) -> Result<usize, anyhow::Error>
+    where
+        F: FnMut(Vec<Trace>) -> Fut + Send,
+        Fut: Future<Output = Result<(), anyhow::Error>> + Send,
+    {
+        let latest_mc_seqno = self.latest_mc_seqno().await?;
+        let mut published = 0;
+        let held = self
+            .backfill_range(&account_id, start_lt, end_lt, latest_mc_seqno, |traces| {
+                published += traces.len();
+                publish(traces)
+            })
+            .await?;
+        if held > 0 {
+            warn!(
+                "Skipped {} traces of account {} that are not final yet",
+                held, account_id
+            );
+        }
+
+        Ok(published)
+    }
+
+    /// Hands the unseen final traces of the range to `on_page`, one page at a time. Returns how
+    /// many traces were held as not final yet.
+    async fn backfill_range<F, Fut>(
+        &self,
+        account_id: &TonAddress,
+        start_lt: i64,
+        end_lt: i64,
+        latest_mc_seqno: Option<u64>,
+        mut on_page: F,
+    ) -> Result<usize, anyhow::Error>
+    where
+        F: FnMut(Vec<Trace>) -> Fut + Send,
+        Fut: Future<Output = Result<(), anyhow::Error>> + Send,
+    {
+        let mut held = 0;
+        let mut from_lt = start_lt;
+        loop {
+            let window = self
+                .client
+                .get_traces_for_account_in_range(account_id.clone(), from_lt, end_lt)
+                .await?;
+            info!(
+                "Backfill got {} traces for account {} from lt {}",
+                window.traces.len(),
+                account_id,
+                from_lt
+            );
+
+            // A truncated window is an lt-ordered prefix, continue from the start of its last
+            // trace. Traces received twice are deduplicated when stored.
+            let next_lt = if window.truncated {
+                window.traces.iter().map(|trace| trace.start_lt).max()
+            } else {
+                None
+            };
+            let (traces, pending) = self.split_final(window.traces, latest_mc_seqno);
+            held += pending.len();
+            let unseen = self.unseen_traces(traces, account_id).await?;
+            if !unseen.is_empty() {
+                on_page(unseen).await?;
+            }
+
+            match next_lt {
+                Some(next_lt) if next_lt > from_lt => from_lt = next_lt,
+                Some(_) => {
+                    warn!(
+                        "Backfill for account {} is not advancing past lt {}, stopping",
+                        account_id, from_lt
+                    );
+                    break;
+                }
+                None => break,
+            }
+        }
+
+        Ok(held)
+    }
+
+    /// Latest masterchain seqno, if traces are held until they are final.
+    async fn latest_mc_seqno(&self) -> Result<Option<u64>, anyhow::Error> {
+        if self.finality_depth.is_none() {
+            return Ok(None);
+        }
+        let info = self.client.get_masterchain_info().await?;
+
+        Ok(Some(info.last.seqno as u64))
+    }
+
+    /// Final traces, and the ones to hold until they are.
+    fn split_final(
+        &self,
+        traces: Vec<Trace>,
+        latest_mc_seqno: Option<u64>,
+    ) -> (Vec<Trace>, Vec<Trace>) {
+        match (self.finality_depth, latest_mc_seqno) {
+            (Some(depth), Some(latest_mc_seqno)) => split_final(traces, latest_mc_seqno, depth),
+            _ => (traces, vec![]),
+        }
+    }
+
+    /// Backfills the transactions of the account missing between the last one seen and the
+    /// polled ones. The head only moves once the gaps are filled with final traces, so a failure
+    /// is retried with the next poll.
+    async fn fill_gaps(
+        &self,
+        account_id: &TonAddress,
+        traces: &[Trace],
+        latest_mc_seqno: Option<u64>,
+    ) -> Result<Vec<Trace>, anyhow::Error> {
+        let Some(account_heads) = &self.account_heads else {
+            return Ok(vec![]);
+        };
+
+        let account = account_id.to_hex();
+        let head = account_heads
+            .get_head(&account)
+            .await?
+            .map(|head| ChainLink {
+                hash: head.tx_hash,
+                lt: head.tx_lt,
+            });
+        let continuity = check_continuity(account_id, head, traces);
+
+        let mut missing = vec![];
+        let mut held = 0;
+        for gap in &continuity.gaps {
+            warn!(
+                "Transactions of account {} missing between lt {} and {}, backfilling",
+                account_id, gap.from_lt, gap.to_lt
+            );
+            held += self
+                .backfill_range(
+                    account_id,
+                    gap.from_lt,
+                    gap.to_lt,
+                    latest_mc_seqno,
+                    |traces| {
+                        missing.extend(traces);
+                        async { Ok(()) }
+                    },
+                )
+                .await?;
+        }
+
+        if held > 0 {
+            info!(
+                "Holding {} missed traces of account {} until they are final",
+                held, account_id
+            );
+        } else if let Some(head) = continuity.head {
+            account_heads
+                .set_head(&account, &head.hash, head.lt)
+                .await?;
+        }
+
+        Ok(missing)
+    }
+
+    /// Streamed traces that are final and not seen before. Held ones are left to polling, which
+    /// keeps the cursor before them until they are final.
+    async fn streamed_traces(
+        &self,
+        traces: Vec<Trace>,
+        account_id: &TonAddress,
+        latest_mc_seqno: Option<u64>,
+    ) -> Result<Vec<Trace>, anyhow::Error> {
+        if traces.is_empty() {
+            return Ok(vec![]);
+        }
+        info!(
+            "Got {} traces for account {} from stream",
+            traces.len(),
+            account_id
+        );
+        let (traces, _) = self.split_final(traces, latest_mc_seqno);
+        self.unseen_traces(traces, account_id).await
+    }
+
+    /// Stores the traces and keeps the complete ones that are new or changed.
+    async fn unseen_traces(
+        &self,
+        traces: Vec<Trace>,
+        account_id: &TonAddress,
+    ) -> Result<Vec<Trace>, anyhow::Error> {
+        let mut unseen_traces: Vec<Trace> = Vec::new();
+
+        for trace in traces {
+            let trace = settle(trace, &self.relevant_accounts);
+            let trace_model = TONTrace::from(&trace).with_retries(self.retry_budget);
+            if self
+                .ton_trace_model
+                .upsert_and_return_if_changed(trace_model)
+                .await?
+                .is_some()
+                && !trace.is_incomplete
+            {
+                debug!("Trace {} added from account {}", trace.trace_id, account_id);
+                unseen_traces.push(trace);
+            } else if trace.is_incomplete {
+                info!("Trace {} is incomplete, skipping", trace.trace_id);
+            } else {
+                info!("Trace {} already seen, skipping", trace.trace_id);
+            }
+        }
+
+        Ok(unseen_traces)
+    }
+
     #[tracing::instrument(skip(self))]
     async fn store_latest_height(&mut self) -> Result<(), SubscriberError> {
         self.db
@@ -61,6 +334,36 @@ impl<DB: Database, TM: AtomicUpsert, CL: RestClient> TONSubscriber<DB, TM, CL> {
     }
 }
 
+/// Trace containing the transaction or message with `hash`, fetched on demand. Fails if Toncenter
+/// doesn't know the hash, or the trace isn't complete or final yet.
+pub(crate) async fn trace_by_hash<CL: RestClient>(
+    client: &CL,
+    hash: &str,
+    relevant_accounts: &[TonAddress],
+    finality_depth: Option<u64>,
+) -> Result<Trace, anyhow::Error> {
+    let trace = match client.get_trace_by_tx_hash(hash.to_string()).await? {
+        Some(trace) => trace,
+        None => client
+            .get_trace_by_message_hash(hash.to_string())
+            .await?
+            .ok_or_else(|| anyhow!("No trace found with transaction or message {hash}"))?,
+    };
+
+    let trace = settle(trace, relevant_accounts);
+    if trace.is_incomplete {
+        bail!("Trace {} with {} is not complete yet", trace.trace_id, hash);
+    }
+    if let Some(depth) = finality_depth {
+        let latest_mc_seqno = client.get_masterchain_info().await?.last.seqno as u64;
+        if !is_final(&trace, latest_mc_seqno, depth) {
+            bail!("Trace {} with {} is not final yet", trace.trace_id, hash);
+        }
+    }
+
+    Ok(trace)
+}
+
 impl<DB: Database, TM: AtomicUpsert, CL: RestClient> TransactionPoller
     for TONSubscriber<DB, TM, CL>
 {
@@ -87,64 +390,136 @@ impl<DB: Database, TM: AtomicUpsert, CL: RestClient> TransactionPoller
         &mut self,
         account_id: TonAddress,
     ) -> Result<Vec<Self::Transaction>, anyhow::Error> {
+        let streamed = match &mut self.stream {
+            Some(stream) => stream.next_traces().await,
+            None => vec![],
+        };
+        if self
+            .stream
+            .as_ref()
+            .is_some_and(|stream| !stream.should_poll())
+        {
+            let latest_mc_seqno = if streamed.is_empty() {
+                None
+            } else {
+                self.latest_mc_seqno().await?
+            };
+            return self
+                .streamed_traces(streamed, &account_id, latest_mc_seqno)
+                .await;
+        }
+
         let start_lt = if self.latest_lt == -1 {
             None
         } else {
             Some(self.latest_lt)
         };
 
-        let traces = self
+        let latest_mc_seqno = self.latest_mc_seqno().await?;
+        let window = match self
             .client
             .get_traces_for_account(Some(account_id.clone()), None, start_lt)
-            .await?;
+            .await
+        {
+            Ok(window) => window,
+            Err(err) if ToncenterError::classify(&err).kind.is_transient() => {
+                // Nothing moved, the same window is polled again next time
+                warn!(
+                    "Toncenter can't serve the traces of account {} right now: {}",
+                    account_id, err
+                );
+                return self
+                    .streamed_traces(streamed, &account_id, latest_mc_seqno)
+                    .await;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let traces = window.traces;
 
         info!("Got {} traces for account {}", traces.len(), account_id);
 
-        let max_lt = traces.iter().map(|trace| trace.end_lt).max();
+        // A truncated window is an lt-ordered prefix: traces starting after the last one we got
+        // may still be missing, so we can only move the cursor up to its start_lt.
+        let mut max_lt = if window.truncated {
+            traces.iter().map(|trace| trace.start_lt).max()
+        } else {
+            traces.iter().map(|trace| trace.end_lt).max()
+        };
+
+        // Held traces have to be fetched again, so the cursor stays before the first one
+        let (traces, pending) = self.split_final(traces, latest_mc_seqno);
+        if let Some(first_pending_lt) = pending.iter().map(|trace| trace.start_lt).min() {
+            info!(
+                "Holding {} traces of account {} until they are final",
+                pending.len(),
+                account_id
+            );
+            max_lt = max_lt.map(|max_lt| max_lt.min(first_pending_lt - 1));
+        }
 
-        if max_lt.is_some() {
-            self.latest_lt = max_lt.unwrap_or(0);
+        if let Some(max_lt) = max_lt {
+            self.latest_lt = max_lt;
             if let Err(err) = self.store_latest_height().await {
                 warn!("{:?}", err);
             }
         }
 
-        let mut unseen_traces: Vec<Trace> = Vec::new();
-
-        for trace in traces {
-            let trace_model = TONTrace::from(&trace);
-            if self
-                .ton_trace_model
-                .upsert_and_return_if_changed(trace_model)
-                .await?
-                .is_some()
-                && !trace.is_incomplete
-            {
-                debug!("Trace {} added from account {}", trace.trace_id, account_id);
-                unseen_traces.push(trace);
-            } else if trace.is_incomplete {
-                info!("Trace {} is incomplete, skipping", trace.trace_id);
-            } else {
-                info!("Trace {} already seen, skipping", trace.trace_id);
-            }
+        let mut unseen_traces = self
+            .fill_gaps(&account_id, &traces, latest_mc_seqno)
+            .await?;
+        unseen_traces.extend(self.unseen_traces(traces, &account_id).await?);
+        unseen_traces.extend(
+            self.streamed_traces(streamed, &account_id, latest_mc_seqno)
+                .await?,
+        );
+        if let Some(stream) = &mut self.stream {
+            stream.polled();
         }
 
         Ok(unseen_traces)
     }
 
-    async fn poll_tx(&mut self, _tx_hash: String) -> Result<Self::Transaction, anyhow::Error> {
-        unimplemented!();
+    /// Trace with the transaction or message `tx_hash`. It is returned even if it was seen before,
+    /// so it can be published again on demand.
+    #[tracing::instrument(skip(self))]
+    async fn poll_tx(&mut self, tx_hash: String) -> Result<Self::Transaction, anyhow::Error> {
+        let trace = trace_by_hash(
+            &self.client,
+            &tx_hash,
+            &self.relevant_accounts,
+            self.finality_depth,
+        )
+        .await?;
+
+        let trace_model = TONTrace::from(&trace).with_retries(self.retry_budget);
+        if self
+            .ton_trace_model
+            .upsert_and_return_if_changed(trace_model)
+            .await?
+            .is_none()
+        {
+            info!(
+                "Trace {} was seen before, returning it again",
+                trace.trace_id
+            );
+        }
+
+        Ok(trace)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::client::MockRestClient;
+    use crate::client::{MockRestClient, TracesWindow};
+    use crate::models::ton_account_head::{MockAccountHeads, TONAccountHead};
+    use crate::stream_client::StreamUpdate;
     use crate::test_utils::fixtures::fixture_traces;
     use crate::ton_trace::MockAtomicUpsert;
+    use crate::types::{BlockRef, MasterchainInfo};
     use mockall::predicate::eq;
     use relayer_core::database::MockDatabase;
+    use std::time::Duration;
 
     #[tokio::test]
     async fn test_subscriber_no_init_height() {
@@ -209,7 +584,10 @@ mod tests {
             })
             .returning(move |_, _, _| {
                 let txs = expected_traces.clone();
-                Ok(txs)
+                Ok(TracesWindow {
+                    traces: txs,
+                    truncated: false,
+                })
             });
     }
 
@@ -242,7 +620,12 @@ mod tests {
 
         mock_client
             .expect_get_traces_for_account()
-            .returning(move |_, _, _| Ok(traces.clone()));
+            .returning(move |_, _, _| {
+                Ok(TracesWindow {
+                    traces: traces.clone(),
+                    truncated: false,
+                })
+            });
 
         let mut mock_upsert = MockAtomicUpsert::new();
         mock_upsert
@@ -277,6 +660,597 @@ mod tests {
         assert!(result.iter().any(|t| t.trace_id == "trace_3"));
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn test_poll_account_with_stream() {
+        let mut mock_db = MockDatabase::new();
+        mock_db
+            .expect_get_latest_height()
+            .returning(|_, _| Ok(Some(0)));
+        mock_db
+            .expect_store_latest_height()
+            .returning(|_, _, _| Ok(()));
+
+        let traces = fixture_traces();
+        let polled = traces[3].clone();
+        let mut mock_client = MockRestClient::new();
+        let mut polls = 0;
+        // Once to catch up after connecting, and once after the stream dropped
+        mock_client
+            .expect_get_traces_for_account()
+            .times(2)
+            .returning(move |_, _, _| {
+                polls += 1;
+                Ok(TracesWindow {
+                    traces: if polls == 1 {
+                        vec![polled.clone()]
+                    } else {
+                        vec![]
+                    },
+                    truncated: false,
+                })
+            });
+
+        let mut mock_upsert = MockAtomicUpsert::new();
+        mock_upsert
+            .expect_upsert_and_return_if_changed()
+            .returning(|trace| Box::pin(async move { Ok(Some(trace)) }));
+
+        let (sender, receiver) = tokio::sync::mpsc::channel(10);
+        let mut subscriber = TONSubscriber::new(
+            mock_client,
+            mock_db,
+            "test-context".to_string(),
+            "test-chain".to_string(),
+            mock_upsert,
+        )
+        .await
+        .unwrap()
+        .with_stream(StreamFeed::new(receiver, Duration::from_secs(60)));
+        let account = traces[1].transactions[1].account.clone();
+
+        sender.send(StreamUpdate::Connected).await.unwrap();
+        sender
+            .send(StreamUpdate::Trace(Box::new(traces[1].clone())))
+            .await
+            .unwrap();
+        let result = subscriber.poll_account(account.clone()).await.unwrap();
+        let ids: Vec<_> = result.iter().map(|trace| trace.trace_id.clone()).collect();
+        assert_eq!(
+            ids,
+            vec![traces[3].trace_id.clone(), traces[1].trace_id.clone()]
+        );
+
+        sender
+            .send(StreamUpdate::Trace(Box::new(traces[2].clone())))
+            .await
+            .unwrap();
+        let result = subscriber.poll_account(account.clone()).await.unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].trace_id, traces[2].trace_id);
+
+        sender.send(StreamUpdate::Disconnected).await.unwrap();
+        let result = subscriber.poll_account(account).await.unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_poll_account_relevant_part_complete() {
+        let mut mock_db = MockDatabase::new();
+        mock_db
+            .expect_get_latest_height()
+            .returning(|_, _| Ok(Some(0)));
+        mock_db
+            .expect_store_latest_height()
+            .returning(|_, _, _| Ok(()));
+
+        // Both incomplete: one is only waiting for a hop after the gateway to an account that
+        // isn't ours, the other for a message sent by the gateway
+        let mut waiting_downstream = fixture_traces()[2].clone();
+        waiting_downstream.is_incomplete = true;
+        let gateway = waiting_downstream.transactions[2].account.clone();
+        let sender = waiting_downstream.transactions[0].account.clone();
+        let notifier = &mut waiting_downstream.transactions[3];
+        let mut notification = notifier.in_msg.clone().unwrap();
+        notification.hash = "notification".to_string();
+        notification.destination = Some(sender);
+        notifier.out_msgs.push(notification);
+
+        let mut waiting_on_gateway = fixture_traces()[2].clone();
+        waiting_on_gateway.is_incomplete = true;
+        waiting_on_gateway.trace_id = "waiting_on_gateway".to_string();
+        waiting_on_gateway
+            .transactions
+            .retain(|tx| !tx.hash.starts_with("8eC9fP1T"));
+
+        let traces = vec![waiting_downstream.clone(), waiting_on_gateway];
+        let mut mock_client = MockRestClient::new();
+        mock_client
+            .expect_get_traces_for_account()
+            .returning(move |_, _, _| {
+                Ok(TracesWindow {
+                    traces: traces.clone(),
+                    truncated: false,
+                })
+            });
+
+        let mut mock_upsert = MockAtomicUpsert::new();
+        mock_upsert
+            .expect_upsert_and_return_if_changed()
+            .returning(|trace| {
+                assert_eq!(trace.is_incomplete, trace.trace_id == "waiting_on_gateway");
+                Box::pin(async move { Ok(Some(trace)) })
+            });
+
+        let mut subscriber = TONSubscriber::new(
+            mock_client,
+            mock_db,
+            "test-context".to_string(),
+            "test-chain".to_string(),
+            mock_upsert,
+        )
+        .await
+        .unwrap()
+        .with_relevant_accounts(vec![gateway.clone()]);
+
+        let result = subscriber.poll_account(gateway).await.unwrap();
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].trace_id, waiting_downstream.trace_id);
+        assert!(!result[0].is_incomplete);
+    }
+
+    #[tokio::test]
+    async fn test_poll_account_cursor_complete_window() {
+        let mut mock_db = MockDatabase::new();
+        mock_db
+            .expect_get_latest_height()
+            .returning(|_, _| Ok(Some(0)));
+        mock_db
+            .expect_store_latest_height()
+            .with(eq("test-chain"), eq("test-context"), eq(40))
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        let mut mock_client = MockRestClient::new();
+        mock_client
+            .expect_get_traces_for_account()
+            .returning(move |_, _, _| {
+                Ok(TracesWindow {
+                    traces: vec![
+                        sample_trace("trace_1", 10, 40),
+                        sample_trace("trace_2", 20, 30),
+                    ],
+                    truncated: false,
+                })
+            });
+
+        let mut mock_upsert = MockAtomicUpsert::new();
+        mock_upsert
+            .expect_upsert_and_return_if_changed()
+            .returning(|trace| Box::pin(async move { Ok(Some(trace)) }));
+
+        let mut subscriber = TONSubscriber::new(
+            mock_client,
+            mock_db,
+            "test-context".to_string(),
+            "test-chain".to_string(),
+            mock_upsert,
+        )
+        .await
+        .unwrap();
+
+        let address =
+            TonAddress::from_base64_url("EQAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAM9c")
+                .unwrap();
+        let result = subscriber.poll_account(address).await.unwrap();
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(subscriber.latest_lt, 40);
+    }
+
+    #[tokio::test]
+    async fn test_poll_account_transient_error() {
+        let mut mock_db = MockDatabase::new();
+        mock_db
+            .expect_get_latest_height()
+            .returning(|_, _| Ok(Some(10)));
+        mock_db.expect_store_latest_height().never();
+
+        // Rate limited first, then a request Toncenter will never accept
+        let calls = std::sync::atomic::AtomicU32::new(0);
+        let mut mock_client = MockRestClient::new();
+        mock_client
+            .expect_get_traces_for_account()
+            .times(2)
+            .returning(move |_, _, _| {
+                let message = if calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0 {
+                    "Ratelimit exceed"
+                } else {
+                    "Invalid address"
+                };
+                Err(relayer_core::error::ClientError::BadResponse(
+                    message.to_string(),
+                ))
+            });
+
+        let mut subscriber = TONSubscriber::new(
+            mock_client,
+            mock_db,
+            "test-context".to_string(),
+            "test-chain".to_string(),
+            MockAtomicUpsert::new(),
+        )
+        .await
+        .unwrap();
+
+        let address =
+            TonAddress::from_base64_url("EQAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAM9c")
+                .unwrap();
+        let result = subscriber.poll_account(address.clone()).await.unwrap();
+        assert!(result.is_empty());
+        assert_eq!(subscriber.latest_lt, 10);
+
+        assert!(subscriber.poll_account(address).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_poll_tx_by_message_hash() {
+        let mut mock_db = MockDatabase::new();
+        mock_db
+            .expect_get_latest_height()
+            .returning(|_, _| Ok(Some(0)));
+        mock_db.expect_store_latest_height().never();
+
+        let trace = fixture_traces()[1].clone();
+        let trace_id = trace.trace_id.clone();
+        let mut mock_client = MockRestClient::new();
+        mock_client
+            .expect_get_trace_by_tx_hash()
+            .with(eq("msg_hash".to_string()))
+            .times(1)
+            .returning(|_| Ok(None));
+        mock_client
+            .expect_get_trace_by_message_hash()
+            .with(eq("msg_hash".to_string()))
+            .times(1)
+            .returning(move |_| Ok(Some(trace.clone())));
+
+        // Seen before, returned anyway
+        let mut mock_upsert = MockAtomicUpsert::new();
+        mock_upsert
+            .expect_upsert_and_return_if_changed()
+            .times(1)
+            .returning(|_| Box::pin(async { Ok(None) }));
+
+        let mut subscriber = TONSubscriber::new(
+            mock_client,
+            mock_db,
+            "test-context".to_string(),
+            "test-chain".to_string(),
+            mock_upsert,
+        )
+        .await
+        .unwrap();
+
+        let result = subscriber.poll_tx("msg_hash".to_string()).await.unwrap();
+
+        assert_eq!(result.trace_id, trace_id);
+        assert_eq!(subscriber.latest_lt, 0);
+    }
+
+    #[tokio::test]
+    async fn test_poll_tx_incomplete_trace() {
+        let mut mock_db = MockDatabase::new();
+        mock_db
+            .expect_get_latest_height()
+            .returning(|_, _| Ok(Some(0)));
+
+        let mut trace = fixture_traces()[1].clone();
+        trace.is_incomplete = true;
+        let mut mock_client = MockRestClient::new();
+        mock_client
+            .expect_get_trace_by_tx_hash()
+            .returning(move |_| Ok(Some(trace.clone())));
+
+        let mut mock_upsert = MockAtomicUpsert::new();
+        mock_upsert.expect_upsert_and_return_if_changed().never();
+
+        let mut subscriber = TONSubscriber::new(
+            mock_client,
+            mock_db,
+            "test-context".to_string(),
+            "test-chain".to_string(),
+            mock_upsert,
+        )
+        .await
+        .unwrap();
+
+        assert!(subscriber.poll_tx("tx_hash".to_string()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_poll_account_cursor_truncated_window() {
+        let mut mock_db = MockDatabase::new();
+        mock_db
+            .expect_get_latest_height()
+            .returning(|_, _| Ok(Some(0)));
+        mock_db
+            .expect_store_latest_height()
+            .with(eq("test-chain"), eq("test-context"), eq(20))
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        let mut mock_client = MockRestClient::new();
+        mock_client
+            .expect_get_traces_for_account()
+            .returning(move |_, _, _| {
+                Ok(TracesWindow {
+                    traces: vec![
+                        sample_trace("trace_1", 10, 40),
+                        sample_trace("trace_2", 20, 30),
+                    ],
+                    truncated: true,
+                })
+            });
+
+        let mut mock_upsert = MockAtomicUpsert::new();
+        mock_upsert
+            .expect_upsert_and_return_if_changed()
+            .returning(|trace| Box::pin(async move { Ok(Some(trace)) }));
+
+        let mut subscriber = TONSubscriber::new(
+            mock_client,
+            mock_db,
+            "test-context".to_string(),
+            "test-chain".to_string(),
+            mock_upsert,
+        )
+        .await
+        .unwrap();
+
+        let address =
+            TonAddress::from_base64_url("EQAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAM9c")
+                .unwrap();
+        let result = subscriber.poll_account(address).await.unwrap();
+
+        // Everything we received is still emitted, only the cursor is held back
+        assert_eq!(result.len(), 2);
+        assert_eq!(subscriber.latest_lt, 20);
+    }
+
+    #[tokio::test]
+    async fn test_backfill_does_not_move_cursor() {
+        let mut mock_db = MockDatabase::new();
+        mock_db
+            .expect_get_latest_height()
+            .returning(|_, _| Ok(Some(1000)));
+        mock_db.expect_store_latest_height().never();
+
+        let mut mock_client = MockRestClient::new();
+        mock_client.expect_get_traces_for_account().never();
+        mock_client
+            .expect_get_traces_for_account_in_range()
+            .with(mockall::predicate::always(), eq(10), eq(100))
+            .times(1)
+            .returning(|_, _, _| {
+                Ok(TracesWindow {
+                    traces: vec![
+                        sample_trace("trace_1", 10, 40),
+                        sample_trace("trace_2", 20, 30),
+                    ],
+                    truncated: true,
+                })
+            });
+        mock_client
+            .expect_get_traces_for_account_in_range()
+            .with(mockall::predicate::always(), eq(20), eq(100))
+            .times(1)
+            .returning(|_, _, _| {
+                Ok(TracesWindow {
+                    traces: vec![
+                        sample_trace("trace_2", 20, 30),
+                        sample_trace("trace_3", 50, 60),
+                    ],
+                    truncated: false,
+                })
+            });
+
+        let mut mock_upsert = MockAtomicUpsert::new();
+        let stored = std::sync::Mutex::new(std::collections::HashSet::new());
+        mock_upsert
+            .expect_upsert_and_return_if_changed()
+            .returning(move |trace| {
+                let changed = stored
+                    .lock()
+                    .unwrap()
+                    .insert(trace.trace_id.clone())
+                    .then_some(trace);
+                Box::pin(async move { Ok(changed) })
+            });
+
+        let subscriber = TONSubscriber::new(
+            mock_client,
+            mock_db,
+            "test-context".to_string(),
+            "test-chain".to_string(),
+            mock_upsert,
+        )
+        .await
+        .unwrap();
+
+        let address =
+            TonAddress::from_base64_url("EQAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAM9c")
+                .unwrap();
+        let mut pages: Vec<Vec<String>> = vec![];
+        let published = subscriber
+            .backfill(address, 10, 100, |traces| {
+                pages.push(traces.into_iter().map(|t| t.trace_id).collect());
+                async { Ok(()) }
+            })
+            .await
+            .unwrap();
+
+        // Published page by page, trace_2 only once
+        assert_eq!(published, 3);
+        assert_eq!(pages, vec![vec!["trace_1", "trace_2"], vec!["trace_3"]]);
+        assert_eq!(subscriber.latest_lt, 1000);
+    }
+
+    #[tokio::test]
+    async fn test_poll_account_backfills_gap() {
+        // Trace with one transaction on the gateway, pointing back to prev
+        let trace = |hash: &str, lt: i64, prev_hash: &str, prev_lt: i64| {
+            let mut trace = fixture_traces()[1].clone();
+            let mut tx = trace.transactions[0].clone();
+            tx.hash = hash.to_string();
+            tx.lt = lt;
+            tx.prev_trans_hash = prev_hash.to_string();
+            tx.prev_trans_lt = prev_lt.to_string();
+            trace.trace_id = format!("trace-{hash}");
+            trace.start_lt = lt;
+            trace.end_lt = lt;
+            trace.transactions = vec![tx];
+            trace
+        };
+        let account = fixture_traces()[1].transactions[0].account.clone();
+
+        let mut mock_db = MockDatabase::new();
+        mock_db
+            .expect_get_latest_height()
+            .returning(|_, _| Ok(Some(25)));
+        mock_db
+            .expect_store_latest_height()
+            .returning(|_, _, _| Ok(()));
+
+        let mut mock_client = MockRestClient::new();
+        let polled = trace("c", 30, "b", 20);
+        mock_client
+            .expect_get_traces_for_account()
+            .returning(move |_, _, _| {
+                Ok(TracesWindow {
+                    traces: vec![polled.clone()],
+                    truncated: false,
+                })
+            });
+        let missed = trace("b", 20, "a", 10);
+        mock_client
+            .expect_get_traces_for_account_in_range()
+            .with(mockall::predicate::always(), eq(11), eq(20))
+            .times(1)
+            .returning(move |_, _, _| {
+                Ok(TracesWindow {
+                    traces: vec![missed.clone()],
+                    truncated: false,
+                })
+            });
+
+        let mut mock_upsert = MockAtomicUpsert::new();
+        mock_upsert
+            .expect_upsert_and_return_if_changed()
+            .returning(|trace| Box::pin(async move { Ok(Some(trace)) }));
+
+        let mut mock_heads = MockAccountHeads::new();
+        let hex = account.to_hex();
+        mock_heads.expect_get_head().returning(move |account| {
+            Ok(Some(TONAccountHead {
+                account: account.to_string(),
+                tx_hash: "a".to_string(),
+                tx_lt: 10,
+                updated_at: chrono::Utc::now(),
+            }))
+        });
+        mock_heads
+            .expect_set_head()
+            .withf(move |account, tx_hash, tx_lt| *account == hex && tx_hash == "c" && *tx_lt == 30)
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        let mut subscriber = TONSubscriber::new(
+            mock_client,
+            mock_db,
+            "test-context".to_string(),
+            "test-chain".to_string(),
+            mock_upsert,
+        )
+        .await
+        .unwrap()
+        .with_account_heads(Arc::new(mock_heads));
+
+        let result = subscriber.poll_account(account).await.unwrap();
+
+        let trace_ids: Vec<&str> = result.iter().map(|t| t.trace_id.as_str()).collect();
+        assert_eq!(trace_ids, vec!["trace-b", "trace-c"]);
+    }
+
+    #[tokio::test]
+    async fn test_poll_account_holds_traces_until_final() {
+        let mut mock_db = MockDatabase::new();
+        mock_db
+            .expect_get_latest_height()
+            .returning(|_, _| Ok(Some(0)));
+        mock_db
+            .expect_store_latest_height()
+            .with(eq("test-chain"), eq("test-context"), eq(19))
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        let mut pending = fixture_traces()[1].clone();
+        pending.trace_id = "trace_2".to_string();
+        pending.start_lt = 20;
+        pending.end_lt = 30;
+        for tx in pending.transactions.iter_mut() {
+            tx.mc_block_seqno = 99;
+        }
+
+        let mut mock_client = MockRestClient::new();
+        mock_client.expect_get_masterchain_info().returning(|| {
+            let block = |seqno| BlockRef {
+                workchain: -1,
+                shard: "8000000000000000".to_string(),
+                seqno,
+            };
+            Ok(MasterchainInfo {
+                first: block(1),
+                last: block(100),
+            })
+        });
+        mock_client
+            .expect_get_traces_for_account()
+            .returning(move |_, _, _| {
+                Ok(TracesWindow {
+                    traces: vec![sample_trace("trace_1", 10, 40), pending.clone()],
+                    truncated: false,
+                })
+            });
+
+        let mut mock_upsert = MockAtomicUpsert::new();
+        mock_upsert
+            .expect_upsert_and_return_if_changed()
+            .withf(|trace| trace.trace_id == "trace_1")
+            .times(1)
+            .returning(|trace| Box::pin(async move { Ok(Some(trace)) }));
+
+        let mut subscriber = TONSubscriber::new(
+            mock_client,
+            mock_db,
+            "test-context".to_string(),
+            "test-chain".to_string(),
+            mock_upsert,
+        )
+        .await
+        .unwrap()
+        .with_finality_depth(2);
+
+        let address =
+            TonAddress::from_base64_url("EQAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAM9c")
+                .unwrap();
+        let result = subscriber.poll_account(address).await.unwrap();
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].trace_id, "trace_1");
+        assert_eq!(subscriber.latest_lt, 19);
+    }
+
     #[test]
     fn test_transaction_id() {
         let mock_db = MockDatabase::new();
@@ -290,6 +1264,11 @@ mod tests {
             context: "test-context".to_string(),
             chain_name: "test-chain".to_string(),
             ton_trace_model: mock_upsert,
+            account_heads: None,
+            finality_depth: None,
+            retry_budget: DEFAULT_TRACE_RETRIES,
+            relevant_accounts: vec![],
+            stream: None,
         };
 
         let trace = sample_trace("test-trace-id", 1, 2);
@@ -311,6 +1290,11 @@ mod tests {
             context: "test-context".to_string(),
             chain_name: "test-chain".to_string(),
             ton_trace_model: mock_upsert,
+            account_heads: None,
+            finality_depth: None,
+            retry_budget: DEFAULT_TRACE_RETRIES,
+            relevant_accounts: vec![],
+            stream: None,
         };
 
         let address =
@@ -4,7 +4,20 @@ pub(crate) mod fixtures {
     use std::fs;
 
     pub fn fixture_traces() -> Vec<Trace> {
-        let file_path = "tests/data/v3_traces.json";
+        traces_from_file("tests/data/v3_traces.json")
+    }
+
+    /// Manually constructed ITS traces, kept apart so `fixture_traces` stays one page of 25.
+    pub fn fixture_its_traces() -> Vec<Trace> {
+        traces_from_file("tests/data/v3_traces_its.json")
+    }
+
+    /// Manually constructed traces of failed executions.
+    pub fn fixture_reverted_traces() -> Vec<Trace> {
+        traces_from_file("tests/data/v3_traces_reverted.json")
+    }
+
+    fn traces_from_file(file_path: &str) -> Vec<Trace> {
         let body = fs::read(file_path).expect("Failed to read JSON test file");
         let clean_text = crate::client::clean_json_string_full(&body);
 
@@ -21,6 +21,23 @@ pub const OP_INTERCHAIN_TRANSFER_LOG: u32 = 0x00000110;
 pub const OP_SIGNERS_ROTATED_LOG: u32 = 0x0000002A;
 
 pub const EXIT_CODE_INSUFFICIENT_GAS: u32 = 106;
+// Highload wallet v3 rejecting an external message
+pub const EXIT_CODE_HIGHLOAD_INVALID_SIGNATURE: u32 = 33;
+pub const EXIT_CODE_HIGHLOAD_INVALID_CREATED_AT: u32 = 35;
+pub const EXIT_CODE_HIGHLOAD_ALREADY_EXECUTED: u32 = 36;
+
+// Get-methods
+pub const GET_METHOD_GATEWAY_OPERATOR: &str = "get_operator";
+pub const GET_METHOD_GATEWAY_EPOCH: &str = "get_epoch";
+pub const GET_METHOD_GAS_SERVICE_GAS_COLLECTOR: &str = "get_gas_collector";
+pub const GET_METHOD_ITS_OPERATOR: &str = "get_operator";
+pub const GET_METHOD_JETTON_DATA: &str = "get_jetton_data";
+pub const GET_METHOD_JETTON_WALLET_ADDRESS: &str = "get_wallet_address";
+pub const GET_METHOD_HIGHLOAD_PUBLIC_KEY: &str = "get_public_key";
+pub const GET_METHOD_HIGHLOAD_SUBWALLET_ID: &str = "get_subwallet_id";
+pub const GET_METHOD_HIGHLOAD_TIMEOUT: &str = "get_timeout";
+pub const GET_METHOD_HIGHLOAD_LAST_CLEAN_TIME: &str = "get_last_clean_time";
+pub const GET_METHOD_HIGHLOAD_PROCESSED: &str = "processed?";
 
 pub const SEND_MODE_ORDINARY_MESSAGE: u8 = 0;
 pub const SEND_MODE_CARRY_VALUE: u8 = 64;
@@ -23,9 +23,13 @@ let wallet = TonWalletHighLoadV3::new(address, key_pair, 698983, 60 * 60);
 let boc = wallet.outgoing_message(&actions, 12345, BigUint::from(100u32));
 // send using reqwest ...
 ```
-# TODO
 
-- Ability to repack more than 254 messages.
+# Packing
+
+One internal transfer carries at most 254 actions. Longer lists are packed the way the reference
+wrapper does it: the first 253 actions go out directly and the last action is another internal
+transfer to the wallet itself, carrying the rest. Every extra hop is a separate transaction of the
+wallet, see `internal_transfer_hops`.
 
 # Notes
 
@@ -39,7 +43,7 @@ to rewrite it so it can become a part of the tonlib core library.
 */
 
 use crate::error::BocError;
-use crate::ton_constants::SEND_MODE_IGNORE_ERRORS;
+use crate::ton_constants::{SEND_MODE_IGNORE_ERRORS, SEND_MODE_PAY_TRANSFER_FEES_SEPARATELY};
 use nacl::sign::signature;
 use num_bigint::{BigInt, BigUint};
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -48,11 +52,22 @@ use tonlib_core::message::{InternalMessage, TonMessage, TonMessageError, Transfe
 use tonlib_core::tlb_types::block::coins::Grams;
 use tonlib_core::tlb_types::block::message::{CommonMsgInfo, ExtInMsgInfo, Message};
 use tonlib_core::tlb_types::block::msg_address::{MsgAddrNone, MsgAddressExt};
-use tonlib_core::tlb_types::block::out_action::{OutAction, OutList};
+use tonlib_core::tlb_types::block::out_action::{OutAction, OutActionSendMsg, OutList};
 use tonlib_core::tlb_types::tlb::TLB;
 use tonlib_core::wallet::mnemonic::KeyPair;
 use tonlib_core::TonAddress;
 
+pub const MAX_ACTIONS_PER_TRANSFER: usize = 254;
+
+/// Number of internal transfers to self needed on top of the first one to send `num_actions`.
+pub fn internal_transfer_hops(num_actions: usize) -> usize {
+    if num_actions <= MAX_ACTIONS_PER_TRANSFER {
+        0
+    } else {
+        (num_actions - MAX_ACTIONS_PER_TRANSFER).div_ceil(MAX_ACTIONS_PER_TRANSFER - 1)
+    }
+}
+
 #[derive(Debug)]
 pub struct SystemTimeProvider;
 
@@ -96,8 +111,10 @@ impl<T: TimeProvider> TonWalletHighLoadV3<T> {
         actions: &[OutAction],
         query_id: u64,
     ) -> anyhow::Result<Cell, TonCellError> {
-        if actions.len() > 254 {
-            panic!("Max allowed action count is 254. Use pack_actions instead.");
+        if actions.len() > MAX_ACTIONS_PER_TRANSFER {
+            return Err(TonCellError::InternalError(format!(
+                "Max allowed action count is {MAX_ACTIONS_PER_TRANSFER}. Use pack_actions instead."
+            )));
         }
 
         let mut builder = CellBuilder::new();
@@ -134,6 +151,31 @@ impl<T: TimeProvider> TonWalletHighLoadV3<T> {
         TransferMessage::new(im, internal_transfer_body.to_arc()).build()
     }
 
+    /// Builds the internal transfer to self carrying `actions`, chaining further transfers to self
+    /// when there are more than fit into one.
+    pub fn pack_actions(
+        &self,
+        actions: &[OutAction],
+        query_id: u64,
+        internal_message_value: BigUint,
+    ) -> Result<Cell, TonMessageError> {
+        let internal_transfer_body = if actions.len() > MAX_ACTIONS_PER_TRANSFER {
+            let (batch, rest) = actions.split_at(MAX_ACTIONS_PER_TRANSFER - 1);
+            let next = self.pack_actions(rest, query_id, internal_message_value.clone())?;
+
+            let mut batch = batch.to_vec();
+            batch.push(OutAction::SendMsg(OutActionSendMsg {
+                mode: SEND_MODE_PAY_TRANSFER_FEES_SEPARATELY,
+                out_msg: next.to_arc(),
+            }));
+            self.internal_transfer_body(&batch, query_id)?
+        } else {
+            self.internal_transfer_body(actions, query_id)?
+        };
+
+        self.internal_transfer_message_cell(internal_transfer_body, internal_message_value)
+    }
+
     fn message_inner(
         &self,
         message: Cell,
@@ -178,15 +220,8 @@ impl<T: TimeProvider> TonWalletHighLoadV3<T> {
         query_id: u64,
         internal_message_value: BigUint,
     ) -> Result<BagOfCells, BocError> {
-        let internal_transfer_body =
-            self.internal_transfer_body(actions, query_id)
-                .map_err(|e| {
-                    BocError::BocEncodingError(format!(
-                        "Failed constructing internal transfer body: {e}"
-                    ))
-                })?;
         let internal_transfer = self
-            .internal_transfer_message_cell(internal_transfer_body, internal_message_value)
+            .pack_actions(actions, query_id, internal_message_value)
             .map_err(|e| {
                 BocError::BocEncodingError(format!(
                     "Failed constructing internal transfer message: {e}"
@@ -315,11 +350,66 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Max allowed action count is 254")]
     fn test_internal_transfer_body_too_many_actions() {
         let actions = vec![mock_out_action(); 255];
         let wallet = TonWalletHighLoadV3::new(mock_address(), mock_keypair(), 698983, 600);
-        let _ = wallet.internal_transfer_body(&actions, 1);
+        let err = wallet.internal_transfer_body(&actions, 1).unwrap_err();
+        assert!(err.to_string().contains("Max allowed action count is 254"));
+    }
+
+    #[test]
+    fn test_pack_actions_single_transfer() {
+        let actions = vec![mock_out_action(); 254];
+        let wallet = TonWalletHighLoadV3::new(mock_address(), mock_keypair(), 698983, 600);
+        let packed = wallet
+            .pack_actions(&actions, 42, BigUint::from(100u32))
+            .unwrap();
+        let body = wallet.internal_transfer_body(&actions, 42).unwrap();
+        let expected = wallet
+            .internal_transfer_message_cell(body, BigUint::from(100u32))
+            .unwrap();
+        assert_eq!(packed, expected);
+    }
+
+    #[test]
+    fn test_pack_actions_chains_transfers() {
+        let wallet = TonWalletHighLoadV3::new(mock_address(), mock_keypair(), 698983, 600);
+        let value = BigUint::from(100u32);
+
+        let rest = vec![mock_out_action(); 3];
+        let inner = wallet.pack_actions(&rest, 42, value.clone()).unwrap();
+
+        let mut batch = vec![mock_out_action(); 253];
+        batch.push(OutAction::SendMsg(OutActionSendMsg {
+            mode: SEND_MODE_PAY_TRANSFER_FEES_SEPARATELY,
+            out_msg: inner.to_arc(),
+        }));
+        let body = wallet.internal_transfer_body(&batch, 42).unwrap();
+        let expected = wallet
+            .internal_transfer_message_cell(body, value.clone())
+            .unwrap();
+
+        let actions = vec![mock_out_action(); 256];
+        let packed = wallet.pack_actions(&actions, 42, value).unwrap();
+        assert_eq!(packed, expected);
+    }
+
+    #[test]
+    fn test_outgoing_message_many_actions() {
+        let wallet = TonWalletHighLoadV3::new(mock_address(), mock_keypair(), 321, 500);
+        let actions = vec![mock_out_action(); 1000];
+        assert!(wallet
+            .outgoing_message(&actions, 42, BigUint::from(999u32))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_internal_transfer_hops() {
+        assert_eq!(internal_transfer_hops(0), 0);
+        assert_eq!(internal_transfer_hops(254), 0);
+        assert_eq!(internal_transfer_hops(255), 1);
+        assert_eq!(internal_transfer_hops(507), 1);
+        assert_eq!(internal_transfer_hops(508), 2);
     }
 
     #[test]
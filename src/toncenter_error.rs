@@ -0,0 +1,335 @@
+/*!
+
+Classification of the errors returned by Toncenter.
+
+`ClientError` only carries a message, so what went wrong is derived from it: the status Toncenter
+answered with, the `error` of its response body, and, when an external message was rejected, the
+exit code in the VM log (e.g. `exitcode=36` or `THROWIF 36` for a highload wallet v3 query id that
+was already used).
+
+Errors that every endpoint would return the same way, like a rejected message or a malformed
+request, are turned into `ClientError::BadRequest`, the other ones into `ClientError::BadResponse`.
+The message of a `BadResponse` starts with the kind (e.g. `Unavailable: Unexpected status 503: ...`),
+since a kind derived from the status alone can't be told from the text. Callers use
+[`ToncenterError::classify`] to act on the kind of an error instead of its text.
+
+*/
+
+use crate::client::V3ErrorResponse;
+use crate::ton_constants::{
+    EXIT_CODE_HIGHLOAD_ALREADY_EXECUTED, EXIT_CODE_HIGHLOAD_INVALID_CREATED_AT,
+    EXIT_CODE_HIGHLOAD_INVALID_SIGNATURE,
+};
+use relayer_core::error::ClientError;
+use reqwest::StatusCode;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToncenterErrorKind {
+    /// The highload wallet already executed a message with the query id
+    QueryIdAlreadyUsed,
+    /// The message was created outside of the wallet's timeout window
+    MessageExpired,
+    InsufficientBalance,
+    InvalidSignature,
+    /// The contract rejected the external message for another reason
+    MessageRejected,
+    RateLimited,
+    /// The indexer or liteserver is behind and can't answer yet
+    IndexerNotSynced,
+    NotFound,
+    /// Malformed request, e.g. an invalid BOC or address
+    InvalidRequest,
+    Unavailable,
+    Unknown,
+}
+
+impl ToncenterErrorKind {
+    /// Whether the same request can succeed later or on another endpoint.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            ToncenterErrorKind::RateLimited
+                | ToncenterErrorKind::IndexerNotSynced
+                | ToncenterErrorKind::Unavailable
+        )
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        let kind = match name {
+            "QueryIdAlreadyUsed" => ToncenterErrorKind::QueryIdAlreadyUsed,
+            "MessageExpired" => ToncenterErrorKind::MessageExpired,
+            "InsufficientBalance" => ToncenterErrorKind::InsufficientBalance,
+            "InvalidSignature" => ToncenterErrorKind::InvalidSignature,
+            "MessageRejected" => ToncenterErrorKind::MessageRejected,
+            "RateLimited" => ToncenterErrorKind::RateLimited,
+            "IndexerNotSynced" => ToncenterErrorKind::IndexerNotSynced,
+            "NotFound" => ToncenterErrorKind::NotFound,
+            "InvalidRequest" => ToncenterErrorKind::InvalidRequest,
+            "Unavailable" => ToncenterErrorKind::Unavailable,
+            "Unknown" => ToncenterErrorKind::Unknown,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// Whether every endpoint would reject the request the same way.
+    pub fn is_rejection(&self) -> bool {
+        matches!(
+            self,
+            ToncenterErrorKind::QueryIdAlreadyUsed
+                | ToncenterErrorKind::MessageExpired
+                | ToncenterErrorKind::InsufficientBalance
+                | ToncenterErrorKind::InvalidSignature
+                | ToncenterErrorKind::MessageRejected
+                | ToncenterErrorKind::InvalidRequest
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToncenterError {
+    pub kind: ToncenterErrorKind,
+    /// Exit code the contract rejected an external message with
+    pub exit_code: Option<i32>,
+    pub message: String,
+}
+
+impl fmt::Display for ToncenterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.kind, self.message)
+    }
+}
+
+impl std::error::Error for ToncenterError {}
+
+impl ToncenterError {
+    pub fn from_response(status: StatusCode, body: &str) -> Self {
+        let error_body = serde_json::from_str::<V3ErrorResponse>(body).ok();
+        let message = match &error_body {
+            Some(error_body) => error_body.error.clone(),
+            None => format!("Unexpected status {status}: {body}"),
+        };
+
+        let mut error = Self::from_message(message);
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            error.kind = ToncenterErrorKind::RateLimited;
+        } else if error.kind == ToncenterErrorKind::Unknown {
+            error.kind = match status {
+                StatusCode::NOT_FOUND => ToncenterErrorKind::NotFound,
+                StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY
+                    if error_body.is_some() =>
+                {
+                    ToncenterErrorKind::InvalidRequest
+                }
+                status if status.is_server_error() => ToncenterErrorKind::Unavailable,
+                _ => ToncenterErrorKind::Unknown,
+            };
+        }
+        error
+    }
+
+    /// Kind of an error returned by a `RestClient`.
+    pub fn classify(error: &ClientError) -> Self {
+        match error {
+            ClientError::ConnectionFailed(message) => Self {
+                kind: ToncenterErrorKind::Unavailable,
+                exit_code: None,
+                message: message.clone(),
+            },
+            ClientError::BadRequest(message) => {
+                let mut error = Self::from_message(message.clone());
+                if error.kind == ToncenterErrorKind::Unknown {
+                    error.kind = ToncenterErrorKind::InvalidRequest;
+                }
+                error
+            }
+            ClientError::BadResponse(message) => {
+                Self::from_tagged(message).unwrap_or_else(|| Self::from_message(error.to_string()))
+            }
+            other => Self::from_message(other.to_string()),
+        }
+    }
+
+    /// The error a `BadResponse` was created from, with the kind it starts with.
+    fn from_tagged(message: &str) -> Option<Self> {
+        let (kind, message) = message.split_once(": ")?;
+        let kind = ToncenterErrorKind::from_name(kind)?;
+        Some(Self {
+            kind,
+            ..Self::from_message(message.to_string())
+        })
+    }
+
+    fn from_message(message: String) -> Self {
+        let lowercase = message.to_lowercase();
+        let exit_code = exit_code(&message);
+
+        let kind = match exit_code {
+            Some(code) if code == EXIT_CODE_HIGHLOAD_ALREADY_EXECUTED as i32 => {
+                ToncenterErrorKind::QueryIdAlreadyUsed
+            }
+            Some(code) if code == EXIT_CODE_HIGHLOAD_INVALID_CREATED_AT as i32 => {
+                ToncenterErrorKind::MessageExpired
+            }
+            Some(code) if code == EXIT_CODE_HIGHLOAD_INVALID_SIGNATURE as i32 => {
+                ToncenterErrorKind::InvalidSignature
+            }
+            _ if [
+                "not enough balance",
+                "insufficient balance",
+                "not enough ton",
+            ]
+            .iter()
+            .any(|text| lowercase.contains(text)) =>
+            {
+                ToncenterErrorKind::InsufficientBalance
+            }
+            Some(_) => ToncenterErrorKind::MessageRejected,
+            None if lowercase.contains("ratelimit") || lowercase.contains("rate limit") => {
+                ToncenterErrorKind::RateLimited
+            }
+            None if ["not synced", "not in sync", "notready", "not ready"]
+                .iter()
+                .any(|text| lowercase.contains(text)) =>
+            {
+                ToncenterErrorKind::IndexerNotSynced
+            }
+            None if lowercase.contains("external message was not accepted") => {
+                ToncenterErrorKind::MessageRejected
+            }
+            None => ToncenterErrorKind::Unknown,
+        };
+
+        Self {
+            kind,
+            exit_code,
+            message,
+        }
+    }
+}
+
+impl From<ToncenterError> for ClientError {
+    fn from(error: ToncenterError) -> Self {
+        if error.kind.is_rejection() {
+            ClientError::BadRequest(error.message)
+        } else {
+            ClientError::BadResponse(error.to_string())
+        }
+    }
+}
+
+/// Exit code in the VM log of a rejected external message.
+fn exit_code(message: &str) -> Option<i32> {
+    [
+        "exitcode=",
+        "exit code ",
+        "THROWIFNOT ",
+        "THROWIF ",
+        "THROW ",
+    ]
+    .iter()
+    .find_map(|prefix| {
+        let (_, rest) = message.split_once(prefix)?;
+        let end = rest
+            .char_indices()
+            .find(|(i, c)| !(c.is_ascii_digit() || (*i == 0 && *c == '-')))
+            .map_or(rest.len(), |(i, _)| i);
+        rest.get(..end)?.parse().ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_id_already_used() {
+        let body = r#"{"code": 500, "error": "LITE_SERVER_UNKNOWN: cannot apply external message to current state : External message was not accepted\nCannot run message on account: inbound external message rejected by transaction 9D3A: \nexitcode=36, steps=40, gas_used=0\nVM Log (truncated):\n...execute THROWIF 36\ndefault exception handler, terminating vm with exit code 36\n"}"#;
+        let error = ToncenterError::from_response(StatusCode::INTERNAL_SERVER_ERROR, body);
+
+        assert_eq!(error.kind, ToncenterErrorKind::QueryIdAlreadyUsed);
+        assert_eq!(error.exit_code, Some(36));
+        assert!(matches!(
+            ClientError::from(error),
+            ClientError::BadRequest(_)
+        ));
+
+        // Also when only the VM log made it into the message
+        let error = ToncenterError::classify(&ClientError::BadResponse(
+            "THROWIF 36 error occurred".to_string(),
+        ));
+        assert_eq!(error.kind, ToncenterErrorKind::QueryIdAlreadyUsed);
+    }
+
+    #[test]
+    fn test_rejected_message_exit_codes() {
+        let rejected = |code: i32| {
+            ToncenterError::classify(&ClientError::BadRequest(format!(
+                "External message was not accepted\nexitcode={code}, steps=12"
+            )))
+            .kind
+        };
+
+        assert_eq!(rejected(33), ToncenterErrorKind::InvalidSignature);
+        assert_eq!(rejected(35), ToncenterErrorKind::MessageExpired);
+        assert_eq!(rejected(37), ToncenterErrorKind::MessageRejected);
+        assert_eq!(rejected(-14), ToncenterErrorKind::MessageRejected);
+    }
+
+    #[test]
+    fn test_statuses() {
+        let error = ToncenterError::from_response(
+            StatusCode::TOO_MANY_REQUESTS,
+            r#"{"code": 429, "error": "Ratelimit exceed"}"#,
+        );
+        assert_eq!(error.kind, ToncenterErrorKind::RateLimited);
+        assert!(error.kind.is_transient());
+
+        let error = ToncenterError::from_response(
+            StatusCode::BAD_REQUEST,
+            r#"{"code": 400, "error": "Invalid BOC format"}"#,
+        );
+        assert_eq!(error.kind, ToncenterErrorKind::InvalidRequest);
+        assert_eq!(
+            ClientError::from(error).to_string(),
+            ClientError::BadRequest("Invalid BOC format".to_string()).to_string()
+        );
+
+        let error = ToncenterError::from_response(StatusCode::BAD_GATEWAY, "bad gateway");
+        assert_eq!(error.kind, ToncenterErrorKind::Unavailable);
+        let client_error = ClientError::from(error);
+        assert!(matches!(client_error, ClientError::BadResponse(_)));
+        // The kind came from the status only, it survives the conversion
+        assert_eq!(
+            ToncenterError::classify(&client_error).kind,
+            ToncenterErrorKind::Unavailable
+        );
+
+        let error = ToncenterError::from_response(StatusCode::TOO_MANY_REQUESTS, "slow down");
+        assert_eq!(
+            ToncenterError::classify(&ClientError::from(error)).kind,
+            ToncenterErrorKind::RateLimited
+        );
+
+        let error = ToncenterError::from_response(
+            StatusCode::CONFLICT,
+            r#"{"code": 409, "error": "masterchain block 123 is not synced yet"}"#,
+        );
+        assert_eq!(error.kind, ToncenterErrorKind::IndexerNotSynced);
+
+        let error = ToncenterError::from_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            r#"{"code": 500, "error": "cannot send message: not enough balance to process message"}"#,
+        );
+        assert_eq!(error.kind, ToncenterErrorKind::InsufficientBalance);
+        assert!(error.kind.is_rejection());
+    }
+
+    #[test]
+    fn test_connection_failed_is_transient() {
+        let error = ToncenterError::classify(&ClientError::ConnectionFailed("timeout".to_string()));
+        assert_eq!(error.kind, ToncenterErrorKind::Unavailable);
+        assert!(error.kind.is_transient());
+    }
+}
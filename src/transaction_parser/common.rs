@@ -1,5 +1,8 @@
+use crate::boc::call_contract_request::CallContractRequest;
 use crate::error::TONRpcError::DataError;
 use crate::error::{GasError, TONRpcError, TransactionParsingError};
+use crate::ton_constants::OP_CALL_CONTRACT;
+use crate::transaction_parser::message_matching_key::MessageMatchingKey;
 use crate::types::Transaction;
 use base64::prelude::BASE64_STANDARD;
 use base64::Engine;
@@ -32,6 +35,39 @@ pub fn is_log_emitted(tx: &Transaction, op_code: u32) -> Result<isize, Transacti
     Ok(-1)
 }
 
+/// Key of the call contract request the transaction sends to the gateway, e.g. the hub message
+/// ITS sends along with its log. It equals the key of the gateway's call contract log for it.
+pub fn call_contract_request_key(
+    tx: &Transaction,
+) -> Result<MessageMatchingKey, TransactionParsingError> {
+    let msg = tx
+        .out_msgs
+        .iter()
+        .find(|msg| msg.destination.is_some() && msg.opcode == Some(OP_CALL_CONTRACT))
+        .ok_or_else(|| {
+            TransactionParsingError::Message(format!(
+                "No call contract request in transaction {}",
+                tx.hash
+            ))
+        })?;
+
+    let request = CallContractRequest::from_boc_b64(&msg.message_content.body)
+        .map_err(|e| TransactionParsingError::BocParsing(e.to_string()))?;
+
+    Ok(MessageMatchingKey {
+        payload_hash: request.payload_hash(),
+        destination_chain: request.destination_chain,
+        destination_address: request.destination_address,
+    })
+}
+
+/// On-chain time of the transaction, in the format events carry it.
+pub fn tx_timestamp(tx: &Transaction) -> String {
+    chrono::DateTime::from_timestamp(tx.now as i64, 0)
+        .unwrap_or_default()
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
 pub fn hash_to_message_id(hash: &str) -> Result<String, TONRpcError> {
     let hash = BASE64_STANDARD
         .decode(hash)
@@ -52,11 +88,11 @@ where
     let coin_to_usd = price_view
         .get_price(&coin_pair)
         .await
-        .map_err(|err| GasError::ConversionError(err.to_string()))?;
+        .map_err(|err| GasError::PriceUnavailable(err.to_string()))?;
     let ton_to_usd = price_view
         .get_price("TON/USD")
         .await
-        .map_err(|err| GasError::ConversionError(err.to_string()))?;
+        .map_err(|err| GasError::PriceUnavailable(err.to_string()))?;
 
     let amount = Decimal::from_str(&amount.to_string())
         .map_err(|e| GasError::ConversionError(format!("Invalid amount: {e}")))?;
@@ -0,0 +1,212 @@
+//! Follows a message through the transactions it leads to, so that a failure can be attributed to
+//! the message that caused it rather than to anything else happening in the same trace.
+
+use crate::ton_constants::{
+    EXIT_CODE_INSUFFICIENT_GAS, OP_APPROVE_MESSAGES, OP_GATEWAY_EXECUTE, OP_MESSAGE_APPROVE,
+    OP_MESSAGE_APPROVED, OP_NATIVE_REFUND, OP_NULLIFIED_SUCCESSFULLY, OP_NULLIFY_IF_APPROVED,
+    OP_RELAYER_EXECUTE,
+};
+use crate::types::{Trace, Transaction};
+use relayer_core::gmp_api::gmp_types::CannotExecuteMessageReason;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Action phase result code when the account can't pay for the messages it sends.
+const RESULT_CODE_NOT_ENOUGH_FUNDS: i32 = 37;
+
+/// In-message opcodes of the transactions an execution goes through, from the relayer to the
+/// destination contract. Failures elsewhere in the trace (e.g. a contract rejecting excesses)
+/// don't affect the execution.
+pub(crate) const EXECUTION_OPCODES: [u32; 4] = [
+    OP_RELAYER_EXECUTE,
+    OP_NULLIFY_IF_APPROVED,
+    OP_NULLIFIED_SUCCESSFULLY,
+    OP_GATEWAY_EXECUTE,
+];
+
+/// In-message opcodes of the transactions an approval goes through, from the relayer to the
+/// message contracts and back to the gateway.
+pub(crate) const APPROVAL_OPCODES: [u32; 3] =
+    [OP_APPROVE_MESSAGES, OP_MESSAGE_APPROVE, OP_MESSAGE_APPROVED];
+
+/// In-message opcodes of the transactions a refund goes through. Only the gas service matters,
+/// the refunded address may reject the coins on its own.
+pub(crate) const REFUND_OPCODES: [u32; 1] = [OP_NATIVE_REFUND];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum FailedPhase {
+    Compute(i32),
+    Action(i32),
+    // The message could not be processed at all, e.g. the account is not deployed
+    ComputeSkipped,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Failure<'a> {
+    pub(crate) tx: &'a Transaction,
+    pub(crate) phase: FailedPhase,
+}
+
+impl<'a> Failure<'a> {
+    pub(crate) fn of(tx: &'a Transaction) -> Option<Self> {
+        let description = &tx.description;
+        if let Some(compute) = &description.compute_ph {
+            if compute.skipped && description.aborted {
+                return Some(Self {
+                    tx,
+                    phase: FailedPhase::ComputeSkipped,
+                });
+            }
+            if !compute.skipped && compute.success == Some(false) {
+                return Some(Self {
+                    tx,
+                    phase: FailedPhase::Compute(compute.exit_code.unwrap_or(-1)),
+                });
+            }
+        }
+        if let Some(action) = &description.action {
+            if action.success == Some(false) {
+                return Some(Self {
+                    tx,
+                    phase: FailedPhase::Action(action.result_code),
+                });
+            }
+        }
+        None
+    }
+
+    pub(crate) fn exit_code(&self) -> Option<i32> {
+        match self.phase {
+            FailedPhase::Compute(code) | FailedPhase::Action(code) => Some(code),
+            FailedPhase::ComputeSkipped => None,
+        }
+    }
+
+    pub(crate) fn reason(&self) -> CannotExecuteMessageReason {
+        match self.phase {
+            FailedPhase::Compute(code) if code == EXIT_CODE_INSUFFICIENT_GAS as i32 => {
+                CannotExecuteMessageReason::InsufficientGas
+            }
+            FailedPhase::Action(RESULT_CODE_NOT_ENOUGH_FUNDS) => {
+                CannotExecuteMessageReason::InsufficientGas
+            }
+            _ => CannotExecuteMessageReason::Error,
+        }
+    }
+
+    pub(crate) fn details(&self) -> HashMap<String, String> {
+        let mut details = HashMap::from([
+            ("failing_account".to_owned(), self.tx.account.to_hex()),
+            ("failing_tx".to_owned(), self.tx.hash.clone()),
+        ]);
+        if let Some(exit_code) = self.exit_code() {
+            details.insert("exit_code".to_owned(), exit_code.to_string());
+        }
+        details
+    }
+
+    /// The gateway has already consumed the message when the destination contract fails, it
+    /// can't be executed again.
+    pub(crate) fn in_destination(&self) -> bool {
+        self.tx.in_msg.as_ref().and_then(|msg| msg.opcode) == Some(OP_GATEWAY_EXECUTE)
+    }
+}
+
+impl fmt::Display for Failure<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.phase {
+            FailedPhase::Compute(code) => write!(f, "compute phase exit code {code}")?,
+            FailedPhase::Action(code) => write!(f, "action phase result code {code}")?,
+            FailedPhase::ComputeSkipped => write!(f, "compute phase skipped")?,
+        }
+        write!(f, " in {} (tx {})", self.tx.account.to_hex(), self.tx.hash)
+    }
+}
+
+/// Transactions the message with `message_hash` led to, in lt order.
+pub(crate) fn message_chain<'a>(trace: &'a Trace, message_hash: &str) -> Vec<&'a Transaction> {
+    let mut messages = vec![message_hash];
+    let mut chain: Vec<&'a Transaction> = Vec::new();
+    while let Some(hash) = messages.pop() {
+        for tx in trace
+            .transactions
+            .iter()
+            .filter(|tx| tx.in_msg.as_ref().is_some_and(|in_msg| in_msg.hash == hash))
+        {
+            messages.extend(tx.out_msgs.iter().map(|msg| msg.hash.as_str()));
+            chain.push(tx);
+        }
+    }
+    chain.sort_by_key(|tx| tx.lt);
+    chain
+}
+
+/// First failure caused by the message with `message_hash`, only looking at the transactions
+/// whose in-message has one of `opcodes`.
+pub(crate) fn chain_failure<'a>(
+    trace: &'a Trace,
+    message_hash: &str,
+    opcodes: &[u32],
+) -> Option<Failure<'a>> {
+    message_chain(trace, message_hash)
+        .into_iter()
+        .filter(|tx| {
+            tx.in_msg
+                .as_ref()
+                .and_then(|msg| msg.opcode)
+                .is_some_and(|opcode| opcodes.contains(&opcode))
+        })
+        .find_map(Failure::of)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fixtures::{fixture_reverted_traces, fixture_traces};
+
+    fn out_msg_hashes(trace: &Trace, opcode: u32) -> Vec<String> {
+        trace
+            .transactions
+            .iter()
+            .flat_map(|tx| tx.out_msgs.iter())
+            .filter(|msg| msg.opcode == Some(opcode))
+            .map(|msg| msg.hash.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_chain_failure_only_in_own_chain() {
+        let traces = fixture_reverted_traces();
+        let tr = &traces[2];
+        let executes = out_msg_hashes(tr, OP_RELAYER_EXECUTE);
+        assert_eq!(executes.len(), 2);
+
+        assert!(chain_failure(tr, &executes[0], &EXECUTION_OPCODES).is_none());
+        let failure = chain_failure(tr, &executes[1], &EXECUTION_OPCODES).unwrap();
+        assert!(failure.in_destination());
+        assert_eq!(failure.exit_code(), Some(9));
+    }
+
+    #[test]
+    fn test_chain_failure_ignores_other_opcodes() {
+        // Successful execution, a contract outside it rejected the excesses
+        let traces = fixture_traces();
+        let tr = &traces[11];
+        let executes = out_msg_hashes(tr, OP_RELAYER_EXECUTE);
+
+        assert!(!message_chain(tr, &executes[0]).is_empty());
+        assert!(chain_failure(tr, &executes[0], &EXECUTION_OPCODES).is_none());
+    }
+
+    #[test]
+    fn test_chain_failure_approval() {
+        let traces = fixture_traces();
+        let tr = &traces[2];
+        let approvals = out_msg_hashes(tr, OP_APPROVE_MESSAGES);
+
+        let chain = message_chain(tr, &approvals[0]);
+        assert!(chain.len() > 1);
+        assert!(chain.windows(2).all(|w| w[0].lt <= w[1].lt));
+        assert!(chain_failure(tr, &approvals[0], &APPROVAL_OPCODES).is_none());
+    }
+}
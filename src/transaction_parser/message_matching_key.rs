@@ -1,6 +1,34 @@
-#[derive(Eq, Hash, PartialEq)]
+use std::fmt;
+
+#[derive(Debug, Eq, Hash, PartialEq)]
 pub struct MessageMatchingKey {
     pub(crate) destination_chain: String,
     pub(crate) destination_address: String,
     pub(crate) payload_hash: [u8; 32],
 }
+
+impl MessageMatchingKey {
+    pub fn new(
+        destination_chain: String,
+        destination_address: String,
+        payload_hash: [u8; 32],
+    ) -> Self {
+        MessageMatchingKey {
+            destination_chain,
+            destination_address,
+            payload_hash,
+        }
+    }
+}
+
+impl fmt::Display for MessageMatchingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}/0x{}",
+            self.destination_chain,
+            self.destination_address,
+            hex::encode(self.payload_hash)
+        )
+    }
+}
@@ -1,10 +1,13 @@
 mod common;
+pub(crate) mod message_chain;
 mod message_matching_key;
 pub mod parser;
 mod parser_call_contract;
 mod parser_execute_insufficient_gas;
+mod parser_execute_reverted;
 mod parser_its_interchain_token_deployment_started;
 mod parser_its_interchain_transfer;
+mod parser_its_interchain_transfer_received;
 mod parser_its_link_token_started;
 mod parser_its_token_metadata_registered;
 mod parser_jetton_gas_added;
@@ -14,4 +17,5 @@ mod parser_message_executed;
 mod parser_native_gas_added;
 mod parser_native_gas_paid;
 mod parser_native_gas_refunded;
+mod parser_registry;
 mod parser_signers_rotated;
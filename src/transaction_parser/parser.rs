@@ -1,31 +1,30 @@
-use super::message_matching_key::MessageMatchingKey;
-use crate::error::TransactionParsingError;
+pub use super::message_matching_key::MessageMatchingKey;
+#[doc(hidden)]
+pub use super::parser_registry::RoutingBench;
+pub use super::parser_registry::{
+    ParserCategory, ParserFactory, ParserTrigger, TraceParserFactory,
+};
+use crate::config::ParsersConfig;
+use crate::error::GasError;
+pub use crate::error::TransactionParsingError;
 use crate::gas_calculator::GasCalculator;
 use crate::transaction_parser::common::convert_jetton_to_native;
-use crate::transaction_parser::parser_call_contract::ParserCallContract;
-use crate::transaction_parser::parser_execute_insufficient_gas::ParserExecuteInsufficientGas;
-use crate::transaction_parser::parser_its_interchain_token_deployment_started::ParserITSInterchainTokenDeploymentStarted;
-use crate::transaction_parser::parser_its_interchain_transfer::ParserITSInterchainTransfer;
-use crate::transaction_parser::parser_its_link_token_started::ParserITSLinkTokenStarted;
-use crate::transaction_parser::parser_its_token_metadata_registered::ParserITSTokenMetadataRegistered;
-use crate::transaction_parser::parser_jetton_gas_added::ParserJettonGasAdded;
-use crate::transaction_parser::parser_jetton_gas_paid::ParserJettonGasPaid;
-use crate::transaction_parser::parser_message_approved::ParserMessageApproved;
-use crate::transaction_parser::parser_message_executed::ParserMessageExecuted;
-use crate::transaction_parser::parser_native_gas_added::ParserNativeGasAdded;
-use crate::transaction_parser::parser_native_gas_paid::ParserNativeGasPaid;
-use crate::transaction_parser::parser_native_gas_refunded::ParserNativeGasRefunded;
-use crate::transaction_parser::parser_signers_rotated::ParserSignersRotated;
-use crate::types::Trace;
+use crate::transaction_parser::parser_registry::ParserRegistry;
+use crate::types::{Trace, Transaction};
 use async_trait::async_trait;
 use num_bigint::BigUint;
 use opentelemetry::trace::{Span, Tracer};
 use opentelemetry::{global, Context, KeyValue};
-use relayer_core::gmp_api::gmp_types::Event;
+use relayer_core::gmp_api::gmp_types::{
+    CommonEventFields, Event, EventMetadata, MessageExecutionStatus,
+};
+use relayer_core::models::gmp_events::EventModel;
 use relayer_core::price_view::PriceViewTrait;
 use relayer_core::utils::ThreadSafe;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::Arc;
 use tonlib_core::TonAddress;
 use tracing::{info, warn};
 
@@ -39,56 +38,179 @@ pub trait Parser {
         message_id: Option<String>,
     ) -> Result<Event, crate::error::TransactionParsingError>;
     async fn message_id(&self) -> Result<Option<String>, crate::error::TransactionParsingError>;
+    /// Source context added to the `MessageExecuted` event of the execution the transaction is
+    /// part of. Only used for `ParserCategory::ExecutionDetails` parsers.
+    async fn execution_details(
+        &self,
+    ) -> Result<HashMap<String, String>, crate::error::TransactionParsingError> {
+        Ok(HashMap::new())
+    }
 }
 
 #[derive(Clone)]
 pub struct TraceParser<PV> {
     price_view: PV,
-    gateway_address: TonAddress,
-    gas_service_address: TonAddress,
-    its_address: TonAddress,
     gas_calculator: GasCalculator,
-    chain_name: String,
+    registry: ParserRegistry,
+    finalized: Option<bool>,
+}
+
+/// Parser that failed to build its event while the rest of the trace was parsed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParserFailure {
+    // None if the event failed after it was built, e.g. converting its jetton amount
+    pub parser: Option<String>,
+    pub message_id: Option<String>,
+    // Key of the message, for events that couldn't be paired with one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_key: Option<String>,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ParsedTrace {
+    pub events: Vec<Event>,
+    pub failures: Vec<ParserFailure>,
 }
 
 #[cfg_attr(test, mockall::automock)]
 #[async_trait]
 pub trait TraceParserTrait: Send + Sync {
-    async fn parse_trace(&self, trace: Trace) -> Result<Vec<Event>, TransactionParsingError>;
+    /// Fails on the first failing parser. The returned failures are events that were skipped
+    /// without failing the trace, e.g. ITS events without a matching call contract.
+    async fn parse_trace(&self, trace: Trace) -> Result<ParsedTrace, TransactionParsingError>;
+    /// Like `parse_trace`, but a failing parser only loses its own event, the other events are
+    /// returned with the failures. Fails only if the whole trace can't be parsed.
+    async fn parse_trace_partial(
+        &self,
+        trace: Trace,
+    ) -> Result<ParsedTrace, TransactionParsingError>;
 }
 
 #[async_trait]
 impl<PV> TraceParserTrait for TraceParser<PV>
+where
+    PV: PriceViewTrait + ThreadSafe,
+{
+    async fn parse_trace(&self, trace: Trace) -> Result<ParsedTrace, TransactionParsingError> {
+        self.parse(trace, false).await
+    }
+
+    async fn parse_trace_partial(
+        &self,
+        trace: Trace,
+    ) -> Result<ParsedTrace, TransactionParsingError> {
+        self.parse(trace, true).await
+    }
+}
+
+type BoxedParser<'a> = Box<dyn Parser + Send + Sync + 'a>;
+
+/// Matched parser, with the name its failures are reported under.
+struct NamedParser<'a> {
+    name: String,
+    parser: BoxedParser<'a>,
+}
+
+impl<'a> NamedParser<'a> {
+    fn new(name: &str, parser: BoxedParser<'a>) -> Self {
+        Self {
+            name: name.to_string(),
+            parser,
+        }
+    }
+}
+
+/// Parser failures of a trace. Unless they are isolated, the first one fails the whole trace.
+struct Failures {
+    isolate: bool,
+    failures: Vec<ParserFailure>,
+}
+
+impl Failures {
+    fn new(isolate: bool) -> Self {
+        Self {
+            isolate,
+            failures: Vec::new(),
+        }
+    }
+
+    /// `None` if the result is an isolated failure, which is recorded. Transient errors, e.g. a
+    /// price that can't be looked up right now, are never isolated: the event would be lost for
+    /// good, while failing the trace has it parsed again later.
+    fn check<T>(
+        &mut self,
+        result: Result<T, TransactionParsingError>,
+        parser: Option<&str>,
+        message_id: Option<&str>,
+    ) -> Result<Option<T>, TransactionParsingError> {
+        let error = match (result, parser) {
+            (Ok(value), _) => return Ok(Some(value)),
+            (Err(e), Some(parser)) => e.in_parser(parser),
+            (Err(e), None) => e,
+        };
+        if !self.isolate || error.is_transient() {
+            return Err(error);
+        }
+
+        warn!("Parser failed, skipping its event: error={}", error);
+        self.failures.push(ParserFailure {
+            parser: error.parser().map(str::to_owned),
+            message_id: message_id.map(str::to_owned),
+            message_key: None,
+            error: error.to_string(),
+        });
+        Ok(None)
+    }
+
+    /// Records a failure that only loses its own event, whether failures are isolated or not.
+    fn skip(&mut self, error: TransactionParsingError, message_key: Option<&MessageMatchingKey>) {
+        warn!("Skipping event: error={}", error);
+        self.failures.push(ParserFailure {
+            parser: error.parser().map(str::to_owned),
+            message_id: None,
+            message_key: message_key.map(ToString::to_string),
+            error: error.to_string(),
+        });
+    }
+}
+
+impl<PV> TraceParser<PV>
 where
     PV: PriceViewTrait + ThreadSafe,
 {
     #[tracing::instrument(skip(self))]
-    async fn parse_trace(&self, trace: Trace) -> Result<Vec<Event>, TransactionParsingError> {
+    async fn parse(
+        &self,
+        trace: Trace,
+        isolate: bool,
+    ) -> Result<ParsedTrace, TransactionParsingError> {
         let trace_id = trace.trace_id.clone();
 
         let tracer = global::tracer("ton_ingestor");
         let mut span =
             tracer.start_with_context("ton_ingestor.parser.parse_trace", &Context::current());
 
+        let mut failures = Failures::new(isolate);
         let mut events: Vec<Event> = Vec::new();
-        let mut parsers: Vec<Box<dyn Parser + Send + Sync>> = Vec::new();
-        let mut call_contract: Vec<Box<dyn Parser + Send + Sync>> = Vec::new();
-        let mut its: Vec<Box<dyn Parser + Send + Sync>> = Vec::new(); // ITS events that need mapping to call contract
-        let mut gas_credit_map: HashMap<MessageMatchingKey, Box<dyn Parser + Send + Sync>> =
-            HashMap::new();
+        let mut parsers: Vec<NamedParser<'_>> = Vec::new();
+        let mut call_contract: Vec<NamedParser<'_>> = Vec::new();
+        let mut its: Vec<NamedParser<'_>> = Vec::new(); // ITS events that need mapping to call contract
+        let mut gas_credit_map: HashMap<MessageMatchingKey, NamedParser<'_>> = HashMap::new();
+        let mut execution_details: Vec<(&Transaction, NamedParser<'_>)> = Vec::new();
 
         let (total_gas_used, refund_gas_used) = self.gas_used(&trace)?;
 
-        let message_approved_count = self
-            .create_parsers(
-                trace,
-                &mut parsers,
-                &mut call_contract,
-                &mut gas_credit_map,
-                &mut its,
-                self.chain_name.clone(),
-            )
-            .await?;
+        self.create_parsers(
+            &trace,
+            &mut parsers,
+            &mut call_contract,
+            &mut gas_credit_map,
+            &mut its,
+            &mut execution_details,
+            &mut failures,
+        )
+        .await?;
 
         info!(
             "Parsing results: trace_id={} parsers={}, call_contract={}, gas_credit_map={}, its={}",
@@ -112,47 +234,144 @@ where
             warn!("Trace did not produce any parsers: trace_id={}", trace_id);
         }
 
-        for cc in call_contract.iter().clone() {
-            let cc_key = cc.key().await?;
-            events.push(cc.event(None).await?);
-            if let Some(parser) = gas_credit_map.remove(&cc_key) {
-                let message_id = cc.message_id().await?.ok_or_else(|| {
-                    TransactionParsingError::Message("Missing message_id".to_string())
-                })?;
-
-                let event = parser.event(Some(message_id)).await?;
+        // Call contracts that no ITS event has been paired with yet
+        let mut unpaired_call_contracts = Vec::new();
+        for cc in call_contract.iter() {
+            let name = Some(cc.name.as_str());
+            let cc_key = match failures.check(cc.parser.key().await, name, None)? {
+                Some(cc_key) => cc_key,
+                None => continue,
+            };
+            let message_id = match failures.check(cc.parser.message_id().await, name, None)? {
+                Some(message_id) => message_id,
+                None => continue,
+            };
+            // The gas credit and ITS events of the message are still reported if this one fails
+            if let Some(event) =
+                failures.check(cc.parser.event(None).await, name, message_id.as_deref())?
+            {
                 events.push(event);
             }
+            if let Some(gas_credit) = gas_credit_map.remove(&cc_key) {
+                let name = Some(gas_credit.name.as_str());
+                let message_id = message_id.clone().ok_or_else(|| {
+                    TransactionParsingError::Message("Missing message_id".to_string())
+                });
+                if let Some(message_id) = failures.check(message_id, name, None)? {
+                    let event = gas_credit.parser.event(Some(message_id.clone())).await;
+                    if let Some(event) = failures.check(event, name, Some(&message_id))? {
+                        events.push(event);
+                    }
+                }
+            }
+            unpaired_call_contracts.push((cc_key, message_id));
         }
 
-        for (i, its_parser) in its.iter().enumerate() {
-            match call_contract.get(i) {
-                Some(contract_parser) => {
-                    let message_id = contract_parser.message_id().await?;
-                    events.push(its_parser.event(message_id).await?);
+        // An ITS event that can't be paired is skipped, the rest of the trace is still reported
+        let mut unpaired_its: i64 = 0;
+        for its_parser in its.iter() {
+            let name = its_parser.name.as_str();
+            let its_key = match its_parser.parser.key().await {
+                Ok(its_key) => its_key,
+                Err(e) => {
+                    unpaired_its += 1;
+                    failures.skip(e.in_parser(name), None);
+                    continue;
+                }
+            };
+            let position = unpaired_call_contracts
+                .iter()
+                .position(|(cc_key, _)| *cc_key == its_key);
+            match position.map(|i| unpaired_call_contracts.remove(i)) {
+                Some((_, message_id)) => {
+                    let event = its_parser.parser.event(message_id.clone()).await;
+                    if let Some(event) = failures.check(event, Some(name), message_id.as_deref())? {
+                        events.push(event);
+                    }
                 }
                 None => {
-                    return Err(TransactionParsingError::ITSWithoutPair(format!(
-                        "No matching call_contract for ITS index {i}"
-                    )));
+                    unpaired_its += 1;
+                    let error = TransactionParsingError::ITSWithoutPair(format!(
+                        "No matching call_contract for {its_key}"
+                    ));
+                    failures.skip(error.in_parser(name), Some(&its_key));
                 }
             }
         }
+        span.set_attribute(KeyValue::new("unpaired_its", unpaired_its));
 
         for parser in parsers {
-            let event = parser.event(None).await?;
-            events.push(event);
+            let event = parser.parser.event(None).await;
+            if let Some(event) = failures.check(event, Some(&parser.name), None)? {
+                events.push(event);
+            }
         }
 
-        self.add_gas_used_and_convert(
-            &mut events,
-            total_gas_used,
-            refund_gas_used,
-            message_approved_count,
-        )
-        .await?;
+        drop_superseded_executions(&mut events);
 
-        Ok(events)
+        for (tx, parser) in execution_details {
+            let details = parser.parser.execution_details().await;
+            let details = match failures.check(details, Some(&parser.name), None)? {
+                Some(details) => details,
+                None => continue,
+            };
+            match executed_event(&trace, tx, &mut events) {
+                Some(Event::MessageExecuted {
+                    common:
+                        CommonEventFields {
+                            meta: Some(meta), ..
+                        },
+                    ..
+                }) => meta
+                    .common_meta
+                    .source_context
+                    .get_or_insert_with(HashMap::new)
+                    .extend(details),
+                _ => warn!(
+                    "No executed message for execution details: trace_id={} tx={}",
+                    trace_id, tx.hash
+                ),
+            }
+        }
+
+        let message_approved_count = events
+            .iter()
+            .filter(|event| matches!(event, Event::MessageApproved { .. }))
+            .count() as u64;
+
+        let mut converted = Vec::with_capacity(events.len());
+        for mut event in events {
+            let result = self
+                .convert_event(
+                    &mut event,
+                    total_gas_used,
+                    refund_gas_used,
+                    message_approved_count,
+                )
+                .await;
+            match result {
+                Ok(()) => {
+                    if let Some(meta) = common_meta(&mut event) {
+                        meta.finalized = self.finalized;
+                    }
+                    converted.push(event)
+                }
+                Err(e) => {
+                    let message_id = EventModel::from_event(event).message_id;
+                    failures.check::<()>(Err(e), None, message_id.as_deref())?;
+                }
+            }
+        }
+
+        span.set_attribute(KeyValue::new(
+            "parser_failures",
+            failures.failures.len() as i64,
+        ));
+
+        Ok(ParsedTrace {
+            events: converted,
+            failures: failures.failures,
+        })
     }
 }
 
@@ -165,39 +384,57 @@ impl<PV: PriceViewTrait> TraceParser<PV> {
         message_approved_count: u64,
     ) -> Result<(), TransactionParsingError> {
         for e in events.iter_mut() {
-            match e {
-                Event::GasCredit { payment, .. } => {
-                    if let Some(token_id) = payment.token_id.take() {
-                        let amount = BigUint::from_str(payment.amount.as_str())
-                            .map_err(|e| TransactionParsingError::Generic(e.to_string()))?;
+            self.convert_event(e, total_gas_used, refund_gas_used, message_approved_count)
+                .await?;
+        }
+
+        Ok(())
+    }
 
-                        let native = convert_jetton_to_native(token_id, &amount, &self.price_view)
-                            .await
-                            .map_err(|e| TransactionParsingError::Generic(e.to_string()))?;
+    async fn convert_event(
+        &self,
+        e: &mut Event,
+        total_gas_used: u64,
+        refund_gas_used: u64,
+        message_approved_count: u64,
+    ) -> Result<(), TransactionParsingError> {
+        match e {
+            Event::GasCredit { payment, .. } => {
+                if let Some(token_id) = payment.token_id.take() {
+                    let amount = BigUint::from_str(payment.amount.as_str())
+                        .map_err(|e| TransactionParsingError::Generic(e.to_string()))?;
 
-                        payment.amount = native.to_string();
-                    }
-                }
+                    let native = convert_jetton_to_native(token_id, &amount, &self.price_view)
+                        .await
+                        .map_err(|e| match e {
+                            GasError::PriceUnavailable(e) => {
+                                TransactionParsingError::PriceUnavailable(e)
+                            }
+                            e => TransactionParsingError::Generic(e.to_string()),
+                        })?;
 
-                Event::MessageApproved { cost, .. } => {
-                    let per = if message_approved_count == 0 {
-                        0
-                    } else {
-                        total_gas_used / message_approved_count
-                    };
-                    cost.amount = per.to_string();
+                    payment.amount = native.to_string();
                 }
+            }
 
-                Event::MessageExecuted { cost, .. } => {
-                    cost.amount = total_gas_used.to_string();
-                }
+            Event::MessageApproved { cost, .. } => {
+                let per = if message_approved_count == 0 {
+                    0
+                } else {
+                    total_gas_used / message_approved_count
+                };
+                cost.amount = per.to_string();
+            }
 
-                Event::GasRefunded { cost, .. } => {
-                    cost.amount = refund_gas_used.to_string();
-                }
+            Event::MessageExecuted { cost, .. } => {
+                cost.amount = total_gas_used.to_string();
+            }
 
-                _ => {}
+            Event::GasRefunded { cost, .. } => {
+                cost.amount = refund_gas_used.to_string();
             }
+
+            _ => {}
         }
 
         Ok(())
@@ -213,174 +450,123 @@ impl<PV: PriceViewTrait> TraceParser<PV> {
         gas_calculator: GasCalculator,
         chain_name: String,
     ) -> Self {
+        let registry = ParserRegistry::new(
+            &gateway_address,
+            &gas_service_address,
+            &its_address,
+            &chain_name,
+        );
         Self {
             price_view,
-            gateway_address,
-            gas_service_address,
-            its_address,
             gas_calculator,
-            chain_name,
+            registry,
+            finalized: None,
         }
     }
 
-    async fn create_parsers(
-        &self,
-        trace: Trace,
-        parsers: &mut Vec<Box<dyn Parser + Send + Sync>>,
-        call_contract: &mut Vec<Box<dyn Parser + Send + Sync>>,
-        gas_credit_map: &mut HashMap<MessageMatchingKey, Box<dyn Parser + Send + Sync>>,
-        its: &mut Vec<Box<dyn Parser + Send + Sync>>,
-        chain_name: String,
-    ) -> Result<u64, TransactionParsingError> {
-        let mut message_approved_count = 0u64;
+    /// Marks events as finalized, for traces the subscribers held until they were final.
+    pub fn with_finality_gated(mut self, gated: bool) -> Self {
+        self.finalized = gated.then_some(true);
+        self
+    }
 
-        let mut parser = ParserExecuteInsufficientGas::new(
-            trace.clone(),
-            self.gateway_address.clone(),
-            chain_name.clone(),
-        )
-        .await?;
-        if parser.check_match().await? {
-            info!(
-                "ParserExecuteInsufficientGas matched, trace_id={}",
-                trace.trace_id
-            );
-            parser.parse().await?;
-            parsers.push(Box::new(parser));
-        }
-
-        for tx in trace.transactions {
-            let mut parser = ParserCallContract::new(
-                tx.clone(),
-                self.gateway_address.clone(),
-                chain_name.clone(),
-            )
-            .await?;
-            if parser.check_match().await? {
-                info!("ParserCallContract matched, trace_id={}", trace.trace_id);
-                parser.parse().await?;
-                call_contract.push(Box::new(parser));
-                continue;
-            }
-            let mut parser =
-                ParserMessageExecuted::new(tx.clone(), self.gateway_address.clone()).await?;
-            if parser.check_match().await? {
-                info!("ParserMessageExecuted matched, trace_id={}", trace.trace_id);
-                parser.parse().await?;
-                parsers.push(Box::new(parser));
-                continue;
-            }
-            let mut parser =
-                ParserMessageApproved::new(tx.clone(), self.gateway_address.clone()).await?;
-            if parser.check_match().await? {
-                info!("ParserMessageApproved matched, trace_id={}", trace.trace_id);
-                parser.parse().await?;
-                parsers.push(Box::new(parser));
-                message_approved_count += 1;
-                continue;
-            }
-            let mut parser =
-                ParserNativeGasPaid::new(tx.clone(), self.gas_service_address.clone()).await?;
-            if parser.check_match().await? {
-                info!("ParserNativeGasPaid matched, trace_id={}", trace.trace_id);
-                parser.parse().await?;
-                let key = parser.key().await?;
-                gas_credit_map.insert(key, Box::new(parser));
-                continue;
-            }
-            let mut parser =
-                ParserNativeGasAdded::new(tx.clone(), self.gas_service_address.clone()).await?;
-            if parser.check_match().await? {
-                info!("ParserNativeGasAdded matched, trace_id={}", trace.trace_id);
-                parser.parse().await?;
-                parsers.push(Box::new(parser));
-                continue;
-            }
-            let mut parser =
-                ParserJettonGasAdded::new(tx.clone(), self.gas_service_address.clone()).await?;
-            if parser.check_match().await? {
-                info!("ParserJettonGasAdded matched, trace_id={}", trace.trace_id);
-                parser.parse().await?;
-                parsers.push(Box::new(parser));
-                continue;
-            }
-            let mut parser =
-                ParserJettonGasPaid::new(tx.clone(), self.gas_service_address.clone()).await?;
-            if parser.check_match().await? {
-                info!("ParserJettonGasPaid matched, trace_id={}", trace.trace_id);
-                parser.parse().await?;
-                let key = parser.key().await?;
-                gas_credit_map.insert(key, Box::new(parser));
-                continue;
-            }
-            let mut parser =
-                ParserNativeGasRefunded::new(tx.clone(), self.gas_service_address.clone()).await?;
-            if parser.check_match().await? {
-                info!(
-                    "ParserNativeGasRefunded matched, trace_id={}",
-                    trace.trace_id
-                );
-                parser.parse().await?;
-                parsers.push(Box::new(parser));
-                continue;
-            }
-            let mut parser =
-                ParserITSTokenMetadataRegistered::new(tx.clone(), self.its_address.clone()).await?;
-            if parser.check_match().await? {
-                info!(
-                    "ParserITSTokenMetadataRegistered matched, trace_id={}",
-                    trace.trace_id
-                );
-                parser.parse().await?;
-                its.push(Box::new(parser));
-                continue;
-            }
-            let mut parser = ParserITSInterchainTokenDeploymentStarted::new(
-                tx.clone(),
-                self.its_address.clone(),
-            )
-            .await?;
-            if parser.check_match().await? {
-                info!(
-                    "ParserITSInterchainTokenDeploymentStarted matched, trace_id={}",
-                    trace.trace_id
-                );
-                parser.parse().await?;
-                its.push(Box::new(parser));
-                continue;
-            }
-            let mut parser =
-                ParserITSInterchainTransfer::new(tx.clone(), self.its_address.clone()).await?;
-            if parser.check_match().await? {
-                info!(
-                    "ParserITSInterchainTransfer matched, trace_id={}",
-                    trace.trace_id
-                );
-                parser.parse().await?;
-                its.push(Box::new(parser));
+    /// Adds a parser, tried after the built-in ones and those added before it.
+    pub fn with_parser(mut self, factory: Arc<dyn ParserFactory>) -> Self {
+        self.registry.register(factory);
+        self
+    }
+
+    /// Adds a parser over the whole trace, tried after the built-in ones and those added before it.
+    pub fn with_trace_parser(mut self, factory: Arc<dyn TraceParserFactory>) -> Self {
+        self.registry.register_trace(factory);
+        self
+    }
+
+    /// Disables the parsers listed in the config, including ones added later.
+    pub fn with_parsers_config(mut self, config: &ParsersConfig) -> Self {
+        self.registry.configure(config);
+        self
+    }
+
+    async fn create_parsers<'a>(
+        &self,
+        trace: &'a Trace,
+        parsers: &mut Vec<NamedParser<'a>>,
+        call_contract: &mut Vec<NamedParser<'a>>,
+        gas_credit_map: &mut HashMap<MessageMatchingKey, NamedParser<'a>>,
+        its: &mut Vec<NamedParser<'a>>,
+        execution_details: &mut Vec<(&'a Transaction, NamedParser<'a>)>,
+        failures: &mut Failures,
+    ) -> Result<(), TransactionParsingError> {
+        // The first trace parser of a category that matches ends the category, e.g. an execution
+        // that ran out of gas isn't reported as reverted as well
+        let mut matched_categories: Vec<ParserCategory> = Vec::new();
+        for factory in self.registry.trace_factories() {
+            let category = factory.category();
+            if matched_categories.contains(&category) {
                 continue;
             }
-            let mut parser =
-                ParserITSLinkTokenStarted::new(tx.clone(), self.its_address.clone()).await?;
-            if parser.check_match().await? {
-                info!(
-                    "ParserITSLinkTokenStarted matched, trace_id={}",
-                    trace.trace_id
-                );
-                parser.parse().await?;
-                its.push(Box::new(parser));
-                continue;
+            let name = factory.name();
+            let created = match failures.check(factory.create(trace).await, Some(name), None)? {
+                Some(created) => created,
+                None => continue,
+            };
+            for parser in created {
+                if let Some(parser) = Self::matched(parser, name, trace, failures).await? {
+                    parsers.push(NamedParser::new(name, parser));
+                    if !matched_categories.contains(&category) {
+                        matched_categories.push(category);
+                    }
+                }
             }
-            let mut parser =
-                ParserSignersRotated::new(tx.clone(), self.gateway_address.clone()).await?;
-            if parser.check_match().await? {
-                info!("ParserSignersRotated matched, trace_id={}", trace.trace_id);
-                parser.parse().await?;
-                parsers.push(Box::new(parser));
-                continue;
+        }
+
+        for tx in &trace.transactions {
+            for factory in self.registry.candidates(tx) {
+                let name = factory.name();
+                let parser = match failures.check(factory.create(tx).await, Some(name), None)? {
+                    Some(parser) => parser,
+                    None => continue,
+                };
+                let parser = match Self::matched(parser, name, trace, failures).await? {
+                    Some(parser) => NamedParser::new(name, parser),
+                    None => continue,
+                };
+                match factory.category() {
+                    ParserCategory::CallContract => call_contract.push(parser),
+                    ParserCategory::GasCredit => {
+                        let key = parser.parser.key().await;
+                        if let Some(key) = failures.check(key, Some(name), None)? {
+                            gas_credit_map.insert(key, parser);
+                        }
+                    }
+                    ParserCategory::Its => its.push(parser),
+                    ParserCategory::Standalone | ParserCategory::ExecutionFailure => {
+                        parsers.push(parser)
+                    }
+                    ParserCategory::ExecutionDetails => execution_details.push((tx, parser)),
+                }
+                break;
             }
         }
-        Ok(message_approved_count)
+        Ok(())
+    }
+
+    /// The parser, parsed, if it matches. A parser that fails to match or to parse is recorded
+    /// as a failure.
+    async fn matched<'a>(
+        mut parser: BoxedParser<'a>,
+        name: &str,
+        trace: &Trace,
+        failures: &mut Failures,
+    ) -> Result<Option<BoxedParser<'a>>, TransactionParsingError> {
+        if failures.check(parser.check_match().await, Some(name), None)? != Some(true) {
+            return Ok(None);
+        }
+        info!("{} matched, trace_id={}", name, trace.trace_id);
+        Ok(failures
+            .check(parser.parse().await, Some(name), None)?
+            .map(|_| parser))
     }
 
     fn gas_used(&self, trace: &Trace) -> Result<(u64, u64), TransactionParsingError> {
@@ -398,10 +584,89 @@ impl<PV: PriceViewTrait> TraceParser<PV> {
     }
 }
 
+/// Metadata every event carries, if it has any.
+fn common_meta(event: &mut Event) -> Option<&mut EventMetadata> {
+    match event {
+        Event::Call { common, .. }
+        | Event::GasCredit { common, .. }
+        | Event::GasRefunded { common, .. }
+        | Event::CannotExecuteMessageV2 { common, .. }
+        | Event::ITSInterchainTransfer { common, .. }
+        | Event::ITSInterchainTokenDeploymentStarted { common, .. }
+        | Event::ITSLinkTokenStarted { common, .. }
+        | Event::ITSTokenMetadataRegistered { common, .. } => common.meta.as_mut(),
+        Event::MessageApproved { common, .. } => {
+            common.meta.as_mut().map(|meta| &mut meta.common_meta)
+        }
+        Event::MessageExecuted { common, .. } => {
+            common.meta.as_mut().map(|meta| &mut meta.common_meta)
+        }
+        Event::SignersRotated { common, .. } => {
+            common.meta.as_mut().map(|meta| &mut meta.common_meta)
+        }
+        _ => None,
+    }
+}
+
+/// The gateway reports an execution as successful once it hands the message to the destination
+/// contract. Drops those events when the destination contract reverted.
+fn drop_superseded_executions(events: &mut Vec<Event>) {
+    let reverted: Vec<String> = events
+        .iter()
+        .filter_map(|event| match event {
+            Event::MessageExecuted {
+                common,
+                status: MessageExecutionStatus::REVERTED,
+                ..
+            } => Some(common.event_id.clone()),
+            _ => None,
+        })
+        .collect();
+
+    events.retain(|event| {
+        !matches!(
+            event,
+            Event::MessageExecuted {
+                common,
+                status: MessageExecutionStatus::SUCCESSFUL,
+                ..
+            } if reverted.contains(&common.event_id)
+        )
+    });
+}
+
+/// `MessageExecuted` event of the execution `tx` is part of, found by following in-messages
+/// back to the gateway transaction that emitted it.
+fn executed_event<'e>(
+    trace: &Trace,
+    tx: &Transaction,
+    events: &'e mut [Event],
+) -> Option<&'e mut Event> {
+    let sender = |tx: &Transaction| {
+        let in_msg_hash = &tx.in_msg.as_ref()?.hash;
+        trace
+            .transactions
+            .iter()
+            .find(|parent| parent.out_msgs.iter().any(|msg| &msg.hash == in_msg_hash))
+    };
+
+    let position = std::iter::successors(Some(tx), |tx| sender(tx))
+        .take(trace.transactions.len())
+        .find_map(|tx| {
+            events.iter().position(|event| {
+                matches!(event, Event::MessageExecuted { common, .. } if common.event_id == tx.hash)
+            })
+        })?;
+
+    events.get_mut(position)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_utils::fixtures::fixture_traces;
+    use crate::test_utils::fixtures::{
+        fixture_its_traces, fixture_reverted_traces, fixture_traces,
+    };
     use mockall::predicate::eq;
     use relayer_core::database::PostgresDB;
     use relayer_core::price_view::MockPriceView;
@@ -435,7 +700,7 @@ mod tests {
             calc,
             "ton2".to_string(),
         );
-        let events = parser.parse_trace(traces[9].clone()).await.unwrap();
+        let events = parser.parse_trace(traces[9].clone()).await.unwrap().events;
         assert_eq!(events.len(), 2);
 
         match events[0].clone() {
@@ -470,6 +735,82 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_finalized_only_when_finality_gated() {
+        let its = TonAddress::from_hex_str(
+            "0:000000000000000000000000000000000000000000000000000000000000ffff",
+        )
+        .unwrap();
+        let traces = fixture_traces();
+        let parser = || {
+            TraceParser::new(
+                self::mock_price_view(),
+                traces[9].transactions[4].account.clone(),
+                traces[9].transactions[1].account.clone(),
+                its.clone(),
+                GasCalculator::new(vec![]),
+                "ton2".to_string(),
+            )
+        };
+
+        let finalized = |events: Vec<Event>| match events[0].clone() {
+            Event::Call { common, .. } => common.meta.unwrap().finalized,
+            _ => panic!("Expected CallContract event"),
+        };
+
+        let events = parser()
+            .parse_trace(traces[9].clone())
+            .await
+            .unwrap()
+            .events;
+        assert_eq!(finalized(events), None);
+
+        let events = parser()
+            .with_finality_gated(true)
+            .parse_trace(traces[9].clone())
+            .await
+            .unwrap()
+            .events;
+        assert_eq!(finalized(events), Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_partial_parsing_fails_on_price_outage() {
+        let traces = fixture_traces();
+        let gateway = traces[9].transactions[4].account.clone();
+        let gas_service = traces[9].transactions[1].account.clone();
+        let its = TonAddress::from_hex_str(
+            "0:000000000000000000000000000000000000000000000000000000000000ffff",
+        )
+        .unwrap();
+
+        let mut price_view: MockPriceView<PostgresDB> = MockPriceView::new();
+        price_view
+            .expect_get_price()
+            .returning(|_| Err(anyhow::anyhow!("No price")));
+
+        let calc = GasCalculator::new(vec![gateway.clone(), gas_service.clone()]);
+        let parser = TraceParser::new(
+            price_view,
+            gateway,
+            gas_service,
+            its,
+            calc,
+            "ton2".to_string(),
+        );
+
+        // The jetton gas payment can't be converted right now. Isolating it would drop its event
+        // for good, so the trace fails either way and is parsed again later.
+        assert!(parser.parse_trace(traces[9].clone()).await.is_err());
+
+        let error = parser
+            .parse_trace_partial(traces[9].clone())
+            .await
+            .unwrap_err();
+        assert!(error.is_transient());
+        assert!(error.to_string().contains("No price"));
+    }
+
     #[tokio::test]
     async fn test_gas_executed() {
         let gateway =
@@ -495,7 +836,7 @@ mod tests {
             calc,
             "ton2".to_string(),
         );
-        let events = parser.parse_trace(traces[11].clone()).await.unwrap();
+        let events = parser.parse_trace(traces[11].clone()).await.unwrap().events;
         assert_eq!(events.len(), 1);
 
         match events[0].clone() {
@@ -507,6 +848,153 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_reverted_execution_replaces_successful() {
+        let gas_service =
+            TonAddress::from_base64_url("EQBcfOiB4SF73vEFm1icuf3oqaFHj1bNQgxvwHKkxAiIjxLZ")
+                .unwrap();
+        let its = TonAddress::from_base64_url("kQD-xq9YjzE6cq10P801OkBA65abvxvID5pnfFjTszltjilk")
+            .unwrap();
+
+        let traces = fixture_reverted_traces();
+        let gateway = traces[0].transactions[2].account.clone();
+
+        let calc = GasCalculator::new(vec![gateway.clone(), gas_service.clone()]);
+        let parser = TraceParser::new(
+            mock_price_view(),
+            gateway,
+            gas_service,
+            its,
+            calc,
+            "ton2".to_string(),
+        );
+
+        let events = parser.parse_trace(traces[0].clone()).await.unwrap().events;
+        assert_eq!(events.len(), 1);
+        match events[0].clone() {
+            Event::MessageExecuted { common, status, .. } => {
+                assert_eq!(status, MessageExecutionStatus::REVERTED);
+                assert_eq!(common.event_id, traces[0].transactions[4].hash);
+            }
+            _ => panic!("Expected MessageExecuted event"),
+        }
+
+        let events = parser.parse_trace(traces[1].clone()).await.unwrap().events;
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], Event::CannotExecuteMessageV2 { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_batched_execution_reverted_separately() {
+        let gas_service =
+            TonAddress::from_base64_url("EQBcfOiB4SF73vEFm1icuf3oqaFHj1bNQgxvwHKkxAiIjxLZ")
+                .unwrap();
+        let its = TonAddress::from_base64_url("kQD-xq9YjzE6cq10P801OkBA65abvxvID5pnfFjTszltjilk")
+            .unwrap();
+
+        let traces = fixture_reverted_traces();
+        let trace = &traces[2];
+        let gateway = trace.transactions[2].account.clone();
+
+        let calc = GasCalculator::new(vec![gateway.clone(), gas_service.clone()]);
+        let parser = TraceParser::new(
+            mock_price_view(),
+            gateway,
+            gas_service,
+            its,
+            calc,
+            "ton2".to_string(),
+        );
+
+        // Only the second of the two executions reverted
+        let events = parser.parse_trace(trace.clone()).await.unwrap().events;
+        let failed: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::MessageExecuted {
+                    common,
+                    message_id,
+                    status: MessageExecutionStatus::REVERTED,
+                    ..
+                } => Some((common.event_id.clone(), message_id.clone())),
+                Event::CannotExecuteMessageV2 { message_id, .. } => {
+                    panic!("Unexpected CannotExecuteMessageV2 for {message_id}")
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            failed,
+            vec![(
+                trace.transactions[7].hash.clone(),
+                "0x584defcdc90f2df657b3acfe484f8e9a187945acbeb492837a42918c6ea2e009".to_string()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_insufficient_gas_reported_once() {
+        let traces = fixture_traces();
+        let gateway = traces[14].transactions[2].account.clone();
+        let gas_service =
+            TonAddress::from_base64_url("EQBcfOiB4SF73vEFm1icuf3oqaFHj1bNQgxvwHKkxAiIjxLZ")
+                .unwrap();
+        let its = TonAddress::from_base64_url("kQD-xq9YjzE6cq10P801OkBA65abvxvID5pnfFjTszltjilk")
+            .unwrap();
+
+        let calc = GasCalculator::new(vec![gateway.clone(), gas_service.clone()]);
+        let parser = TraceParser::new(
+            mock_price_view(),
+            gateway,
+            gas_service,
+            its,
+            calc,
+            "ton2".to_string(),
+        );
+
+        for trace in [&traces[14], &traces[15]] {
+            let events = parser.parse_trace(trace.clone()).await.unwrap().events;
+            assert_eq!(events.len(), 1);
+            assert!(matches!(events[0], Event::CannotExecuteMessageV2 { .. }));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_its_transfer_received_added_to_executed() {
+        let gas_service =
+            TonAddress::from_base64_url("EQBcfOiB4SF73vEFm1icuf3oqaFHj1bNQgxvwHKkxAiIjxLZ")
+                .unwrap();
+
+        let traces = fixture_its_traces();
+        let gateway = traces[0].transactions[4].account.clone();
+        let its = traces[0].transactions[5].account.clone();
+
+        let calc = GasCalculator::new(vec![gateway.clone(), gas_service.clone()]);
+        let parser = TraceParser::new(
+            mock_price_view(),
+            gateway,
+            gas_service,
+            its,
+            calc,
+            "ton2".to_string(),
+        );
+        let events = parser.parse_trace(traces[0].clone()).await.unwrap().events;
+        assert_eq!(events.len(), 1);
+
+        match events[0].clone() {
+            Event::MessageExecuted { common, .. } => {
+                let context = common.meta.unwrap().common_meta.source_context.unwrap();
+                assert_eq!(context.get("its_amount").unwrap(), "1000000");
+                assert_eq!(context.get("its_source_chain").unwrap(), "avalanche-fuji");
+                assert_eq!(
+                    context.get("its_tx_id").unwrap(),
+                    &traces[0].transactions[5].hash
+                );
+            }
+            _ => panic!("Expected MessageExecuted event"),
+        }
+    }
+
     #[tokio::test]
     async fn test_its_call_contract_connection() {
         let gateway =
@@ -531,7 +1019,7 @@ mod tests {
             calc,
             "ton2".to_string(),
         );
-        let events = parser.parse_trace(traces[19].clone()).await.unwrap();
+        let events = parser.parse_trace(traces[19].clone()).await.unwrap().events;
         assert_eq!(events.len(), 2);
 
         match events[0].clone() {
@@ -584,6 +1072,46 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_its_without_call_contract_is_skipped() {
+        let gateway = TonAddress::from_hex_str(
+            "0:0000000000000000000000000000000000000000000000000000000000000000",
+        )
+        .unwrap();
+        let gas_service =
+            TonAddress::from_base64_url("EQBcfOiB4SF73vEFm1icuf3oqaFHj1bNQgxvwHKkxAiIjxLZ")
+                .unwrap();
+        let its = TonAddress::from_base64_url("kQDdU6MZZX_QYO4RPTMaPJ9kFUdX2474z2yxRvDuhnXZv-aH")
+            .unwrap();
+
+        let calc = GasCalculator::new(vec![gateway.clone(), gas_service.clone(), its.clone()]);
+
+        // Gateway is not ours, so the call contract accompanying the ITS event is not parsed
+        let parser = TraceParser::new(
+            mock_price_view(),
+            gateway,
+            gas_service,
+            its,
+            calc,
+            "ton2".to_string(),
+        );
+        let parsed = parser
+            .parse_trace(fixture_traces()[19].clone())
+            .await
+            .unwrap();
+        assert!(parsed.events.is_empty());
+
+        // Reported on its own, under the key of its message
+        assert_eq!(parsed.failures.len(), 1);
+        let failure = &parsed.failures[0];
+        assert!(failure.parser.is_some());
+        assert!(failure.error.contains("ITSWithoutPair: "));
+        assert!(failure.message_id.is_none());
+        let key = failure.message_key.as_deref().unwrap();
+        assert!(key.contains("/0x"));
+        assert!(failure.error.ends_with(key));
+    }
+
     #[tokio::test]
     async fn test_gas_approved() {
         let gateway =
@@ -608,7 +1136,7 @@ mod tests {
             calc,
             "ton2".to_string(),
         );
-        let events = parser.parse_trace(traces[2].clone()).await.unwrap();
+        let events = parser.parse_trace(traces[2].clone()).await.unwrap().events;
         assert_eq!(events.len(), 1);
 
         match events[0].clone() {
@@ -644,7 +1172,7 @@ mod tests {
             calc,
             "ton2".to_string(),
         );
-        let events = parser.parse_trace(traces[8].clone()).await.unwrap();
+        let events = parser.parse_trace(traces[8].clone()).await.unwrap().events;
         assert_eq!(events.len(), 1);
 
         match events[0].clone() {
@@ -656,6 +1184,209 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_registry_candidates_cover_all_matches() {
+        let mut matched = 0;
+        for trace in fixture_traces() {
+            for tx in &trace.transactions {
+                // Every contract at the transaction's account, so only opcodes decide
+                let account = tx.account.clone();
+                let parser = TraceParser::new(
+                    mock_price_view(),
+                    account.clone(),
+                    account.clone(),
+                    account.clone(),
+                    GasCalculator::new(vec![account]),
+                    "ton2".to_string(),
+                );
+                let candidates: Vec<String> = parser
+                    .registry
+                    .candidates(tx)
+                    .iter()
+                    .map(|factory| factory.name().to_string())
+                    .collect();
+
+                for factory in parser.registry.factories() {
+                    let mut transaction_parser = factory.create(tx).await.unwrap();
+                    if transaction_parser.check_match().await.unwrap() {
+                        matched += 1;
+                        assert!(
+                            candidates.contains(&factory.name().to_string()),
+                            "{} matches {} but is not a candidate",
+                            factory.name(),
+                            tx.hash
+                        );
+                    }
+                }
+            }
+        }
+        assert!(matched > 0);
+    }
+
+    struct RenamedFactory {
+        inner: Arc<dyn ParserFactory>,
+    }
+
+    #[async_trait]
+    impl ParserFactory for RenamedFactory {
+        fn name(&self) -> &str {
+            "CustomCallContract"
+        }
+
+        fn category(&self) -> ParserCategory {
+            self.inner.category()
+        }
+
+        fn address(&self) -> &TonAddress {
+            self.inner.address()
+        }
+
+        fn triggers(&self) -> Vec<ParserTrigger> {
+            self.inner.triggers()
+        }
+
+        async fn create<'a>(
+            &self,
+            tx: &'a Transaction,
+        ) -> Result<Box<dyn Parser + Send + Sync + 'a>, TransactionParsingError> {
+            self.inner.create(tx).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_parser_replaces_disabled_one() {
+        let traces = fixture_traces();
+        let gateway = traces[1].transactions[1].account.clone();
+        let other = TonAddress::from_hex_str(
+            "0:0000000000000000000000000000000000000000000000000000000000000000",
+        )
+        .unwrap();
+        let new_parser = || {
+            TraceParser::new(
+                mock_price_view(),
+                gateway.clone(),
+                other.clone(),
+                other.clone(),
+                GasCalculator::new(vec![gateway.clone()]),
+                "ton2".to_string(),
+            )
+        };
+        let config = ParsersConfig {
+            disabled: vec!["ParserCallContract".to_string()],
+            ..Default::default()
+        };
+
+        let events = new_parser()
+            .parse_trace(traces[1].clone())
+            .await
+            .unwrap()
+            .events;
+        assert!(matches!(events[0], Event::Call { .. }));
+        let builtin = new_parser()
+            .registry
+            .factories()
+            .first()
+            .map(Arc::clone)
+            .unwrap();
+        assert_eq!(builtin.name(), "ParserCallContract");
+
+        let disabled = new_parser().with_parsers_config(&config);
+        let events = disabled
+            .parse_trace(traces[1].clone())
+            .await
+            .unwrap()
+            .events;
+        assert!(events.is_empty());
+
+        let custom = disabled.with_parser(Arc::new(RenamedFactory { inner: builtin }));
+        let events = custom.parse_trace(traces[1].clone()).await.unwrap().events;
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], Event::Call { .. }));
+    }
+
+    struct RenamedTraceFactory {
+        inner: Arc<dyn TraceParserFactory>,
+    }
+
+    #[async_trait]
+    impl TraceParserFactory for RenamedTraceFactory {
+        fn name(&self) -> &str {
+            "CustomExecuteReverted"
+        }
+
+        fn category(&self) -> ParserCategory {
+            self.inner.category()
+        }
+
+        async fn create<'a>(
+            &self,
+            trace: &'a Trace,
+        ) -> Result<Vec<Box<dyn Parser + Send + Sync + 'a>>, TransactionParsingError> {
+            self.inner.create(trace).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_trace_parser_replaces_disabled_one() {
+        let traces = fixture_reverted_traces();
+        let gateway = traces[0].transactions[2].account.clone();
+        // Bounced by the gateway
+        let trace = &traces[1];
+        let other = TonAddress::from_hex_str(
+            "0:0000000000000000000000000000000000000000000000000000000000000000",
+        )
+        .unwrap();
+        let new_parser = || {
+            TraceParser::new(
+                mock_price_view(),
+                gateway.clone(),
+                other.clone(),
+                other.clone(),
+                GasCalculator::new(vec![gateway.clone()]),
+                "ton2".to_string(),
+            )
+        };
+        let cannot_execute = |events: &[Event]| {
+            events
+                .iter()
+                .filter(|event| matches!(event, Event::CannotExecuteMessageV2 { .. }))
+                .count()
+        };
+
+        let events = new_parser()
+            .parse_trace(trace.clone())
+            .await
+            .unwrap()
+            .events;
+        assert_eq!(cannot_execute(&events), 1);
+
+        let by_category = new_parser().with_parsers_config(&ParsersConfig {
+            disabled: vec!["execution_failure".to_string()],
+            ..Default::default()
+        });
+        let events = by_category.parse_trace(trace.clone()).await.unwrap().events;
+        assert_eq!(cannot_execute(&events), 0);
+
+        let builtin = new_parser()
+            .registry
+            .trace_factories()
+            .last()
+            .map(Arc::clone)
+            .unwrap();
+        assert_eq!(builtin.name(), "ParserExecuteReverted");
+
+        let disabled = new_parser().with_parsers_config(&ParsersConfig {
+            disabled: vec!["ParserExecuteReverted".to_string()],
+            ..Default::default()
+        });
+        let events = disabled.parse_trace(trace.clone()).await.unwrap().events;
+        assert_eq!(cannot_execute(&events), 0);
+
+        let custom = disabled.with_trace_parser(Arc::new(RenamedTraceFactory { inner: builtin }));
+        let events = custom.parse_trace(trace.clone()).await.unwrap().events;
+        assert_eq!(cannot_execute(&events), 1);
+    }
+
     fn mock_price_view() -> MockPriceView<PostgresDB> {
         let mut price_view: MockPriceView<PostgresDB> = MockPriceView::new();
         price_view
@@ -1,7 +1,9 @@
 use crate::boc::call_contract::CallContractMessage;
 use crate::error::TransactionParsingError;
 use crate::ton_constants::OP_CALL_CONTRACT;
-use crate::transaction_parser::common::{hash_to_message_id, is_log_emmitted_in_opcode};
+use crate::transaction_parser::common::{
+    hash_to_message_id, is_log_emmitted_in_opcode, tx_timestamp,
+};
 use crate::transaction_parser::message_matching_key::MessageMatchingKey;
 use crate::transaction_parser::parser::Parser;
 use crate::types::Transaction;
@@ -12,16 +14,16 @@ use relayer_core::gmp_api::gmp_types::{CommonEventFields, Event, EventMetadata,
 use std::collections::HashMap;
 use tonlib_core::TonAddress;
 
-pub struct ParserCallContract {
+pub struct ParserCallContract<'a> {
     log: Option<CallContractMessage>,
-    tx: Transaction,
+    tx: &'a Transaction,
     allowed_address: TonAddress,
     chain_name: String,
 }
 
-impl ParserCallContract {
+impl<'a> ParserCallContract<'a> {
     pub(crate) async fn new(
-        tx: Transaction,
+        tx: &'a Transaction,
         allowed_address: TonAddress,
         chain_name: String,
     ) -> Result<Self, TransactionParsingError> {
@@ -35,7 +37,7 @@ impl ParserCallContract {
 }
 
 #[async_trait]
-impl Parser for ParserCallContract {
+impl Parser for ParserCallContract<'_> {
     async fn parse(&mut self) -> Result<bool, TransactionParsingError> {
         if self.log.is_none() {
             self.log = Some(
@@ -51,7 +53,7 @@ impl Parser for ParserCallContract {
             return Ok(false);
         }
 
-        is_log_emmitted_in_opcode(&self.tx, OP_CALL_CONTRACT, 0)
+        is_log_emmitted_in_opcode(self.tx, OP_CALL_CONTRACT, 0)
     }
 
     async fn key(&self) -> Result<MessageMatchingKey, TransactionParsingError> {
@@ -69,7 +71,7 @@ impl Parser for ParserCallContract {
     }
 
     async fn event(&self, _: Option<String>) -> Result<Event, TransactionParsingError> {
-        let tx = &self.tx;
+        let tx = self.tx;
         let log = match self.log.clone() {
             Some(log) => log,
             None => return Err(TransactionParsingError::Message("Missing log".to_string())),
@@ -110,8 +112,7 @@ impl Parser for ParserCallContract {
                     from_address: None,
                     finalized: None,
                     source_context: Some(source_context),
-                    timestamp: chrono::Utc::now()
-                        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                    timestamp: tx_timestamp(tx),
                 }),
             },
             message: GatewayV2Message {
@@ -148,7 +149,7 @@ mod tests {
         )
         .unwrap();
 
-        let tx = traces[1].transactions[1].clone();
+        let tx = &traces[1].transactions[1];
         let mut parser = ParserCallContract::new(tx, address.clone(), "ton2".to_string())
             .await
             .unwrap();
@@ -187,6 +188,8 @@ mod tests {
                     meta.tx_id.as_deref(),
                     Some("1gzNp2NZGxr1oXcfCROmhRF07xYdoh7X51CgJA2x/QM=")
                 );
+                assert_eq!(meta.finalized, None);
+                assert_eq!(meta.timestamp, "2025-07-12T16:34:29Z");
             }
             _ => panic!("Expected CallContract event"),
         }
@@ -200,7 +203,7 @@ mod tests {
             "0:00194AAD8E422BEDF43FEE746D6D929D369DBAB25468A69D513706EA6978B63A",
         )
         .unwrap();
-        let tx = traces[1].transactions[0].clone();
+        let tx = &traces[1].transactions[0];
         let mut parser = ParserCallContract::new(tx, address.clone(), "ton2".to_string())
             .await
             .unwrap();
@@ -216,7 +219,7 @@ mod tests {
         )
         .unwrap();
 
-        let tx = traces[1].transactions[1].clone();
+        let tx = &traces[1].transactions[1];
         let mut parser = ParserCallContract::new(tx, address.clone(), "ton2".to_string())
             .await
             .unwrap();
@@ -3,29 +3,35 @@ use crate::error::TransactionParsingError;
 use crate::ton_constants::{
     EXIT_CODE_INSUFFICIENT_GAS, OP_NULLIFIED_SUCCESSFULLY, OP_RELAYER_EXECUTE,
 };
+use crate::transaction_parser::common::tx_timestamp;
 use crate::transaction_parser::message_matching_key::MessageMatchingKey;
 use crate::transaction_parser::parser::Parser;
-use crate::types::Trace;
+use crate::types::{Trace, Transaction};
 use async_trait::async_trait;
-use relayer_core::gmp_api::gmp_types::{CannotExecuteMessageReason, CommonEventFields, Event};
+use relayer_core::gmp_api::gmp_types::{
+    CannotExecuteMessageReason, CommonEventFields, Event, EventMetadata,
+};
 use tonlib_core::TonAddress;
 
-pub struct ParserExecuteInsufficientGas {
+pub struct ParserExecuteInsufficientGas<'a> {
     log: Option<RelayerExecuteWrappedMessage>,
-    trace: Trace,
+    trace: &'a Trace,
+    // Transaction that ran out of gas, once matched
+    failed_tx: Option<&'a Transaction>,
     allowed_address: TonAddress,
     _chain_name: String,
 }
 
-impl ParserExecuteInsufficientGas {
+impl<'a> ParserExecuteInsufficientGas<'a> {
     pub(crate) async fn new(
-        trace: Trace,
+        trace: &'a Trace,
         allowed_address: TonAddress,
         chain_name: String,
     ) -> Result<Self, TransactionParsingError> {
         Ok(Self {
             log: None,
             trace,
+            failed_tx: None,
             allowed_address,
             _chain_name: chain_name,
         })
@@ -33,7 +39,7 @@ impl ParserExecuteInsufficientGas {
 }
 
 #[async_trait]
-impl Parser for ParserExecuteInsufficientGas {
+impl Parser for ParserExecuteInsufficientGas<'_> {
     async fn parse(&mut self) -> Result<bool, TransactionParsingError> {
         if self.log.is_none() {
             self.log = Some(
@@ -62,6 +68,7 @@ impl Parser for ParserExecuteInsufficientGas {
                         .unwrap_or(0) as u32;
 
                     if exit_code == EXIT_CODE_INSUFFICIENT_GAS {
+                        self.failed_tx = Some(tx2);
                         return Ok(true);
                     }
                 }
@@ -74,6 +81,7 @@ impl Parser for ParserExecuteInsufficientGas {
                     if in_msg4.opcode == Some(OP_NULLIFIED_SUCCESSFULLY) {
                         if let Some(action) = &tx4.description.action {
                             if action.result_code == 37 {
+                                self.failed_tx = Some(tx4);
                                 return Ok(true);
                             }
                         }
@@ -99,7 +107,13 @@ impl Parser for ParserExecuteInsufficientGas {
             common: CommonEventFields {
                 r#type: "CANNOT_EXECUTE_MESSAGE/V2".to_owned(),
                 event_id: format!("cannot-execute-task-v2-{}", self.trace.end_lt),
-                meta: None,
+                meta: self.failed_tx.map(|tx| EventMetadata {
+                    tx_id: Some(tx.hash.clone()),
+                    from_address: None,
+                    finalized: None,
+                    source_context: None,
+                    timestamp: tx_timestamp(tx),
+                }),
             },
             message_id: log.message_id,
             source_chain: log.source_chain,
@@ -129,7 +143,7 @@ mod tests {
         )
         .unwrap();
 
-        let tr = traces[14].clone();
+        let tr = &traces[14];
         let mut parser = ParserExecuteInsufficientGas::new(tr, address.clone(), "ton2".to_string())
             .await
             .unwrap();
@@ -137,11 +151,16 @@ mod tests {
         parser.parse().await.unwrap();
         let event = parser.event(None).await.unwrap();
         match event {
-            Event::CannotExecuteMessageV2 { message_id, .. } => {
+            Event::CannotExecuteMessageV2 {
+                common, message_id, ..
+            } => {
                 assert_eq!(
                     message_id,
                     "0x89f3252fb9ad7003c25471685f48d14c842a2910b9386d35f859694babf7b1cf"
                 );
+                let meta = common.meta.unwrap();
+                assert_eq!(meta.tx_id, Some(tr.transactions[2].hash.clone()));
+                assert_eq!(meta.timestamp, tx_timestamp(&tr.transactions[2]));
             }
             _ => panic!("Expected CallContract event"),
         }
@@ -156,7 +175,7 @@ mod tests {
         )
         .unwrap();
 
-        let tr = traces[12].clone();
+        let tr = &traces[12];
         let mut parser = ParserExecuteInsufficientGas::new(tr, address.clone(), "ton2".to_string())
             .await
             .unwrap();
@@ -172,7 +191,7 @@ mod tests {
         )
         .unwrap();
 
-        let tr = traces[15].clone();
+        let tr = &traces[15];
         let mut parser = ParserExecuteInsufficientGas::new(tr, address.clone(), "ton2".to_string())
             .await
             .unwrap();
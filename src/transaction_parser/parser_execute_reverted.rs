@@ -0,0 +1,326 @@
+use crate::boc::relayer_execute_wrapped::RelayerExecuteWrappedMessage;
+use crate::error::TransactionParsingError;
+use crate::ton_constants::OP_RELAYER_EXECUTE;
+use crate::transaction_parser::common::tx_timestamp;
+use crate::transaction_parser::message_chain::{chain_failure, Failure, EXECUTION_OPCODES};
+use crate::transaction_parser::message_matching_key::MessageMatchingKey;
+use crate::transaction_parser::parser::Parser;
+use crate::types::{Trace, Transaction, TransactionMessage};
+use async_trait::async_trait;
+use relayer_core::gmp_api::gmp_types::{
+    Amount, CommonEventFields, Event, EventMetadata, MessageExecutedEventMetadata,
+    MessageExecutionStatus,
+};
+use tonlib_core::TonAddress;
+
+/// Executions that failed for any reason, one parser per relayer execute message, since a wallet
+/// message can carry several. If the destination contract failed, the message is reported as
+/// executed with a reverted status, otherwise as one that can't be executed.
+pub struct ParserExecuteReverted<'a> {
+    log: Option<RelayerExecuteWrappedMessage>,
+    failure: Option<Failure<'a>>,
+    trace: &'a Trace,
+    relayer_execute: &'a TransactionMessage,
+    allowed_address: TonAddress,
+}
+
+impl<'a> ParserExecuteReverted<'a> {
+    pub(crate) async fn new(
+        trace: &'a Trace,
+        relayer_execute: &'a TransactionMessage,
+        allowed_address: TonAddress,
+    ) -> Result<Self, TransactionParsingError> {
+        Ok(Self {
+            log: None,
+            failure: None,
+            trace,
+            relayer_execute,
+            allowed_address,
+        })
+    }
+
+    /// Relayer execute messages the trace sends to the gateway.
+    pub(crate) fn relayer_executes(
+        trace: &'a Trace,
+        gateway: &TonAddress,
+    ) -> Vec<&'a TransactionMessage> {
+        trace
+            .transactions
+            .iter()
+            .flat_map(|tx| tx.out_msgs.iter())
+            .filter(|msg| {
+                msg.opcode == Some(OP_RELAYER_EXECUTE) && msg.destination.as_ref() == Some(gateway)
+            })
+            .collect()
+    }
+
+    /// Transaction that sent the message to `tx`.
+    fn sender(&self, tx: &Transaction) -> Option<&'a Transaction> {
+        let in_msg_hash = &tx.in_msg.as_ref()?.hash;
+        self.trace
+            .transactions
+            .iter()
+            .find(|parent| parent.out_msgs.iter().any(|msg| &msg.hash == in_msg_hash))
+    }
+}
+
+#[async_trait]
+impl Parser for ParserExecuteReverted<'_> {
+    async fn parse(&mut self) -> Result<bool, TransactionParsingError> {
+        if self.log.is_none() {
+            self.log = Some(
+                RelayerExecuteWrappedMessage::from_message_boc_b64(
+                    &self.relayer_execute.message_content.body,
+                )
+                .map_err(|e| TransactionParsingError::BocParsing(e.to_string()))?,
+            );
+        }
+        Ok(true)
+    }
+
+    async fn check_match(&mut self) -> Result<bool, TransactionParsingError> {
+        if self.relayer_execute.opcode != Some(OP_RELAYER_EXECUTE)
+            || self.relayer_execute.destination.as_ref() != Some(&self.allowed_address)
+        {
+            return Ok(false);
+        }
+
+        // Only failures caused by this message, another execution in the same trace may have failed
+        self.failure = chain_failure(self.trace, &self.relayer_execute.hash, &EXECUTION_OPCODES);
+
+        Ok(self.failure.is_some())
+    }
+
+    async fn key(&self) -> Result<MessageMatchingKey, TransactionParsingError> {
+        Err(TransactionParsingError::Message(
+            "Reverted execution has no matching key".to_string(),
+        ))
+    }
+
+    async fn event(&self, _: Option<String>) -> Result<Event, TransactionParsingError> {
+        let log = match self.log.clone() {
+            Some(log) => log,
+            None => return Err(TransactionParsingError::Message("Missing log".to_string())),
+        };
+        let failure = match &self.failure {
+            Some(failure) => failure,
+            None => {
+                return Err(TransactionParsingError::Message(
+                    "Missing failure".to_string(),
+                ))
+            }
+        };
+
+        if !failure.in_destination() {
+            return Ok(Event::CannotExecuteMessageV2 {
+                common: CommonEventFields {
+                    r#type: "CANNOT_EXECUTE_MESSAGE/V2".to_owned(),
+                    event_id: format!("cannot-execute-task-v2-{}", failure.tx.hash),
+                    meta: Some(EventMetadata {
+                        tx_id: Some(failure.tx.hash.clone()),
+                        from_address: None,
+                        finalized: None,
+                        source_context: None,
+                        timestamp: tx_timestamp(failure.tx),
+                    }),
+                },
+                message_id: log.message_id,
+                source_chain: log.source_chain,
+                reason: failure.reason(),
+                details: format!("{}: {}", self.trace.trace_id, failure),
+            });
+        }
+
+        // Same id as the successful event of the gateway transaction, which this one replaces
+        let gateway_tx = self.sender(failure.tx).unwrap_or(failure.tx);
+
+        Ok(Event::MessageExecuted {
+            common: CommonEventFields {
+                r#type: "MESSAGE_EXECUTED".to_owned(),
+                event_id: gateway_tx.hash.clone(),
+                meta: Some(MessageExecutedEventMetadata {
+                    common_meta: EventMetadata {
+                        tx_id: Some(gateway_tx.hash.clone()),
+                        from_address: None,
+                        finalized: None,
+                        source_context: Some(failure.details()),
+                        timestamp: tx_timestamp(gateway_tx),
+                    },
+                    command_id: None,
+                    child_message_ids: None,
+                    revert_reason: Some(failure.to_string()),
+                }),
+            },
+            message_id: log.message_id,
+            source_chain: log.source_chain,
+            status: MessageExecutionStatus::REVERTED,
+            cost: Amount {
+                token_id: None,
+                amount: "0".to_string(),
+            },
+        })
+    }
+
+    async fn message_id(&self) -> Result<Option<String>, TransactionParsingError> {
+        Err(TransactionParsingError::Message(
+            "Reverted execution has no message id of its own".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fixtures::{fixture_reverted_traces, fixture_traces};
+    use relayer_core::gmp_api::gmp_types::CannotExecuteMessageReason;
+
+    async fn parser_for<'a>(
+        tr: &'a Trace,
+        address: &TonAddress,
+        index: usize,
+    ) -> ParserExecuteReverted<'a> {
+        let relayer_executes = ParserExecuteReverted::relayer_executes(tr, address);
+        ParserExecuteReverted::new(tr, relayer_executes[index], address.clone())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_reverted_in_destination() {
+        let traces = fixture_reverted_traces();
+        let tr = &traces[0];
+        let address = tr.transactions[2].account.clone();
+
+        let mut parser = parser_for(tr, &address, 0).await;
+        assert!(parser.check_match().await.unwrap());
+        parser.parse().await.unwrap();
+        assert!(parser.key().await.is_err());
+        assert!(parser.message_id().await.is_err());
+
+        match parser.event(None).await.unwrap() {
+            Event::MessageExecuted {
+                common,
+                message_id,
+                status,
+                ..
+            } => {
+                assert_eq!(common.event_id, tr.transactions[4].hash);
+                assert_eq!(status, MessageExecutionStatus::REVERTED);
+                assert!(message_id.starts_with("0x"));
+
+                let meta = common.meta.unwrap();
+                assert!(meta.revert_reason.unwrap().contains("exit code 9"));
+                let context = meta.common_meta.source_context.unwrap();
+                assert_eq!(context.get("exit_code").unwrap(), "9");
+                assert_eq!(
+                    context.get("failing_account").unwrap(),
+                    &tr.transactions[5].account.to_hex()
+                );
+                assert_eq!(context.get("failing_tx").unwrap(), &tr.transactions[5].hash);
+            }
+            _ => panic!("Expected MessageExecuted event"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bounced_by_gateway() {
+        let traces = fixture_reverted_traces();
+        let tr = &traces[1];
+        let address = tr.transactions[2].account.clone();
+
+        let mut parser = parser_for(tr, &address, 0).await;
+        assert!(parser.check_match().await.unwrap());
+        parser.parse().await.unwrap();
+
+        match parser.event(None).await.unwrap() {
+            Event::CannotExecuteMessageV2 {
+                reason, details, ..
+            } => {
+                assert!(matches!(reason, CannotExecuteMessageReason::Error));
+                assert!(details.starts_with("execute-bounced: "));
+                assert!(details.contains("compute phase exit code 9"));
+                assert!(details.contains(&address.to_hex()));
+            }
+            _ => panic!("Expected CannotExecuteMessageV2 event"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_insufficient_gas_mapped() {
+        let traces = fixture_traces();
+
+        for tr in [&traces[14], &traces[15]] {
+            let address = tr.transactions[2].account.clone();
+            let mut parser = parser_for(tr, &address, 0).await;
+            assert!(parser.check_match().await.unwrap());
+            parser.parse().await.unwrap();
+
+            match parser.event(None).await.unwrap() {
+                Event::CannotExecuteMessageV2 { reason, .. } => {
+                    assert!(matches!(
+                        reason,
+                        CannotExecuteMessageReason::InsufficientGas
+                    ));
+                }
+                _ => panic!("Expected CannotExecuteMessageV2 event"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_no_match() {
+        let traces = fixture_traces();
+
+        // Successful executions, with failures outside the execution
+        for tr in [&traces[11], &traces[17]] {
+            let address = tr.transactions[2].account.clone();
+            let mut parser = parser_for(tr, &address, 0).await;
+            assert!(!parser.check_match().await.unwrap());
+        }
+
+        // Not an execution
+        let tr = &traces[19];
+        let address = tr.transactions[1].account.clone();
+        assert!(ParserExecuteReverted::relayer_executes(tr, &address).is_empty());
+        let mut parser = ParserExecuteReverted::new(tr, &tr.transactions[0].out_msgs[0], address)
+            .await
+            .unwrap();
+        assert!(!parser.check_match().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_batched_executions() {
+        let traces = fixture_reverted_traces();
+        let tr = &traces[2];
+        let address = tr.transactions[2].account.clone();
+        assert_eq!(
+            ParserExecuteReverted::relayer_executes(tr, &address).len(),
+            2
+        );
+
+        // The first execution succeeded
+        let mut parser = parser_for(tr, &address, 0).await;
+        assert!(!parser.check_match().await.unwrap());
+
+        let mut parser = parser_for(tr, &address, 1).await;
+        assert!(parser.check_match().await.unwrap());
+        parser.parse().await.unwrap();
+
+        match parser.event(None).await.unwrap() {
+            Event::MessageExecuted {
+                common,
+                message_id,
+                status,
+                ..
+            } => {
+                assert_eq!(status, MessageExecutionStatus::REVERTED);
+                assert_eq!(
+                    message_id,
+                    "0x584defcdc90f2df657b3acfe484f8e9a187945acbeb492837a42918c6ea2e009"
+                );
+                assert_eq!(common.event_id, tr.transactions[7].hash);
+            }
+            _ => panic!("Expected MessageExecuted event"),
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use crate::boc::its_interchain_token_deployment_started::LogITSInterchainTokenDeploymentStartedMessage;
 use crate::error::TransactionParsingError;
 use crate::ton_constants::OP_INTERCHAIN_TOKEN_DEPLOYMENT_STARTED_LOG;
-use crate::transaction_parser::common::is_log_emitted;
+use crate::transaction_parser::common::{call_contract_request_key, is_log_emitted, tx_timestamp};
 use crate::transaction_parser::message_matching_key::MessageMatchingKey;
 use crate::transaction_parser::parser::Parser;
 use crate::types::Transaction;
@@ -11,16 +11,16 @@ use relayer_core::gmp_api::gmp_types::{
 };
 use tonlib_core::TonAddress;
 
-pub struct ParserITSInterchainTokenDeploymentStarted {
+pub struct ParserITSInterchainTokenDeploymentStarted<'a> {
     log: Option<LogITSInterchainTokenDeploymentStartedMessage>,
-    tx: Transaction,
+    tx: &'a Transaction,
     allowed_address: TonAddress,
     log_position: isize,
 }
 
-impl ParserITSInterchainTokenDeploymentStarted {
+impl<'a> ParserITSInterchainTokenDeploymentStarted<'a> {
     pub(crate) async fn new(
-        tx: Transaction,
+        tx: &'a Transaction,
         allowed_address: TonAddress,
     ) -> Result<Self, TransactionParsingError> {
         Ok(Self {
@@ -33,7 +33,7 @@ impl ParserITSInterchainTokenDeploymentStarted {
 }
 
 #[async_trait]
-impl Parser for ParserITSInterchainTokenDeploymentStarted {
+impl Parser for ParserITSInterchainTokenDeploymentStarted<'_> {
     async fn parse(&mut self) -> Result<bool, TransactionParsingError> {
         if self.log_position == -1 {
             return Ok(false);
@@ -56,7 +56,7 @@ impl Parser for ParserITSInterchainTokenDeploymentStarted {
         if self.tx.account != self.allowed_address {
             return Ok(false);
         }
-        let pos = is_log_emitted(&self.tx, OP_INTERCHAIN_TOKEN_DEPLOYMENT_STARTED_LOG)?;
+        let pos = is_log_emitted(self.tx, OP_INTERCHAIN_TOKEN_DEPLOYMENT_STARTED_LOG)?;
         if pos >= 0 {
             self.log_position = pos;
             return Ok(true);
@@ -66,11 +66,11 @@ impl Parser for ParserITSInterchainTokenDeploymentStarted {
     }
 
     async fn key(&self) -> Result<MessageMatchingKey, TransactionParsingError> {
-        unimplemented!()
+        call_contract_request_key(self.tx)
     }
 
     async fn event(&self, message_id: Option<String>) -> Result<Event, TransactionParsingError> {
-        let tx = &self.tx;
+        let tx = self.tx;
 
         let message_id = if let Some(id) = message_id {
             id
@@ -97,8 +97,7 @@ impl Parser for ParserITSInterchainTokenDeploymentStarted {
                     from_address: None,
                     finalized: None,
                     source_context: None,
-                    timestamp: chrono::Utc::now()
-                        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                    timestamp: tx_timestamp(tx),
                 }),
             },
             message_id,
@@ -127,8 +126,8 @@ mod tests {
     async fn test_parser() {
         let traces = fixture_traces();
 
-        let tx = traces[20].transactions[3].clone();
-        let address = tx.clone().account;
+        let tx = &traces[20].transactions[3];
+        let address = tx.account.clone();
 
         let mut parser = ParserITSInterchainTokenDeploymentStarted::new(tx, address)
             .await
@@ -174,7 +173,7 @@ mod tests {
             "0:0000000000000000000000000000000000000000000000000000000000000000",
         )
         .unwrap();
-        let tx = traces[20].transactions[1].clone();
+        let tx = &traces[20].transactions[1];
         let mut parser = ParserITSInterchainTokenDeploymentStarted::new(tx, address.clone())
             .await
             .unwrap();
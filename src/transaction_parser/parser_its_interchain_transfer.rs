@@ -2,7 +2,7 @@ use crate::boc::its_interchain_transfer::LogITSInterchainTransferMessage;
 use crate::error::TransactionParsingError;
 use crate::hashing::payload_hash;
 use crate::ton_constants::OP_INTERCHAIN_TRANSFER_LOG;
-use crate::transaction_parser::common::is_log_emitted;
+use crate::transaction_parser::common::{call_contract_request_key, is_log_emitted, tx_timestamp};
 use crate::transaction_parser::message_matching_key::MessageMatchingKey;
 use crate::transaction_parser::parser::Parser;
 use crate::types::Transaction;
@@ -10,16 +10,16 @@ use async_trait::async_trait;
 use relayer_core::gmp_api::gmp_types::{Amount, CommonEventFields, Event, EventMetadata};
 use tonlib_core::TonAddress;
 
-pub struct ParserITSInterchainTransfer {
+pub struct ParserITSInterchainTransfer<'a> {
     log: Option<LogITSInterchainTransferMessage>,
-    tx: Transaction,
+    tx: &'a Transaction,
     allowed_address: TonAddress,
     log_position: isize,
 }
 
-impl ParserITSInterchainTransfer {
+impl<'a> ParserITSInterchainTransfer<'a> {
     pub(crate) async fn new(
-        tx: Transaction,
+        tx: &'a Transaction,
         allowed_address: TonAddress,
     ) -> Result<Self, TransactionParsingError> {
         Ok(Self {
@@ -32,7 +32,7 @@ impl ParserITSInterchainTransfer {
 }
 
 #[async_trait]
-impl Parser for ParserITSInterchainTransfer {
+impl Parser for ParserITSInterchainTransfer<'_> {
     async fn parse(&mut self) -> Result<bool, TransactionParsingError> {
         if self.log_position < 0 {
             return Ok(false);
@@ -55,7 +55,7 @@ impl Parser for ParserITSInterchainTransfer {
             return Ok(false);
         }
 
-        let pos = is_log_emitted(&self.tx, OP_INTERCHAIN_TRANSFER_LOG)?;
+        let pos = is_log_emitted(self.tx, OP_INTERCHAIN_TRANSFER_LOG)?;
         if pos >= 0 {
             self.log_position = pos;
             return Ok(true);
@@ -65,11 +65,11 @@ impl Parser for ParserITSInterchainTransfer {
     }
 
     async fn key(&self) -> Result<MessageMatchingKey, TransactionParsingError> {
-        unimplemented!()
+        call_contract_request_key(self.tx)
     }
 
     async fn event(&self, message_id: Option<String>) -> Result<Event, TransactionParsingError> {
-        let tx = &self.tx;
+        let tx = self.tx;
 
         let message_id = if let Some(id) = message_id {
             id
@@ -99,8 +99,7 @@ impl Parser for ParserITSInterchainTransfer {
                     from_address: None,
                     finalized: None,
                     source_context: None,
-                    timestamp: chrono::Utc::now()
-                        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                    timestamp: tx_timestamp(tx),
                 }),
             },
             message_id,
@@ -130,8 +129,8 @@ mod tests {
     async fn test_parser() {
         let traces = fixture_traces();
 
-        let tx = traces[21].transactions[5].clone();
-        let address = tx.clone().account;
+        let tx = &traces[21].transactions[5];
+        let address = tx.account.clone();
 
         let mut parser = ParserITSInterchainTransfer::new(tx, address).await.unwrap();
 
@@ -181,8 +180,8 @@ mod tests {
     async fn test_parser_different_position() {
         let traces = fixture_traces();
 
-        let tx = traces[23].transactions[7].clone();
-        let address = tx.clone().account;
+        let tx = &traces[23].transactions[7];
+        let address = tx.account.clone();
 
         let mut parser = ParserITSInterchainTransfer::new(tx, address).await.unwrap();
 
@@ -236,7 +235,7 @@ mod tests {
             "0:0000000000000000000000000000000000000000000000000000000000000000",
         )
         .unwrap();
-        let tx = traces[20].transactions[3].clone();
+        let tx = &traces[20].transactions[3];
         let mut parser = ParserITSInterchainTransfer::new(tx, address.clone())
             .await
             .unwrap();
@@ -0,0 +1,165 @@
+use crate::boc::its_interchain_transfer_received::LogITSInterchainTransferReceivedMessage;
+use crate::error::TransactionParsingError;
+use crate::hashing::payload_hash;
+use crate::ton_constants::OP_INTERCHAIN_TRANSFER_RECEIVED_LOG;
+use crate::transaction_parser::common::is_log_emitted;
+use crate::transaction_parser::message_matching_key::MessageMatchingKey;
+use crate::transaction_parser::parser::Parser;
+use crate::types::Transaction;
+use async_trait::async_trait;
+use relayer_core::gmp_api::gmp_types::Event;
+use std::collections::HashMap;
+use tonlib_core::TonAddress;
+
+/// Inbound ITS transfer. It emits no event of its own, its details are added to the
+/// `MessageExecuted` event of the execution.
+pub struct ParserITSInterchainTransferReceived<'a> {
+    log: Option<LogITSInterchainTransferReceivedMessage>,
+    tx: &'a Transaction,
+    allowed_address: TonAddress,
+    log_position: isize,
+}
+
+impl<'a> ParserITSInterchainTransferReceived<'a> {
+    pub(crate) async fn new(
+        tx: &'a Transaction,
+        allowed_address: TonAddress,
+    ) -> Result<Self, TransactionParsingError> {
+        Ok(Self {
+            log: None,
+            tx,
+            allowed_address,
+            log_position: -1,
+        })
+    }
+}
+
+#[async_trait]
+impl Parser for ParserITSInterchainTransferReceived<'_> {
+    async fn parse(&mut self) -> Result<bool, TransactionParsingError> {
+        if self.log_position < 0 {
+            return Ok(false);
+        }
+        if self.log.is_none() {
+            let out_msg = self
+                .tx
+                .out_msgs
+                .get(self.log_position as usize)
+                .ok_or_else(|| TransactionParsingError::Message("Missing log".to_string()))?;
+            self.log = Some(
+                LogITSInterchainTransferReceivedMessage::from_boc_b64(
+                    &out_msg.message_content.body,
+                )
+                .map_err(|e| TransactionParsingError::BocParsing(e.to_string()))?,
+            );
+        }
+        Ok(true)
+    }
+
+    async fn check_match(&mut self) -> Result<bool, TransactionParsingError> {
+        if self.tx.account != self.allowed_address {
+            return Ok(false);
+        }
+
+        let pos = is_log_emitted(self.tx, OP_INTERCHAIN_TRANSFER_RECEIVED_LOG)?;
+        if pos >= 0 {
+            self.log_position = pos;
+            return Ok(true);
+        };
+
+        Ok(false)
+    }
+
+    async fn key(&self) -> Result<MessageMatchingKey, TransactionParsingError> {
+        Err(TransactionParsingError::Message(
+            "Interchain transfer received has no matching key".to_string(),
+        ))
+    }
+
+    async fn event(&self, _: Option<String>) -> Result<Event, TransactionParsingError> {
+        Err(TransactionParsingError::Message(
+            "Interchain transfer received has no event of its own".to_string(),
+        ))
+    }
+
+    async fn message_id(&self) -> Result<Option<String>, TransactionParsingError> {
+        Ok(None)
+    }
+
+    async fn execution_details(&self) -> Result<HashMap<String, String>, TransactionParsingError> {
+        let log = match self.log.clone() {
+            Some(log) => log,
+            None => return Err(TransactionParsingError::Message("Missing log".to_string())),
+        };
+
+        let data_hash = if log.data.is_empty() {
+            "0".repeat(32)
+        } else {
+            payload_hash(&log.data).to_string()
+        };
+
+        Ok(HashMap::from([
+            (
+                "its_token_id".to_owned(),
+                format!("0x{}", log.token_id.to_str_radix(16)),
+            ),
+            ("its_amount".to_owned(), log.jetton_amount.to_string()),
+            ("its_source_chain".to_owned(), log.source_chain),
+            ("its_source_address".to_owned(), log.source_address),
+            ("its_recipient".to_owned(), log.recipient.to_hex()),
+            ("its_data_hash".to_owned(), data_hash),
+            ("its_tx_id".to_owned(), self.tx.hash.clone()),
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fixtures::{fixture_its_traces, fixture_traces};
+
+    #[tokio::test]
+    async fn test_parser() {
+        let traces = fixture_its_traces();
+
+        let tx = &traces[0].transactions[5];
+        let address = tx.account.clone();
+
+        let mut parser = ParserITSInterchainTransferReceived::new(tx, address)
+            .await
+            .unwrap();
+        assert!(parser.check_match().await.unwrap());
+        assert!(parser.parse().await.unwrap());
+        assert!(parser.event(None).await.is_err());
+        assert!(parser.key().await.is_err());
+
+        let details = parser.execution_details().await.unwrap();
+        assert_eq!(
+            details.get("its_token_id").unwrap(),
+            "0x6aa24f155d481a7dfb355504941e4650d520387c0e4ad4ccd467880c2ea0953"
+        );
+        assert_eq!(details.get("its_amount").unwrap(), "1000000");
+        assert_eq!(details.get("its_source_chain").unwrap(), "avalanche-fuji");
+        assert_eq!(
+            details.get("its_source_address").unwrap(),
+            "0x1234567890123456789012345678901234567890"
+        );
+        assert_eq!(
+            details.get("its_recipient").unwrap(),
+            "0:1fe0fa0e78288928ca05908c617fb90bdc7edde429e146ca048bdcd31c890e76"
+        );
+        assert_eq!(details.get("its_tx_id").unwrap(), &tx.hash);
+    }
+
+    #[tokio::test]
+    async fn test_no_match() {
+        let traces = fixture_traces();
+
+        let tx = &traces[21].transactions[0];
+        let address = tx.account.clone();
+        let mut parser = ParserITSInterchainTransferReceived::new(tx, address)
+            .await
+            .unwrap();
+        assert!(!parser.check_match().await.unwrap());
+    }
+}
@@ -1,7 +1,7 @@
 use crate::boc::its_link_token_started::LogITSLinkTokenStartedMessage;
 use crate::error::TransactionParsingError;
 use crate::ton_constants::OP_LINK_TOKEN_STARTED_LOG;
-use crate::transaction_parser::common::is_log_emitted;
+use crate::transaction_parser::common::{call_contract_request_key, is_log_emitted, tx_timestamp};
 use crate::transaction_parser::message_matching_key::MessageMatchingKey;
 use crate::transaction_parser::parser::Parser;
 use async_trait::async_trait;
@@ -9,16 +9,16 @@ use relayer_core::gmp_api::gmp_types::{CommonEventFields, Event, EventMetadata};
 use crate::types::Transaction;
 use tonlib_core::TonAddress;
 
-pub struct ParserITSLinkTokenStarted {
+pub struct ParserITSLinkTokenStarted<'a> {
     log: Option<LogITSLinkTokenStartedMessage>,
-    tx: Transaction,
+    tx: &'a Transaction,
     allowed_address: TonAddress,
     log_position: isize,
 }
 
-impl ParserITSLinkTokenStarted {
+impl<'a> ParserITSLinkTokenStarted<'a> {
     pub(crate) async fn new(
-        tx: Transaction,
+        tx: &'a Transaction,
         allowed_address: TonAddress,
     ) -> Result<Self, TransactionParsingError> {
         Ok(Self {
@@ -31,7 +31,7 @@ impl ParserITSLinkTokenStarted {
 }
 
 #[async_trait]
-impl Parser for ParserITSLinkTokenStarted {
+impl Parser for ParserITSLinkTokenStarted<'_> {
     async fn parse(&mut self) -> Result<bool, TransactionParsingError> {
         if self.log_position < 0 {
             return Ok(false);
@@ -55,7 +55,7 @@ impl Parser for ParserITSLinkTokenStarted {
             return Ok(false);
         }
 
-        let pos = is_log_emitted(&self.tx, OP_LINK_TOKEN_STARTED_LOG)?;
+        let pos = is_log_emitted(self.tx, OP_LINK_TOKEN_STARTED_LOG)?;
         if pos >= 0 {
             self.log_position = pos;
             return Ok(true);
@@ -65,11 +65,11 @@ impl Parser for ParserITSLinkTokenStarted {
     }
 
     async fn key(&self) -> Result<MessageMatchingKey, TransactionParsingError> {
-        unimplemented!()
+        call_contract_request_key(self.tx)
     }
 
     async fn event(&self, message_id: Option<String>) -> Result<Event, TransactionParsingError> {
-        let tx = &self.tx;
+        let tx = self.tx;
 
         let message_id = if let Some(id) = message_id {
             id
@@ -100,8 +100,7 @@ impl Parser for ParserITSLinkTokenStarted {
                     from_address: None,
                     finalized: None,
                     source_context: None,
-                    timestamp: chrono::Utc::now()
-                        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                    timestamp: tx_timestamp(tx),
                 }),
             },
             message_id,
@@ -129,8 +128,8 @@ mod tests {
     async fn test_parser() {
         let traces = fixture_traces();
 
-        let tx = traces[22].transactions[3].clone();
-        let address = tx.clone().account;
+        let tx = &traces[22].transactions[3];
+        let address = tx.account.clone();
 
         let mut parser = ParserITSLinkTokenStarted::new(tx, address).await.unwrap();
 
@@ -182,7 +181,7 @@ mod tests {
             "0:0000000000000000000000000000000000000000000000000000000000000000",
         )
         .unwrap();
-        let tx = traces[20].transactions[3].clone();
+        let tx = &traces[20].transactions[3];
         let mut parser = ParserITSLinkTokenStarted::new(tx, address.clone())
             .await
             .unwrap();
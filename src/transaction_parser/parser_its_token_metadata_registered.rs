@@ -1,7 +1,7 @@
 use crate::boc::its_token_metadata_registered::LogTokenMetadataRegisteredMessage;
 use crate::error::TransactionParsingError;
 use crate::ton_constants::OP_REGISTER_TOKEN_METADATA;
-use crate::transaction_parser::common::is_log_emmitted_in_opcode;
+use crate::transaction_parser::common::{call_contract_request_key, is_log_emmitted_in_opcode, tx_timestamp};
 use crate::transaction_parser::message_matching_key::MessageMatchingKey;
 use crate::transaction_parser::parser::Parser;
 use async_trait::async_trait;
@@ -9,15 +9,15 @@ use relayer_core::gmp_api::gmp_types::{CommonEventFields, Event, EventMetadata};
 use crate::types::Transaction;
 use tonlib_core::TonAddress;
 
-pub struct ParserITSTokenMetadataRegistered {
+pub struct ParserITSTokenMetadataRegistered<'a> {
     log: Option<LogTokenMetadataRegisteredMessage>,
-    tx: Transaction,
+    tx: &'a Transaction,
     allowed_address: TonAddress,
 }
 
-impl ParserITSTokenMetadataRegistered {
+impl<'a> ParserITSTokenMetadataRegistered<'a> {
     pub(crate) async fn new(
-        tx: Transaction,
+        tx: &'a Transaction,
         allowed_address: TonAddress,
     ) -> Result<Self, TransactionParsingError> {
         Ok(Self {
@@ -29,7 +29,7 @@ impl ParserITSTokenMetadataRegistered {
 }
 
 #[async_trait]
-impl Parser for ParserITSTokenMetadataRegistered {
+impl Parser for ParserITSTokenMetadataRegistered<'_> {
     async fn parse(&mut self) -> Result<bool, TransactionParsingError> {
         if self.log.is_none() {
             self.log = Some(
@@ -47,15 +47,15 @@ impl Parser for ParserITSTokenMetadataRegistered {
             return Ok(false);
         }
 
-        is_log_emmitted_in_opcode(&self.tx, OP_REGISTER_TOKEN_METADATA, 0)
+        is_log_emmitted_in_opcode(self.tx, OP_REGISTER_TOKEN_METADATA, 0)
     }
 
     async fn key(&self) -> Result<MessageMatchingKey, TransactionParsingError> {
-        unimplemented!()
+        call_contract_request_key(self.tx)
     }
 
     async fn event(&self, message_id: Option<String>) -> Result<Event, TransactionParsingError> {
-        let tx = &self.tx;
+        let tx = self.tx;
 
         let message_id = if let Some(id) = message_id {
             id
@@ -86,8 +86,7 @@ impl Parser for ParserITSTokenMetadataRegistered {
                     from_address: None,
                     finalized: None,
                     source_context: None,
-                    timestamp: chrono::Utc::now()
-                        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                    timestamp: tx_timestamp(tx),
                 }),
             },
             message_id,
@@ -105,14 +104,15 @@ impl Parser for ParserITSTokenMetadataRegistered {
 mod tests {
     use super::*;
     use crate::test_utils::fixtures::fixture_traces;
+    use crate::transaction_parser::parser_call_contract::ParserCallContract;
     use crate::transaction_parser::parser_its_token_metadata_registered::ParserITSTokenMetadataRegistered;
 
     #[tokio::test]
     async fn test_parser() {
         let traces = fixture_traces();
 
-        let tx = traces[19].transactions[1].clone();
-        let address = tx.clone().account;
+        let tx = &traces[19].transactions[1];
+        let address = tx.account.clone();
 
         let mut parser = ParserITSTokenMetadataRegistered::new(tx, address)
             .await
@@ -147,6 +147,32 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_key_matches_call_contract() {
+        let traces = fixture_traces();
+
+        let tx = &traces[19].transactions[1];
+        let parser = ParserITSTokenMetadataRegistered::new(tx, tx.account.clone())
+            .await
+            .unwrap();
+
+        let gateway_tx = &traces[19].transactions[2];
+        let mut call_contract = ParserCallContract::new(
+            gateway_tx,
+            gateway_tx.account.clone(),
+            "ton2".to_string(),
+        )
+        .await
+        .unwrap();
+        assert!(call_contract.check_match().await.unwrap());
+        call_contract.parse().await.unwrap();
+
+        assert_eq!(
+            parser.key().await.unwrap(),
+            call_contract.key().await.unwrap()
+        );
+    }
+
     #[tokio::test]
     async fn test_no_match() {
         let traces = fixture_traces();
@@ -155,7 +181,7 @@ mod tests {
             "0:0000000000000000000000000000000000000000000000000000000000000000",
         )
         .unwrap();
-        let tx = traces[10].transactions[3].clone();
+        let tx = &traces[10].transactions[3];
         let mut parser = ParserITSTokenMetadataRegistered::new(tx, address.clone())
             .await
             .unwrap();
@@ -1,7 +1,7 @@
 use crate::boc::jetton_gas_added::JettonGasAddedMessage;
 use crate::error::TransactionParsingError;
 use crate::ton_constants::OP_TRANSFER_NOTIFICATION;
-use crate::transaction_parser::common::is_log_emmitted_in_opcode;
+use crate::transaction_parser::common::{is_log_emmitted_in_opcode, tx_timestamp};
 use crate::transaction_parser::message_matching_key::MessageMatchingKey;
 use crate::transaction_parser::parser::Parser;
 use async_trait::async_trait;
@@ -9,15 +9,15 @@ use relayer_core::gmp_api::gmp_types::{Amount, CommonEventFields, Event, EventMe
 use crate::types::Transaction;
 use tonlib_core::TonAddress;
 
-pub struct ParserJettonGasAdded {
+pub struct ParserJettonGasAdded<'a> {
     log: Option<JettonGasAddedMessage>,
-    tx: Transaction,
+    tx: &'a Transaction,
     allowed_address: TonAddress,
 }
 
-impl ParserJettonGasAdded {
+impl<'a> ParserJettonGasAdded<'a> {
     pub(crate) async fn new(
-        tx: Transaction,
+        tx: &'a Transaction,
         allowed_address: TonAddress,
     ) -> Result<Self, TransactionParsingError> {
         Ok(Self {
@@ -29,7 +29,7 @@ impl ParserJettonGasAdded {
 }
 
 #[async_trait]
-impl Parser for ParserJettonGasAdded {
+impl Parser for ParserJettonGasAdded<'_> {
     async fn parse(&mut self) -> Result<bool, TransactionParsingError> {
         if self.log.is_none() {
             self.log = Some(
@@ -45,7 +45,7 @@ impl Parser for ParserJettonGasAdded {
             return Ok(false);
         }
 
-        let candidate = is_log_emmitted_in_opcode(&self.tx, OP_TRANSFER_NOTIFICATION, 0)?;
+        let candidate = is_log_emmitted_in_opcode(self.tx, OP_TRANSFER_NOTIFICATION, 0)?;
 
         if !candidate {
             return Ok(false);
@@ -60,7 +60,7 @@ impl Parser for ParserJettonGasAdded {
     }
 
     async fn event(&self, _: Option<String>) -> Result<Event, TransactionParsingError> {
-        let tx = &self.tx;
+        let tx = self.tx;
         let log = match self.log.clone() {
             Some(log) => log,
             None => return Err(TransactionParsingError::Message("Missing log".to_string())),
@@ -83,8 +83,7 @@ impl Parser for ParserJettonGasAdded {
                     from_address: None,
                     finalized: None,
                     source_context: None,
-                    timestamp: chrono::Utc::now()
-                        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                    timestamp: tx_timestamp(tx),
                 }),
             },
             message_id,
@@ -117,8 +116,8 @@ mod tests {
     async fn test_parser() {
         let traces = fixture_traces();
 
-        let tx = traces[10].transactions[3].clone();
-        let address = tx.clone().account;
+        let tx = &traces[10].transactions[3];
+        let address = tx.account.clone();
         let mut parser = ParserJettonGasAdded::new(tx, address).await.unwrap();
         assert!(parser.check_match().await.unwrap());
         parser.parse().await.unwrap();
@@ -162,7 +161,7 @@ mod tests {
             "0:0000000000000000000000000000000000000000000000000000000000000000",
         )
         .unwrap();
-        let tx = traces[1].transactions[0].clone();
+        let tx = &traces[1].transactions[0];
         let mut parser = ParserJettonGasAdded::new(tx, address.clone())
             .await
             .unwrap();
@@ -1,7 +1,7 @@
 use crate::boc::jetton_gas_paid::JettonGasPaidMessage;
 use crate::error::TransactionParsingError;
 use crate::ton_constants::OP_TRANSFER_NOTIFICATION;
-use crate::transaction_parser::common::is_log_emmitted_in_opcode;
+use crate::transaction_parser::common::{is_log_emmitted_in_opcode, tx_timestamp};
 use crate::transaction_parser::message_matching_key::MessageMatchingKey;
 use crate::transaction_parser::parser::Parser;
 use async_trait::async_trait;
@@ -9,15 +9,15 @@ use relayer_core::gmp_api::gmp_types::{Amount, CommonEventFields, Event, EventMe
 use crate::types::Transaction;
 use tonlib_core::TonAddress;
 
-pub struct ParserJettonGasPaid {
+pub struct ParserJettonGasPaid<'a> {
     log: Option<JettonGasPaidMessage>,
-    tx: Transaction,
+    tx: &'a Transaction,
     allowed_address: TonAddress,
 }
 
-impl ParserJettonGasPaid {
+impl<'a> ParserJettonGasPaid<'a> {
     pub(crate) async fn new(
-        tx: Transaction,
+        tx: &'a Transaction,
         allowed_address: TonAddress,
     ) -> Result<Self, TransactionParsingError> {
         Ok(Self {
@@ -29,7 +29,7 @@ impl ParserJettonGasPaid {
 }
 
 #[async_trait]
-impl Parser for ParserJettonGasPaid {
+impl Parser for ParserJettonGasPaid<'_> {
     async fn parse(&mut self) -> Result<bool, TransactionParsingError> {
         if self.log.is_none() {
             self.log = Some(
@@ -45,7 +45,7 @@ impl Parser for ParserJettonGasPaid {
             return Ok(false);
         }
 
-        let candidate = is_log_emmitted_in_opcode(&self.tx, OP_TRANSFER_NOTIFICATION, 0)?;
+        let candidate = is_log_emmitted_in_opcode(self.tx, OP_TRANSFER_NOTIFICATION, 0)?;
 
         if !candidate {
             return Ok(false);
@@ -70,7 +70,7 @@ impl Parser for ParserJettonGasPaid {
     }
 
     async fn event(&self, message_id: Option<String>) -> Result<Event, TransactionParsingError> {
-        let tx = &self.tx;
+        let tx = self.tx;
 
         let message_id = if let Some(id) = message_id {
             id
@@ -94,8 +94,7 @@ impl Parser for ParserJettonGasPaid {
                     from_address: None,
                     finalized: None,
                     source_context: None,
-                    timestamp: chrono::Utc::now()
-                        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                    timestamp: tx_timestamp(tx),
                 }),
             },
             message_id,
@@ -122,8 +121,8 @@ mod tests {
     async fn test_parser() {
         let traces = fixture_traces();
 
-        let tx = traces[9].transactions[3].clone();
-        let address = tx.clone().account;
+        let tx = &traces[9].transactions[3];
+        let address = tx.account.clone();
 
         let mut parser = ParserJettonGasPaid::new(tx, address).await.unwrap();
         assert!(parser.check_match().await.unwrap());
@@ -162,7 +161,7 @@ mod tests {
             "0:0000000000000000000000000000000000000000000000000000000000000000",
         )
         .unwrap();
-        let tx = traces[10].transactions[3].clone();
+        let tx = &traces[10].transactions[3];
         let mut parser = ParserJettonGasPaid::new(tx, address.clone()).await.unwrap();
         assert!(!parser.check_match().await.unwrap());
     }
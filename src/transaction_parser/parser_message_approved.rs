@@ -1,7 +1,7 @@
 use crate::boc::cc_message::TonCCMessage;
 use crate::error::TransactionParsingError;
 use crate::ton_constants::OP_MESSAGE_APPROVED;
-use crate::transaction_parser::common::is_log_emmitted_in_opcode;
+use crate::transaction_parser::common::{is_log_emmitted_in_opcode, tx_timestamp};
 use crate::transaction_parser::message_matching_key::MessageMatchingKey;
 use crate::transaction_parser::parser::Parser;
 use crate::types::Transaction;
@@ -11,15 +11,15 @@ use relayer_core::gmp_api::gmp_types::{
 };
 use tonlib_core::TonAddress;
 
-pub struct ParserMessageApproved {
+pub struct ParserMessageApproved<'a> {
     log: Option<TonCCMessage>,
-    tx: Transaction,
+    tx: &'a Transaction,
     allowed_address: TonAddress,
 }
 
-impl ParserMessageApproved {
+impl<'a> ParserMessageApproved<'a> {
     pub(crate) async fn new(
-        tx: Transaction,
+        tx: &'a Transaction,
         allowed_address: TonAddress,
     ) -> Result<Self, TransactionParsingError> {
         Ok(Self {
@@ -31,7 +31,7 @@ impl ParserMessageApproved {
 }
 
 #[async_trait]
-impl Parser for ParserMessageApproved {
+impl Parser for ParserMessageApproved<'_> {
     async fn parse(&mut self) -> Result<bool, TransactionParsingError> {
         if self.log.is_none() {
             self.log = Some(
@@ -47,7 +47,7 @@ impl Parser for ParserMessageApproved {
             return Ok(false);
         }
 
-        is_log_emmitted_in_opcode(&self.tx, OP_MESSAGE_APPROVED, 0)
+        is_log_emmitted_in_opcode(self.tx, OP_MESSAGE_APPROVED, 0)
     }
 
     async fn key(&self) -> Result<MessageMatchingKey, TransactionParsingError> {
@@ -65,7 +65,7 @@ impl Parser for ParserMessageApproved {
     }
 
     async fn event(&self, _: Option<String>) -> Result<Event, TransactionParsingError> {
-        let tx = &self.tx;
+        let tx = self.tx;
         let log = match self.log.clone() {
             Some(log) => log,
             None => return Err(TransactionParsingError::Message("Missing log".to_string())),
@@ -81,8 +81,7 @@ impl Parser for ParserMessageApproved {
                         from_address: None,
                         finalized: None,
                         source_context: None,
-                        timestamp: chrono::Utc::now()
-                            .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                        timestamp: tx_timestamp(tx),
                     },
                     command_id: None,
                 }),
@@ -116,8 +115,8 @@ mod tests {
     async fn test_parser() {
         let traces = fixture_traces();
 
-        let tx = traces[0].transactions[0].clone();
-        let address = tx.clone().account;
+        let tx = &traces[0].transactions[0];
+        let address = tx.account.clone();
 
         let mut parser = ParserMessageApproved::new(tx, address).await.unwrap();
         assert!(parser.check_match().await.unwrap());
@@ -156,7 +155,7 @@ mod tests {
             "0:0000000000000000000000000000000000000000000000000000000000000000",
         )
         .unwrap();
-        let tx = traces[1].transactions[0].clone();
+        let tx = &traces[1].transactions[0];
         let mut parser = ParserMessageApproved::new(tx, address.clone())
             .await
             .unwrap();
@@ -9,19 +9,19 @@ use tonlib_core::TonAddress;
 use crate::boc::nullified_message::NullifiedSuccessfullyMessage;
 use crate::error::TransactionParsingError;
 use crate::ton_constants::{OP_GATEWAY_EXECUTE, OP_NULLIFIED_SUCCESSFULLY};
-use crate::transaction_parser::common::is_log_emmitted_in_opcode;
+use crate::transaction_parser::common::{is_log_emmitted_in_opcode, tx_timestamp};
 use crate::transaction_parser::message_matching_key::MessageMatchingKey;
 use crate::transaction_parser::parser::Parser;
 
-pub struct ParserMessageExecuted {
+pub struct ParserMessageExecuted<'a> {
     log: Option<NullifiedSuccessfullyMessage>,
-    tx: Transaction,
+    tx: &'a Transaction,
     allowed_address: TonAddress,
 }
 
-impl ParserMessageExecuted {
+impl<'a> ParserMessageExecuted<'a> {
     pub(crate) async fn new(
-        tx: Transaction,
+        tx: &'a Transaction,
         allowed_address: TonAddress,
     ) -> Result<Self, TransactionParsingError> {
         Ok(Self {
@@ -33,10 +33,10 @@ impl ParserMessageExecuted {
 }
 
 #[async_trait]
-impl Parser for ParserMessageExecuted {
+impl Parser for ParserMessageExecuted<'_> {
     async fn parse(&mut self) -> Result<bool, TransactionParsingError> {
         if self.log.is_none() {
-            let in_msg = &self.tx.clone().in_msg.ok_or_else(|| {
+            let in_msg = self.tx.in_msg.as_ref().ok_or_else(|| {
                 TransactionParsingError::Message("Transaction has no in_msg".to_string())
             })?;
             self.log = Some(
@@ -54,9 +54,9 @@ impl Parser for ParserMessageExecuted {
 
         let mut msg_idx = 1usize;
         let mut second_log = false;
-        let first_log = is_log_emmitted_in_opcode(&self.tx, OP_NULLIFIED_SUCCESSFULLY, 0)?;
+        let first_log = is_log_emmitted_in_opcode(self.tx, OP_NULLIFIED_SUCCESSFULLY, 0)?;
         if !first_log {
-            second_log = is_log_emmitted_in_opcode(&self.tx, OP_NULLIFIED_SUCCESSFULLY, 1)?;
+            second_log = is_log_emmitted_in_opcode(self.tx, OP_NULLIFIED_SUCCESSFULLY, 1)?;
             msg_idx = 0;
         }
 
@@ -78,7 +78,7 @@ impl Parser for ParserMessageExecuted {
     }
 
     async fn event(&self, _: Option<String>) -> Result<Event, TransactionParsingError> {
-        let tx = &self.tx;
+        let tx = self.tx;
         let log = match self.log.clone() {
             Some(log) => log,
             None => return Err(TransactionParsingError::Message("Missing log".to_string())),
@@ -94,8 +94,7 @@ impl Parser for ParserMessageExecuted {
                         from_address: None,
                         finalized: None,
                         source_context: None,
-                        timestamp: chrono::Utc::now()
-                            .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                        timestamp: tx_timestamp(tx),
                     },
                     command_id: None,
                     child_message_ids: None,
@@ -127,8 +126,8 @@ mod tests {
     async fn test_parser() {
         let traces = fixture_traces();
 
-        let tx = traces[0].transactions[3].clone();
-        let address = tx.clone().account;
+        let tx = &traces[0].transactions[3];
+        let address = tx.account.clone();
 
         let mut parser = ParserMessageExecuted::new(tx, address).await.unwrap();
         assert!(parser.check_match().await.unwrap());
@@ -163,8 +162,8 @@ mod tests {
     async fn test_parser_b() {
         let traces = fixture_traces();
 
-        let tx = traces[17].transactions[4].clone();
-        let address = tx.clone().account;
+        let tx = &traces[17].transactions[4];
+        let address = tx.account.clone();
 
         let mut parser = ParserMessageExecuted::new(tx, address).await.unwrap();
         assert!(parser.check_match().await.unwrap());
@@ -206,7 +205,7 @@ mod tests {
             "0:0000000000000000000000000000000000000000000000000000000000000000",
         )
         .unwrap();
-        let tx = traces[1].transactions[0].clone();
+        let tx = &traces[1].transactions[0];
         let mut parser = ParserMessageExecuted::new(tx, address.clone())
             .await
             .unwrap();
@@ -1,7 +1,7 @@
 use crate::boc::native_gas_added::NativeGasAddedMessage;
 use crate::error::TransactionParsingError;
 use crate::ton_constants::OP_ADD_NATIVE_GAS;
-use crate::transaction_parser::common::is_log_emmitted_in_opcode;
+use crate::transaction_parser::common::{is_log_emmitted_in_opcode, tx_timestamp};
 use crate::transaction_parser::message_matching_key::MessageMatchingKey;
 use crate::transaction_parser::parser::Parser;
 use crate::types::Transaction;
@@ -9,15 +9,15 @@ use async_trait::async_trait;
 use relayer_core::gmp_api::gmp_types::{Amount, CommonEventFields, Event, EventMetadata};
 use tonlib_core::TonAddress;
 
-pub struct ParserNativeGasAdded {
+pub struct ParserNativeGasAdded<'a> {
     log: Option<NativeGasAddedMessage>,
-    tx: Transaction,
+    tx: &'a Transaction,
     allowed_address: TonAddress,
 }
 
-impl ParserNativeGasAdded {
+impl<'a> ParserNativeGasAdded<'a> {
     pub(crate) async fn new(
-        tx: Transaction,
+        tx: &'a Transaction,
         allowed_address: TonAddress,
     ) -> Result<Self, TransactionParsingError> {
         Ok(Self {
@@ -29,7 +29,7 @@ impl ParserNativeGasAdded {
 }
 
 #[async_trait]
-impl Parser for ParserNativeGasAdded {
+impl Parser for ParserNativeGasAdded<'_> {
     async fn parse(&mut self) -> Result<bool, TransactionParsingError> {
         if self.log.is_none() {
             self.log = Some(
@@ -45,7 +45,7 @@ impl Parser for ParserNativeGasAdded {
             return Ok(false);
         }
 
-        is_log_emmitted_in_opcode(&self.tx, OP_ADD_NATIVE_GAS, 0)
+        is_log_emmitted_in_opcode(self.tx, OP_ADD_NATIVE_GAS, 0)
     }
 
     async fn key(&self) -> Result<MessageMatchingKey, TransactionParsingError> {
@@ -53,7 +53,7 @@ impl Parser for ParserNativeGasAdded {
     }
 
     async fn event(&self, _: Option<String>) -> Result<Event, TransactionParsingError> {
-        let tx = &self.tx;
+        let tx = self.tx;
         let log = match self.log.clone() {
             Some(log) => log,
             None => return Err(TransactionParsingError::Message("Missing log".to_string())),
@@ -77,8 +77,7 @@ impl Parser for ParserNativeGasAdded {
                     from_address: None,
                     finalized: None,
                     source_context: None,
-                    timestamp: chrono::Utc::now()
-                        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                    timestamp: tx_timestamp(tx),
                 }),
             },
             message_id,
@@ -110,8 +109,8 @@ mod tests {
     async fn test_parser() {
         let traces = fixture_traces();
 
-        let tx = traces[5].transactions[1].clone();
-        let address = tx.clone().account;
+        let tx = &traces[5].transactions[1];
+        let address = tx.account.clone();
 
         let mut parser = ParserNativeGasAdded::new(tx, address).await.unwrap();
         assert!(parser.check_match().await.unwrap());
@@ -156,7 +155,7 @@ mod tests {
             "0:0000000000000000000000000000000000000000000000000000000000000000",
         )
         .unwrap();
-        let tx = traces[1].transactions[0].clone();
+        let tx = &traces[1].transactions[0];
         let mut parser = ParserNativeGasAdded::new(tx, address.clone())
             .await
             .unwrap();
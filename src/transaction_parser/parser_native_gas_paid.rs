@@ -1,7 +1,7 @@
 use crate::boc::native_gas_paid::NativeGasPaidMessage;
 use crate::error::TransactionParsingError;
 use crate::ton_constants::{OP_PAY_GAS, OP_PAY_NATIVE_GAS_FOR_CONTRACT_CALL};
-use crate::transaction_parser::common::is_log_emmitted_in_opcode;
+use crate::transaction_parser::common::{is_log_emmitted_in_opcode, tx_timestamp};
 use crate::transaction_parser::message_matching_key::MessageMatchingKey;
 use crate::transaction_parser::parser::Parser;
 use async_trait::async_trait;
@@ -9,15 +9,15 @@ use relayer_core::gmp_api::gmp_types::{Amount, CommonEventFields, Event, EventMe
 use crate::types::Transaction;
 use tonlib_core::TonAddress;
 
-pub struct ParserNativeGasPaid {
+pub struct ParserNativeGasPaid<'a> {
     log: Option<NativeGasPaidMessage>,
-    tx: Transaction,
+    tx: &'a Transaction,
     allowed_address: TonAddress,
 }
 
-impl ParserNativeGasPaid {
+impl<'a> ParserNativeGasPaid<'a> {
     pub(crate) async fn new(
-        tx: Transaction,
+        tx: &'a Transaction,
         allowed_address: TonAddress,
     ) -> Result<Self, TransactionParsingError> {
         Ok(Self {
@@ -29,7 +29,7 @@ impl ParserNativeGasPaid {
 }
 
 #[async_trait]
-impl Parser for ParserNativeGasPaid {
+impl Parser for ParserNativeGasPaid<'_> {
     async fn parse(&mut self) -> Result<bool, TransactionParsingError> {
         if self.log.is_none() {
             self.log = Some(
@@ -45,11 +45,11 @@ impl Parser for ParserNativeGasPaid {
             return Ok(false);
         }
 
-        if is_log_emmitted_in_opcode(&self.tx, OP_PAY_NATIVE_GAS_FOR_CONTRACT_CALL, 0)? {
+        if is_log_emmitted_in_opcode(self.tx, OP_PAY_NATIVE_GAS_FOR_CONTRACT_CALL, 0)? {
             return Ok(true);
         }
 
-        is_log_emmitted_in_opcode(&self.tx, OP_PAY_GAS, 0)
+        is_log_emmitted_in_opcode(self.tx, OP_PAY_GAS, 0)
     }
 
     async fn key(&self) -> Result<MessageMatchingKey, TransactionParsingError> {
@@ -67,7 +67,7 @@ impl Parser for ParserNativeGasPaid {
     }
 
     async fn event(&self, message_id: Option<String>) -> Result<Event, TransactionParsingError> {
-        let tx = &self.tx;
+        let tx = self.tx;
         let message_id = if let Some(id) = message_id {
             id
         } else {
@@ -89,8 +89,7 @@ impl Parser for ParserNativeGasPaid {
                     from_address: None,
                     finalized: None,
                     source_context: None,
-                    timestamp: chrono::Utc::now()
-                        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                    timestamp: tx_timestamp(tx),
                 }),
             },
             message_id,
@@ -117,8 +116,8 @@ mod tests {
     async fn test_parser_native_gas_paid_for_contract_call() {
         let traces = fixture_traces();
 
-        let tx = traces[4].transactions[2].clone();
-        let address = tx.clone().account;
+        let tx = &traces[4].transactions[2];
+        let address = tx.account.clone();
 
         let mut parser = ParserNativeGasPaid::new(tx, address).await.unwrap();
         assert!(parser.check_match().await.unwrap());
@@ -153,8 +152,8 @@ mod tests {
     async fn test_parser_gas_paid() {
         let traces = fixture_traces();
 
-        let tx = traces[18].transactions[1].clone();
-        let address = tx.clone().account;
+        let tx = &traces[18].transactions[1];
+        let address = tx.account.clone();
 
         let mut parser = ParserNativeGasPaid::new(tx, address).await.unwrap();
         assert!(parser.check_match().await.unwrap());
@@ -193,7 +192,7 @@ mod tests {
             "0:0000000000000000000000000000000000000000000000000000000000000000",
         )
         .unwrap();
-        let tx = traces[1].transactions[0].clone();
+        let tx = &traces[1].transactions[0];
         let mut parser = ParserNativeGasPaid::new(tx, address.clone()).await.unwrap();
         assert!(!parser.check_match().await.unwrap());
     }
@@ -9,15 +9,15 @@ use relayer_core::gmp_api::gmp_types::{Amount, CommonEventFields, Event};
 use crate::types::Transaction;
 use tonlib_core::TonAddress;
 
-pub struct ParserNativeGasRefunded {
+pub struct ParserNativeGasRefunded<'a> {
     log: Option<NativeGasRefundedMessage>,
-    tx: Transaction,
+    tx: &'a Transaction,
     allowed_address: TonAddress,
 }
 
-impl ParserNativeGasRefunded {
+impl<'a> ParserNativeGasRefunded<'a> {
     pub(crate) async fn new(
-        tx: Transaction,
+        tx: &'a Transaction,
         allowed_address: TonAddress,
     ) -> Result<Self, TransactionParsingError> {
         Ok(Self {
@@ -29,7 +29,7 @@ impl ParserNativeGasRefunded {
 }
 
 #[async_trait]
-impl Parser for ParserNativeGasRefunded {
+impl Parser for ParserNativeGasRefunded<'_> {
     async fn parse(&mut self) -> Result<bool, TransactionParsingError> {
         if self.log.is_none() {
             self.log = Some(
@@ -45,7 +45,7 @@ impl Parser for ParserNativeGasRefunded {
             return Ok(false);
         }
 
-        is_log_emmitted_in_opcode(&self.tx, OP_NATIVE_REFUND, 1)
+        is_log_emmitted_in_opcode(self.tx, OP_NATIVE_REFUND, 1)
     }
 
     async fn key(&self) -> Result<MessageMatchingKey, TransactionParsingError> {
@@ -53,7 +53,7 @@ impl Parser for ParserNativeGasRefunded {
     }
 
     async fn event(&self, _: Option<String>) -> Result<Event, TransactionParsingError> {
-        let tx = &self.tx;
+        let tx = self.tx;
 
         let log = match self.log.clone() {
             Some(log) => log,
@@ -108,8 +108,8 @@ mod tests {
     async fn test_parser() {
         let traces = fixture_traces();
 
-        let tx = traces[7].transactions[2].clone();
-        let address = tx.clone().account;
+        let tx = &traces[7].transactions[2];
+        let address = tx.account.clone();
         let mut parser = ParserNativeGasRefunded::new(tx, address).await.unwrap();
         assert!(parser.check_match().await.unwrap());
         parser.parse().await.unwrap();
@@ -152,7 +152,7 @@ mod tests {
             "0:0000000000000000000000000000000000000000000000000000000000000000",
         )
         .unwrap();
-        let tx = traces[7].transactions[0].clone();
+        let tx = &traces[7].transactions[0];
         let mut parser = ParserNativeGasRefunded::new(tx, address.clone())
             .await
             .unwrap();
@@ -0,0 +1,784 @@
+/*!
+
+Routes transactions to the parsers that can match them.
+
+Every transaction parser only matches transactions on the contract it watches, and only when the
+in-message carries a specific opcode or when a specific log (an out-message with no destination)
+is emitted. Parsers are registered through a `ParserFactory`, which declares those triggers along
+with the parser's category, and `ParserRegistry` indexes factories by them, so a transaction is
+only offered to the handful of parsers that can match it instead of all of them.
+
+Candidates are returned in registration order. The built-in parsers are registered first, in the
+order they have always been tried in, so the first matching parser stays the same. Parsers can be
+disabled by name or by category (see `ParsersConfig`).
+
+Some parsers need the whole trace, e.g. to tell why an execution failed. They are registered
+through a `TraceParserFactory` instead and run before the transaction parsers. Within a category,
+once one of them matched the later ones are skipped, as an execution that ran out of gas shouldn't
+also be reported as reverted.
+
+# Usage Example
+
+```rust,no_run
+use async_trait::async_trait;
+use relayer_core::gmp_api::gmp_types::Event;
+use std::collections::HashMap;
+use ton::parser::{
+    MessageMatchingKey, Parser, ParserCategory, ParserFactory, ParserTrigger,
+    TransactionParsingError,
+};
+use ton::types::Transaction;
+use tonlib_core::TonAddress;
+
+/// Adds the logical time of the transaction that emitted the log to the execution's details.
+struct MyParser<'a> {
+    tx: &'a Transaction,
+    address: TonAddress,
+}
+
+#[async_trait]
+impl Parser for MyParser<'_> {
+    async fn parse(&mut self) -> Result<bool, TransactionParsingError> {
+        Ok(true)
+    }
+
+    async fn check_match(&mut self) -> Result<bool, TransactionParsingError> {
+        Ok(self.tx.account == self.address)
+    }
+
+    async fn key(&self) -> Result<MessageMatchingKey, TransactionParsingError> {
+        Err(TransactionParsingError::Generic("MyParser has no key".to_string()))
+    }
+
+    async fn event(&self, _: Option<String>) -> Result<Event, TransactionParsingError> {
+        Err(TransactionParsingError::Generic("MyParser emits no event".to_string()))
+    }
+
+    async fn message_id(&self) -> Result<Option<String>, TransactionParsingError> {
+        Ok(None)
+    }
+
+    async fn execution_details(&self) -> Result<HashMap<String, String>, TransactionParsingError> {
+        Ok(HashMap::from([("my_log_lt".to_string(), self.tx.lt.to_string())]))
+    }
+}
+
+struct MyParserFactory {
+    address: TonAddress,
+}
+
+#[async_trait]
+impl ParserFactory for MyParserFactory {
+    fn name(&self) -> &str {
+        "MyParser"
+    }
+
+    fn category(&self) -> ParserCategory {
+        ParserCategory::ExecutionDetails
+    }
+
+    fn address(&self) -> &TonAddress {
+        &self.address
+    }
+
+    fn triggers(&self) -> Vec<ParserTrigger> {
+        vec![ParserTrigger::LogOpcode(0x1000)]
+    }
+
+    async fn create<'a>(
+        &self,
+        tx: &'a Transaction,
+    ) -> Result<Box<dyn Parser + Send + Sync + 'a>, TransactionParsingError> {
+        Ok(Box::new(MyParser {
+            tx,
+            address: self.address.clone(),
+        }))
+    }
+}
+```
+
+# See also
+
+- `TraceParser::with_parser`
+- `TraceParser::with_parsers_config`
+
+*/
+
+use super::parser::Parser;
+use crate::config::ParsersConfig;
+use crate::error::TransactionParsingError;
+use crate::ton_constants::{
+    OP_ADD_NATIVE_GAS, OP_CALL_CONTRACT, OP_INTERCHAIN_TOKEN_DEPLOYMENT_STARTED_LOG,
+    OP_INTERCHAIN_TRANSFER_LOG, OP_INTERCHAIN_TRANSFER_RECEIVED_LOG, OP_LINK_TOKEN_STARTED_LOG,
+    OP_MESSAGE_APPROVED, OP_NATIVE_REFUND, OP_NULLIFIED_SUCCESSFULLY, OP_PAY_GAS,
+    OP_PAY_NATIVE_GAS_FOR_CONTRACT_CALL, OP_REGISTER_TOKEN_METADATA, OP_SIGNERS_ROTATED_LOG,
+    OP_TRANSFER_NOTIFICATION,
+};
+use crate::transaction_parser::parser_call_contract::ParserCallContract;
+use crate::transaction_parser::parser_execute_insufficient_gas::ParserExecuteInsufficientGas;
+use crate::transaction_parser::parser_execute_reverted::ParserExecuteReverted;
+use crate::transaction_parser::parser_its_interchain_token_deployment_started::ParserITSInterchainTokenDeploymentStarted;
+use crate::transaction_parser::parser_its_interchain_transfer::ParserITSInterchainTransfer;
+use crate::transaction_parser::parser_its_interchain_transfer_received::ParserITSInterchainTransferReceived;
+use crate::transaction_parser::parser_its_link_token_started::ParserITSLinkTokenStarted;
+use crate::transaction_parser::parser_its_token_metadata_registered::ParserITSTokenMetadataRegistered;
+use crate::transaction_parser::parser_jetton_gas_added::ParserJettonGasAdded;
+use crate::transaction_parser::parser_jetton_gas_paid::ParserJettonGasPaid;
+use crate::transaction_parser::parser_message_approved::ParserMessageApproved;
+use crate::transaction_parser::parser_message_executed::ParserMessageExecuted;
+use crate::transaction_parser::parser_native_gas_added::ParserNativeGasAdded;
+use crate::transaction_parser::parser_native_gas_paid::ParserNativeGasPaid;
+use crate::transaction_parser::parser_native_gas_refunded::ParserNativeGasRefunded;
+use crate::transaction_parser::parser_signers_rotated::ParserSignersRotated;
+use crate::types::{Trace, Transaction};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tonlib_core::TonAddress;
+
+/// How events of a parser are combined with the rest of the trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParserCategory {
+    /// Emits its own message id, other events may be attached to it.
+    CallContract,
+    /// Attached to a call contract event by key.
+    GasCredit,
+    /// Attached to a call contract event by key, like gas credits.
+    Its,
+    Standalone,
+    /// Emits no event, adds `execution_details` to the `MessageExecuted` event of the
+    /// execution the transaction is part of.
+    ExecutionDetails,
+    /// Reports an execution that failed, from the whole trace.
+    ExecutionFailure,
+}
+
+impl ParserCategory {
+    /// Name used to disable the whole category in the config.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ParserCategory::CallContract => "call_contract",
+            ParserCategory::GasCredit => "gas_credit",
+            ParserCategory::Its => "its",
+            ParserCategory::Standalone => "standalone",
+            ParserCategory::ExecutionDetails => "execution_details",
+            ParserCategory::ExecutionFailure => "execution_failure",
+        }
+    }
+}
+
+/// What makes a transaction on the watched contract a candidate for a parser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParserTrigger {
+    /// The in-message carries this opcode.
+    InMsgOpcode(u32),
+    /// A log (out-message with no destination) carries this opcode.
+    LogOpcode(u32),
+}
+
+/// Creates parsers for a single transaction and tells the registry which transactions to
+/// offer it.
+#[async_trait]
+pub trait ParserFactory: Send + Sync {
+    /// Unique name, used in logs and to disable the parser in the config.
+    fn name(&self) -> &str;
+    fn category(&self) -> ParserCategory;
+    /// Contract the parser watches. Transactions on other accounts are never offered to it.
+    fn address(&self) -> &TonAddress;
+    fn triggers(&self) -> Vec<ParserTrigger>;
+    async fn create<'a>(
+        &self,
+        tx: &'a Transaction,
+    ) -> Result<Box<dyn Parser + Send + Sync + 'a>, TransactionParsingError>;
+}
+
+/// Creates parsers that look at the whole trace rather than a single transaction.
+#[async_trait]
+pub trait TraceParserFactory: Send + Sync {
+    /// Unique name, used in logs and to disable the parser in the config.
+    fn name(&self) -> &str;
+    fn category(&self) -> ParserCategory;
+    /// Parsers for the trace, e.g. one per execution when a wallet message batches several.
+    async fn create<'a>(
+        &self,
+        trace: &'a Trace,
+    ) -> Result<Vec<Box<dyn Parser + Send + Sync + 'a>>, TransactionParsingError>;
+}
+
+/// Built-in parsers that look at the whole trace, in the order they are tried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TraceParserKind {
+    ExecuteInsufficientGas,
+    // Also covers insufficient gas, so it comes after it
+    ExecuteReverted,
+}
+
+impl TraceParserKind {
+    const ALL: [TraceParserKind; 2] = [
+        TraceParserKind::ExecuteInsufficientGas,
+        TraceParserKind::ExecuteReverted,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            TraceParserKind::ExecuteInsufficientGas => "ParserExecuteInsufficientGas",
+            TraceParserKind::ExecuteReverted => "ParserExecuteReverted",
+        }
+    }
+}
+
+/// Factory for one of the trace parsers that come with this crate.
+struct BuiltinTraceParserFactory {
+    kind: TraceParserKind,
+    gateway_address: TonAddress,
+    chain_name: String,
+}
+
+#[async_trait]
+impl TraceParserFactory for BuiltinTraceParserFactory {
+    fn name(&self) -> &str {
+        self.kind.name()
+    }
+
+    fn category(&self) -> ParserCategory {
+        ParserCategory::ExecutionFailure
+    }
+
+    async fn create<'a>(
+        &self,
+        trace: &'a Trace,
+    ) -> Result<Vec<Box<dyn Parser + Send + Sync + 'a>>, TransactionParsingError> {
+        let gateway = self.gateway_address.clone();
+
+        let mut parsers: Vec<Box<dyn Parser + Send + Sync + 'a>> = Vec::new();
+        match self.kind {
+            TraceParserKind::ExecuteInsufficientGas => parsers.push(Box::new(
+                ParserExecuteInsufficientGas::new(trace, gateway, self.chain_name.clone()).await?,
+            )),
+            TraceParserKind::ExecuteReverted => {
+                // A wallet message can batch several executions, each may fail on its own
+                for relayer_execute in ParserExecuteReverted::relayer_executes(trace, &gateway) {
+                    parsers.push(Box::new(
+                        ParserExecuteReverted::new(trace, relayer_execute, gateway.clone()).await?,
+                    ));
+                }
+            }
+        }
+
+        Ok(parsers)
+    }
+}
+
+/// Built-in parsers that look at a single transaction, in the order they are tried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParserKind {
+    CallContract,
+    MessageExecuted,
+    MessageApproved,
+    NativeGasPaid,
+    NativeGasAdded,
+    JettonGasAdded,
+    JettonGasPaid,
+    NativeGasRefunded,
+    ITSTokenMetadataRegistered,
+    ITSInterchainTokenDeploymentStarted,
+    ITSInterchainTransfer,
+    ITSLinkTokenStarted,
+    ITSInterchainTransferReceived,
+    SignersRotated,
+}
+
+impl ParserKind {
+    const ALL: [ParserKind; 14] = [
+        ParserKind::CallContract,
+        ParserKind::MessageExecuted,
+        ParserKind::MessageApproved,
+        ParserKind::NativeGasPaid,
+        ParserKind::NativeGasAdded,
+        ParserKind::JettonGasAdded,
+        ParserKind::JettonGasPaid,
+        ParserKind::NativeGasRefunded,
+        ParserKind::ITSTokenMetadataRegistered,
+        ParserKind::ITSInterchainTokenDeploymentStarted,
+        ParserKind::ITSInterchainTransfer,
+        ParserKind::ITSLinkTokenStarted,
+        ParserKind::ITSInterchainTransferReceived,
+        ParserKind::SignersRotated,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            ParserKind::CallContract => "ParserCallContract",
+            ParserKind::MessageExecuted => "ParserMessageExecuted",
+            ParserKind::MessageApproved => "ParserMessageApproved",
+            ParserKind::NativeGasPaid => "ParserNativeGasPaid",
+            ParserKind::NativeGasAdded => "ParserNativeGasAdded",
+            ParserKind::JettonGasAdded => "ParserJettonGasAdded",
+            ParserKind::JettonGasPaid => "ParserJettonGasPaid",
+            ParserKind::NativeGasRefunded => "ParserNativeGasRefunded",
+            ParserKind::ITSTokenMetadataRegistered => "ParserITSTokenMetadataRegistered",
+            ParserKind::ITSInterchainTokenDeploymentStarted => {
+                "ParserITSInterchainTokenDeploymentStarted"
+            }
+            ParserKind::ITSInterchainTransfer => "ParserITSInterchainTransfer",
+            ParserKind::ITSLinkTokenStarted => "ParserITSLinkTokenStarted",
+            ParserKind::ITSInterchainTransferReceived => "ParserITSInterchainTransferReceived",
+            ParserKind::SignersRotated => "ParserSignersRotated",
+        }
+    }
+
+    fn category(&self) -> ParserCategory {
+        match self {
+            ParserKind::CallContract => ParserCategory::CallContract,
+            ParserKind::NativeGasPaid | ParserKind::JettonGasPaid => ParserCategory::GasCredit,
+            ParserKind::ITSTokenMetadataRegistered
+            | ParserKind::ITSInterchainTokenDeploymentStarted
+            | ParserKind::ITSInterchainTransfer
+            | ParserKind::ITSLinkTokenStarted => ParserCategory::Its,
+            ParserKind::ITSInterchainTransferReceived => ParserCategory::ExecutionDetails,
+            ParserKind::MessageExecuted
+            | ParserKind::MessageApproved
+            | ParserKind::NativeGasAdded
+            | ParserKind::JettonGasAdded
+            | ParserKind::NativeGasRefunded
+            | ParserKind::SignersRotated => ParserCategory::Standalone,
+        }
+    }
+
+    fn triggers(&self) -> Vec<ParserTrigger> {
+        match self {
+            ParserKind::CallContract => vec![ParserTrigger::InMsgOpcode(OP_CALL_CONTRACT)],
+            ParserKind::MessageExecuted => {
+                vec![ParserTrigger::InMsgOpcode(OP_NULLIFIED_SUCCESSFULLY)]
+            }
+            ParserKind::MessageApproved => vec![ParserTrigger::InMsgOpcode(OP_MESSAGE_APPROVED)],
+            ParserKind::NativeGasPaid => vec![
+                ParserTrigger::InMsgOpcode(OP_PAY_NATIVE_GAS_FOR_CONTRACT_CALL),
+                ParserTrigger::InMsgOpcode(OP_PAY_GAS),
+            ],
+            ParserKind::NativeGasAdded => vec![ParserTrigger::InMsgOpcode(OP_ADD_NATIVE_GAS)],
+            ParserKind::JettonGasAdded | ParserKind::JettonGasPaid => {
+                vec![ParserTrigger::InMsgOpcode(OP_TRANSFER_NOTIFICATION)]
+            }
+            ParserKind::NativeGasRefunded => vec![ParserTrigger::InMsgOpcode(OP_NATIVE_REFUND)],
+            ParserKind::ITSTokenMetadataRegistered => {
+                vec![ParserTrigger::InMsgOpcode(OP_REGISTER_TOKEN_METADATA)]
+            }
+            ParserKind::ITSInterchainTokenDeploymentStarted => vec![ParserTrigger::LogOpcode(
+                OP_INTERCHAIN_TOKEN_DEPLOYMENT_STARTED_LOG,
+            )],
+            ParserKind::ITSInterchainTransfer => {
+                vec![ParserTrigger::LogOpcode(OP_INTERCHAIN_TRANSFER_LOG)]
+            }
+            ParserKind::ITSLinkTokenStarted => {
+                vec![ParserTrigger::LogOpcode(OP_LINK_TOKEN_STARTED_LOG)]
+            }
+            ParserKind::ITSInterchainTransferReceived => {
+                vec![ParserTrigger::LogOpcode(
+                    OP_INTERCHAIN_TRANSFER_RECEIVED_LOG,
+                )]
+            }
+            ParserKind::SignersRotated => vec![ParserTrigger::LogOpcode(OP_SIGNERS_ROTATED_LOG)],
+        }
+    }
+}
+
+/// Factory for one of the parsers that come with this crate.
+struct BuiltinParserFactory {
+    kind: ParserKind,
+    address: TonAddress,
+    chain_name: String,
+}
+
+#[async_trait]
+impl ParserFactory for BuiltinParserFactory {
+    fn name(&self) -> &str {
+        self.kind.name()
+    }
+
+    fn category(&self) -> ParserCategory {
+        self.kind.category()
+    }
+
+    fn address(&self) -> &TonAddress {
+        &self.address
+    }
+
+    fn triggers(&self) -> Vec<ParserTrigger> {
+        self.kind.triggers()
+    }
+
+    async fn create<'a>(
+        &self,
+        tx: &'a Transaction,
+    ) -> Result<Box<dyn Parser + Send + Sync + 'a>, TransactionParsingError> {
+        let address = self.address.clone();
+
+        let parser: Box<dyn Parser + Send + Sync + 'a> = match self.kind {
+            ParserKind::CallContract => {
+                Box::new(ParserCallContract::new(tx, address, self.chain_name.clone()).await?)
+            }
+            ParserKind::MessageExecuted => Box::new(ParserMessageExecuted::new(tx, address).await?),
+            ParserKind::MessageApproved => Box::new(ParserMessageApproved::new(tx, address).await?),
+            ParserKind::NativeGasPaid => Box::new(ParserNativeGasPaid::new(tx, address).await?),
+            ParserKind::NativeGasAdded => Box::new(ParserNativeGasAdded::new(tx, address).await?),
+            ParserKind::JettonGasAdded => Box::new(ParserJettonGasAdded::new(tx, address).await?),
+            ParserKind::JettonGasPaid => Box::new(ParserJettonGasPaid::new(tx, address).await?),
+            ParserKind::NativeGasRefunded => {
+                Box::new(ParserNativeGasRefunded::new(tx, address).await?)
+            }
+            ParserKind::ITSTokenMetadataRegistered => {
+                Box::new(ParserITSTokenMetadataRegistered::new(tx, address).await?)
+            }
+            ParserKind::ITSInterchainTokenDeploymentStarted => {
+                Box::new(ParserITSInterchainTokenDeploymentStarted::new(tx, address).await?)
+            }
+            ParserKind::ITSInterchainTransfer => {
+                Box::new(ParserITSInterchainTransfer::new(tx, address).await?)
+            }
+            ParserKind::ITSLinkTokenStarted => {
+                Box::new(ParserITSLinkTokenStarted::new(tx, address).await?)
+            }
+            ParserKind::ITSInterchainTransferReceived => {
+                Box::new(ParserITSInterchainTransferReceived::new(tx, address).await?)
+            }
+            ParserKind::SignersRotated => Box::new(ParserSignersRotated::new(tx, address).await?),
+        };
+
+        Ok(parser)
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct ParserRegistry {
+    trace_factories: Vec<Arc<dyn TraceParserFactory>>,
+    factories: Vec<Arc<dyn ParserFactory>>,
+    by_in_msg_opcode: HashMap<(TonAddress, u32), Vec<usize>>,
+    by_log_opcode: HashMap<(TonAddress, u32), Vec<usize>>,
+    disabled: Vec<String>,
+}
+
+impl ParserRegistry {
+    /// Registry with all built-in parsers.
+    pub(crate) fn new(
+        gateway_address: &TonAddress,
+        gas_service_address: &TonAddress,
+        its_address: &TonAddress,
+        chain_name: &str,
+    ) -> Self {
+        let mut registry = ParserRegistry::default();
+
+        for kind in TraceParserKind::ALL {
+            registry.register_trace(Arc::new(BuiltinTraceParserFactory {
+                kind,
+                gateway_address: gateway_address.clone(),
+                chain_name: chain_name.to_string(),
+            }));
+        }
+
+        for kind in ParserKind::ALL {
+            let address = match kind {
+                ParserKind::CallContract
+                | ParserKind::MessageExecuted
+                | ParserKind::MessageApproved
+                | ParserKind::SignersRotated => gateway_address,
+                ParserKind::NativeGasPaid
+                | ParserKind::NativeGasAdded
+                | ParserKind::JettonGasAdded
+                | ParserKind::JettonGasPaid
+                | ParserKind::NativeGasRefunded => gas_service_address,
+                ParserKind::ITSTokenMetadataRegistered
+                | ParserKind::ITSInterchainTokenDeploymentStarted
+                | ParserKind::ITSInterchainTransfer
+                | ParserKind::ITSLinkTokenStarted
+                | ParserKind::ITSInterchainTransferReceived => its_address,
+            };
+            registry.register(Arc::new(BuiltinParserFactory {
+                kind,
+                address: address.clone(),
+                chain_name: chain_name.to_string(),
+            }));
+        }
+
+        registry
+    }
+
+    /// Adds a factory after the ones already registered. Ignored if the parser is disabled.
+    pub(crate) fn register(&mut self, factory: Arc<dyn ParserFactory>) {
+        if self.is_disabled(factory.name(), factory.category()) {
+            return;
+        }
+
+        let index = self.factories.len();
+        for trigger in factory.triggers() {
+            let (index_map, opcode) = match trigger {
+                ParserTrigger::InMsgOpcode(opcode) => (&mut self.by_in_msg_opcode, opcode),
+                ParserTrigger::LogOpcode(opcode) => (&mut self.by_log_opcode, opcode),
+            };
+            let indices = index_map
+                .entry((factory.address().clone(), opcode))
+                .or_default();
+            if !indices.contains(&index) {
+                indices.push(index);
+            }
+        }
+        self.factories.push(factory);
+    }
+
+    /// Adds a trace factory after the ones already registered. Ignored if the parser is disabled.
+    pub(crate) fn register_trace(&mut self, factory: Arc<dyn TraceParserFactory>) {
+        if self.is_disabled(factory.name(), factory.category()) {
+            return;
+        }
+        self.trace_factories.push(factory);
+    }
+
+    /// Drops parsers disabled by the config, both registered and registered later.
+    pub(crate) fn configure(&mut self, config: &ParsersConfig) {
+        self.disabled.extend(config.disabled.iter().cloned());
+
+        for factory in std::mem::take(&mut self.trace_factories) {
+            self.register_trace(factory);
+        }
+
+        let factories = std::mem::take(&mut self.factories);
+        self.by_in_msg_opcode.clear();
+        self.by_log_opcode.clear();
+        for factory in factories {
+            self.register(factory);
+        }
+    }
+
+    fn is_disabled(&self, name: &str, category: ParserCategory) -> bool {
+        self.disabled
+            .iter()
+            .any(|disabled| disabled == name || disabled == category.as_str())
+    }
+
+    /// Parsers that look at the whole trace, in the order they should be tried.
+    pub(crate) fn trace_factories(&self) -> &[Arc<dyn TraceParserFactory>] {
+        &self.trace_factories
+    }
+
+    /// Parsers that may match the transaction, in the order they should be tried.
+    pub(crate) fn candidates(&self, tx: &Transaction) -> Vec<Arc<dyn ParserFactory>> {
+        let mut indices = Vec::new();
+
+        if let Some(opcode) = tx.in_msg.as_ref().and_then(|in_msg| in_msg.opcode) {
+            if let Some(found) = self.by_in_msg_opcode.get(&(tx.account.clone(), opcode)) {
+                indices.extend_from_slice(found);
+            }
+        }
+
+        for out_msg in tx.out_msgs.iter().filter(|msg| msg.destination.is_none()) {
+            if let Some(found) = out_msg
+                .opcode
+                .and_then(|opcode| self.by_log_opcode.get(&(tx.account.clone(), opcode)))
+            {
+                indices.extend_from_slice(found);
+            }
+        }
+
+        indices.sort();
+        indices.dedup();
+        indices
+            .into_iter()
+            .filter_map(|index| self.factories.get(index).map(Arc::clone))
+            .collect()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn factories(&self) -> &[Arc<dyn ParserFactory>] {
+        &self.factories
+    }
+}
+
+/// Matches transactions with and without routing, for `benches/parser_routing.rs`. Not part of
+/// the API.
+#[doc(hidden)]
+pub struct RoutingBench {
+    registry: ParserRegistry,
+}
+
+impl RoutingBench {
+    pub fn new(
+        gateway_address: &TonAddress,
+        gas_service_address: &TonAddress,
+        its_address: &TonAddress,
+        chain_name: &str,
+    ) -> Self {
+        Self {
+            registry: ParserRegistry::new(
+                gateway_address,
+                gas_service_address,
+                its_address,
+                chain_name,
+            ),
+        }
+    }
+
+    /// Number of transactions in the trace a parser matched, only trying the candidates the
+    /// registry routes each transaction to.
+    pub async fn routed(&self, trace: &Trace) -> usize {
+        let mut matched = 0;
+        for tx in &trace.transactions {
+            if Self::first_match(&self.registry.candidates(tx), tx).await {
+                matched += 1;
+            }
+        }
+        matched
+    }
+
+    /// Same as `routed`, creating and checking every parser in turn as was done before routing.
+    pub async fn sequential(&self, trace: &Trace) -> usize {
+        let mut matched = 0;
+        for tx in &trace.transactions {
+            if Self::first_match(&self.registry.factories, tx).await {
+                matched += 1;
+            }
+        }
+        matched
+    }
+
+    async fn first_match(factories: &[Arc<dyn ParserFactory>], tx: &Transaction) -> bool {
+        for factory in factories {
+            let Ok(mut parser) = factory.create(tx).await else {
+                continue;
+            };
+            if parser.check_match().await.unwrap_or(false) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fixtures::fixture_traces;
+
+    fn other() -> TonAddress {
+        TonAddress::from_hex_str(
+            "0:0000000000000000000000000000000000000000000000000000000000000000",
+        )
+        .unwrap()
+    }
+
+    fn names(candidates: &[Arc<dyn ParserFactory>]) -> Vec<String> {
+        candidates.iter().map(|f| f.name().to_string()).collect()
+    }
+
+    #[test]
+    fn test_candidates_by_in_msg_opcode() {
+        let traces = fixture_traces();
+        let tx = &traces[1].transactions[1];
+
+        let registry = ParserRegistry::new(&tx.account, &other(), &other(), "ton2");
+        assert_eq!(names(&registry.candidates(tx)), vec!["ParserCallContract"]);
+
+        let registry = ParserRegistry::new(&other(), &other(), &other(), "ton2");
+        assert!(registry.candidates(tx).is_empty());
+    }
+
+    #[test]
+    fn test_candidates_keep_order() {
+        let traces = fixture_traces();
+        let tx = &traces[9].transactions[3];
+
+        let registry = ParserRegistry::new(&other(), &tx.account, &other(), "ton2");
+        assert_eq!(
+            names(&registry.candidates(tx)),
+            vec!["ParserJettonGasAdded", "ParserJettonGasPaid"]
+        );
+    }
+
+    #[test]
+    fn test_candidates_by_log_opcode() {
+        let traces = fixture_traces();
+        let tx = &traces[24].transactions[1];
+
+        let registry = ParserRegistry::new(&tx.account, &other(), &other(), "ton2");
+        assert!(names(&registry.candidates(tx)).contains(&"ParserSignersRotated".to_string()));
+    }
+
+    #[test]
+    fn test_disabled_by_name_and_category() {
+        let traces = fixture_traces();
+        let tx = &traces[9].transactions[3];
+
+        let mut registry = ParserRegistry::new(&other(), &tx.account, &other(), "ton2");
+        registry.configure(&ParsersConfig {
+            disabled: vec!["ParserJettonGasAdded".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(names(&registry.candidates(tx)), vec!["ParserJettonGasPaid"]);
+
+        registry.configure(&ParsersConfig {
+            disabled: vec!["gas_credit".to_string()],
+            ..Default::default()
+        });
+        assert!(registry.candidates(tx).is_empty());
+
+        // Disabled parsers stay out when registered later
+        registry.register(Arc::new(BuiltinParserFactory {
+            kind: ParserKind::JettonGasPaid,
+            address: tx.account.clone(),
+            chain_name: "ton2".to_string(),
+        }));
+        assert!(registry.candidates(tx).is_empty());
+    }
+
+    fn trace_names(registry: &ParserRegistry) -> Vec<String> {
+        registry
+            .trace_factories()
+            .iter()
+            .map(|f| f.name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_trace_factories_disabled_by_name_and_category() {
+        let mut registry = ParserRegistry::new(&other(), &other(), &other(), "ton2");
+        assert_eq!(
+            trace_names(&registry),
+            vec!["ParserExecuteInsufficientGas", "ParserExecuteReverted"]
+        );
+
+        registry.configure(&ParsersConfig {
+            disabled: vec!["ParserExecuteInsufficientGas".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(trace_names(&registry), vec!["ParserExecuteReverted"]);
+
+        registry.configure(&ParsersConfig {
+            disabled: vec!["execution_failure".to_string()],
+            ..Default::default()
+        });
+        assert!(registry.trace_factories().is_empty());
+
+        // Disabled parsers stay out when registered later
+        registry.register_trace(Arc::new(BuiltinTraceParserFactory {
+            kind: TraceParserKind::ExecuteReverted,
+            gateway_address: other(),
+            chain_name: "ton2".to_string(),
+        }));
+        assert!(registry.trace_factories().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_routing_matches_sequential() {
+        let traces = fixture_traces();
+        let gateway = &traces[9].transactions[4].account;
+        let gas_service = &traces[9].transactions[1].account;
+        let bench = RoutingBench::new(gateway, gas_service, &other(), "ton2");
+
+        let mut matched = 0;
+        for trace in &traces {
+            let routed = bench.routed(trace).await;
+            assert_eq!(routed, bench.sequential(trace).await);
+            matched += routed;
+        }
+        assert!(matched > 0);
+    }
+}
@@ -1,7 +1,7 @@
 use crate::boc::signers_rotated::LogSignersRotatedMessage;
 use crate::error::TransactionParsingError;
 use crate::ton_constants::OP_SIGNERS_ROTATED_LOG;
-use crate::transaction_parser::common::{hash_to_message_id, is_log_emitted};
+use crate::transaction_parser::common::{hash_to_message_id, is_log_emitted, tx_timestamp};
 use crate::transaction_parser::message_matching_key::MessageMatchingKey;
 use crate::transaction_parser::parser::Parser;
 use crate::types::Transaction;
@@ -11,16 +11,16 @@ use relayer_core::gmp_api::gmp_types::{
 };
 use tonlib_core::TonAddress;
 
-pub struct ParserSignersRotated {
+pub struct ParserSignersRotated<'a> {
     log: Option<LogSignersRotatedMessage>,
-    tx: Transaction,
+    tx: &'a Transaction,
     allowed_address: TonAddress,
     log_position: isize,
 }
 
-impl ParserSignersRotated {
+impl<'a> ParserSignersRotated<'a> {
     pub(crate) async fn new(
-        tx: Transaction,
+        tx: &'a Transaction,
         allowed_address: TonAddress,
     ) -> Result<Self, TransactionParsingError> {
         Ok(Self {
@@ -33,7 +33,7 @@ impl ParserSignersRotated {
 }
 
 #[async_trait]
-impl Parser for ParserSignersRotated {
+impl Parser for ParserSignersRotated<'_> {
     async fn parse(&mut self) -> Result<bool, TransactionParsingError> {
         if self.log_position == -1 {
             return Ok(false);
@@ -56,7 +56,7 @@ impl Parser for ParserSignersRotated {
         if self.tx.account != self.allowed_address {
             return Ok(false);
         }
-        let pos = is_log_emitted(&self.tx, OP_SIGNERS_ROTATED_LOG)?;
+        let pos = is_log_emitted(self.tx, OP_SIGNERS_ROTATED_LOG)?;
         if pos >= 0 {
             self.log_position = pos;
             return Ok(true);
@@ -70,7 +70,7 @@ impl Parser for ParserSignersRotated {
     }
 
     async fn event(&self, _message_id: Option<String>) -> Result<Event, TransactionParsingError> {
-        let tx = &self.tx;
+        let tx = self.tx;
         let message_id = match self.message_id().await? {
             Some(id) => id,
             None => {
@@ -95,8 +95,7 @@ impl Parser for ParserSignersRotated {
                         from_address: None,
                         finalized: None,
                         source_context: None,
-                        timestamp: chrono::Utc::now()
-                            .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                        timestamp: tx_timestamp(tx),
                     },
                     signers_hash: Some(log.signers_hash),
                     epoch: Some(log.epoch),
@@ -123,8 +122,8 @@ mod tests {
     async fn test_parser() {
         let traces = fixture_traces();
 
-        let tx = traces[24].transactions[1].clone();
-        let address = tx.clone().account;
+        let tx = &traces[24].transactions[1];
+        let address = tx.account.clone();
 
         let mut parser = ParserSignersRotated::new(tx, address).await.unwrap();
 
@@ -166,7 +165,7 @@ mod tests {
             "0:0000000000000000000000000000000000000000000000000000000000000000",
         )
         .unwrap();
-        let tx = traces[20].transactions[1].clone();
+        let tx = &traces[20].transactions[1];
         let mut parser = ParserSignersRotated::new(tx, address.clone())
             .await
             .unwrap();
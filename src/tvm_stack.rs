@@ -0,0 +1,296 @@
+/*!
+
+TVM stack entries, as passed to and returned by Toncenter's `runGetMethod`.
+
+Toncenter encodes every entry as `{"type": ..., "value": ...}`: integers as hex strings, cells and
+slices as base64 BOCs, and tuples as lists of entries. [`StackEntry`] is the decoded form, and
+[`StackReader`] reads the values a get-method returned in order.
+
+# Example Usage
+
+```rust,no_run
+use ton::tvm_stack::{StackEntry, StackReader};
+
+let stack = vec![StackEntry::int(42), StackEntry::Null];
+let mut reader = StackReader::new(stack);
+assert_eq!(reader.next_u64().unwrap(), 42);
+```
+
+*/
+
+use crate::error::TvmStackError;
+use base64::engine::general_purpose;
+use base64::Engine;
+use num_bigint::{BigInt, BigUint, Sign};
+use serde::{Deserialize, Serialize};
+use tonlib_core::cell::{BagOfCells, Cell, CellBuilder};
+use tonlib_core::TonAddress;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StackEntry {
+    Int(BigInt),
+    Cell(Cell),
+    Slice(Cell),
+    Tuple(Vec<StackEntry>),
+    Null,
+}
+
+/// Entry as Toncenter sends it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum RawStackEntry {
+    Num(String),
+    Cell(String),
+    Slice(String),
+    Tuple(Vec<RawStackEntry>),
+    List(Vec<RawStackEntry>),
+    Null,
+}
+
+impl StackEntry {
+    pub fn int(value: impl Into<BigInt>) -> Self {
+        StackEntry::Int(value.into())
+    }
+
+    /// Slice holding the address, the way get-methods take addresses.
+    pub fn address(address: &TonAddress) -> Result<Self, TvmStackError> {
+        let mut builder = CellBuilder::new();
+        builder
+            .store_address(address)
+            .map_err(|err| TvmStackError::Encoding(err.to_string()))?;
+        let cell = builder
+            .build()
+            .map_err(|err| TvmStackError::Encoding(err.to_string()))?;
+        Ok(StackEntry::Slice(cell))
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            StackEntry::Int(_) => "int",
+            StackEntry::Cell(_) => "cell",
+            StackEntry::Slice(_) => "slice",
+            StackEntry::Tuple(_) => "tuple",
+            StackEntry::Null => "null",
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> TvmStackError {
+        TvmStackError::UnexpectedEntry {
+            expected: expected.to_string(),
+            got: self.type_name().to_string(),
+        }
+    }
+
+    pub fn as_int(&self) -> Result<&BigInt, TvmStackError> {
+        match self {
+            StackEntry::Int(value) => Ok(value),
+            other => Err(other.unexpected("int")),
+        }
+    }
+
+    /// Cell of a cell or slice entry.
+    pub fn as_cell(&self) -> Result<&Cell, TvmStackError> {
+        match self {
+            StackEntry::Cell(cell) | StackEntry::Slice(cell) => Ok(cell),
+            other => Err(other.unexpected("cell")),
+        }
+    }
+
+    pub fn as_tuple(&self) -> Result<&[StackEntry], TvmStackError> {
+        match self {
+            StackEntry::Tuple(entries) => Ok(entries),
+            other => Err(other.unexpected("tuple")),
+        }
+    }
+
+    pub fn to_raw(&self) -> Result<RawStackEntry, TvmStackError> {
+        Ok(match self {
+            StackEntry::Int(value) => RawStackEntry::Num(match value.sign() {
+                Sign::Minus => format!("-0x{:x}", value.magnitude()),
+                _ => format!("0x{:x}", value.magnitude()),
+            }),
+            StackEntry::Cell(cell) => RawStackEntry::Cell(boc_b64(cell)?),
+            StackEntry::Slice(cell) => RawStackEntry::Slice(boc_b64(cell)?),
+            StackEntry::Tuple(entries) => RawStackEntry::Tuple(
+                entries
+                    .iter()
+                    .map(StackEntry::to_raw)
+                    .collect::<Result<_, _>>()?,
+            ),
+            StackEntry::Null => RawStackEntry::Null,
+        })
+    }
+}
+
+impl TryFrom<RawStackEntry> for StackEntry {
+    type Error = TvmStackError;
+
+    fn try_from(raw: RawStackEntry) -> Result<Self, Self::Error> {
+        Ok(match raw {
+            RawStackEntry::Num(value) => StackEntry::Int(parse_int(&value)?),
+            RawStackEntry::Cell(boc) => StackEntry::Cell(parse_boc(&boc)?),
+            RawStackEntry::Slice(boc) => StackEntry::Slice(parse_boc(&boc)?),
+            RawStackEntry::Tuple(entries) | RawStackEntry::List(entries) => StackEntry::Tuple(
+                entries
+                    .into_iter()
+                    .map(StackEntry::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+            RawStackEntry::Null => StackEntry::Null,
+        })
+    }
+}
+
+/// Reads the entries a get-method returned, first to last.
+#[derive(Debug)]
+pub struct StackReader {
+    entries: std::vec::IntoIter<StackEntry>,
+}
+
+impl StackReader {
+    pub fn new(stack: Vec<StackEntry>) -> Self {
+        Self {
+            entries: stack.into_iter(),
+        }
+    }
+
+    pub fn next_entry(&mut self) -> Result<StackEntry, TvmStackError> {
+        self.entries.next().ok_or(TvmStackError::MissingEntry)
+    }
+
+    pub fn next_int(&mut self) -> Result<BigInt, TvmStackError> {
+        self.next_entry()?.as_int().cloned()
+    }
+
+    pub fn next_biguint(&mut self) -> Result<BigUint, TvmStackError> {
+        let value = self.next_int()?;
+        value
+            .to_biguint()
+            .ok_or_else(|| TvmStackError::OutOfRange(value.to_string()))
+    }
+
+    pub fn next_u64(&mut self) -> Result<u64, TvmStackError> {
+        let value = self.next_int()?;
+        u64::try_from(&value).map_err(|_| TvmStackError::OutOfRange(value.to_string()))
+    }
+
+    /// TVM booleans are -1 (true) and 0 (false).
+    pub fn next_bool(&mut self) -> Result<bool, TvmStackError> {
+        Ok(self.next_int()?.sign() != Sign::NoSign)
+    }
+
+    pub fn next_cell(&mut self) -> Result<Cell, TvmStackError> {
+        self.next_entry()?.as_cell().cloned()
+    }
+
+    /// Address stored at the start of a cell or slice.
+    pub fn next_address(&mut self) -> Result<TonAddress, TvmStackError> {
+        self.next_cell()?
+            .parser()
+            .load_address()
+            .map_err(|err| TvmStackError::Decoding(err.to_string()))
+    }
+}
+
+fn parse_int(value: &str) -> Result<BigInt, TvmStackError> {
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value),
+    };
+    let parsed = match digits.strip_prefix("0x") {
+        Some(hex) => BigInt::parse_bytes(hex.as_bytes(), 16),
+        None => BigInt::parse_bytes(digits.as_bytes(), 10),
+    }
+    .ok_or_else(|| TvmStackError::Decoding(format!("Invalid number {value}")))?;
+
+    Ok(if negative { -parsed } else { parsed })
+}
+
+fn parse_boc(boc: &str) -> Result<Cell, TvmStackError> {
+    Cell::from_boc_b64(boc).map_err(|err| TvmStackError::Decoding(err.to_string()))
+}
+
+fn boc_b64(cell: &Cell) -> Result<String, TvmStackError> {
+    let boc = BagOfCells::from_root(cell.clone())
+        .serialize(false)
+        .map_err(|err| TvmStackError::Encoding(err.to_string()))?;
+    Ok(general_purpose::STANDARD.encode(boc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_raw_round_trip() {
+        let address = TonAddress::from_str(
+            "0:0000000000000000000000000000000000000000000000000000000000000fff",
+        )
+        .unwrap();
+        let stack = vec![
+            StackEntry::int(-255),
+            StackEntry::address(&address).unwrap(),
+            StackEntry::Tuple(vec![StackEntry::int(1), StackEntry::Null]),
+        ];
+
+        let raw: Vec<RawStackEntry> = stack.iter().map(|e| e.to_raw().unwrap()).collect();
+        let json = serde_json::to_value(&raw).unwrap();
+        assert_eq!(json[0], json!({"type": "num", "value": "-0xff"}));
+        assert_eq!(json[1]["type"], "slice");
+        assert_eq!(
+            json[2],
+            json!({"type": "tuple", "value": [{"type": "num", "value": "0x1"}, {"type": "null"}]})
+        );
+
+        let decoded: Vec<StackEntry> = serde_json::from_value::<Vec<RawStackEntry>>(json)
+            .unwrap()
+            .into_iter()
+            .map(|raw| StackEntry::try_from(raw).unwrap())
+            .collect();
+        assert_eq!(decoded, stack);
+
+        let mut reader = StackReader::new(decoded);
+        assert_eq!(reader.next_int().unwrap(), BigInt::from(-255));
+        assert_eq!(reader.next_address().unwrap(), address);
+        assert_eq!(reader.next_entry().unwrap().as_tuple().unwrap().len(), 2);
+        assert!(matches!(
+            reader.next_entry(),
+            Err(TvmStackError::MissingEntry)
+        ));
+    }
+
+    #[test]
+    fn test_reader_types() {
+        let raw: Vec<RawStackEntry> = serde_json::from_value(json!([
+            {"type": "num", "value": "-0x1"},
+            {"type": "num", "value": "0x0"},
+            {"type": "num", "value": "-0x2"},
+            {"type": "list", "value": []},
+        ]))
+        .unwrap();
+        let stack = raw
+            .into_iter()
+            .map(|raw| StackEntry::try_from(raw).unwrap())
+            .collect();
+
+        let mut reader = StackReader::new(stack);
+        assert!(reader.next_bool().unwrap());
+        assert!(!reader.next_bool().unwrap());
+        assert!(matches!(
+            reader.next_u64(),
+            Err(TvmStackError::OutOfRange(_))
+        ));
+        assert!(matches!(
+            reader.next_int(),
+            Err(TvmStackError::UnexpectedEntry { .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_entries() {
+        assert!(StackEntry::try_from(RawStackEntry::Num("0xzz".to_string())).is_err());
+        assert!(StackEntry::try_from(RawStackEntry::Cell("not a boc".to_string())).is_err());
+    }
+}
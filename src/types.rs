@@ -209,38 +209,45 @@ pub struct AccountState {
     pub status: String,
 }
 
+/// First and last masterchain blocks known to the indexer.
+#[derive(Debug, Deserialize, Clone)]
+pub struct MasterchainInfo {
+    pub first: BlockRef,
+    pub last: BlockRef,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct AccountStatesResponse {
     pub accounts: Vec<AccountState>,
 }
 
-impl From<TracesResponseRest> for TracesResponse {
-    fn from(rest: TracesResponseRest) -> Self {
-        let traces = rest
-            .traces
+impl From<TraceRest> for Trace {
+    fn from(trace_rest: TraceRest) -> Self {
+        let transactions = trace_rest
+            .transactions_order
             .into_iter()
-            .map(|trace_rest| {
-                let transactions = trace_rest
-                    .transactions_order
-                    .into_iter()
-                    .filter_map(|key| {
-                        trace_rest.transactions.get(&key).cloned().or_else(|| {
-                            error!("Transaction key '{}' not found in map", key);
-                            None
-                        })
-                    })
-                    .collect();
-
-                Trace {
-                    is_incomplete: trace_rest.is_incomplete,
-                    start_lt: trace_rest.start_lt,
-                    end_lt: trace_rest.end_lt,
-                    trace_id: trace_rest.trace_id,
-                    transactions,
-                }
+            .filter_map(|key| {
+                trace_rest.transactions.get(&key).cloned().or_else(|| {
+                    error!("Transaction key '{}' not found in map", key);
+                    None
+                })
             })
             .collect();
 
+        Trace {
+            is_incomplete: trace_rest.is_incomplete,
+            start_lt: trace_rest.start_lt,
+            end_lt: trace_rest.end_lt,
+            trace_id: trace_rest.trace_id,
+            transactions,
+        }
+    }
+}
+
+impl From<TracesResponseRest> for TracesResponse {
+    fn from(rest: TracesResponseRest) -> Self {
+        let traces = rest.traces.into_iter().map(Trace::from).collect();
+
         TracesResponse { traces }
     }
 }
@@ -132,6 +132,23 @@ impl WalletManager {
         Err(WalletManagerError::NoAvailableWallet)
     }
 
+    /// Acquires one specific wallet, e.g. to sign a message again with the wallet that sent it.
+    pub async fn acquire_address(
+        &self,
+        address: &TonAddress,
+    ) -> Result<&TonWalletHighLoadV3, WalletManagerError> {
+        let wallet = self
+            .wallets
+            .get(address)
+            .ok_or(WalletManagerError::NoAvailableWallet)?;
+        if self.lock_manager.lock(&address.to_string()).await {
+            debug!("Acquired wallet: {:?}", address);
+            Ok(wallet)
+        } else {
+            Err(WalletManagerError::NoAvailableWallet)
+        }
+    }
+
     pub async fn release(&self, wallet: &TonWalletHighLoadV3) {
         self.lock_manager.unlock(&wallet.address.to_string()).await;
         debug!("Released wallet: {:?}", wallet.address);
@@ -146,6 +163,7 @@ pub(crate) mod wallet_manager_tests {
     use std::any::type_name;
     use std::collections::HashSet;
     use std::sync::{Arc, Mutex};
+    use tonlib_core::TonAddress;
 
     pub struct MockLockManager {
         locked: Mutex<HashSet<String>>,
@@ -238,4 +256,30 @@ pub(crate) mod wallet_manager_tests {
         wallet_manager.release(w3).await;
         let _w6 = wallet_manager.acquire().await.expect("wallet 6 ok");
     }
+
+    #[tokio::test]
+    async fn test_acquire_address() {
+        let wallet_manager = load_wallets().await;
+        let address =
+            TonAddress::from_base64_url("EQAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAADz6z")
+                .unwrap();
+
+        let wallet = wallet_manager
+            .acquire_address(&address)
+            .await
+            .expect("Should acquire wallet");
+        assert_eq!(wallet.address, address);
+
+        let result = wallet_manager.acquire_address(&address).await;
+        assert!(matches!(result, Err(WalletManagerError::NoAvailableWallet)));
+
+        wallet_manager.release(wallet).await;
+        assert!(wallet_manager.acquire_address(&address).await.is_ok());
+
+        let unknown =
+            TonAddress::from_base64_url("EQD__________________________________________0vo")
+                .unwrap();
+        let result = wallet_manager.acquire_address(&unknown).await;
+        assert!(matches!(result, Err(WalletManagerError::NoAvailableWallet)));
+    }
 }